@@ -0,0 +1,233 @@
+//! Simple timing comparisons between the extension methods and the
+//! equivalent combinations of standard library methods.
+//!
+//! Run with `cargo bench`. This uses a plain [`Instant`] based harness to
+//! avoid adding a benchmarking framework as a dependency, so the numbers are
+//! only useful for comparisons within a single run.
+
+use std::{hint::black_box, time::Instant};
+
+use std::collections::VecDeque;
+
+use retain_more::{
+    memoized, CompiledRetain, RetainMoreString as _, RetainMoreVec as _, RetainMoreVecDeque as _,
+};
+
+/// Run `f` `iterations` times, and print the mean time per iteration.
+fn bench<F: FnMut()>(name: &str, iterations: u32, mut f: F) {
+    // Warm up
+    for _ in 0..iterations / 10 {
+        f();
+    }
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    println!("{:<40} {:>12.2?}", name, start.elapsed() / iterations);
+}
+
+/// Removing from the middle section of a large vector, leaving a long tail
+/// which must be shifted down.
+fn retain_range() {
+    const LEN: usize = 100_000;
+    let source: Vec<u64> = (0..LEN as u64).collect();
+    let range = LEN / 4..LEN / 2;
+
+    bench("retain_range", 200, || {
+        let mut v = source.clone();
+        v.retain_range(range.clone(), |it| *it % 3 != 0);
+        black_box(v);
+    });
+    bench("drain + filter + splice", 200, || {
+        let mut v = source.clone();
+        let kept: Vec<u64> = v.drain(range.clone()).filter(|it| *it % 3 != 0).collect();
+        v.splice(range.start..range.start, kept);
+        black_box(v);
+    });
+}
+
+/// Filter-mapping a deque whose contents wrap around the end of its buffer.
+fn retain_map() {
+    const LEN: u64 = 100_000;
+    let mut source: VecDeque<u64> = VecDeque::with_capacity(LEN as usize);
+    for i in (0..LEN / 2).rev() {
+        source.push_front(i);
+    }
+    source.extend(LEN / 2..LEN);
+    let f = |it: u64| (!it.is_multiple_of(3)).then_some(it / 2);
+
+    bench("VecDeque::retain_map", 200, || {
+        let mut v = source.clone();
+        v.retain_map(f);
+        black_box(v);
+    });
+    bench("make_contiguous + Vec::retain_mut", 200, || {
+        let mut v = source.clone();
+        v.make_contiguous();
+        let mut v = Vec::from(v);
+        v.retain_mut(|it| match f(*it) {
+            Some(mapped) => {
+                *it = mapped;
+                true
+            }
+            None => false,
+        });
+        black_box(VecDeque::from(v));
+    });
+    bench("drain + filter_map + collect", 200, || {
+        let mut v = source.clone();
+        let mapped: VecDeque<u64> = v.drain(..).filter_map(f).collect();
+        black_box(mapped);
+    });
+}
+
+/// An artificially expensive predicate, which is pure.
+fn slow_predicate(c: char) -> bool {
+    let mut hash = c as u32;
+    for _ in 0..200 {
+        hash = black_box(hash.wrapping_mul(31).rotate_left(5));
+    }
+    c.is_alphabetic() || hash == 0
+}
+
+/// Filtering text which repeats a small set of characters with an expensive
+/// predicate.
+fn memoized_predicate() {
+    let source: String = "The quick brown föx jumps över the lazy dog! "
+        .chars()
+        .cycle()
+        .take(20_000)
+        .collect();
+
+    bench("retain_default", 50, || {
+        let mut s = source.clone();
+        s.retain_default(slow_predicate);
+        black_box(s);
+    });
+    bench("retain_default(memoized)", 50, || {
+        let mut s = source.clone();
+        s.retain_default(memoized(slow_predicate));
+        black_box(s);
+    });
+}
+
+/// Applying the same filter to many short strings.
+fn compiled_retain() {
+    let source: Vec<String> = (0..10_000)
+        .map(|i| format!("user-{}@exämple.com, id_{:x}", i, i * 7919))
+        .collect();
+    let predicate = |c: char| c.is_alphanumeric() || c == '@' || c == '.';
+
+    bench("retain_default per string", 50, || {
+        let mut strings = source.clone();
+        for s in &mut strings {
+            s.retain_default(predicate);
+        }
+        black_box(strings);
+    });
+    let compiled = CompiledRetain::new(predicate);
+    bench("CompiledRetain::apply per string", 50, || {
+        let mut strings = source.clone();
+        for s in &mut strings {
+            compiled.apply(s);
+        }
+        black_box(strings);
+    });
+}
+
+/// The trait's `retain_default`, which is `String::retain` through a trait
+/// object.
+fn retain_default() {
+    let source: String = "Ünïcode and ASCII, mixed_together; "
+        .chars()
+        .cycle()
+        .take(200_000)
+        .collect();
+    let predicate = |c: char| c.is_alphanumeric();
+
+    bench("RetainMoreString::retain_default", 50, || {
+        let mut s = source.clone();
+        s.retain_default(predicate);
+        black_box(s);
+    });
+    bench("String::retain", 50, || {
+        let mut s = source.clone();
+        s.retain(predicate);
+        black_box(s);
+    });
+    bench("retain_all (ignoring context)", 50, || {
+        let mut s = source.clone();
+        s.retain_all(|_, c, _| predicate(c));
+        black_box(s);
+    });
+}
+
+/// Extracting a few characters from a large string.
+fn retain_sparse() {
+    let mut source = "lorem ipsum dolor sit amet ".repeat(400_000);
+    for (i, digit) in "0123456789AB".chars().enumerate() {
+        source.insert(i * 900_000 + 5, digit);
+    }
+    let predicate = |c: char| c.is_ascii_digit() || c.is_ascii_uppercase();
+
+    bench("retain_sparse", 20, || {
+        let mut s = source.clone();
+        s.retain_sparse(12, predicate);
+        black_box(s);
+    });
+    bench("retain_default (sparse)", 20, || {
+        let mut s = source.clone();
+        s.retain_default(predicate);
+        black_box(s);
+    });
+}
+
+/// A pass which removes nothing, and so makes no writes, compared with one
+/// which removes the first character, and so moves every other byte.
+fn clean_pass() {
+    let source = "Already clean text, which needs no changes. ".repeat(20_000);
+
+    bench("retain_all (nothing removed)", 50, || {
+        let mut s = source.clone();
+        s.retain_all(|_, c, _| c != '\0');
+        black_box(s);
+    });
+    bench("retain_all (first char removed)", 50, || {
+        let mut s = source.clone();
+        let mut first = true;
+        s.retain_all(|_, _, _| !std::mem::take(&mut first));
+        black_box(s);
+    });
+}
+
+/// Chaining simple filters, compared to a single combined filter.
+fn filter_chars() {
+    let source: String = (0..10_000)
+        .map(|i| format!("item {}: value_{:x};\t", i, i * 31))
+        .collect();
+
+    bench("filter_chars chain", 50, || {
+        let s = source
+            .clone()
+            .filter_chars(|c| c != ' ')
+            .filter_chars(|c| c != '\t')
+            .filter_chars(|c| !c.is_ascii_punctuation());
+        black_box(s);
+    });
+    bench("retain_default combined", 50, || {
+        let mut s = source.clone();
+        s.retain_default(|c| c != ' ' && c != '\t' && !c.is_ascii_punctuation());
+        black_box(s);
+    });
+}
+
+fn main() {
+    retain_range();
+    retain_map();
+    memoized_predicate();
+    compiled_retain();
+    retain_default();
+    retain_sparse();
+    clean_pass();
+    filter_chars();
+}
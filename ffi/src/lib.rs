@@ -0,0 +1,8 @@
+//! Builds the C interface of `retain_more` as a static and dynamic library.
+//!
+//! `retain_more` is `no_std`, so it can't be linked into a C program on its
+//! own: that needs a global allocator and a panic handler, which this crate
+//! provides by linking `std`. The functions are declared in
+//! `include/retain_more.h`.
+
+pub use retain_more::retain_more_ascii;
@@ -0,0 +1,33 @@
+/*
+ * C interface to retain_more, provided by the `retain_more_ffi` library
+ * (built with `cargo build --release -p retain_more_ffi`).
+ */
+#ifndef RETAIN_MORE_H
+#define RETAIN_MORE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Filter the `len` bytes at `buf` in place, keeping each ASCII byte `b` if
+ * `keep_table[b]` is true, and each byte of 0x80 or above if
+ * `keep_non_ascii` is true. The kept bytes are moved to the start of `buf`,
+ * in order, and their number is returned. The bytes after them are
+ * unspecified.
+ *
+ * `buf` must be valid for reads and writes of `len` bytes (it may be NULL if
+ * `len` is 0), and `keep_table` must point to 128 `bool`s.
+ */
+size_t retain_more_ascii(uint8_t *buf, size_t len, const bool *keep_table,
+                         bool keep_non_ascii);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RETAIN_MORE_H */
@@ -0,0 +1,100 @@
+//! A C interface to ASCII filtering, declared in `include/retain_more.h`.
+//!
+//! This crate is `no_std`, so it has no global allocator or panic handler of
+//! its own, and can't be linked into a C program directly. The
+//! `retain_more_ffi` crate in `ffi/` links `std` to provide them, and is
+//! built as both a `staticlib` and a `cdylib` by
+//! `cargo build --release -p retain_more_ffi`.
+
+use core::slice;
+
+/// Filter the `len` bytes at `buf` in place against `keep_table`, returning
+/// the number of bytes kept.
+///
+/// Each ASCII byte `b` is kept if `keep_table[b]` is `true`, and each byte of
+/// `0x80` or above is kept if `keep_non_ascii` is `true`. The kept bytes are
+/// moved to the start of the buffer, in order. The bytes after them are left
+/// unspecified.
+///
+/// Removing non-ASCII bytes one at a time can split a multibyte UTF-8
+/// character, so if the buffer is UTF-8 it is only guaranteed to stay UTF-8
+/// when `keep_non_ascii` is `true`.
+///
+/// # Safety
+///
+/// - `buf` must be valid for reads and writes of `len` bytes, and not be
+///   accessed by anything else during the call. It may be null if `len` is
+///   `0`.
+/// - `keep_table` must be valid for reads of 128 `bool`s, each of which must
+///   be `0` or `1`.
+#[no_mangle]
+pub unsafe extern "C" fn retain_more_ascii(
+    buf: *mut u8,
+    len: usize,
+    keep_table: *const bool,
+    keep_non_ascii: bool,
+) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    // SAFETY: The caller guarantees that `buf` is valid for `len` bytes, that
+    // `keep_table` is valid for 128 `bool`s, and that neither is aliased.
+    let (buf, table) = unsafe {
+        (
+            slice::from_raw_parts_mut(buf, len),
+            &*keep_table.cast::<[bool; 128]>(),
+        )
+    };
+    let mut kept = 0;
+    for idx in 0..buf.len() {
+        let byte = buf[idx];
+        let keep = if byte.is_ascii() {
+            table[usize::from(byte)]
+        } else {
+            keep_non_ascii
+        };
+        if keep {
+            buf[kept] = byte;
+            kept += 1;
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ascii_table_except;
+    use core::ptr;
+
+    /// The C declaration, so the tests call through the same ABI as C does.
+    type RetainAscii = unsafe extern "C" fn(*mut u8, usize, *const bool, bool) -> usize;
+    const RETAIN: RetainAscii = retain_more_ascii;
+
+    fn retain(input: &[u8], table: &[bool; 128], keep_non_ascii: bool) -> alloc::vec::Vec<u8> {
+        let mut buf = input.to_vec();
+        let len = unsafe { RETAIN(buf.as_mut_ptr(), buf.len(), table.as_ptr(), keep_non_ascii) };
+        buf.truncate(len);
+        buf
+    }
+
+    #[test]
+    fn filters_in_place() {
+        let table = ascii_table_except(" -");
+        assert_eq!(retain(b"a b-c", &table, true), b"abc");
+        assert_eq!(retain(b" - ", &table, true), b"");
+        assert_eq!(retain(b"abc", &table, true), b"abc");
+        let input = "caf\u{e9} - ok".as_bytes();
+        assert_eq!(retain(input, &table, true), "caf\u{e9}ok".as_bytes());
+        assert_eq!(retain(input, &table, false), b"cafok");
+    }
+
+    #[test]
+    fn empty_buffer() {
+        let table = [true; 128];
+        assert_eq!(
+            unsafe { RETAIN(ptr::null_mut(), 0, table.as_ptr(), true) },
+            0
+        );
+    }
+}
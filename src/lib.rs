@@ -1,6 +1,32 @@
 #![no_std]
 extern crate alloc;
+#[cfg(test)]
+extern crate std;
 
+#[cfg(feature = "ffi")]
+mod ffi;
+mod literal;
+mod pattern;
+mod predicate;
+mod slice;
 mod string;
+mod vec;
+mod vec_deque;
 
-pub use string::RetainMoreString;
+#[cfg(feature = "ffi")]
+pub use ffi::retain_more_ascii;
+pub use literal::{ascii_table_except, retain_ascii_const};
+pub use pattern::CharPattern;
+pub use predicate::{memoized, MemoizedPredicate};
+pub use slice::RetainMoreSlice;
+pub use string::{
+    retain_many, retain_many_by, retain_many_with, ApplyError, BatchOptions, BatchReport,
+    BetweenOptions, BlankEdges, BlankLineOptions, CharFrequencies, ClassCounts, ClassifiedReport,
+    CompiledRetain, CountingVisitor, DeletionScript, DigitKind, DivergenceError, EmptyPieces,
+    ExtractIf, FillTooWide, IndentOptions, JsonMinifyError, MaskLenError, OffsetMapper, RangeError,
+    RemovalObserver, RemovalStreak, RetainAction, RetainBudgeted, RetainContext, RetainDecision,
+    RetainMoreString, RetainOptions, RetainReport, RetainScratch, RetainTrace, RetainVisitor,
+    Route, RouteCounts, ShrinkPolicy, StepResult, TraceEntry, ZipLengthError,
+};
+pub use vec::{retain_str_in_vec, ExtractIfCtx, RetainMoreVec, Utf8RetainError, VecRetainReport};
+pub use vec_deque::{RetainMoreByteDeque, RetainMoreVecDeque, Utf8Error};
@@ -0,0 +1,148 @@
+//! Filtering ASCII string literals at compile time.
+
+/// Filter the ASCII string `input` against `table`, in a `const` context.
+///
+/// Each byte `b` of `input` is kept if `table[b]` is `true`. The kept bytes
+/// are returned at the start of the array, followed by zeroes, along with the
+/// number of bytes kept. See [`retain_literal!`](crate::retain_literal) for a
+/// convenient wrapper which produces a `&'static str`.
+///
+/// # Panics
+///
+/// If `input` contains a non-ASCII character, or more than `N` bytes are
+/// kept. When evaluated in a `const` context, this is a compile error.
+///
+/// # Usage
+///
+/// ```
+/// use retain_more::{ascii_table_except, retain_ascii_const};
+/// const NO_SPACES: [bool; 128] = ascii_table_except(" ");
+/// const FILTERED: ([u8; 8], usize) = retain_ascii_const("a b c d", &NO_SPACES);
+/// assert_eq!(&FILTERED.0[..FILTERED.1], b"abcd");
+/// ```
+pub const fn retain_ascii_const<const N: usize>(
+    input: &str,
+    table: &[bool; 128],
+) -> ([u8; N], usize) {
+    let bytes = input.as_bytes();
+    let mut output = [0; N];
+    let mut len = 0;
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let byte = bytes[idx];
+        if !byte.is_ascii() {
+            panic!("retain_ascii_const: the input must only contain ASCII characters");
+        }
+        if table[byte as usize] {
+            if len == N {
+                panic!("retain_ascii_const: the retained bytes do not fit in the output array");
+            }
+            output[len] = byte;
+            len += 1;
+        }
+        idx += 1;
+    }
+    (output, len)
+}
+
+/// A table for [`retain_ascii_const`] which keeps every ASCII character
+/// except for those in `removed`.
+///
+/// # Panics
+///
+/// If `removed` contains a non-ASCII character.
+pub const fn ascii_table_except(removed: &str) -> [bool; 128] {
+    let bytes = removed.as_bytes();
+    let mut table = [true; 128];
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if !bytes[idx].is_ascii() {
+            panic!("ascii_table_except: the removed characters must be ASCII");
+        }
+        table[bytes[idx] as usize] = false;
+        idx += 1;
+    }
+    table
+}
+
+/// Filter an ASCII string literal at compile time, producing a
+/// `&'static str`.
+///
+/// The first argument is the input, which must be a constant `&str`, and the
+/// second is a constant `[bool; 128]` table of the ASCII characters to keep,
+/// as in [`retain_ascii_const`]. A non-ASCII input is a compile error.
+///
+/// # Usage
+///
+/// ```
+/// use retain_more::{ascii_table_except, retain_literal};
+/// const NO_PUNCTUATION: [bool; 128] = ascii_table_except(",.!");
+/// const GREETING: &str = retain_literal!("Hello, world!", NO_PUNCTUATION);
+/// assert_eq!(GREETING, "Hello world");
+/// ```
+///
+/// Non-ASCII input is rejected when compiling:
+///
+/// ```compile_fail
+/// use retain_more::{ascii_table_except, retain_literal};
+/// const CAFE: &str = retain_literal!("caf\u{e9}", ascii_table_except(""));
+/// ```
+#[macro_export]
+macro_rules! retain_literal {
+    ($input:expr, $table:expr $(,)?) => {{
+        const INPUT: &str = $input;
+        const RETAINED: ([u8; INPUT.len()], usize) =
+            $crate::retain_ascii_const::<{ INPUT.len() }>(INPUT, &$table);
+        // SAFETY: `retain_ascii_const` rejects any input which is not ASCII,
+        // so the retained bytes are all ASCII, and hence valid UTF-8
+        const OUTPUT: &str =
+            unsafe { ::core::str::from_utf8_unchecked(RETAINED.0.split_at(RETAINED.1).0) };
+        OUTPUT
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGITS: [bool; 128] = {
+        let mut table = [false; 128];
+        let mut digit = b'0';
+        while digit <= b'9' {
+            table[digit as usize] = true;
+            digit += 1;
+        }
+        table
+    };
+
+    const PHONE: &str = retain_literal!("+44 (0)20 7946-0018", DIGITS);
+    const EMPTY: &str = retain_literal!("no digits", DIGITS);
+    const UNCHANGED: &str = retain_literal!("12345", DIGITS);
+    const TRIMMED: &str = retain_literal!(" a b ", ascii_table_except(" "));
+
+    #[test]
+    fn retain_literal() {
+        assert_eq!(PHONE, "4402079460018");
+        assert_eq!(EMPTY, "");
+        assert_eq!(UNCHANGED, "12345");
+        assert_eq!(TRIMMED, "ab");
+    }
+
+    #[test]
+    fn retain_ascii_const_padding() {
+        const OUT: ([u8; 6], usize) = retain_ascii_const("a1b2", &DIGITS);
+        assert_eq!(OUT, ([b'1', b'2', 0, 0, 0, 0], 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "do not fit")]
+    fn retain_ascii_const_overflow() {
+        let _: ([u8; 2], usize) = retain_ascii_const("123", &DIGITS);
+    }
+
+    #[test]
+    #[should_panic(expected = "only contain ASCII")]
+    fn retain_ascii_const_non_ascii() {
+        let _: ([u8; 8], usize) = retain_ascii_const("1\u{e9}", &DIGITS);
+    }
+}
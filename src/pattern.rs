@@ -0,0 +1,137 @@
+//! Patterns which describe a set of characters, for
+//! [`retain_matching`](crate::RetainMoreString::retain_matching) and
+//! [`remove_matching`](crate::RetainMoreString::remove_matching).
+
+use core::ops::RangeInclusive;
+
+use alloc::vec::Vec;
+
+/// A set of characters, used to choose the characters to keep or remove.
+///
+/// This is implemented for:
+///  - `char`: That character.
+///  - `[char; N]`, `&[char; N]` and `&[char]`: Any of those characters.
+///  - `RangeInclusive<char>`: Any character in the range.
+///  - `&str`: Any character in the string.
+///  - `F: FnMut(char) -> bool`: Any character for which the predicate returns
+///    `true`.
+///
+/// The sets of several characters are sorted once when the pattern is
+/// converted into a predicate, so each character is tested by binary search.
+///
+/// This trait is sealed, so cannot be implemented outside of this crate.
+pub trait CharPattern: sealed::Sealed {
+    /// Convert this pattern into a predicate which returns `true` for the
+    /// characters in the set.
+    fn into_predicate(self) -> impl FnMut(char) -> bool;
+}
+
+/// The predicate for a sorted, deduplicated set of characters.
+fn sorted_set(mut chars: Vec<char>) -> impl FnMut(char) -> bool {
+    chars.sort_unstable();
+    chars.dedup();
+    move |c| chars.binary_search(&c).is_ok()
+}
+
+impl CharPattern for char {
+    fn into_predicate(self) -> impl FnMut(char) -> bool {
+        move |c| c == self
+    }
+}
+
+impl<const N: usize> CharPattern for [char; N] {
+    fn into_predicate(mut self) -> impl FnMut(char) -> bool {
+        // Sorted in place, so this doesn't allocate
+        self.sort_unstable();
+        move |c| self.binary_search(&c).is_ok()
+    }
+}
+
+impl<const N: usize> CharPattern for &[char; N] {
+    fn into_predicate(self) -> impl FnMut(char) -> bool {
+        (*self).into_predicate()
+    }
+}
+
+impl CharPattern for &[char] {
+    fn into_predicate(self) -> impl FnMut(char) -> bool {
+        sorted_set(self.to_vec())
+    }
+}
+
+impl CharPattern for RangeInclusive<char> {
+    fn into_predicate(self) -> impl FnMut(char) -> bool {
+        move |c| self.contains(&c)
+    }
+}
+
+impl CharPattern for &str {
+    fn into_predicate(self) -> impl FnMut(char) -> bool {
+        sorted_set(self.chars().collect())
+    }
+}
+
+impl<F: FnMut(char) -> bool> CharPattern for F {
+    fn into_predicate(self) -> impl FnMut(char) -> bool {
+        self
+    }
+}
+
+mod sealed {
+    use core::ops::RangeInclusive;
+
+    pub trait Sealed {}
+
+    impl Sealed for char {}
+    impl<const N: usize> Sealed for [char; N] {}
+    impl<const N: usize> Sealed for &[char; N] {}
+    impl Sealed for &[char] {}
+    impl Sealed for RangeInclusive<char> {}
+    impl Sealed for &str {}
+    impl<F: FnMut(char) -> bool> Sealed for F {}
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreString;
+    use alloc::string::{String, ToString};
+
+    const INPUT: &str = "a\u{200b}B c\u{feff}\u{e9}-z";
+
+    fn remove(pattern: impl super::CharPattern) -> String {
+        let mut s = INPUT.to_string();
+        s.remove_matching(pattern);
+        s
+    }
+
+    #[test]
+    fn each_pattern() {
+        let invisible = ['\u{feff}', '\u{200b}'];
+        assert_eq!(remove(' '), "a\u{200b}Bc\u{feff}\u{e9}-z");
+        assert_eq!(remove(invisible), "aB c\u{e9}-z");
+        let borrowed = &invisible;
+        assert_eq!(remove(borrowed), "aB c\u{e9}-z");
+        assert_eq!(remove(&invisible[..]), "aB c\u{e9}-z");
+        assert_eq!(remove('a'..='z'), "\u{200b}B \u{feff}\u{e9}-");
+        assert_eq!(remove("-z a-"), "\u{200b}Bc\u{feff}\u{e9}");
+        assert_eq!(remove(char::is_alphabetic), "\u{200b} \u{feff}-");
+        assert_eq!(remove(""), INPUT);
+        assert_eq!(remove(['z', 'z', 'a']), "\u{200b}B c\u{feff}\u{e9}-");
+    }
+
+    #[test]
+    fn retain_matching() {
+        let mut s = INPUT.to_string();
+        s.retain_matching('a'..='z');
+        assert_eq!(s, "acz");
+
+        // A stateful predicate is called once per character, in order
+        let mut s = INPUT.to_string();
+        let mut count = 0;
+        s.retain_matching(|_| {
+            count += 1;
+            count % 2 == 1
+        });
+        assert_eq!(s, "aBc\u{e9}z");
+    }
+}
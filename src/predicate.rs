@@ -0,0 +1,185 @@
+//! Wrappers which make character predicates cheaper to evaluate repeatedly.
+
+use alloc::vec::Vec;
+
+/// A character predicate which caches its decision for each distinct
+/// character.
+///
+/// Decisions for ASCII characters are cached in a fixed table, and decisions
+/// for other characters in a small sorted list. This is worthwhile when the
+/// wrapped predicate is expensive (such as a large Unicode table lookup), and
+/// the text repeats a limited set of characters.
+///
+/// The wrapped predicate must be pure: it must always return the same result
+/// for the same character, since it is only called the first time each
+/// character is seen. When `debug_assertions` are enabled, a sample of the
+/// cached decisions are checked against the predicate, and a mismatch
+/// panics.
+///
+/// To pass the wrapper to the retain methods, either use [`memoized`], or
+/// [`MemoizedPredicate::predicate`] to share one cache between several
+/// calls:
+///
+/// ```
+/// use retain_more::{MemoizedPredicate, RetainMoreString as _};
+/// let mut is_kept = MemoizedPredicate::new(|c: char| c.is_alphanumeric());
+/// let mut first = "a-b-c".to_string();
+/// let mut second = "c-b-a".to_string();
+/// first.retain_default(is_kept.predicate());
+/// second.retain_default(is_kept.predicate());
+/// assert_eq!((first.as_str(), second.as_str()), ("abc", "cba"));
+/// // Only the first occurrence of each character called the wrapped predicate
+/// assert_eq!(is_kept.cached(), 4);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MemoizedPredicate<F> {
+    f: F,
+    ascii: [Option<bool>; 128],
+    /// Sorted by character, for binary search
+    other: Vec<(char, bool)>,
+    #[cfg(debug_assertions)]
+    hits: usize,
+}
+
+/// How often a cache hit is checked against the wrapped predicate, when
+/// `debug_assertions` are enabled.
+#[cfg(debug_assertions)]
+const CHECK_INTERVAL: usize = 64;
+
+impl<F: FnMut(char) -> bool> MemoizedPredicate<F> {
+    /// Wrap `f`, which must be pure.
+    pub fn new(f: F) -> Self {
+        MemoizedPredicate {
+            f,
+            ascii: [None; 128],
+            other: Vec::new(),
+            #[cfg(debug_assertions)]
+            hits: 0,
+        }
+    }
+
+    /// The decision of the wrapped predicate for `ch`, using the cache if
+    /// possible.
+    pub fn call(&mut self, ch: char) -> bool {
+        let cached = if ch.is_ascii() {
+            self.ascii[ch as usize]
+        } else {
+            self.other
+                .binary_search_by_key(&ch, |&(c, _)| c)
+                .ok()
+                .map(|idx| self.other[idx].1)
+        };
+        match cached {
+            Some(decision) => {
+                #[cfg(debug_assertions)]
+                self.check(ch, decision);
+                decision
+            }
+            None => {
+                let decision = (self.f)(ch);
+                if ch.is_ascii() {
+                    self.ascii[ch as usize] = Some(decision);
+                } else if let Err(idx) = self.other.binary_search_by_key(&ch, |&(c, _)| c) {
+                    self.other.insert(idx, (ch, decision));
+                }
+                decision
+            }
+        }
+    }
+
+    /// A closure which calls [`Self::call`], for passing to the retain
+    /// methods whilst keeping the cache.
+    pub fn predicate(&mut self) -> impl FnMut(char) -> bool + '_ {
+        move |ch| self.call(ch)
+    }
+
+    /// The number of distinct characters whose decision is cached.
+    pub fn cached(&self) -> usize {
+        self.ascii.iter().filter(|it| it.is_some()).count() + self.other.len()
+    }
+
+    #[cfg(debug_assertions)]
+    fn check(&mut self, ch: char, decision: bool) {
+        self.hits += 1;
+        // `is_multiple_of` would need Rust 1.87
+        #[allow(clippy::manual_is_multiple_of)]
+        if self.hits % CHECK_INTERVAL == 0 {
+            assert_eq!(
+                (self.f)(ch),
+                decision,
+                "memoized predicate is not pure: its decision for {:?} changed",
+                ch
+            );
+        }
+    }
+}
+
+/// Wrap `f` in a [`MemoizedPredicate`], returning a closure which can be
+/// passed directly to the retain methods.
+///
+/// `f` must be pure, see [`MemoizedPredicate`].
+///
+/// ```
+/// use retain_more::{memoized, RetainMoreString as _};
+/// let mut my_string = "Größe: 12 cm³".to_string();
+/// my_string.retain_default(memoized(|c: char| c.is_alphabetic() || c == ' '));
+/// assert_eq!(my_string, "Größe  cm");
+/// ```
+pub fn memoized<F: FnMut(char) -> bool>(f: F) -> impl FnMut(char) -> bool {
+    let mut predicate = MemoizedPredicate::new(f);
+    move |ch| predicate.call(ch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetainMoreString;
+    use alloc::string::{String, ToString};
+
+    #[test]
+    fn memoized_calls_once_per_char() {
+        let mut calls = 0;
+        let text = "abc\u{e9}\u{e9}\u{1F980}cba\u{1F980}\u{65e5}";
+        let mut s = text.to_string();
+        s.retain_default(memoized(|c| {
+            calls += 1;
+            c.is_ascii()
+        }));
+        assert_eq!(s, "abccba");
+        // Each distinct character called the predicate once, as well as any
+        // consistency checks
+        let distinct = 6;
+        assert!(calls >= distinct && calls <= text.chars().count());
+
+        let mut expected = text.to_string();
+        expected.retain(|c| c.is_ascii());
+        assert_eq!(s, expected);
+    }
+
+    #[test]
+    fn memoized_predicate_cache() {
+        let mut predicate = MemoizedPredicate::new(|c: char| c.is_uppercase());
+        let text: String = "Hello Wörld ÄÖÜ äöü".chars().cycle().take(500).collect();
+        let mut s = text.clone();
+        s.retain_default(predicate.predicate());
+        let mut expected = text;
+        expected.retain(|c| c.is_uppercase());
+        assert_eq!(s, expected);
+        assert_eq!(predicate.cached(), 14);
+        // The cache sees every character
+        assert!(predicate.call('Ä'));
+        assert!(!predicate.call('ä'));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "memoized predicate is not pure")]
+    fn memoized_impure() {
+        let mut count = 0;
+        let mut s = "a".repeat(CHECK_INTERVAL + 1);
+        s.retain_default(memoized(|_| {
+            count += 1;
+            count % 2 == 0
+        }));
+    }
+}
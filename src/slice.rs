@@ -0,0 +1,99 @@
+/// In-place filtering for mutable slices, implemented as extension methods
+/// on [`[T]`](slice).
+///
+/// This trait is sealed and cannot be implemented for types outside of
+/// `retain_more`
+pub trait RetainMoreSlice<T>: sealed::Sealed {
+    /// Moves the elements specified by the predicate to the front of the
+    /// slice, returning `(kept, leftover)`.
+    ///
+    /// The kept elements are in their original order. The leftover elements
+    /// are the rest of the original elements, in an unspecified order. As the
+    /// slice does not own its elements, nothing is ever dropped, or needs to
+    /// be replaced with a placeholder: every original element is still
+    /// present in the slice exactly once, even if the predicate panics.
+    ///
+    /// This takes one pass, calls the predicate once for each element, in
+    /// order, and never allocates.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreSlice as _;
+    /// let mut buffer = [5, 2, 8, 1, 9, 4];
+    /// let (kept, leftover) = buffer.retain_in_place(|it| *it > 3);
+    /// assert_eq!(kept, [5, 8, 9, 4]);
+    /// leftover.sort();
+    /// assert_eq!(leftover, [1, 2]);
+    /// ```
+    fn retain_in_place<F: FnMut(&T) -> bool>(&mut self, f: F) -> (&mut [T], &mut [T]);
+}
+
+impl<T> RetainMoreSlice<T> for [T] {
+    fn retain_in_place<F: FnMut(&T) -> bool>(&mut self, mut f: F) -> (&mut [T], &mut [T]) {
+        let mut kept = 0;
+        for idx in 0..self.len() {
+            if f(&self[idx]) {
+                // `kept..idx` are all leftover elements, so moving one of them
+                // to `idx` keeps the order of the kept elements.
+                self.swap(kept, idx);
+                kept += 1;
+            }
+        }
+        self.split_at_mut(kept)
+    }
+}
+
+/// Implementation of the sealed pattern for [`RetainMoreSlice`]
+/// See [C-SEALED] from rust-api-guidelines for explanation
+///
+/// [C-SEALED]: https://rust-lang.github.io/api-guidelines/future-proofing.html#sealed-traits-protect-against-downstream-implementations-c-sealed
+mod sealed {
+    pub trait Sealed {}
+    impl<T> Sealed for [T] {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// An element which is neither `Default` nor `Clone`.
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Token(u32);
+
+    #[test]
+    fn retain_in_place() {
+        let mut tokens: Vec<Token> = [7, 3, 3, 10, 0, 6, 1, 12, 5]
+            .iter()
+            .map(|&i| Token(i))
+            .collect();
+        let mut before: Vec<u32> = tokens.iter().map(|it| it.0).collect();
+        let (kept, leftover) = tokens.retain_in_place(|it| it.0 % 3 == 0);
+        assert_eq!(kept, [Token(3), Token(3), Token(0), Token(6), Token(12)]);
+        assert_eq!(leftover.len(), 4);
+        assert!(leftover.iter().all(|it| it.0 % 3 != 0));
+
+        // Nothing was lost or duplicated
+        let mut after: Vec<u32> = tokens.iter().map(|it| it.0).collect();
+        before.sort_unstable();
+        after.sort_unstable();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn retain_in_place_edges() {
+        let mut empty: [Token; 0] = [];
+        let (kept, leftover) = empty.retain_in_place(|_| unreachable!());
+        assert!(kept.is_empty() && leftover.is_empty());
+
+        let mut all = [1, 2, 3];
+        assert_eq!(
+            all.retain_in_place(|_| true),
+            (&mut [1, 2, 3][..], &mut [][..])
+        );
+        let (kept, leftover) = all.retain_in_place(|_| false);
+        assert!(kept.is_empty());
+        assert_eq!(leftover, [1, 2, 3]);
+    }
+}
@@ -1,12 +1,101 @@
-use core::{slice, str::from_utf8_unchecked_mut};
+use core::{
+    hash::Hasher,
+    ops::{ControlFlow, Range, RangeBounds},
+};
 
-use alloc::string::String;
+use alloc::{boxed::Box, collections::TryReserveError, string::String, vec::Vec};
+
+use crate::CharPattern;
+
+mod balanced;
+mod batch;
+mod between;
+mod budgeted;
+mod classified;
+mod compiled;
+mod context;
+mod cursor;
+mod diff;
+mod extract;
+mod frequency;
+mod hash;
+mod json;
+mod lines;
+mod map;
+mod mask;
+mod numeric;
+mod observer;
+mod offsets;
+mod options;
+mod ranges;
+mod replace;
+mod report;
+mod route;
+mod runs;
+#[cfg(feature = "rand")]
+mod sample;
+mod scratch;
+mod sparse;
+mod splice;
+mod split;
+mod trace;
+mod visitor;
+#[cfg(feature = "unicode-width")]
+mod width;
+mod words;
+mod zip;
+
+pub use batch::{retain_many, retain_many_by, retain_many_with, BatchOptions, BatchReport};
+pub use between::BetweenOptions;
+pub use budgeted::{RetainBudgeted, StepResult};
+pub use classified::{ClassCounts, ClassifiedReport};
+pub use compiled::CompiledRetain;
+pub use context::RetainContext;
+use cursor::Cursor;
+pub use diff::{ApplyError, DeletionScript};
+pub use extract::ExtractIf;
+pub use frequency::CharFrequencies;
+pub use json::JsonMinifyError;
+pub use lines::{BlankEdges, BlankLineOptions, IndentOptions};
+pub use mask::FillTooWide;
+pub use numeric::DigitKind;
+pub use observer::{RemovalObserver, RetainAction};
+pub use offsets::OffsetMapper;
+pub use options::{RetainOptions, ShrinkPolicy};
+pub use ranges::RangeError;
+pub use replace::RetainDecision;
+pub use report::{RemovalStreak, RetainReport};
+pub use route::{Route, RouteCounts};
+pub use scratch::RetainScratch;
+pub use split::EmptyPieces;
+pub use trace::{DivergenceError, RetainTrace, TraceEntry};
+pub use visitor::{CountingVisitor, RetainVisitor};
+pub use zip::{MaskLenError, ZipLengthError};
 
 /// More advanced versions of [`String::retain`], implemented as extension
 /// methods on [`String`].
 ///
 /// This trait is sealed and cannot be implemented for types outside of
 /// `retain_more`
+///
+/// # Allocation
+///
+/// The methods which take `&mut self` work in place, and never reallocate
+/// the string, so its [pointer](str::as_ptr) and
+/// [capacity](String::capacity) are the same afterwards as before, including
+/// when a predicate panics. This includes the methods which return the
+/// removed text, since only the returned value is allocated. Slices of the
+/// string's buffer obtained through its pointer therefore stay within the
+/// allocation. The only exceptions are
+/// [`retain_with_options`](Self::retain_with_options) with
+/// [`ShrinkPolicy::ToFit`], and the methods which insert replacements, when
+/// the replacements are longer than the text they replace:
+/// [`retain_map`](Self::retain_map),
+/// [`retain_replace`](Self::retain_replace) and
+/// [`retain_splice`](Self::retain_splice). These are documented on each
+/// method. The methods which take
+/// `self` by value may reallocate the value they return, such as
+/// [`filter_into_boxed_str`](Self::filter_into_boxed_str).
 pub trait RetainMoreString: sealed::Sealed {
     /// Retains only the characters specified by the predicate.
     ///
@@ -60,225 +149,5094 @@ pub trait RetainMoreString: sealed::Sealed {
     /// my_string.retain_all(|before, _, _| !matches!(before.chars().rev().next(), Some(' ') | None));
     /// assert_eq!(&my_string, "");
     /// ```
-    /// A more correct implementation of this would look like
+    /// A more correct implementation of this would thread whether the current
+    /// character starts a word through [`Self::retain_scan`], which also
+    /// hands that state back afterwards
     /// ```
     /// # use retain_more::RetainMoreString as _;
     /// # let mut my_string = "Remove the first letter of each word".to_string();
-    /// let mut word_start = true;
-    /// my_string.retain_all(|_, it, _| {
-    ///     if word_start {
-    ///         word_start = false;
+    /// // The state is whether the next character starts a word, and the
+    /// // number of words seen so far
+    /// let (_, words) = my_string.retain_scan((true, 0), |(word_start, words), it, _| {
+    ///     if *word_start {
+    ///         *word_start = false;
+    ///         *words += 1;
     ///         false
     ///     } else if it == ' ' {
-    ///         word_start = true;
+    ///         *word_start = true;
     ///         true
     ///     } else {
     ///         true
     ///     }
     /// });
     /// assert_eq!(&my_string, "emove he irst etter f ach ord");
+    /// assert_eq!(words, 7);
     /// ```
     /// Notice however that this implementation could also simply use
     /// [`Self::retain_default`] or indeed [`String::retain`]
+    ///
+    /// # What the predicate observes
+    ///
+    /// On every call, `before` is exactly the characters kept so far, in
+    /// order, including any changes the predicate made to them in earlier
+    /// calls, and `after` is exactly the rest of the string after the
+    /// current character, including any earlier changes. Helpers which
+    /// don't pass `before`, such as [`Self::retain_after`] and
+    /// [`Self::retain_default`], make no such promise about the retained
+    /// prefix, so may be implemented differently.
+    ///
+    /// # Writes
+    ///
+    /// No bytes of the string's buffer are written until the first character
+    /// is removed, so a call which removes nothing makes no writes at all
+    /// (other than any made by the predicate itself). This means that a
+    /// speculative pass over a string which usually needs no changes does not
+    /// dirty its memory.
     fn retain_all<F: FnMut(&mut str, char, &mut str) -> bool>(&mut self, f: F);
 
-    /// A helper for the common case where only access to the parts of the
-    /// [`String`] which haven't been considered yet is required, i.e. the
-    /// predicate only uses arguments 1 and 2 from [`Self::retain_all`].
-    fn retain_after<F: FnMut(char, &mut str) -> bool>(&mut self, mut f: F) {
-        self.retain_all(move |_, current, after| f(current, after))
-    }
+    /// [`retain_all`](Self::retain_all), with the predicate as a trait
+    /// object.
+    ///
+    /// This is not generic, so there is only one copy of it however many
+    /// predicates it is used with, which is useful when predicates are chosen
+    /// at runtime. The implementation of [`retain_all`](Self::retain_all) for
+    /// [`String`] uses this, so the results are always the same.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut filters: Vec<Box<dyn FnMut(&mut str, char, &mut str) -> bool>> = vec![
+    ///     Box::new(|_, it, _| !it.is_ascii_digit()),
+    ///     Box::new(|before, it, _| !(it == ' ' && before.ends_with(' '))),
+    /// ];
+    /// let mut my_string = "a1  b22 c".to_string();
+    /// for filter in &mut filters {
+    ///     my_string.retain_all_dyn(filter.as_mut());
+    /// }
+    /// assert_eq!(my_string, "a b c");
+    /// ```
+    fn retain_all_dyn(&mut self, f: &mut dyn FnMut(&mut str, char, &mut str) -> bool);
 
-    /// A reimplmentation of [`String::retain`] using
-    /// [`retain_all`](`RetainMoreString::retain_all`)
+    /// [`retain_all`](Self::retain_all), with the arguments of the predicate
+    /// bundled into a [`RetainContext`].
     ///
-    /// This is used to demonstrate that
-    /// [`retain_all`](`RetainMoreString::retain_all`) is a strictly more
-    /// powerful abstraction than [`String::retain`] from [`alloc`].
+    /// The context is accessed by name rather than position, so `before` and
+    /// `after` can't be swapped by mistake. It also has the byte offset of
+    /// the current character, and the number of characters retained so far.
     ///
-    /// The predicate therefore only uses argument 1 from [`Self::retain_all`].
+    /// # Usage
     ///
-    /// ## Standard retain docs
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "a1b2c3d4".to_string();
+    /// // Keep the first three letters, and every digit after the fourth byte
+    /// my_string.retain_ctx(|ctx| {
+    ///     if ctx.current().is_ascii_digit() {
+    ///         ctx.byte_offset() >= 4
+    ///     } else {
+    ///         ctx.kept_chars() < 3
+    ///     }
+    /// });
+    /// assert_eq!(my_string, "abc34");
+    /// ```
+    fn retain_ctx<F: FnMut(RetainContext<'_>) -> bool>(&mut self, f: F);
+
+    /// [`retain_all`](Self::retain_all), reporting how many characters and
+    /// bytes were kept and removed.
     ///
-    /// This documentation is taken from [`String::retain`] from [`alloc`].
+    /// Only the counts of the [`RetainReport`] are set, since the pass can't
+    /// stop early.
     ///
-    /// Retains only the characters specified by the predicate.
+    /// # Usage
     ///
-    /// In other words, remove all characters `c` such that `f(c)` returns
-    /// false. This method operates in place, visiting each character exactly
-    /// once in the original order, and preserves the order of the retained
-    /// characters.
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "caf\u{e9}\u{0}!\u{7}".to_string();
+    /// let report = my_string.retain_report(|_, it, _| !it.is_control());
+    /// assert!(report.changed());
+    /// assert_eq!((report.chars_removed, report.bytes_removed), (2, 2));
+    /// assert_eq!((report.chars_kept, report.bytes_kept), (5, 6));
+    /// ```
+    fn retain_report<F: FnMut(&mut str, char, &mut str) -> bool>(&mut self, f: F) -> RetainReport;
+
+    /// [`retain_all`](Self::retain_all), returning whether any characters
+    /// were removed.
     ///
-    /// # Examples
+    /// # Usage
     ///
     /// ```
     /// use retain_more::RetainMoreString as _;
-    /// let mut s = String::from("f_o_ob_ar");
+    /// let mut my_string = "a  b".to_string();
+    /// let no_doubled_spaces = |before: &mut str, it, _: &mut str| !(it == ' ' && before.ends_with(' '));
+    /// assert!(my_string.retain_all_changed(no_doubled_spaces));
+    /// assert!(!my_string.retain_all_changed(no_doubled_spaces));
+    /// assert_eq!(my_string, "a b");
+    /// ```
+    fn retain_all_changed<F: FnMut(&mut str, char, &mut str) -> bool>(&mut self, f: F) -> bool;
+
+    /// [`retain_default`](Self::retain_default), returning whether any
+    /// characters were removed.
     ///
-    /// s.retain_default(|c| c != '_');
+    /// This is useful for invalidating data derived from the string only
+    /// when the string changed.
+    ///
+    /// # Usage
     ///
-    /// assert_eq!(s, "foobar");
     /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "clean".to_string();
+    /// assert!(!my_string.retain_changed(|c| !c.is_control()));
+    /// my_string.push('\u{7}');
+    /// assert!(my_string.retain_changed(|c| !c.is_control()));
+    /// assert_eq!(my_string, "clean");
+    /// ```
+    fn retain_changed<F: FnMut(char) -> bool>(&mut self, f: F) -> bool;
+
+    /// [`retain_all`](Self::retain_all), with a fallible predicate.
     ///
-    /// The exact order may be useful for tracking external state, like an
-    /// index.
+    /// If the predicate returns an error, the pass stops immediately and
+    /// returns that error. The characters already removed stay removed, and
+    /// the current character and everything after it are kept, so the string
+    /// is the retained prefix followed by the unconsidered rest.
+    ///
+    /// # Usage
     ///
     /// ```
     /// use retain_more::RetainMoreString as _;
-    /// let mut s = String::from("abcde");
-    /// let keep = [false, true, true, false, true];
-    /// let mut i = 0;
-    /// s.retain_default(|_| (keep[i], i += 1).0);
-    /// assert_eq!(s, "bce");
+    /// let mut my_string = "a b\0c d".to_string();
+    /// let result = my_string.try_retain_all(|_, it, _| match it {
+    ///     '\0' => Err("unexpected NUL"),
+    ///     it => Ok(it != ' '),
+    /// });
+    /// assert_eq!(result, Err("unexpected NUL"));
+    /// assert_eq!(my_string, "ab\0c d");
     /// ```
-    fn retain_default<F: FnMut(char) -> bool>(&mut self, mut f: F) {
-        self.retain_all(move |_, current, _| f(current))
-    }
-}
+    fn try_retain_all<E, F: FnMut(&mut str, char, &mut str) -> Result<bool, E>>(
+        &mut self,
+        f: F,
+    ) -> Result<(), E>;
 
-// Future work - support this for strings with all allocators once/if <https://github.com/rust-lang/rust/pull/79500> lands
-impl RetainMoreString for String {
-    fn retain_all<F: FnMut(&mut str, char, &mut str) -> bool>(&mut self, mut f: F) {
-        let len = self.len();
-        // This is required for panic safety, see https://github.com/rust-lang/rust/issues/78498
-        // SAFETY: 0..0 is empty and hence that region is valid UTF-8
-        // SAFETY: 0 <= self.len(), since self.len() is a usize
-        unsafe {
-            self.as_mut_vec().set_len(0);
-        }
-        let mut del_bytes = 0;
-        // The index of the start of the region which has not yet been considered.
-        // This is always at a UTF-8 character boundary.
-        let mut idx = 0;
-
-        while idx < len {
-            let ptr = self.as_mut_ptr();
-            // The implementation in `alloc` uses `self.get_unchecked(idx..len)` for
-            // the equivalent section. <https://github.com/rust-lang/rust/blob/a6bd5246da78/library/alloc/src/string.rs#L1243>
-            // This would be unsafe here because the reciever of that method
-            // (`DerefMut::deref_mut(&mut self)`) is the empty `str`, since `len` is set to
-            // 0 above. However, `get_unchecked` requires that the index is
-            // within the bounds of the reciever, not just the allocation of the
-            // reciever. This is not a safety issue within `alloc`, because the
-            // implementation of `get_unchecked` within `core` expands to the
-            // equivalent code as below. However, we cannot make that assumption
-            // here, so have to go the long way around.
-            let ch = unsafe {
-                // SAFETY: `len` came from `self.len()`. Therefore `idx < len` implies `idx` is
-                // within the heap allocation owned by self. Therefore the
-                // result is within the same allocation as `ptr`.
-                let start = ptr.add(idx);
-                // SAFETY: The region is not aliased because the method has a mutable reference
-                // to self. Additionally, there is no other acess across the
-                // loop, and this is the start of the loop body, and no other references exist
-                // before this line. We drop the region before any further
-                // access later in the loop body.
-                let region = slice::from_raw_parts_mut(start, len - idx);
-
-                // `region` is `idx..len` within the original string.
-                // idx is on a character boundary, and the rest of this method has not modified
-                // this region of bytes (except through the `&mut str` as the third closure
-                // parameter, any access through which is required to maintain the UTF-8
-                // invariant of that region)
-                let ch = from_utf8_unchecked_mut(region).chars().next().unwrap();
-                ch
-                // region is dropped here, so its access to the region of
-            };
-            let ch_len = ch.len_utf8();
-            let (before, after) = unsafe {
-                (
-                    // SAFETY: UTF-8 is maintained in the before section by only copying
-                    // a full character at a time.
-                    from_utf8_unchecked_mut(slice::from_raw_parts_mut(ptr, idx - del_bytes)),
-                    // SAFETY: idx + ch_len <= len because self, hence `idx + ch_len` is within the
-                    // allocation of self. was valid UTF-8 by invariant, hence
-                    // after is valid. This does not alias with `before`,
-                    // because `-del_bytes < ch_len`
-                    from_utf8_unchecked_mut(slice::from_raw_parts_mut(
-                        ptr.add(idx + ch_len),
-                        len - idx - ch_len,
-                    )),
-                )
-            };
-            if !f(before, ch, after) {
-                del_bytes += ch_len;
-            } else if del_bytes > 0 {
-                // Copy `ch` del_bytes bytes back.
-                // Use the version in the allocation of self, which is already UTF-8 encoded.
-
-                // Safety: We copy a region which is a single UTF-8 character.
-                // We can't use copy_nonoverlapping here in case del_bytes > ch_len
-                unsafe {
-                    core::ptr::copy(ptr.add(idx), ptr.add(idx - del_bytes), ch_len);
-                }
-            }
+    /// [`retain_all`](Self::retain_all), with a predicate which can stop the
+    /// pass early.
+    ///
+    /// The predicate returns [`ControlFlow::Continue`] with whether to keep
+    /// the current character, or [`ControlFlow::Break`] to stop. When it
+    /// breaks, the current character and everything after it are kept
+    /// without being considered, so only the start of a long string needs to
+    /// be visited.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// use std::ops::ControlFlow;
+    /// let mut my_string = "\u{feff}\r\n\r\n# Title\r\nBody \r\n".to_string();
+    /// // Strip the byte order mark and blank lines before the first heading
+    /// my_string.retain_control_flow(|_, it, _| match it {
+    ///     '#' => ControlFlow::Break(()),
+    ///     _ => ControlFlow::Continue(false),
+    /// });
+    /// assert_eq!(my_string, "# Title\r\nBody \r\n");
+    /// ```
+    fn retain_control_flow<F: FnMut(&mut str, char, &mut str) -> ControlFlow<(), bool>>(
+        &mut self,
+        f: F,
+    );
 
-            // 'Point' idx to the next char
-            idx += ch_len;
-        }
-        // len - del_bytes <= len <= capacity
-        unsafe {
-            self.as_mut_vec().set_len(len - del_bytes);
-        }
-    }
-}
+    /// Keeps characters until the predicate first returns `false`, then
+    /// truncates the string before that character, returning the number of
+    /// bytes removed.
+    ///
+    /// The predicate has the same arguments as in
+    /// [`retain_all`](Self::retain_all), and is not called again after it
+    /// first returns `false`, so only the kept prefix is visited. Nothing is
+    /// moved, since nothing before the cut is removed.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "data EOF\u{0} junk".to_string();
+    /// let removed = my_string.retain_truncate(|before, it, _| !it.is_control() && !before.ends_with("EOF"));
+    /// assert_eq!(my_string, "data EOF");
+    /// assert_eq!(removed, 6);
+    /// ```
+    fn retain_truncate<F: FnMut(&mut str, char, &mut str) -> bool>(&mut self, f: F) -> usize;
 
-/// Implementation of the sealed pattern for [`RetainMoreString`]
-/// See [C-SEALED] from rust-api-guidelines for explanation
-///
-/// [C-SEALED]: https://rust-lang.github.io/api-guidelines/future-proofing.html#sealed-traits-protect-against-downstream-implementations-c-sealed
-mod sealed {
-    use alloc::string::String;
+    /// Retains only the characters specified by the predicate, which is given
+    /// the context as bytes.
+    ///
+    /// This is [`retain_all`](Self::retain_all), but with shared access to the
+    /// retained and unconsidered parts of the string as `&[u8]`, for
+    /// predicates which scan the context bytewise (e.g. for an ASCII
+    /// terminator). Each slice starts and ends on a character boundary, but
+    /// an offset found within it by a bytewise search may fall in the middle
+    /// of a multibyte character.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "key=\"a b\"; next=c d;".to_string();
+    /// // Remove spaces, except those in a value which is closed by a `;`
+    /// my_string.retain_all_bytes_ctx(|before, it, after| {
+    ///     let in_value = before.iter().rev().take_while(|&&b| b != b';').any(|&b| b == b'=');
+    ///     it != ' ' || (in_value && after.contains(&b';'))
+    /// });
+    /// assert_eq!(my_string, "key=\"a b\";next=c d;");
+    /// ```
+    fn retain_all_bytes_ctx<F: FnMut(&[u8], char, &[u8]) -> bool>(&mut self, f: F);
 
-    pub trait Sealed {}
-    impl Sealed for String {}
-}
+    /// [`retain_all`](Self::retain_all), with shared access to the retained
+    /// and unconsidered parts of the string.
+    ///
+    /// For predicates which only read the context, this avoids holding
+    /// mutable borrows, so the predicate can capture other borrows freely,
+    /// such as slices which were borrowed from the string before the call.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "Code: -1234 EOF trailing".to_string();
+    /// let terminator = "EOF";
+    /// // Remove all numbers, including a single leading `'-'`, and everything
+    /// // after the terminator
+    /// my_string.retain_all_ref(|before, it, after| {
+    ///     if before.ends_with(terminator) {
+    ///         false
+    ///     } else {
+    ///         match (it, after.chars().next()) {
+    ///             ('-', Some(c)) => !c.is_ascii_digit(),
+    ///             (c, _) => !c.is_ascii_digit(),
+    ///         }
+    ///     }
+    /// });
+    /// assert_eq!(my_string, "Code:  EOF");
+    /// ```
+    fn retain_all_ref<F: FnMut(&str, char, &str) -> bool>(&mut self, f: F);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use alloc::string::ToString;
+    /// [`retain_all`](Self::retain_all), additionally passing the byte offset
+    /// of the current character in the original string.
+    ///
+    /// The offset is unaffected by the characters which have been removed,
+    /// so is generally not the length of the retained region before it.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut log_line = "user=\u{e9}ve token=42 ok".to_string();
+    /// let mut removed = Vec::new();
+    /// log_line.retain_all_indexed(|offset, _, it, _| {
+    ///     let keep = !it.is_ascii_digit() && it != '\u{e9}';
+    ///     if !keep {
+    ///         removed.push(offset);
+    ///     }
+    ///     keep
+    /// });
+    /// assert_eq!(log_line, "user=ve token= ok");
+    /// assert_eq!(removed, [5, 16, 17]);
+    /// ```
+    fn retain_all_indexed<F: FnMut(usize, &mut str, char, &mut str) -> bool>(&mut self, f: F);
 
-    fn redact(current: char, rest: &mut str) -> bool {
-        match (current, rest.chars().next()) {
-            ('-', Some(c)) => !c.is_ascii_digit(),
-            (c, _) => !c.is_ascii_digit(),
-        }
+    /// [`retain_all`](Self::retain_all), additionally passing the index of the
+    /// current character in the original string.
+    ///
+    /// The index counts [`char`]s (Unicode scalar values), not bytes, and is
+    /// unaffected by the characters which have been removed. This is the
+    /// character counterpart to [`retain_all_indexed`](Self::retain_all_indexed).
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "\u{2603}\u{2603}abc".to_string();
+    /// // Remove every other character, by position
+    /// my_string.retain_all_char_indexed(|index, _, _, _| index % 2 == 0);
+    /// assert_eq!(my_string, "\u{2603}ac");
+    /// ```
+    fn retain_all_char_indexed<F: FnMut(usize, &mut str, char, &mut str) -> bool>(&mut self, f: F);
+
+    /// [`retain_all`](Self::retain_all), recording each call of the predicate
+    /// in a [`RetainTrace`].
+    ///
+    /// This is [`retain_traced_with`](Self::retain_traced_with) without
+    /// snapshots of the context.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let original = "a-b-c".to_string();
+    /// let mut my_string = original.clone();
+    /// let mut seen = 0;
+    /// let trace = my_string.retain_traced(|_, c, _| {
+    ///     seen += 1;
+    ///     c != '-' || seen > 3
+    /// });
+    /// assert_eq!(my_string, "ab-c");
+    /// assert!(!trace.entries()[1].kept);
+    ///
+    /// // Reproduce the result without the (stateful) predicate
+    /// let mut replica = original.clone();
+    /// trace.replay_on(&mut replica).unwrap();
+    /// assert_eq!(replica, my_string);
+    /// ```
+    fn retain_traced<F: FnMut(&mut str, char, &mut str) -> bool>(&mut self, f: F) -> RetainTrace {
+        self.retain_traced_with(0, f)
     }
 
-    fn after_helper<F: FnMut(char, &mut str) -> bool>(input: &str, output: &str, f: F) {
-        let mut input = input.to_string();
-        input.retain_after(f);
+    /// [`retain_all`](Self::retain_all), recording each call of the predicate
+    /// in a [`RetainTrace`], with snapshots of up to `snapshot_len` bytes of
+    /// the context.
+    ///
+    /// The snapshots are the end of the retained region and the start of the
+    /// unconsidered region, i.e. the context nearest to the character, as
+    /// they were after the predicate returned. Each is cut back to a
+    /// character boundary, so may be shorter than `snapshot_len`. If
+    /// `snapshot_len` is `0`, no snapshots are stored.
+    fn retain_traced_with<F: FnMut(&mut str, char, &mut str) -> bool>(
+        &mut self,
+        snapshot_len: usize,
+        f: F,
+    ) -> RetainTrace;
 
-        assert_eq!(&input[..], output);
-    }
-    #[test]
-    fn retain_after() {
-        after_helper("this has no numbers", "this has no numbers", redact);
-        after_helper("54321", "", redact);
-        after_helper("-12345", "", redact);
-        after_helper("--12345", "-", redact);
-        after_helper("-12-3-45--", "--", redact);
+    /// [`retain_all`](Self::retain_all), recording the removed characters in
+    /// `mapper`.
+    ///
+    /// The removals are composed with those already in `mapper`, so after a
+    /// sequence of passes using the same mapper, it maps between offsets in
+    /// the string before the first pass and after the last.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::{OffsetMapper, RetainMoreString as _};
+    /// let mut my_string = "a, b; c".to_string();
+    /// let mut mapper = OffsetMapper::new();
+    /// my_string.retain_all_mapped(&mut mapper, |_, c, _| c != ',' && c != ';');
+    /// my_string.retain_all_mapped(&mut mapper, |_, c, _| c != ' ');
+    /// assert_eq!(my_string, "abc");
+    /// // `c` was at offset 6, and is now at offset 2
+    /// assert_eq!(mapper.map_forward(6), Some(2));
+    /// assert_eq!(mapper.map_backward(2), 6);
+    /// assert_eq!(mapper.map_forward(1), None);
+    /// ```
+    fn retain_all_mapped<F: FnMut(&mut str, char, &mut str) -> bool>(
+        &mut self,
+        mapper: &mut OffsetMapper,
+        f: F,
+    );
+
+    /// Retains only the characters specified by the predicate, returning an
+    /// [`OffsetMapper`] from offsets in the original string to offsets in the
+    /// retained string.
+    ///
+    /// This is a single pass of [`retain_all_mapped`](Self::retain_all_mapped)
+    /// with a new mapper. The mapper stores the coalesced removed runs, so
+    /// its size depends on the number of runs, not the length of the string.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut source = "let  x =\t1;".to_string();
+    /// let map = source.retain_with_offset_map(|c| !c.is_whitespace());
+    /// assert_eq!(source, "letx=1;");
+    /// // An error span pointing at `1`
+    /// assert_eq!(map.map_forward(9), Some(5));
+    /// // Positions in the removed whitespace snap to the following `x`
+    /// assert_eq!(map.map_forward(4), None);
+    /// assert_eq!(map.map_forward_clamped(4), 3);
+    /// ```
+    fn retain_with_offset_map<F: FnMut(char) -> bool>(&mut self, mut f: F) -> OffsetMapper {
+        let mut mapper = OffsetMapper::new();
+        self.retain_all_mapped(&mut mapper, |_, c, _| f(c));
+        mapper
     }
 
-    #[test]
-    fn retain_default() {
-        // Adapted from https://github.com/rust-lang/rust/blob/2ad5292aea63/library/alloc/tests/string.rs#L364-L396
-        let mut s = String::from("α_β_γ");
+    /// Retains only the characters specified by the predicate, adjusting
+    /// `spans` to cover the same text in the retained string.
+    ///
+    /// The spans are byte ranges of the original string, which may be in any
+    /// order, and may overlap each other. Each endpoint is moved back by the
+    /// number of bytes removed before it, so a span which partially overlaps
+    /// removed text is clipped to the text which was kept, and a span of only
+    /// removed text becomes an empty range where that text was.
+    ///
+    /// Nothing is allocated. If the starts of the spans are in increasing
+    /// order, and so are their ends (as with sorted spans which don't nest),
+    /// each endpoint is adjusted as the pass reaches it, so this takes time
+    /// proportional to the length of the string plus the number of spans.
+    /// Otherwise, each run of removed characters adjusts every span, so this
+    /// takes time proportional to the number of runs multiplied by the number
+    /// of spans; for many such spans,
+    /// [`retain_with_offset_map`](Self::retain_with_offset_map) is faster. If
+    /// `f` panics, the spans may not have been adjusted for the characters
+    /// removed up to that point.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut line = "let  x =  1;".to_string();
+    /// // The highlighted `x`, `=` and `1`, and the whole statement
+    /// let mut spans = [5..6, 7..8, 10..11, 0..12];
+    /// line.retain_adjust_spans(|c| c != ' ', &mut spans);
+    /// assert_eq!(line, "letx=1;");
+    /// assert_eq!(spans, [3..4, 4..5, 5..6, 0..7]);
+    /// ```
+    fn retain_adjust_spans<F: FnMut(char) -> bool>(&mut self, f: F, spans: &mut [Range<usize>]);
 
-        s.retain_default(|_| true);
-        assert_eq!(s, "α_β_γ");
+    /// Retains only the characters specified by the predicate, removing the
+    /// corresponding elements of `meta` along with them.
+    ///
+    /// `meta` holds one element for each character of the string, such as
+    /// the colour of each character of a line of a terminal. The predicate is
+    /// given each character with its element, and both are removed if it
+    /// returns false. `meta` is compacted in place by [`Vec::retain`], so
+    /// neither is reallocated.
+    ///
+    /// If `f` panics, the characters and elements which had not yet been
+    /// considered are all kept, so the two stay in step.
+    ///
+    /// # Errors
+    ///
+    /// If the length of `meta` is not the number of characters in the string.
+    /// Neither is modified in that case.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut line = "\u{2714} ok".to_string();
+    /// let mut bold = vec![true, false, true, true];
+    /// line.retain_zip(&mut bold, |c, _| c != ' ').unwrap();
+    /// assert_eq!(line, "\u{2714}ok");
+    /// assert_eq!(bold, [true, true, true]);
+    ///
+    /// assert!(line.retain_zip(&mut vec![false], |_, &bold| bold).is_err());
+    /// ```
+    fn retain_zip<T, F: FnMut(char, &T) -> bool>(
+        &mut self,
+        meta: &mut Vec<T>,
+        f: F,
+    ) -> Result<(), ZipLengthError>;
 
-        s.retain_default(|c| c != '_');
-        assert_eq!(s, "αβγ");
+    /// Retains only the characters whose element of `mask` is `true`.
+    ///
+    /// `mask` holds one decision for each character of the string, such as
+    /// from an earlier analysis. Each run of equal decisions is kept or
+    /// removed as a block.
+    ///
+    /// # Errors
+    ///
+    /// If the length of `mask` is not the number of characters in the
+    /// string. The string is not modified in that case.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "r\u{e9}sum\u{e9}".to_string();
+    /// let mask: Vec<bool> = my_string.chars().map(|c| c.is_ascii()).collect();
+    /// my_string.retain_by_mask(&mask).unwrap();
+    /// assert_eq!(my_string, "rsum");
+    ///
+    /// assert!(my_string.retain_by_mask(&mask).is_err());
+    /// ```
+    fn retain_by_mask(&mut self, mask: &[bool]) -> Result<(), MaskLenError>;
 
-        s.retain_default(|c| c != 'β');
-        assert_eq!(s, "αγ");
+    /// A helper for the common case where only access to the parts of the
+    /// [`String`] which haven't been considered yet is required, i.e. the
+    /// predicate only uses arguments 1 and 2 from [`Self::retain_all`].
+    ///
+    /// The predicate observes the current character, and the rest of the
+    /// string after it exactly as in [`Self::retain_all`]. It can't observe
+    /// the retained prefix, so the implementation for [`String`] never
+    /// constructs it, and the prefix is free to be stale whilst the pass is
+    /// running.
+    fn retain_after<F: FnMut(char, &mut str) -> bool>(&mut self, mut f: F) {
+        self.retain_all(move |_, current, after| f(current, after))
+    }
 
-        s.retain_default(|c| c == 'α');
-        assert_eq!(s, "α");
+    /// Retains only the characters specified by the predicate, replacing
+    /// each retained character with the one it returns.
+    ///
+    /// The predicate is given the current character, and the contents of the
+    /// string after it, as in [`retain_after`](Self::retain_after). It
+    /// returns `None` to remove the character, or `Some` of the character to
+    /// put in its place, which may be the same character.
+    ///
+    /// # Allocation
+    ///
+    /// Replacements are written in place when they fit in the space of the
+    /// characters considered so far, such as when they are no longer than
+    /// the characters they replace. When a replacement doesn't fit, the
+    /// string is moved to a new allocation of at least twice the capacity,
+    /// with the characters not yet considered at its end, leaving room for
+    /// later growth. This reallocates the string, unlike most methods of
+    /// this trait, but the extra memory used is no more than that of
+    /// pushing the replacements onto a new string.
+    ///
+    /// If the predicate panics, the string is left containing the
+    /// replacements for the characters considered before it.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut text = "\u{201c}Tabs\u{201d}\tand\u{2026}\u{200b}".to_string();
+    /// text.retain_map(|c, _| match c {
+    ///     '\u{201c}' | '\u{201d}' => Some('"'),
+    ///     '\t' => Some(' '),
+    ///     '\u{200b}' => None,
+    ///     c => Some(c),
+    /// });
+    /// assert_eq!(text, "\"Tabs\" and\u{2026}");
+    /// ```
+    fn retain_map<F: FnMut(char, &mut str) -> Option<char>>(&mut self, f: F);
 
-        s.retain_default(|_| false);
-        assert_eq!(s, "");
+    /// Retains only the characters specified by the predicate, replacing
+    /// characters with strings as directed.
+    ///
+    /// This is [`retain_map`](Self::retain_map), where each character can
+    /// be kept, removed, or replaced with a string, as described by the
+    /// [`RetainDecision`] returned by the predicate. Replacements are not
+    /// given to the predicate, so they are never replaced again.
+    ///
+    /// # Allocation
+    ///
+    /// As in [`retain_map`](Self::retain_map), the string is moved to a
+    /// larger allocation when a replacement doesn't fit in place. Allocation
+    /// failure is reported as an error rather than aborting.
+    ///
+    /// If the predicate panics, the string is left containing the
+    /// replacements for the characters considered before it.
+    ///
+    /// # Errors
+    ///
+    /// If growing the string fails. The pass stops at the character whose
+    /// replacement didn't fit, and no text is lost: the characters before it
+    /// are kept, removed or replaced as decided, and it and the rest of the
+    /// string are kept unchanged, as in
+    /// [`try_retain_all`](Self::try_retain_all).
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::{RetainDecision, RetainMoreString as _};
+    /// let mut html = "1 < 2 & \u{7}3 > 2".to_string();
+    /// html.retain_replace(|c, _| match c {
+    ///     '<' => RetainDecision::Replace("&lt;"),
+    ///     '>' => RetainDecision::Replace("&gt;"),
+    ///     '&' => RetainDecision::Replace("&amp;"),
+    ///     c if c.is_control() => RetainDecision::Remove,
+    ///     _ => RetainDecision::Keep,
+    /// })?;
+    /// assert_eq!(html, "1 &lt; 2 &amp; 3 &gt; 2");
+    /// # Ok::<(), std::collections::TryReserveError>(())
+    /// ```
+    fn retain_replace<'r, F: FnMut(char, &mut str) -> RetainDecision<'r>>(
+        &mut self,
+        f: F,
+    ) -> Result<(), TryReserveError>;
+
+    /// Retains only the characters specified by the predicate, replacing
+    /// each run of consecutive removed characters with `replacement`.
+    ///
+    /// The predicate is given the current character, and the contents of the
+    /// string after it, as in [`retain_after`](Self::retain_after). A new
+    /// iterator is created from `replacement` for each run, so each run is
+    /// replaced by the whole sequence, once.
+    ///
+    /// # Allocation
+    ///
+    /// As in [`retain_map`](Self::retain_map), the string is moved to a
+    /// larger allocation when a replacement doesn't fit in place.
+    ///
+    /// If the predicate panics, the string is left containing the characters
+    /// kept before it, and the replacements for the runs which ended before
+    /// it.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut s = "top -123- secret -456-".to_string();
+    /// s.retain_splice(|c, _| !c.is_ascii_digit(), Some('\u{2026}'));
+    /// assert_eq!(s, "top -\u{2026}- secret -\u{2026}-");
+    /// ```
+    fn retain_splice<F, I>(&mut self, f: F, replacement: I)
+    where
+        F: FnMut(char, &mut str) -> bool,
+        I: IntoIterator<Item = char> + Clone;
+
+    /// Replaces the characters rejected by the predicate with `fill`, rather
+    /// than removing them.
+    ///
+    /// The predicate is given the current character, and the contents of the
+    /// string after it, as in [`retain_after`](Self::retain_after). If `fill`
+    /// is shorter than a rejected character, the rest of the string is
+    /// shifted down, so the string gets shorter in bytes, but not in
+    /// characters.
+    ///
+    /// # Errors
+    ///
+    /// If `fill` is longer than some character of the string, since it
+    /// might not fit in place. This is checked before the predicate is called, and
+    /// the string is unchanged in that case. An ASCII `fill` never fails.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut card = "4111 1111 1111 1234".to_string();
+    /// let mut digits_after = 0;
+    /// card.mask_retain(
+    ///     |c, after| {
+    ///         digits_after = after.chars().filter(char::is_ascii_digit).count();
+    ///         !c.is_ascii_digit() || digits_after < 4
+    ///     },
+    ///     '#',
+    /// )
+    /// .unwrap();
+    /// assert_eq!(card, "#### #### #### 1234");
+    /// ```
+    fn mask_retain<F: FnMut(char, &mut str) -> bool>(
+        &mut self,
+        f: F,
+        fill: char,
+    ) -> Result<(), FillTooWide>;
+
+    /// A helper for the common case where only access to the parts of the
+    /// [`String`] which have already been retained is required, i.e. the
+    /// predicate only uses arguments 0 and 1 from [`Self::retain_all`].
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "Message body EOF\n-- \nSent from my phone".to_string();
+    /// // Remove everything after the first occurence of `"EOF"`
+    /// my_string.retain_before(|before, _| !before.ends_with("EOF"));
+    /// assert_eq!(my_string, "Message body EOF");
+    /// ```
+    fn retain_before<F: FnMut(&mut str, char) -> bool>(&mut self, mut f: F) {
+        self.retain_all(move |before, current, _| f(before, current))
+    }
+
+    /// A helper for threading a state value through every call of the
+    /// predicate, which is returned once the whole string has been
+    /// considered. The predicate is given the state, and arguments 1 and 2
+    /// from [`Self::retain_all`].
+    ///
+    /// This avoids needing a separate `let mut` binding for state which is
+    /// captured by the predicate, when the final state is needed afterwards.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "a,b;;c".to_string();
+    /// // Remove the delimiters, remembering the last one
+    /// let last = my_string.retain_scan(None, |last, it, _| {
+    ///     let delimiter = matches!(it, ',' | ';');
+    ///     if delimiter {
+    ///         *last = Some(it);
+    ///     }
+    ///     !delimiter
+    /// });
+    /// assert_eq!(my_string, "abc");
+    /// assert_eq!(last, Some(';'));
+    /// ```
+    fn retain_scan<S, F: FnMut(&mut S, char, &mut str) -> bool>(&mut self, init: S, mut f: F) -> S {
+        let mut state = init;
+        self.retain_after(|current, after| f(&mut state, current, after));
+        state
+    }
+
+    /// A helper for predicates which depend on how many characters have been
+    /// retained so far, which is passed as the first argument, followed by
+    /// arguments 1 and 2 from [`Self::retain_all`].
+    ///
+    /// The count only increases when the predicate returns `true`. This
+    /// avoids counting the characters of `before` on every call, which would
+    /// make the pass quadratic.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "a b\u{e9} cd ef gh".to_string();
+    /// // Keep only the first 5 characters which aren't spaces
+    /// my_string.retain_counted(|kept, it, _| kept < 5 && it != ' ');
+    /// assert_eq!(my_string, "ab\u{e9}cd");
+    /// ```
+    fn retain_counted<F: FnMut(usize, char, &mut str) -> bool>(&mut self, mut f: F) {
+        let mut kept = 0;
+        self.retain_after(|current, after| {
+            let keep = f(kept, current, after);
+            kept += usize::from(keep);
+            keep
+        });
+    }
+
+    /// [`String::retain`], as a method of this trait.
+    ///
+    /// The provided implementation uses
+    /// [`retain_all`](`RetainMoreString::retain_all`), which demonstrates
+    /// that it is a strictly more powerful abstraction than [`String::retain`]
+    /// from [`alloc`]; the predicate only uses argument 1 from
+    /// [`Self::retain_all`].
+    ///
+    /// The predicate observes only the current character. The
+    /// implementation for [`String`] delegates to [`String::retain`] through
+    /// [`retain_default_dyn`](Self::retain_default_dyn), so there is only one
+    /// copy of the loop, at the cost of an indirect call for each character.
+    /// Since this calls [`String::retain`] directly, the behaviour is exactly
+    /// that of [`String::retain`], including when the predicate panics.
+    ///
+    /// ## Standard retain docs
+    ///
+    /// This documentation is taken from [`String::retain`] from [`alloc`].
+    ///
+    /// Retains only the characters specified by the predicate.
+    ///
+    /// In other words, remove all characters `c` such that `f(c)` returns
+    /// false. This method operates in place, visiting each character exactly
+    /// once in the original order, and preserves the order of the retained
+    /// characters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut s = String::from("f_o_ob_ar");
+    ///
+    /// s.retain_default(|c| c != '_');
+    ///
+    /// assert_eq!(s, "foobar");
+    /// ```
+    ///
+    /// The exact order may be useful for tracking external state, like an
+    /// index.
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut s = String::from("abcde");
+    /// let keep = [false, true, true, false, true];
+    /// let mut i = 0;
+    /// s.retain_default(|_| (keep[i], i += 1).0);
+    /// assert_eq!(s, "bce");
+    /// ```
+    fn retain_default<F: FnMut(char) -> bool>(&mut self, mut f: F) {
+        self.retain_all(move |_, current, _| f(current))
+    }
+
+    /// [`retain_default`](Self::retain_default), with the predicate as a
+    /// trait object.
+    ///
+    /// As with [`retain_all_dyn`](Self::retain_all_dyn), there is only one
+    /// copy of this however many predicates it is used with. The
+    /// implementation of [`retain_default`](Self::retain_default) for
+    /// [`String`] uses this.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut filter: Box<dyn FnMut(char) -> bool> = Box::new(|c| c != '_');
+    /// let mut my_string = "f_o_o".to_string();
+    /// my_string.retain_default_dyn(&mut filter);
+    /// assert_eq!(my_string, "foo");
+    /// ```
+    fn retain_default_dyn(&mut self, f: &mut dyn FnMut(char) -> bool);
+
+    /// Retains only the characters in `keep`.
+    ///
+    /// `keep` is any [`CharPattern`], such as a character, an array or slice
+    /// of characters, a range of characters, or a predicate. This is
+    /// [`retain_default`](Self::retain_default) with the pattern's
+    /// predicate.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut s = "Hello, World! 123".to_string();
+    /// s.retain_matching('a'..='z');
+    /// assert_eq!(s, "elloorld");
+    /// ```
+    fn retain_matching<P: CharPattern>(&mut self, keep: P) {
+        self.retain_default(keep.into_predicate());
+    }
+
+    /// Removes the characters in `remove`.
+    ///
+    /// This is the opposite of [`retain_matching`](Self::retain_matching).
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut s = "zero\u{200b}width\u{feff}".to_string();
+    /// s.remove_matching(['\u{200B}', '\u{FEFF}']);
+    /// assert_eq!(s, "zerowidth");
+    /// ```
+    fn remove_matching<P: CharPattern>(&mut self, remove: P) {
+        let mut remove = remove.into_predicate();
+        self.retain_default(|c| !remove(c));
+    }
+
+    /// Retains only the characters specified by the predicate, which is given
+    /// the first byte of each character.
+    ///
+    /// The predicate is called once per character, and its decision applies
+    /// to the whole character, so continuation bytes are never passed to it
+    /// and the string stays valid UTF-8. This suits table driven predicates,
+    /// since no characters are decoded.
+    ///
+    /// For ASCII characters, the byte is the character itself. Otherwise, the
+    /// byte only identifies a range of characters:
+    ///  - `0xC2..=0xDF`: the two byte characters, `'\u{80}'..='\u{7FF}'`.
+    ///  - `0xE0..=0xEF`: the three byte characters, `'\u{800}'..='\u{FFFF}'`.
+    ///  - `0xF0..=0xF4`: the four byte characters, `'\u{10000}'..`.
+    ///
+    /// Within each of those, the lead byte is the character's code point
+    /// shifted right by 6, 12 or 18 bits, plus `0xC0`, `0xE0` or `0xF0`.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "caf\u{e9} \u{2615}\u{1F980}!".to_string();
+    /// // Remove every character outside the Basic Multilingual Plane
+    /// my_string.retain_by_lead_byte(|b| b < 0xF0);
+    /// assert_eq!(my_string, "caf\u{e9} \u{2615}!");
+    /// ```
+    fn retain_by_lead_byte<F: FnMut(u8) -> bool>(&mut self, f: F);
+
+    /// Retains only the characters specified by the predicate, which is also
+    /// given the UTF-8 encoding of each character.
+    ///
+    /// The arguments of the predicate are:
+    ///  - 0: `&[u8]`; The bytes of the current character, borrowed from the
+    ///    string itself rather than encoded again, so between 1 and 4 bytes
+    ///    long.
+    ///  - 1: [`char`]; The current character being considered.
+    ///  - 2: `&mut str`; The parts of `self` yet to be considered, as in
+    ///    [`retain_all`](Self::retain_all).
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// // Byte patterns from elsewhere, such as a tokenizer's vocabulary
+    /// let banned: [&[u8]; 2] = [b"\xE2\x80\x8B", b"\xEF\xBB\xBF"];
+    /// let mut my_string = "\u{feff}zero\u{200b}width".to_string();
+    /// my_string.retain_char_bytes(|bytes, _, _| !banned.contains(&bytes));
+    /// assert_eq!(my_string, "zerowidth");
+    /// ```
+    fn retain_char_bytes<F: FnMut(&[u8], char, &mut str) -> bool>(&mut self, f: F);
+
+    /// Retains only the characters specified by the predicate, which is given
+    /// the simple case folding of each character rather than the character
+    /// itself. The original character is what is kept, so the casing of the
+    /// retained text is unchanged.
+    ///
+    /// The folding used is the character's uppercase mapping followed by its
+    /// lowercase mapping, each used only when it is a single character. This
+    /// gives the same character for every case variant, including letters
+    /// with several lowercase forms, so `'Σ'`, `'σ'` and the final `'ς'` are
+    /// all passed as `'σ'`, and `'ſ'` (long s) is passed as `'s'`. Characters
+    /// whose full case folding is several characters are passed through their
+    /// simple folding, so `'ß'` is passed as `'ß'` (not `"ss"`), and
+    /// `'\u{130}'` (`'İ'`) is passed unchanged.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "AbrAcadAbra, \u{c9}t\u{e9}".to_string();
+    /// my_string.retain_folded(|c| !"aeiou\u{e9}".contains(c));
+    /// assert_eq!(my_string, "brcdbr, t");
+    /// ```
+    fn retain_folded<F: FnMut(char) -> bool>(&mut self, mut f: F) {
+        self.retain_default(move |current| f(simple_fold(current)))
+    }
+
+    /// Retains only the characters specified by the predicate, returning a
+    /// [`DeletionScript`] which describes what was removed.
+    ///
+    /// The script can then be [applied](DeletionScript::apply) to other copies
+    /// of the original string, to reproduce the same result without running
+    /// the predicate again. This is useful when the same text is held in
+    /// several places, as only the (usually small) script needs to be
+    /// shared.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "a1b22c333".to_string();
+    /// let mut replica = my_string.clone();
+    ///
+    /// let script = my_string.retain_diff(|c| !c.is_ascii_digit());
+    /// assert_eq!(script.deletions(), &[(1, 1), (3, 2), (6, 3)]);
+    ///
+    /// script.apply(&mut replica).unwrap();
+    /// assert_eq!(replica, my_string);
+    /// ```
+    fn retain_diff<F: FnMut(char) -> bool>(&mut self, f: F) -> DeletionScript;
+
+    /// Retains only the characters specified by the predicate, returning the
+    /// byte ranges of the original string which were removed.
+    ///
+    /// The ranges are in ascending order, and adjacent removed characters
+    /// are merged into a single range, so the ranges never overlap or touch.
+    /// Each range starts and ends on a character boundary of the original
+    /// string. These are the [deletions](DeletionScript::deletions) of
+    /// [`retain_diff`](Self::retain_diff), as ranges.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut line = "  let x = 1;  ".to_string();
+    /// let ranges = line.retain_removed_ranges(|c| c != ' ');
+    /// assert_eq!(line, "letx=1;");
+    /// assert_eq!(ranges, [0..2, 5..6, 7..8, 9..10, 12..14]);
+    /// ```
+    fn retain_removed_ranges<F: FnMut(char) -> bool>(&mut self, f: F) -> Vec<Range<usize>> {
+        let deletions = self.retain_diff(f).into_deletions();
+        deletions
+            .into_iter()
+            .map(|(offset, len)| offset..offset + len)
+            .collect()
+    }
+
+    /// Retains only the characters specified by the predicate, reporting each
+    /// removed character to `observer`.
+    ///
+    /// This keeps the decision logic in `f` separate from any bookkeeping
+    /// about what was removed, such as metrics. The observer is given the
+    /// byte offset of each removed character in the original string, and any
+    /// type implementing [`RemovalObserver`] can be used, including closures.
+    ///
+    /// The observer is called for a character immediately after `f` decides to
+    /// remove it, so before `f` is called for the next character. This means
+    /// that if `f` (or the observer) panics later, every observer call which
+    /// has already happened corresponds to a character which was removed.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "user: bob\u{7}\u{0}".to_string();
+    /// let mut removed = Vec::new();
+    /// my_string.retain_observed(|c| !c.is_control(), |offset, c| removed.push((offset, c)));
+    /// assert_eq!(my_string, "user: bob");
+    /// assert_eq!(removed, [(9, '\u{7}'), (10, '\u{0}')]);
+    /// ```
+    fn retain_observed<F, O>(&mut self, mut f: F, observer: O)
+    where
+        F: FnMut(char) -> bool,
+        O: RemovalObserver,
+    {
+        self.retain_observed_until(
+            |c| {
+                if f(c) {
+                    RetainAction::Keep
+                } else {
+                    RetainAction::Remove
+                }
+            },
+            observer,
+        )
+    }
+
+    /// [`retain_observed`](Self::retain_observed), where the predicate can
+    /// also end the pass early, by keeping or removing the rest of the string
+    /// at once.
+    ///
+    /// When the predicate returns [`RetainAction::RemoveRest`], the current
+    /// character and everything after it are removed in one step, and then
+    /// the observer is called for each of them in order, with their original
+    /// offsets, exactly as if each had been removed individually. With
+    /// [`RetainAction::KeepRest`], the rest is kept in one move, and the
+    /// observer is not called again.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::{RetainAction, RetainMoreString as _};
+    /// let mut my_string = "key=value # comment".to_string();
+    /// let mut removed = String::new();
+    /// my_string.retain_observed_until(
+    ///     |c| match c {
+    ///         '#' => RetainAction::RemoveRest,
+    ///         ' ' => RetainAction::Remove,
+    ///         _ => RetainAction::Keep,
+    ///     },
+    ///     |_, c| removed.push(c),
+    /// );
+    /// assert_eq!(my_string, "key=value");
+    /// assert_eq!(removed, " # comment");
+    /// ```
+    fn retain_observed_until<F, O>(&mut self, f: F, observer: O)
+    where
+        F: FnMut(char) -> RetainAction,
+        O: RemovalObserver;
+
+    /// Retains only the characters specified by the predicate, appending an
+    /// entry to `log` for each removed character.
+    ///
+    /// Each entry is the byte offset of the removed character in the original
+    /// string, and the character itself, in the order they were removed. The
+    /// retained string and the new entries of the log together describe the
+    /// original string exactly. Nothing is allocated apart from growing
+    /// `log`.
+    ///
+    /// This is [`retain_observed`](Self::retain_observed) with an observer
+    /// which pushes onto `log`.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut field = "card 4111-1111".to_string();
+    /// let mut log = Vec::new();
+    /// field.retain_logged(|c| !c.is_ascii_digit(), &mut log);
+    /// assert_eq!(field, "card -");
+    /// assert_eq!(log[..2], [(5, '4'), (6, '1')]);
+    /// assert_eq!(log.len(), 8);
+    ///
+    /// // The original can be rebuilt from the log
+    /// for &(offset, c) in &log {
+    ///     field.insert(offset, c);
+    /// }
+    /// assert_eq!(field, "card 4111-1111");
+    /// ```
+    fn retain_logged<F: FnMut(char) -> bool>(&mut self, f: F, log: &mut Vec<(usize, char)>) {
+        self.retain_observed(f, |offset, c| log.push((offset, c)));
+    }
+
+    /// Retains only the characters specified by the predicate, configured by
+    /// `opts`, and reports what happened.
+    ///
+    /// See [`RetainOptions`] for the available knobs. These are all handled by
+    /// a single pass, and can be combined freely. When combined, the checks
+    /// for each character happen in this order:
+    ///  1. If the [cancel token](RetainOptions::cancel_token) is set, or the
+    ///     [removal budget](RetainOptions::max_removals) is used up, the rest
+    ///     of the string is kept.
+    ///  2. If the character is [protected](RetainOptions::protected), it is
+    ///     kept.
+    ///  3. Otherwise, `f` decides whether to keep it, and the
+    ///     [observer](RetainOptions::observer) is called if it is removed.
+    ///
+    /// The options are taken by value, since they may hold a mutable borrow
+    /// of the observer.
+    ///
+    /// # Allocation
+    ///
+    /// Unlike most methods of this trait, this reallocates the string
+    /// when `opts` has the [`ShrinkPolicy::ToFit`] shrink policy and
+    /// anything was removed. With the default policy, the allocation is
+    /// unchanged.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::{RetainMoreString as _, RetainOptions};
+    /// let mut my_string = "id: 12-34-56".to_string();
+    /// // Never remove the label
+    /// let protected = [0..3];
+    /// let opts = RetainOptions::new().protected(&protected);
+    /// let report = my_string.retain_with_options(opts, |c| c.is_ascii_digit());
+    /// assert_eq!(my_string, "id:123456");
+    /// assert_eq!(report.chars_removed, 3);
+    /// ```
+    fn retain_with_options<F: FnMut(char) -> bool>(
+        &mut self,
+        opts: RetainOptions<'_>,
+        f: F,
+    ) -> RetainReport;
+
+    /// Retains only the characters specified by the predicate, reporting how
+    /// many characters of each class were kept and removed.
+    ///
+    /// The characters are classified during the same pass, as described in
+    /// [`ClassCounts`]. Reports from several strings can be aggregated with
+    /// `+=`.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "name=\u{1F980} 42!".to_string();
+    /// let report = my_string.retain_classified(|c| c.is_alphanumeric());
+    /// assert_eq!(my_string, "name42");
+    /// assert_eq!(report.removed.punctuation, 2);
+    /// assert_eq!(report.removed.whitespace, 1);
+    /// assert_eq!(report.removed.other, 1);
+    /// assert_eq!(report.bytes_removed, 7);
+    /// ```
+    fn retain_classified<F: FnMut(char) -> bool>(&mut self, f: F) -> ClassifiedReport;
+
+    /// Retains only the characters specified by the predicate, optimised for
+    /// when at most `expected_kept` characters are kept.
+    ///
+    /// While no more than `expected_kept` characters have been kept, they are
+    /// gathered into a separate buffer, and written to the start of the
+    /// string once at the end, so the removed bulk of the string is never
+    /// moved. If more characters than that are kept, the gathered characters
+    /// are written back, and the rest of the string is filtered as by
+    /// [`retain_default`](Self::retain_default), so the result is the same
+    /// whatever the hint, and the predicate is called once for each
+    /// character in order.
+    ///
+    /// If the predicate panics before the hint is exceeded, the string is
+    /// left unchanged.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut blob = "x".repeat(10_000) + "555-0123" + &"y".repeat(10_000);
+    /// blob.retain_sparse(12, |c| c.is_ascii_digit());
+    /// assert_eq!(blob, "5550123");
+    /// ```
+    fn retain_sparse<F: FnMut(char) -> bool>(&mut self, expected_kept: usize, f: F);
+
+    /// [`retain_sparse`](Self::retain_sparse), gathering the kept characters
+    /// in `scratch`, which avoids allocating when it is reused.
+    fn retain_sparse_with_scratch<F: FnMut(char) -> bool>(
+        &mut self,
+        expected_kept: usize,
+        scratch: &mut RetainScratch,
+        f: F,
+    );
+
+    /// Starts a retain which is run in steps, each of which copies a bounded
+    /// number of bytes.
+    ///
+    /// This is for when the time spent in a single call must be bounded: see
+    /// [`RetainBudgeted::step`]. The result once finished is the same as
+    /// [`retain_default`](Self::retain_default).
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "a-b-c-d".to_string();
+    /// let mut retain = my_string.retain_budgeted(|c| c != '-');
+    /// while !retain.step(2).finished {
+    ///     // Do other work
+    /// }
+    /// drop(retain);
+    /// assert_eq!(my_string, "abcd");
+    /// ```
+    fn retain_budgeted<F: FnMut(char) -> bool>(&mut self, f: F) -> RetainBudgeted<'_, F>;
+
+    /// Retains only the characters specified by the predicate, but never
+    /// reduces the string below `min_chars` characters.
+    ///
+    /// Once removing any more characters would leave fewer than `min_chars`,
+    /// the rest of the string is kept without calling the predicate. If the
+    /// string already has at most `min_chars` characters, the predicate is
+    /// never called.
+    ///
+    /// This counts the characters of the string up front, so takes one extra
+    /// (read only) pass over the string, i.e. it is still `O(len)`.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "a.b.c.d".to_string();
+    /// my_string.retain_with_min_len(5, |c| c != '.');
+    /// assert_eq!(my_string, "abc.d");
+    /// ```
+    fn retain_with_min_len<F: FnMut(char) -> bool>(&mut self, min_chars: usize, f: F);
+
+    /// Removes the characters rejected by the predicate, until `max_removals`
+    /// characters have been removed, returning how many were removed.
+    ///
+    /// Once the limit is reached, the predicate is not called again, and the
+    /// rest of the string is kept, moving it down as a single block.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "a\nb\nc\nd\ne".to_string();
+    /// assert_eq!(my_string.remove_limited(|c| c != '\n', 3), 3);
+    /// assert_eq!(my_string, "abcd\ne");
+    /// ```
+    fn remove_limited<F: FnMut(char) -> bool>(&mut self, f: F, max_removals: usize) -> usize;
+
+    /// Retains only the characters specified by the predicate, until
+    /// `max_kept` characters have been kept, returning how many were kept.
+    ///
+    /// Once the limit is reached, the predicate is not called again, and the
+    /// rest of the string is removed by truncating it.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "#a #b #c #d".to_string();
+    /// assert_eq!(my_string.keep_limited(|c| c != ' ', 6), 6);
+    /// assert_eq!(my_string, "#a#b#c");
+    /// ```
+    fn keep_limited<F: FnMut(char) -> bool>(&mut self, f: F, max_kept: usize) -> usize;
+
+    /// Retains only the characters specified by the predicate, except that
+    /// the first `head` and the last `tail` characters are always kept.
+    ///
+    /// The predicate is only called for the characters between the two
+    /// protected ends. If `head + tail` is at least the number of characters
+    /// in the string, the whole string is kept and the predicate is never
+    /// called.
+    ///
+    /// The end of the head is found by a forward scan of `head` characters,
+    /// and the start of the tail by a backward scan of `tail` characters, so
+    /// the whole string is never counted.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut id = "abcd-1234-5678-wxyz".to_string();
+    /// id.retain_protect_ends(4, 4, |_| false);
+    /// assert_eq!(id, "abcdwxyz");
+    /// ```
+    fn retain_protect_ends<F: FnMut(char) -> bool>(&mut self, head: usize, tail: usize, f: F);
+
+    /// Retains part of each run of identical characters.
+    ///
+    /// The predicate is called once for each maximal run of repeated
+    /// characters, with the character and the length of the run (in
+    /// characters), and returns how many copies of the character to keep.
+    /// This is clamped to the length of the run. The kept copies are the ones
+    /// at the start of the run, and the rest of the run is removed at once.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "Wow!!!! Sooooo good?!?".to_string();
+    /// // Keep at most 2 of any repeated punctuation, and drop long runs of
+    /// // letters entirely
+    /// my_string.retain_char_runs(|c, len| match c {
+    ///     '!' | '?' => 2,
+    ///     _ if len > 3 => 0,
+    ///     _ => len,
+    /// });
+    /// assert_eq!(my_string, "Wow!! S good?!?");
+    /// ```
+    fn retain_char_runs<F: FnMut(char, usize) -> usize>(&mut self, f: F);
+
+    /// Normalizes the indentation at the start of every line, in place.
+    ///
+    /// Only transformations which shrink the string are supported, so this
+    /// never reallocates. See [`IndentOptions`] for what counts as indentation
+    /// and for the available transformations. All lines are handled in a
+    /// single compaction pass, and `"\r\n"` line endings are preserved.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::{IndentOptions, RetainMoreString as _};
+    /// let mut my_string = "fn main() {\n\t\tprintln!();\n    }\n".to_string();
+    /// my_string.normalize_indent_in_place(&IndentOptions::Cap(1));
+    /// assert_eq!(my_string, "fn main() {\n\tprintln!();\n }\n");
+    /// ```
+    fn normalize_indent_in_place(&mut self, opts: &IndentOptions);
+
+    /// Removes the common leading whitespace from every line, in place,
+    /// returning the number of columns removed.
+    ///
+    /// This is an in place version of `textwrap::dedent`. The first pass
+    /// finds the longest indentation (spaces and tabs) shared by every line
+    /// which is not blank, where a blank line contains only whitespace. This
+    /// comparison is literal, so a tab and the equivalent number of spaces
+    /// are different, and a line indented with tabs shares no indentation
+    /// with a line indented with spaces. The second pass removes that many
+    /// bytes from the start of each non-blank line.
+    ///
+    /// Blank lines don't affect the amount removed, but lose up to that much
+    /// leading indentation, whatever it is made of. Line endings, including
+    /// `"\r\n"`, are preserved. These are the only differences from
+    /// `textwrap::dedent`, which removes all whitespace from blank lines and
+    /// converts `"\r\n"` to `"\n"`.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "
+    ///     fn main() {
+    ///         println!();
+    ///     }"
+    ///     .to_string();
+    /// assert_eq!(my_string.dedent_in_place(), 4);
+    /// assert_eq!(my_string, "\nfn main() {\n    println!();\n}");
+    /// ```
+    fn dedent_in_place(&mut self) -> usize;
+
+    /// Removes the common leading whitespace from every line, in place,
+    /// returning the number of columns removed.
+    ///
+    /// This is the same as [`dedent_in_place`](Self::dedent_in_place), under
+    /// the name used by `textwrap` and Python's `textwrap` module. Unlike
+    /// `textwrap`, a line containing only whitespace only loses the common
+    /// indentation, so any whitespace beyond it is kept.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "\r\n\t\tif x:\r\n\t\t\treturn\r\n".to_string();
+    /// assert_eq!(my_string.dedent(), 2);
+    /// assert_eq!(my_string, "\r\nif x:\r\n\treturn\r\n");
+    ///
+    /// let mut my_string = "    a\n        \n    b".to_string();
+    /// assert_eq!(my_string.dedent(), 4);
+    /// assert_eq!(my_string, "a\n    \nb");
+    /// ```
+    fn dedent(&mut self) -> usize {
+        self.dedent_in_place()
+    }
+
+    /// Removes blank lines beyond the first `max_consecutive` of each run of
+    /// consecutive blank lines, in place.
+    ///
+    /// A blank line is one which is empty or contains only whitespace. Each
+    /// removed line is removed along with its terminator, and the surplus of
+    /// each run is removed at once. This uses the default
+    /// [`BlankLineOptions`]; see
+    /// [`collapse_blank_lines_with`](Self::collapse_blank_lines_with) for more
+    /// control.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "# Title\n\n\n\nSome text\n\n\n".to_string();
+    /// my_string.collapse_blank_lines(1);
+    /// assert_eq!(my_string, "# Title\n\nSome text\n\n");
+    /// ```
+    fn collapse_blank_lines(&mut self, max_consecutive: usize) {
+        self.collapse_blank_lines_with(&BlankLineOptions::new(max_consecutive))
+    }
+
+    /// Removes surplus blank lines as configured by `opts`, in place.
+    ///
+    /// See [`collapse_blank_lines`](Self::collapse_blank_lines).
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::{BlankEdges, BlankLineOptions, RetainMoreString as _};
+    /// let mut my_string = "\n\nSome text\n\n\nMore text\n\n".to_string();
+    /// my_string.collapse_blank_lines_with(&BlankLineOptions {
+    ///     leading: BlankEdges::Strip,
+    ///     trailing: BlankEdges::Strip,
+    ///     ..BlankLineOptions::new(1)
+    /// });
+    /// assert_eq!(my_string, "Some text\n\nMore text\n");
+    /// ```
+    fn collapse_blank_lines_with(&mut self, opts: &BlankLineOptions);
+
+    /// Removes the insignificant whitespace from a JSON document, in place.
+    ///
+    /// Whitespace (`' '`, `'\t'`, `'\n'` and `'\r'`) is removed everywhere
+    /// except inside string literals. Escape sequences are respected, so an
+    /// escaped quote (`\"`) does not end a string literal.
+    ///
+    /// This is only a small state machine which tracks string literals, not a
+    /// JSON parser. The structure of the document is not validated, so any
+    /// input without an unterminated string is accepted, and the result is
+    /// only valid JSON if the input was.
+    ///
+    /// # Errors
+    ///
+    /// If a string literal is not terminated, the whitespace before it is
+    /// removed, everything from its opening quote onwards is left untouched,
+    /// and the offset of the opening quote in the original string is
+    /// returned.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut document = r#"{
+    ///     "name": "Ferris the crab",
+    ///     "friends": [ ]
+    /// }"#
+    /// .to_string();
+    /// document.minify_json_whitespace_in_place().unwrap();
+    /// assert_eq!(document, r#"{"name":"Ferris the crab","friends":[]}"#);
+    /// ```
+    fn minify_json_whitespace_in_place(&mut self) -> Result<(), JsonMinifyError>;
+
+    /// Retains only the characters specified by the predicate, where the
+    /// predicate can see the most recently retained character.
+    ///
+    /// The arguments of the predicate are:
+    ///  - 0: [`Option<char>`]; The last character for which the predicate
+    ///    returned `true`, or `None` if nothing has been kept yet. This is
+    ///    *not* the previous character of the original string if that
+    ///    character was removed.
+    ///  - 1: [`char`]; The current character being considered.
+    ///
+    /// This is the same as `before.chars().next_back()` in
+    /// [`retain_all`](Self::retain_all), but the character is remembered when
+    /// it is kept rather than decoded from the string again. To also see the
+    /// rest of the string, use
+    /// [`retain_with_last_kept_after`](Self::retain_with_last_kept_after).
+    /// For the previous character of the original string whether or not it
+    /// was kept, use [`retain_adjacent`](Self::retain_adjacent).
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "Too   many    spaces ".to_string();
+    /// my_string.retain_with_last_kept(|last, it| !(it == ' ' && last == Some(' ')));
+    /// assert_eq!(my_string, "Too many spaces ");
+    /// ```
+    ///
+    /// This has the same pitfall as using `before` in [`retain_all`](Self::retain_all):
+    /// if `f` returns `false` because of the last kept character, that
+    /// character is unchanged for the next call. Removing the first letter of
+    /// each word by looking for a kept `' '` therefore removes every letter
+    /// after the first space.
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "Remove the first letter of each word".to_string();
+    /// my_string.retain_with_last_kept(|last, it| it == ' ' || !matches!(last, Some(' ') | None));
+    /// assert_eq!(&my_string, "      ");
+    /// ```
+    ///
+    /// Instead, the decision has to consider the current character too. Here,
+    /// a letter is only removed when the last kept character is a space *and*
+    /// that space was the previous character of the original string. Tracking
+    /// that needs one `bool` of state:
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "Remove the first letter of each word".to_string();
+    /// let mut previous_kept = true;
+    /// my_string.retain_with_last_kept(|last, it| {
+    ///     let at_word_start = previous_kept && matches!(last, Some(' ') | None);
+    ///     previous_kept = it == ' ' || !at_word_start;
+    ///     previous_kept
+    /// });
+    /// assert_eq!(&my_string, "emove he irst etter f ach ord");
+    /// ```
+    fn retain_with_last_kept<F: FnMut(Option<char>, char) -> bool>(&mut self, mut f: F) {
+        self.retain_with_last_kept_after(|last, it, _| f(last, it))
+    }
+
+    /// [`retain_with_last_kept`](Self::retain_with_last_kept), where the
+    /// predicate is also given the contents of the string after the current
+    /// character, as in [`retain_after`](Self::retain_after).
+    ///
+    /// The last kept character is still tracked as each character is kept,
+    /// so it is the character the predicate was given, even if it has since
+    /// been changed through `after`.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "  Too   many    spaces  ".to_string();
+    /// // Collapse each run of spaces, and remove those at the ends
+    /// my_string.retain_with_last_kept_after(|last, it, after| {
+    ///     it != ' ' || (last.is_some_and(|last| last != ' ') && !after.trim_start().is_empty())
+    /// });
+    /// assert_eq!(my_string, "Too many spaces");
+    /// ```
+    fn retain_with_last_kept_after<F: FnMut(Option<char>, char, &mut str) -> bool>(&mut self, f: F);
+
+    /// Retains only the characters specified by the predicate, which is given
+    /// the previous and next characters of the original string.
+    ///
+    /// Unlike the last kept character in
+    /// [`retain_with_last_kept`](Self::retain_with_last_kept), the previous
+    /// character is passed whether or not it was kept, so the predicate sees
+    /// the original neighbours of every character. Each is `None` at the
+    /// corresponding end of the string. Every character is only decoded
+    /// once, so this is cheaper than decoding the start of `after` in
+    /// [`retain_all`](Self::retain_all).
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "a  -  -1 --2".to_string();
+    /// // Remove repeated spaces, and `-`s which are followed by a digit
+    /// my_string.retain_adjacent(|previous, it, next| match it {
+    ///     ' ' => previous != Some(' '),
+    ///     '-' => !next.map_or(false, |next| next.is_ascii_digit()),
+    ///     _ => true,
+    /// });
+    /// assert_eq!(my_string, "a - 1 -2");
+    /// ```
+    fn retain_adjacent<F: FnMut(Option<char>, char, Option<char>) -> bool>(&mut self, f: F);
+
+    /// Retains only the characters specified by the predicate, where the
+    /// predicate is told how many characters have been removed.
+    ///
+    /// The [`RemovalStreak`] passed to the predicate counts both the
+    /// characters removed immediately before the current character, and in
+    /// total. The former can't be recovered from the contents of the string.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "a--------b".to_string();
+    /// // Remove at most 3 characters in a row, then force one to be kept
+    /// my_string.retain_with_streak(|streak, it| it != '-' || streak.consecutive_removed == 3);
+    /// assert_eq!(my_string, "a--b");
+    /// ```
+    fn retain_with_streak<F: FnMut(RemovalStreak, char) -> bool>(&mut self, f: F);
+
+    /// Retains only the characters specified by the predicate, then removes
+    /// any separators which would be doubled, leading or trailing.
+    ///
+    /// Each character in `separators` is a separator. The predicate is called
+    /// for every character, including separators. A separator which it
+    /// keeps is then only kept if the last kept character was not a
+    /// separator, and a separator left at the end is removed. So in the
+    /// result, no two separators are adjacent, even if they are different
+    /// characters, and the result neither starts nor ends with a separator.
+    /// This is all done in the same pass.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "red, TODO, green,, blue, TODO".to_string();
+    /// my_string.retain_tidy(&[' '], |c| !c.is_ascii_uppercase());
+    /// assert_eq!(my_string, "red, , green,, blue,");
+    ///
+    /// let mut my_string = "/usr//local/./bin/".to_string();
+    /// my_string.retain_tidy(&['/'], |c| c != '.');
+    /// assert_eq!(my_string, "usr/local/bin");
+    /// ```
+    fn retain_tidy<F: FnMut(char) -> bool>(&mut self, separators: &[char], f: F);
+
+    /// Sends each character to one of three [`Route`]s: keeping it, removing
+    /// it, or removing it and appending it to `diverted`.
+    ///
+    /// The diverted characters are appended in their original order, and the
+    /// number of characters sent to each route is returned.
+    ///
+    /// If the predicate panics, `diverted` contains exactly the characters
+    /// diverted before the panic, and `self` contains the characters kept
+    /// before the panic.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::{Route, RetainMoreString as _};
+    /// let mut input = "user\u{200b}name\u{7}!".to_string();
+    /// let mut quarantine = String::new();
+    /// let counts = input.retain_route(&mut quarantine, |c| match c {
+    ///     '\u{200b}' => Route::Drop,
+    ///     c if c.is_control() => Route::Divert,
+    ///     _ => Route::Keep,
+    /// });
+    /// assert_eq!(input, "username!");
+    /// assert_eq!(quarantine, "\u{7}");
+    /// assert_eq!((counts.kept, counts.dropped, counts.diverted), (9, 1, 1));
+    /// ```
+    fn retain_route<F: FnMut(char) -> Route>(&mut self, diverted: &mut String, f: F)
+        -> RouteCounts;
+
+    /// [`retain_default`](Self::retain_default), appending every removed
+    /// character to `sink` in its original order.
+    ///
+    /// This splits the string in a single pass. The removed characters are
+    /// pushed onto the end of `sink`, so nothing is allocated if it already
+    /// has enough spare capacity. This is [`retain_route`](Self::retain_route)
+    /// without [`Route::Drop`].
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut order_id = "ORD-2024-0042".to_string();
+    /// let mut digits = String::with_capacity(order_id.len());
+    /// order_id.extract_removed_into(|c| !c.is_ascii_digit(), &mut digits);
+    /// assert_eq!(order_id, "ORD--");
+    /// assert_eq!(digits, "20240042");
+    /// ```
+    fn extract_removed_into<F: FnMut(char) -> bool>(&mut self, mut f: F, sink: &mut String) {
+        self.retain_route(sink, |c| if f(c) { Route::Keep } else { Route::Divert });
+    }
+
+    /// [`retain_default`](Self::retain_default), passing every removed
+    /// character to `sink`.
+    ///
+    /// `sink` is called exactly once for each removed character, in order,
+    /// straight after `pred` rejects it and before its bytes are overwritten.
+    /// Unlike [`extract_removed_into`](Self::extract_removed_into), nothing is
+    /// buffered, so this is suitable for forwarding the removed characters to
+    /// a writer.
+    ///
+    /// # Usage
+    ///
+    /// Counting the removed characters:
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut s = "a\tb\r\nc".to_string();
+    /// let mut removed = 0;
+    /// s.retain_with_sink(|c| !c.is_control(), |_| removed += 1);
+    /// assert_eq!(s, "abc");
+    /// assert_eq!(removed, 3);
+    /// ```
+    ///
+    /// Forwarding them to a [`core::fmt::Write`]:
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use retain_more::RetainMoreString as _;
+    ///
+    /// /// Stands in for a serial port, which can only write bytes.
+    /// struct Serial(Vec<u8>);
+    ///
+    /// impl Write for Serial {
+    ///     fn write_str(&mut self, s: &str) -> core::fmt::Result {
+    ///         self.0.extend_from_slice(s.as_bytes());
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let mut serial = Serial(Vec::new());
+    /// let mut s = "temp=21\u{b0}C".to_string();
+    /// s.retain_with_sink(|c| c.is_ascii_digit(), |c| {
+    ///     // A logger shouldn't fail the operation it is logging
+    ///     let _ = serial.write_char(c);
+    /// });
+    /// assert_eq!(s, "21");
+    /// assert_eq!(serial.0, "temp=\u{b0}C".as_bytes());
+    /// ```
+    fn retain_with_sink<F: FnMut(char) -> bool, S: FnMut(char)>(
+        &mut self,
+        mut pred: F,
+        mut sink: S,
+    ) {
+        self.retain_default(|c| {
+            let keep = pred(c);
+            if !keep {
+                sink(c);
+            }
+            keep
+        });
+    }
+
+    /// Creates an iterator which removes the characters specified by the
+    /// predicate, and yields them along with their byte offset in the
+    /// original string.
+    ///
+    /// This is the equivalent of [`Vec::extract_if`] for strings. The
+    /// predicate is given the current character, and the contents of the
+    /// string after it, as in [`retain_after`](Self::retain_after). It
+    /// returns `true` to extract the current character. Note that this is the
+    /// opposite of the `retain` methods.
+    ///
+    /// Characters are only considered as the iterator is advanced. If the
+    /// iterator is dropped before it is exhausted, or if the predicate
+    /// panics, the characters which have not yet been considered are kept.
+    /// If the iterator is leaked, the string may be truncated.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut template = "Dear {name}, your order {id} has shipped".to_string();
+    /// let mut depth = 0;
+    /// let placeholders: Vec<_> = template
+    ///     .extract_if(|c, _| {
+    ///         let in_braces = c == '{' || depth > 0;
+    ///         match c {
+    ///             '{' => depth += 1,
+    ///             '}' => depth -= 1,
+    ///             _ => {}
+    ///         }
+    ///         in_braces
+    ///     })
+    ///     .filter(|&(_, c)| c == '{')
+    ///     .map(|(offset, _)| offset)
+    ///     .collect();
+    /// assert_eq!(template, "Dear , your order  has shipped");
+    /// assert_eq!(placeholders, [5, 24]);
+    /// ```
+    fn extract_if<F: FnMut(char, &mut str) -> bool>(&mut self, f: F) -> ExtractIf<'_, F>;
+
+    /// Count the number of times each character occurs in the string.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let frequencies = "hello".to_string().char_frequencies();
+    /// assert_eq!(frequencies.get('l'), 2);
+    /// assert_eq!(frequencies.get('z'), 0);
+    /// ```
+    fn char_frequencies(&self) -> CharFrequencies;
+
+    /// Retains only the characters specified by the predicate, where the
+    /// predicate is given the number of times each character occurs.
+    ///
+    /// The string is scanned twice: once to count the characters (as
+    /// [`char_frequencies`](Self::char_frequencies)), then to retain them.
+    /// The count passed to the predicate is the count in the original string,
+    /// so is the same for every occurrence of a character.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "mississippi".to_string();
+    /// my_string.retain_by_frequency(|it, count| it == 'm' || count < 4);
+    /// assert_eq!(my_string, "mpp");
+    /// ```
+    fn retain_by_frequency<F: FnMut(char, usize) -> bool>(&mut self, f: F);
+
+    /// Retains only the characters which occur at least `min` times.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// // Characters which occur only once are likely to be noise
+    /// let mut my_string = "a#b.a.b!".to_string();
+    /// my_string.retain_min_frequency(2);
+    /// assert_eq!(my_string, "ab.a.b");
+    /// ```
+    fn retain_min_frequency(&mut self, min: usize) {
+        self.retain_by_frequency(|_, count| count >= min)
+    }
+
+    /// Removes every character which occurs again later in the string, so
+    /// that only the last occurrence of each distinct character is kept.
+    ///
+    /// The order of the kept characters is preserved. This takes two passes:
+    /// one to find the last occurrence of each character, then one to
+    /// retain them.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "abracadabra".to_string();
+    /// my_string.dedup_chars_keep_last();
+    /// assert_eq!(my_string, "cdbra");
+    /// ```
+    fn dedup_chars_keep_last(&mut self);
+
+    /// Retains only the words specified by the predicate.
+    ///
+    /// A word is a maximal run of non-whitespace characters, so punctuation
+    /// is part of the word it is attached to. The predicate is called once
+    /// for each word, in order, with a slice of the original string, so no
+    /// allocation is needed for each word.
+    ///
+    /// When a word is removed, one of the separators next to it is also
+    /// removed, so the remaining words are still separated as they
+    /// were. Any whitespace at the start and end of the string is kept.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "  the quick  brown\tfox\n".to_string();
+    /// my_string.retain_words(|word| word != "quick" && word != "fox");
+    /// assert_eq!(my_string, "  the  brown\n");
+    /// ```
+    fn retain_words<F: FnMut(&str) -> bool>(&mut self, f: F);
+
+    /// Retains only the words which `dictionary` accepts.
+    ///
+    /// This is [`retain_words`](Self::retain_words), for a dictionary which
+    /// can be queried by shared reference. The words are exactly as they
+    /// occur in the string, so any case folding is the dictionary's
+    /// responsibility, and words with attached punctuation are looked up
+    /// including that punctuation.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// use std::collections::BTreeSet;
+    /// let dictionary: BTreeSet<&str> = ["hello", "world"].iter().copied().collect();
+    /// let mut my_string = "hello there world Hello world!".to_string();
+    /// my_string.retain_words_in(&|word: &str| dictionary.contains(word));
+    /// assert_eq!(my_string, "hello world");
+    /// ```
+    fn retain_words_in<D: Fn(&str) -> bool + ?Sized>(&mut self, dictionary: &D) {
+        self.retain_words(|word| dictionary(word))
+    }
+
+    /// Retains only the words whose length is within `bounds`.
+    ///
+    /// Words are as in [`retain_words`](Self::retain_words), and separators
+    /// are merged in the same way. The length of a word is the number of
+    /// [`char`]s it contains, not its length in bytes, and `bounds` has its
+    /// usual inclusive or exclusive meaning, so `2..=30` keeps words of
+    /// between 2 and 30 characters.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "I saw a supercalifragilisticexpialidocious ox".to_string();
+    /// my_string.retain_words_by_len(2..=20);
+    /// assert_eq!(my_string, "saw ox");
+    /// ```
+    fn retain_words_by_len<R: RangeBounds<usize>>(&mut self, bounds: R);
+
+    /// Removes each word which is equal to the word immediately before it.
+    ///
+    /// This is [`dedup_words_by`](Self::dedup_words_by) with exact equality,
+    /// so punctuation and case must also match.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "the the cat sat on on\nthe mat".to_string();
+    /// my_string.dedup_words();
+    /// assert_eq!(my_string, "the cat sat on\nthe mat");
+    /// ```
+    fn dedup_words(&mut self) {
+        self.dedup_words_by(|previous, word| previous == word)
+    }
+
+    /// Removes each word which `eq` considers equal to the word immediately
+    /// before it.
+    ///
+    /// Words are as in [`retain_words`](Self::retain_words), and the separator
+    /// before a removed word is removed with it. `eq` is called with the
+    /// previous kept word and then the current word, both as slices of the
+    /// string, so a run of repeated words is collapsed to its first word.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "And and then THEN then".to_string();
+    /// my_string.dedup_words_by(|previous, word| previous.eq_ignore_ascii_case(word));
+    /// assert_eq!(my_string, "And then");
+    /// ```
+    fn dedup_words_by<F: FnMut(&str, &str) -> bool>(&mut self, eq: F);
+
+    /// Removes each character which is equal to the last kept character, so
+    /// each run of a repeated character is collapsed to its first.
+    ///
+    /// This is [`retain_dedup_by`](Self::retain_dedup_by) with equality.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "bookkeeper \u{1F980}\u{1F980}!!".to_string();
+    /// my_string.retain_dedup();
+    /// assert_eq!(my_string, "bokeper \u{1F980}!");
+    /// ```
+    fn retain_dedup(&mut self) {
+        self.retain_dedup_by(|last, it| last == it)
+    }
+
+    /// Removes each character which `same` considers the same as the last
+    /// kept character.
+    ///
+    /// `same` is called with the last kept character and then the current
+    /// character, so the first character of each run is kept, and is compared
+    /// with the rest of the run. It is not called for the first character of
+    /// the string. This is the equivalent of [`Vec::dedup_by`], and is
+    /// [`retain_with_last_kept`](Self::retain_with_last_kept), which tracks
+    /// the last kept character as it goes.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "Hello  \t world".to_string();
+    /// my_string.retain_dedup_by(|last, it| last.is_whitespace() && it.is_whitespace());
+    /// assert_eq!(my_string, "Hello world");
+    /// ```
+    fn retain_dedup_by<F: FnMut(char, char) -> bool>(&mut self, mut same: F) {
+        self.retain_with_last_kept(|last, it| last.is_none_or(|last| !same(last, it)));
+    }
+
+    /// Replaces each run of whitespace with a single ASCII space, and
+    /// removes the whitespace at the start and end of the string.
+    ///
+    /// Whitespace is as in [`char::is_whitespace`], so this includes
+    /// newlines and non-ASCII spaces, which are replaced in place. A string
+    /// which is already normalised is not written to.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "\n  Scraped\t\ttext,\u{3000}with \r\n gaps  ".to_string();
+    /// my_string.collapse_whitespace();
+    /// assert_eq!(my_string, "Scraped text, with gaps");
+    /// ```
+    fn collapse_whitespace(&mut self);
+
+    /// Removes each of `separators` which is between two ASCII digits.
+    ///
+    /// This is [`remove_digit_separators_with`](Self::remove_digit_separators_with)
+    /// using [`DigitKind::Ascii`].
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "1,234,567 apples, 1_000 pears".to_string();
+    /// my_string.remove_digit_separators(&[',', '_']);
+    /// assert_eq!(my_string, "1234567 apples, 1000 pears");
+    /// ```
+    fn remove_digit_separators(&mut self, separators: &[char]) {
+        self.remove_digit_separators_with(separators, DigitKind::Ascii)
+    }
+
+    /// Removes each of `separators` which is between two digits.
+    ///
+    /// A separator is removed only if the characters immediately before and
+    /// after it in the original string are both digits, as chosen by
+    /// `digits`. This means a run of several separators (such as `"1,,2"`) is
+    /// kept in full, since each of them is next to another separator, as is
+    /// a separator at the start or end of the string.
+    fn remove_digit_separators_with(&mut self, separators: &[char], digits: DigitKind);
+
+    /// Removes every section of the string which starts with `open` and
+    /// ends with `close`, returning the number of sections removed.
+    ///
+    /// Each section ends at the first `close` after its `open`, and the
+    /// delimiters themselves are removed unless `keep_delimiters` is `true`.
+    /// An `open` without a `close` after it is not the start of a section,
+    /// so the rest of the string from there is kept untouched, rather than
+    /// removed. An empty delimiter never matches. See
+    /// [`remove_between_with`](Self::remove_between_with) for nested
+    /// sections.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "let x (* the answer *) = 42 (* unfinished".to_string();
+    /// assert_eq!(my_string.remove_between("(*", "*)", false), 1);
+    /// assert_eq!(my_string, "let x  = 42 (* unfinished");
+    ///
+    /// let mut my_string = "HAMLET [aside] Words, [pause] words".to_string();
+    /// assert_eq!(my_string.remove_between("[", "]", true), 2);
+    /// assert_eq!(my_string, "HAMLET [] Words, [] words");
+    /// ```
+    fn remove_between(&mut self, open: &str, close: &str, keep_delimiters: bool) -> usize {
+        let opts = BetweenOptions {
+            keep_delimiters,
+            ..BetweenOptions::new()
+        };
+        self.remove_between_with(open, close, &opts)
+    }
+
+    /// Removes every section of the string between `open` and `close`, as
+    /// chosen by `opts`, returning the number of sections removed.
+    ///
+    /// With [`BetweenOptions::nested`], each `open` within a section must be
+    /// matched by its own `close` before the section ends. As in
+    /// [`remove_between`](Self::remove_between), the rest of the string
+    /// after an `open` which is never matched is kept untouched, including
+    /// any complete sections within it.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::{BetweenOptions, RetainMoreString as _};
+    /// let opts = BetweenOptions {
+    ///     nested: true,
+    ///     ..BetweenOptions::new()
+    /// };
+    /// let mut my_string = "f(g(x), y) + z".to_string();
+    /// assert_eq!(my_string.remove_between_with("(", ")", &opts), 1);
+    /// assert_eq!(my_string, "f + z");
+    /// ```
+    fn remove_between_with(&mut self, open: &str, close: &str, opts: &BetweenOptions) -> usize;
+
+    /// Keeps only the contents of the sections of the string which start
+    /// with `open` and end with `close`, removing the delimiters.
+    ///
+    /// This is the inverse of [`remove_between`](Self::remove_between): the
+    /// sections are found in the same way, and everything else is removed,
+    /// including the rest of the string after an unterminated `open`.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "HAMLET [aside] Words, [pause] words".to_string();
+    /// my_string.retain_between("[", "]");
+    /// assert_eq!(my_string, "asidepause");
+    /// ```
+    fn retain_between(&mut self, open: &str, close: &str) {
+        self.retain_between_with(open, close, &BetweenOptions::new())
+    }
+
+    /// Keeps only the sections of the string between `open` and `close`, as
+    /// chosen by `opts`.
+    ///
+    /// This is the inverse of
+    /// [`remove_between_with`](Self::remove_between_with). With
+    /// [`BetweenOptions::keep_delimiters`], the delimiters of each section
+    /// are kept along with its contents.
+    fn retain_between_with(&mut self, open: &str, close: &str, opts: &BetweenOptions);
+
+    /// Retains only the characters specified by the predicate, feeding the
+    /// removed characters into `hasher`.
+    ///
+    /// Each maximal run of consecutively removed characters is hashed as a
+    /// [`str`] (using its [`Hash`](core::hash::Hash) implementation), in
+    /// order, once the run ends. As that implementation terminates each
+    /// string with a byte which never occurs in UTF-8, removing `"ab"` and
+    /// then `"c"` hashes differently to removing `"a"` and then `"bc"`. Nothing
+    /// is fed into `hasher` if nothing is removed.
+    ///
+    /// The removed runs are hashed directly from the string's buffer, so no
+    /// copy of the removed content is made.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// use std::{collections::hash_map::DefaultHasher, hash::{Hash, Hasher}};
+    /// let mut my_string = "user=ferris password=hunter2".to_string();
+    /// let mut hasher = DefaultHasher::new();
+    /// my_string.retain_hash_removed(&mut hasher, |it| !it.is_ascii_digit());
+    ///
+    /// let mut expected = DefaultHasher::new();
+    /// "2".hash(&mut expected);
+    /// assert_eq!(hasher.finish(), expected.finish());
+    /// ```
+    fn retain_hash_removed<H: Hasher, F: FnMut(char) -> bool>(&mut self, hasher: &mut H, f: F);
+
+    /// Retains only the characters accepted by `visitor`, notifying it of
+    /// each character kept or removed.
+    ///
+    /// See [`RetainVisitor`] for the order of the calls.
+    fn retain_visit<V: RetainVisitor + ?Sized>(&mut self, visitor: &mut V);
+
+    /// Retains only the characters specified by the predicate, until the
+    /// display width of the kept characters would exceed `max_width`
+    /// columns, then removes the rest of the string.
+    ///
+    /// Each character's width is measured as by the `unicode-width` crate, so
+    /// most CJK characters take two columns, and combining marks take none.
+    /// Characters removed by the predicate don't count towards the width.
+    /// If the string is cut directly after a zero width joiner, the joiner is
+    /// also removed.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "\x1b日本\x1b語".to_string();
+    /// my_string.retain_width(5, |c| !c.is_control());
+    /// assert_eq!(my_string, "日本");
+    /// ```
+    #[cfg(feature = "unicode-width")]
+    fn retain_width<F: FnMut(char) -> bool>(&mut self, max_width: usize, f: F);
+
+    /// Truncates the string to at most `max_width` display columns,
+    /// optionally ending it with `ellipsis` if anything was removed.
+    ///
+    /// Widths are measured as in [`retain_width`](Self::retain_width). When
+    /// the string is too wide, the ellipsis (if any) replaces the final
+    /// columns, so the result including the ellipsis fits within
+    /// `max_width`. The ellipsis is written into the space freed by
+    /// truncation, so this never reallocates: in the rare case where the
+    /// removed characters were shorter in bytes than the ellipsis, more
+    /// characters are removed. The ellipsis is omitted if it is wider than
+    /// `max_width` on its own, or is longer in bytes than the whole string.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "Ferris the crab".to_string();
+    /// my_string.truncate_width(10, Some('…'));
+    /// assert_eq!(my_string, "Ferris th…");
+    /// ```
+    #[cfg(feature = "unicode-width")]
+    fn truncate_width(&mut self, max_width: usize, ellipsis: Option<char>);
+
+    /// Retains only the characters specified by the predicate, until the
+    /// predicate breaks, then splits the rest of the string off into a new
+    /// [`String`].
+    ///
+    /// The predicate returns [`ControlFlow::Continue`] with whether to keep
+    /// the current character, or [`ControlFlow::Break`] to stop. When it
+    /// breaks, the current character and everything after it is returned,
+    /// and `self` is left holding only the characters which were kept. If
+    /// the predicate never breaks, the returned string is empty.
+    ///
+    /// The returned string is allocated once, with exactly the length of the
+    /// rest of the string (so no allocation is made if the predicate never
+    /// breaks).
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// use std::ops::ControlFlow;
+    /// let mut message = "Content-Length: 5\n\nhello".to_string();
+    /// // Remove the spaces from the header, and split off the body
+    /// let body = message.split_off_when(|it| match it {
+    ///     '\n' => ControlFlow::Break(()),
+    ///     it => ControlFlow::Continue(it != ' '),
+    /// });
+    /// assert_eq!(message, "Content-Length:5");
+    /// assert_eq!(body, "\n\nhello");
+    /// ```
+    fn split_off_when<F: FnMut(char) -> ControlFlow<(), bool>>(&mut self, f: F) -> String;
+
+    /// Splits the string into the pieces between the characters specified by
+    /// the predicate, which are removed.
+    ///
+    /// This is [`split_when_with`](Self::split_when_with) with
+    /// [`EmptyPieces::Keep`], so gives the same pieces as [`str::split`].
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let fields = "name,,age".to_string().split_when(|c| c == ',');
+    /// assert_eq!(fields, ["name", "", "age"]);
+    /// ```
+    fn split_when<F: FnMut(char) -> bool>(self, f: F) -> Vec<String>
+    where
+        Self: Sized,
+    {
+        self.split_when_with(EmptyPieces::Keep, f)
+    }
+
+    /// Splits the string into the pieces between the characters specified by
+    /// the predicate, which are removed.
+    ///
+    /// The first piece reuses the allocation of `self`, by truncating it in
+    /// place, and every other piece is allocated with exactly its length.
+    /// If `empty` is [`EmptyPieces::Skip`], empty pieces are left out, and the
+    /// allocation is instead reused by the first non-empty piece.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::{EmptyPieces, RetainMoreString as _};
+    /// let words = "  split  these words ".to_string();
+    /// let words = words.split_when_with(EmptyPieces::Skip, |c| c == ' ');
+    /// assert_eq!(words, ["split", "these", "words"]);
+    /// ```
+    fn split_when_with<F: FnMut(char) -> bool>(self, empty: EmptyPieces, f: F) -> Vec<String>
+    where
+        Self: Sized;
+
+    /// Retains only the characters specified by the predicate, returning the
+    /// result as an exactly sized [`Box<str>`].
+    ///
+    /// The characters are filtered in place, so the only (re)allocation is
+    /// the final shrink of the buffer to the length of the result, which many
+    /// allocators can perform without copying. No allocation is made if the
+    /// string had no spare capacity and nothing was removed, and an empty
+    /// result frees the buffer.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let name = " Ferris  the crab ".to_string();
+    /// let name: Box<str> = name.filter_into_boxed_str(|c| c != ' ');
+    /// assert_eq!(&*name, "Ferristhecrab");
+    /// ```
+    fn filter_into_boxed_str<F: FnMut(char) -> bool>(self, f: F) -> Box<str>
+    where
+        Self: Sized;
+
+    /// Retains only the characters specified by the predicate, returning the
+    /// string so that calls can be chained.
+    ///
+    /// This is [`retain_default`](Self::retain_default), taking and returning
+    /// the string by value. Each call is a separate pass over the string, so
+    /// when the predicates are known together, combining them into one
+    /// predicate is faster.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let cleaned = "  Hello,\tWorld! 123 "
+    ///     .to_string()
+    ///     .filter_chars(|c| !c.is_ascii_digit())
+    ///     .filter_chars(|c| !c.is_ascii_punctuation())
+    ///     .filter_chars(|c| c != '\t');
+    /// assert_eq!(cleaned, "  HelloWorld  ");
+    /// ```
+    fn filter_chars<F: FnMut(char) -> bool>(self, f: F) -> String
+    where
+        Self: Sized;
+
+    /// [`retain_all`](Self::retain_all), taking and returning the string by
+    /// value so that calls can be chained.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let cleaned = "a  b   c"
+    ///     .to_string()
+    ///     .filter_chars_ctx(|before, it, _| !(it == ' ' && before.ends_with(' ')))
+    ///     .filter_chars(|c| c != 'b');
+    /// assert_eq!(cleaned, "a  c");
+    /// ```
+    fn filter_chars_ctx<F: FnMut(&mut str, char, &mut str) -> bool>(self, f: F) -> String
+    where
+        Self: Sized;
+
+    /// Removes and returns the first maximal run of characters specified by
+    /// the predicate.
+    ///
+    /// The rest of the string is moved back over the run at most once. If no
+    /// character matches, `None` is returned and `self` is left untouched.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut line = "id: 1234 (active)".to_string();
+    /// let id = line.take_span(|c| c.is_ascii_digit());
+    /// assert_eq!(id.as_deref(), Some("1234"));
+    /// assert_eq!(line, "id:  (active)");
+    /// ```
+    fn take_span<F: FnMut(char) -> bool>(&mut self, f: F) -> Option<String>;
+
+    /// Removes and returns the first occurrence of `needle`.
+    ///
+    /// If `needle` does not occur, `None` is returned and `self` is left
+    /// untouched. An empty `needle` is found at the start of the string.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut flags = "--quiet --force --verbose".to_string();
+    /// assert_eq!(flags.take_span_matches(" --force").as_deref(), Some(" --force"));
+    /// assert_eq!(flags, "--quiet --verbose");
+    /// assert_eq!(flags.take_span_matches("--force"), None);
+    /// ```
+    fn take_span_matches(&mut self, needle: &str) -> Option<String>;
+
+    /// Retains only the occurrences of `needle`, returning how many were
+    /// kept.
+    ///
+    /// The occurrences are found as by [`str::matches`], so overlapping
+    /// occurrences are counted from the left, and are concatenated in order.
+    /// If `needle` is empty, every occurrence is empty, so the string is
+    /// cleared, and the count is one more than its number of characters, as
+    /// with [`str::matches`].
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "abababa, ab".to_string();
+    /// assert_eq!(my_string.keep_matches("aba"), 2);
+    /// assert_eq!(my_string, "abaaba");
+    /// ```
+    fn keep_matches(&mut self, needle: &str) -> usize;
+
+    /// Removes every occurrence of `needle`, returning how many were removed.
+    ///
+    /// The occurrences are found as by [`str::matches`], so overlapping
+    /// occurrences are counted from the left. Each search is a
+    /// [`str::find`] over the rest of the string, and the text between
+    /// occurrences is moved down as a block. If `needle` is empty, every
+    /// occurrence is empty, so the string is unchanged, and the count is one
+    /// more than its number of characters, as with [`str::matches`].
+    ///
+    /// This is [`remove_first_n_matches`](Self::remove_first_n_matches) with
+    /// no limit.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "a<br>b<br><br>c".to_string();
+    /// assert_eq!(my_string.remove_all_matches("<br>"), 3);
+    /// assert_eq!(my_string, "abc");
+    /// ```
+    fn remove_all_matches(&mut self, needle: &str) -> usize {
+        self.remove_first_n_matches(needle, usize::MAX)
+    }
+
+    /// Removes the first `n` occurrences of `needle`, returning how many were
+    /// removed.
+    ///
+    /// This is [`remove_all_matches`](Self::remove_all_matches), stopping
+    /// after `n` occurrences, in a single pass. Like [`str::replacen`], the
+    /// occurrences are found in the original string, so text which only
+    /// becomes an occurrence once others are removed is left alone. If `n`
+    /// is `0`, the string is not written to.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut row = "*id,*name,*email".to_string();
+    /// assert_eq!(row.remove_first_n_matches("*", 2), 2);
+    /// assert_eq!(row, "id,name,*email");
+    /// ```
+    fn remove_first_n_matches(&mut self, needle: &str, n: usize) -> usize;
+
+    /// Removes each of the byte ranges in `ranges`, returning the number of
+    /// bytes removed.
+    ///
+    /// The ranges can be in any order, and can overlap or touch each other;
+    /// they are sorted and merged, then all removed in a single pass. Each
+    /// range must lie on character boundaries of the string.
+    ///
+    /// # Errors
+    ///
+    /// If any range is reversed, extends past the end of the string, or is
+    /// not on character boundaries. Every range is checked before anything
+    /// is removed, so the string is unchanged in that case.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::{RangeError, RetainMoreString as _};
+    /// let mut my_string = "keep [drop] keep (drop)".to_string();
+    /// assert_eq!(my_string.remove_byte_ranges(&[16..23, 4..11]), Ok(14));
+    /// assert_eq!(my_string, "keep keep");
+    ///
+    /// assert_eq!(
+    ///     my_string.remove_byte_ranges(&[5..10]),
+    ///     Err(RangeError::OutOfBounds { range: 5..10 })
+    /// );
+    /// ```
+    fn remove_byte_ranges(&mut self, ranges: &[Range<usize>]) -> Result<usize, RangeError>;
+
+    /// Removes every occurrence of `needle`, ignoring case, returning how
+    /// many were removed.
+    ///
+    /// This is [`remove_all_matches`](Self::remove_all_matches), where each
+    /// character is compared by its simple case folding, as in
+    /// [`retain_folded`](Self::retain_folded). Matching is one character at a
+    /// time, so an occurrence has as many characters as `needle`, although
+    /// they can have a different length in bytes, such as the Kelvin sign
+    /// `'\u{212a}'` matching `'k'`. Characters whose full case folding is
+    /// several characters only match their simple folding, so `'ß'` does not
+    /// match `"ss"`.
+    ///
+    /// Each position is compared with `needle` in turn, so this takes time
+    /// proportional to the length of the string multiplied by the length of
+    /// `needle` in the worst case.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "password: PaSsWoRd, PASSWORD".to_string();
+    /// assert_eq!(my_string.remove_matches_ignore_case("password"), 3);
+    /// assert_eq!(my_string, ": , ");
+    /// ```
+    fn remove_matches_ignore_case(&mut self, needle: &str) -> usize;
+
+    /// Repeatedly removes any of `prefixes` from the start of the string,
+    /// until none of them match, returning the number of bytes removed.
+    ///
+    /// When more than one prefix matches, the longest is removed. Empty
+    /// prefixes are ignored. The rest of the string is moved to the start at
+    /// most once, however many prefixes are removed.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "ns2:xml:xmlns".to_string();
+    /// assert_eq!(my_string.strip_prefixes_in_place(&["xml:", "ns2:"]), 8);
+    /// assert_eq!(my_string, "xmlns");
+    /// ```
+    fn strip_prefixes_in_place(&mut self, prefixes: &[&str]) -> usize;
+
+    /// Repeatedly removes any of `suffixes` from the end of the string, until
+    /// none of them match, returning the number of bytes removed.
+    ///
+    /// When more than one suffix matches, the longest is removed. Empty
+    /// suffixes are ignored.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "config_tmp~_tmp~".to_string();
+    /// assert_eq!(my_string.strip_suffixes_in_place(&["_tmp", "~"]), 10);
+    /// assert_eq!(my_string, "config");
+    /// ```
+    fn strip_suffixes_in_place(&mut self, suffixes: &[&str]) -> usize;
+
+    /// Removes every maximal run of characters specified by the predicate,
+    /// returning the runs in order.
+    ///
+    /// The runs are extracted in the same pass which compacts the rest of
+    /// the string.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut order = "2 apples, 13 pears".to_string();
+    /// let counts = order.extract_runs(|c| c.is_ascii_digit());
+    /// assert_eq!(counts, ["2", "13"]);
+    /// assert_eq!(order, " apples,  pears");
+    /// ```
+    fn extract_runs<F: FnMut(char) -> bool>(&mut self, f: F) -> Vec<String>;
+
+    /// Removes every maximal run of characters specified by the predicate,
+    /// appending the runs to `out` separated by `sep`.
+    ///
+    /// This is [`extract_runs`](Self::extract_runs) without allocating a
+    /// [`String`] for each run. No separator is added before the first run
+    /// or after the last.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut order = "2 apples, 13 pears".to_string();
+    /// let mut counts = String::new();
+    /// order.extract_runs_into(&mut counts, '+', |c| c.is_ascii_digit());
+    /// assert_eq!(counts, "2+13");
+    /// assert_eq!(order, " apples,  pears");
+    /// ```
+    fn extract_runs_into<F: FnMut(char) -> bool>(&mut self, out: &mut String, sep: char, f: F);
+
+    /// Removes every bracket which has no matching partner, returning the
+    /// number of brackets removed.
+    ///
+    /// `pairs` lists the `(open, close)` characters of each kind of bracket.
+    /// A pair may use the same character for both, such as `('"', '"')`, in
+    /// which case the character closes an open quote, and otherwise opens
+    /// one. Matched brackets and all other characters are kept.
+    ///
+    /// A closing bracket only matches the innermost open bracket, so in
+    /// interleaved input such as `([)]`, the `)` and the `(` are both
+    /// unmatched, leaving `[]`.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "f(x)) [see figure".to_string();
+    /// assert_eq!(my_string.repair_balanced(&[('(', ')'), ('[', ']')]), 2);
+    /// assert_eq!(my_string, "f(x) see figure");
+    /// ```
+    fn repair_balanced(&mut self, pairs: &[(char, char)]) -> usize;
+
+    /// [`repair_balanced`](Self::repair_balanced), tracking the brackets in
+    /// `scratch`, which avoids allocating when it is reused.
+    fn repair_balanced_with_scratch(
+        &mut self,
+        pairs: &[(char, char)],
+        scratch: &mut RetainScratch,
+    ) -> usize;
+
+    /// Keeps each character independently with probability
+    /// `keep_probability`.
+    ///
+    /// Each character uses one `u64` from `rng`.
+    ///
+    /// # Panics
+    ///
+    /// If `keep_probability` is not between `0.0` and `1.0` (inclusive).
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// # struct Counter(u64);
+    /// # impl rand_core::RngCore for Counter {
+    /// #     fn next_u32(&mut self) -> u32 { self.next_u64() as u32 }
+    /// #     fn next_u64(&mut self) -> u64 { self.0 = self.0.wrapping_add(1 << 62); self.0 }
+    /// #     fn fill_bytes(&mut self, dst: &mut [u8]) { rand_core::impls::fill_bytes_via_next(self, dst) }
+    /// # }
+    /// # let mut rng = Counter(0);
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "a sample of this string".to_string();
+    /// my_string.retain_sample(&mut rng, 0.5);
+    /// assert!(my_string.len() < "a sample of this string".len());
+    /// ```
+    #[cfg(feature = "rand")]
+    fn retain_sample<R: rand_core::RngCore + ?Sized>(&mut self, rng: &mut R, keep_probability: f64);
+
+    /// Keeps exactly `k` characters, chosen uniformly at random, in their
+    /// original order.
+    ///
+    /// Every subset of `k` characters is equally likely to be kept. If the
+    /// string has at most `k` characters, it is left untouched.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// # struct Counter(u64);
+    /// # impl rand_core::RngCore for Counter {
+    /// #     fn next_u32(&mut self) -> u32 { self.next_u64() as u32 }
+    /// #     fn next_u64(&mut self) -> u64 { self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15); self.0 }
+    /// #     fn fill_bytes(&mut self, dst: &mut [u8]) { rand_core::impls::fill_bytes_via_next(self, dst) }
+    /// # }
+    /// # let mut rng = Counter(0);
+    /// use retain_more::RetainMoreString as _;
+    /// let mut my_string = "a sample of this string".to_string();
+    /// my_string.retain_reservoir(&mut rng, 5);
+    /// assert_eq!(my_string.chars().count(), 5);
+    /// ```
+    #[cfg(feature = "rand")]
+    fn retain_reservoir<R: rand_core::RngCore + ?Sized>(&mut self, rng: &mut R, k: usize);
+}
+
+// Future work - support this for strings with all allocators once/if <https://github.com/rust-lang/rust/pull/79500> lands
+impl RetainMoreString for String {
+    fn retain_all<F: FnMut(&mut str, char, &mut str) -> bool>(&mut self, mut f: F) {
+        stable(self, |s| s.retain_all_dyn(&mut f))
+    }
+
+    fn retain_all_dyn(&mut self, f: &mut dyn FnMut(&mut str, char, &mut str) -> bool) {
+        stable(self, |s| {
+            s.retain_ctx(|mut ctx| {
+                let current = ctx.current();
+                let (before, after) = ctx.split();
+                f(before, current, after)
+            })
+        })
+    }
+
+    fn retain_ctx<F: FnMut(RetainContext<'_>) -> bool>(&mut self, f: F) {
+        stable(self, |s| context::retain_ctx(s, f))
+    }
+
+    fn retain_report<F: FnMut(&mut str, char, &mut str) -> bool>(
+        &mut self,
+        mut f: F,
+    ) -> RetainReport {
+        let mut report = RetainReport::default();
+        self.retain_all(|before, it, after| {
+            let keep = f(before, it, after);
+            if keep {
+                report.record_kept(it);
+            } else {
+                report.record_removed(it);
+            }
+            keep
+        });
+        report
+    }
+
+    fn retain_all_changed<F: FnMut(&mut str, char, &mut str) -> bool>(&mut self, f: F) -> bool {
+        // Removing anything makes the string shorter
+        let len = self.len();
+        self.retain_all(f);
+        self.len() != len
+    }
+
+    fn retain_changed<F: FnMut(char) -> bool>(&mut self, f: F) -> bool {
+        let len = self.len();
+        self.retain_default(f);
+        self.len() != len
+    }
+
+    fn retain_after<F: FnMut(char, &mut str) -> bool>(&mut self, mut f: F) {
+        stable(self, |s| {
+            let mut cursor = Cursor::new(s);
+            while let Some(ch) = cursor.peek() {
+                let ch_len = ch.len_utf8();
+                if f(ch, cursor.after(ch_len)) {
+                    cursor.keep(ch_len);
+                } else {
+                    cursor.remove(ch_len);
+                }
+            }
+        })
+    }
+
+    fn retain_map<F: FnMut(char, &mut str) -> Option<char>>(&mut self, f: F) {
+        map::retain_map(self, f)
+    }
+
+    fn retain_replace<'r, F: FnMut(char, &mut str) -> RetainDecision<'r>>(
+        &mut self,
+        f: F,
+    ) -> Result<(), TryReserveError> {
+        replace::retain_replace(self, f)
+    }
+
+    fn mask_retain<F: FnMut(char, &mut str) -> bool>(
+        &mut self,
+        f: F,
+        fill: char,
+    ) -> Result<(), FillTooWide> {
+        stable(self, |s| mask::mask_retain(s, f, fill))
+    }
+
+    fn retain_splice<F, I>(&mut self, f: F, replacement: I)
+    where
+        F: FnMut(char, &mut str) -> bool,
+        I: IntoIterator<Item = char> + Clone,
+    {
+        splice::retain_splice(self, f, replacement)
+    }
+
+    fn retain_all_indexed<F: FnMut(usize, &mut str, char, &mut str) -> bool>(&mut self, mut f: F) {
+        stable(self, |s| {
+            let mut cursor = Cursor::new(s);
+            while let Some(ch) = cursor.peek() {
+                let ch_len = ch.len_utf8();
+                let offset = cursor.offset();
+                let (before, after) = cursor.split(ch_len);
+                if f(offset, before, ch, after) {
+                    cursor.keep(ch_len);
+                } else {
+                    cursor.remove(ch_len);
+                }
+            }
+        })
+    }
+
+    fn retain_all_char_indexed<F: FnMut(usize, &mut str, char, &mut str) -> bool>(
+        &mut self,
+        mut f: F,
+    ) {
+        stable(self, |s| {
+            let mut index = 0;
+            s.retain_all(|before, it, after| {
+                let keep = f(index, before, it, after);
+                index += 1;
+                keep
+            })
+        })
+    }
+
+    fn retain_traced_with<F: FnMut(&mut str, char, &mut str) -> bool>(
+        &mut self,
+        snapshot_len: usize,
+        f: F,
+    ) -> RetainTrace {
+        stable(self, |s| trace::retain_traced(s, snapshot_len, f))
+    }
+
+    fn try_retain_all<E, F: FnMut(&mut str, char, &mut str) -> Result<bool, E>>(
+        &mut self,
+        mut f: F,
+    ) -> Result<(), E> {
+        stable(self, |s| {
+            let mut cursor = Cursor::new(s);
+            while let Some(ch) = cursor.peek() {
+                let ch_len = ch.len_utf8();
+                let (before, after) = cursor.split(ch_len);
+                match f(before, ch, after) {
+                    Ok(true) => cursor.keep(ch_len),
+                    Ok(false) => cursor.remove(ch_len),
+                    Err(err) => {
+                        // Close the gap, keeping the current character
+                        cursor.keep_rest();
+                        return Err(err);
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn retain_control_flow<F: FnMut(&mut str, char, &mut str) -> ControlFlow<(), bool>>(
+        &mut self,
+        mut f: F,
+    ) {
+        stable(self, |s| {
+            let mut cursor = Cursor::new(s);
+            while let Some(ch) = cursor.peek() {
+                let ch_len = ch.len_utf8();
+                let (before, after) = cursor.split(ch_len);
+                match f(before, ch, after) {
+                    ControlFlow::Continue(true) => cursor.keep(ch_len),
+                    ControlFlow::Continue(false) => cursor.remove(ch_len),
+                    ControlFlow::Break(()) => {
+                        // A single copy closes any gap before the rest
+                        cursor.keep_rest();
+                        return;
+                    }
+                }
+            }
+        })
+    }
+
+    fn retain_truncate<F: FnMut(&mut str, char, &mut str) -> bool>(&mut self, mut f: F) -> usize {
+        stable(self, |s| {
+            let mut cursor = Cursor::new(s);
+            while let Some(ch) = cursor.peek() {
+                let ch_len = ch.len_utf8();
+                let (before, after) = cursor.split(ch_len);
+                if !f(before, ch, after) {
+                    // Dropping the cursor truncates the string to the kept region
+                    return cursor.original_len() - cursor.kept_len();
+                }
+                cursor.keep(ch_len);
+            }
+            0
+        })
+    }
+
+    fn retain_all_ref<F: FnMut(&str, char, &str) -> bool>(&mut self, mut f: F) {
+        stable(self, |s| {
+            let mut cursor = Cursor::new(s);
+            while let Some(ch) = cursor.peek() {
+                let ch_len = ch.len_utf8();
+                let (before, after) = cursor.split_ref(ch_len);
+                if f(before, ch, after) {
+                    cursor.keep(ch_len);
+                } else {
+                    cursor.remove(ch_len);
+                }
+            }
+        })
+    }
+
+    fn retain_all_bytes_ctx<F: FnMut(&[u8], char, &[u8]) -> bool>(&mut self, mut f: F) {
+        stable(self, |s| {
+            let mut cursor = Cursor::new(s);
+            while let Some(ch) = cursor.peek() {
+                let ch_len = ch.len_utf8();
+                let (before, after) = cursor.split_bytes(ch_len);
+                if f(before, ch, after) {
+                    cursor.keep(ch_len);
+                } else {
+                    cursor.remove(ch_len);
+                }
+            }
+        })
+    }
+
+    fn retain_all_mapped<F: FnMut(&mut str, char, &mut str) -> bool>(
+        &mut self,
+        mapper: &mut OffsetMapper,
+        f: F,
+    ) {
+        stable(self, |s| offsets::retain_all_mapped(s, mapper, f))
+    }
+
+    fn retain_adjust_spans<F: FnMut(char) -> bool>(&mut self, f: F, spans: &mut [Range<usize>]) {
+        stable(self, |s| offsets::retain_adjust_spans(s, f, spans))
+    }
+
+    fn retain_zip<T, F: FnMut(char, &T) -> bool>(
+        &mut self,
+        meta: &mut Vec<T>,
+        f: F,
+    ) -> Result<(), ZipLengthError> {
+        stable(self, |s| zip::retain_zip(s, meta, f))
+    }
+
+    fn retain_by_mask(&mut self, mask: &[bool]) -> Result<(), MaskLenError> {
+        stable(self, |s| zip::retain_by_mask(s, mask))
+    }
+
+    fn retain_default<F: FnMut(char) -> bool>(&mut self, mut f: F) {
+        stable(self, |s| s.retain_default_dyn(&mut f))
+    }
+
+    fn retain_default_dyn(&mut self, f: &mut dyn FnMut(char) -> bool) {
+        stable(self, |s| s.retain(f))
+    }
+
+    fn retain_by_lead_byte<F: FnMut(u8) -> bool>(&mut self, mut f: F) {
+        stable(self, |s| {
+            let mut cursor = Cursor::new(s);
+            while let Some(&lead) = cursor.rest().as_bytes().first() {
+                let ch_len = match lead {
+                    0x00..=0x7F => 1,
+                    0xC0..=0xDF => 2,
+                    0xE0..=0xEF => 3,
+                    _ => 4,
+                };
+                if f(lead) {
+                    cursor.keep(ch_len);
+                } else {
+                    cursor.remove(ch_len);
+                }
+            }
+        })
+    }
+
+    fn retain_char_bytes<F: FnMut(&[u8], char, &mut str) -> bool>(&mut self, mut f: F) {
+        stable(self, |s| {
+            let mut cursor = Cursor::new(s);
+            while let Some(ch) = cursor.peek() {
+                let ch_len = ch.len_utf8();
+                let (bytes, after) = cursor.current_and_after(ch_len);
+                if f(bytes, ch, after) {
+                    cursor.keep(ch_len);
+                } else {
+                    cursor.remove(ch_len);
+                }
+            }
+        })
+    }
+
+    fn retain_diff<F: FnMut(char) -> bool>(&mut self, f: F) -> DeletionScript {
+        stable(self, |s| diff::retain_diff(s, f))
+    }
+
+    fn retain_observed_until<F, O>(&mut self, f: F, observer: O)
+    where
+        F: FnMut(char) -> RetainAction,
+        O: RemovalObserver,
+    {
+        stable(self, |s| observer::retain_observed_until(s, f, observer))
+    }
+
+    fn retain_with_options<F: FnMut(char) -> bool>(
+        &mut self,
+        opts: RetainOptions<'_>,
+        f: F,
+    ) -> RetainReport {
+        options::retain_with_options(self, opts, f)
+    }
+
+    fn retain_classified<F: FnMut(char) -> bool>(&mut self, f: F) -> ClassifiedReport {
+        stable(self, |s| classified::retain_classified(s, f))
+    }
+
+    fn retain_budgeted<F: FnMut(char) -> bool>(&mut self, f: F) -> RetainBudgeted<'_, F> {
+        RetainBudgeted::new(self, f)
+    }
+
+    fn extract_if<F: FnMut(char, &mut str) -> bool>(&mut self, f: F) -> ExtractIf<'_, F> {
+        ExtractIf::new(self, f)
+    }
+
+    fn retain_sparse<F: FnMut(char) -> bool>(&mut self, expected_kept: usize, f: F) {
+        stable(self, |s| {
+            sparse::retain_sparse(s, expected_kept, &mut RetainScratch::new(), f)
+        })
+    }
+
+    fn retain_sparse_with_scratch<F: FnMut(char) -> bool>(
+        &mut self,
+        expected_kept: usize,
+        scratch: &mut RetainScratch,
+        f: F,
+    ) {
+        stable(self, |s| {
+            sparse::retain_sparse(s, expected_kept, scratch, f)
+        })
+    }
+
+    fn retain_with_min_len<F: FnMut(char) -> bool>(&mut self, min_chars: usize, mut f: F) {
+        stable(self, |s| {
+            let mut removable = s.chars().count().saturating_sub(min_chars);
+            let mut cursor = Cursor::new(s);
+            while removable > 0 {
+                let ch = match cursor.peek() {
+                    Some(ch) => ch,
+                    None => break,
+                };
+                if f(ch) {
+                    cursor.keep(ch.len_utf8());
+                } else {
+                    cursor.remove(ch.len_utf8());
+                    removable -= 1;
+                }
+            }
+            cursor.keep_rest();
+        })
+    }
+
+    fn remove_limited<F: FnMut(char) -> bool>(&mut self, mut f: F, max_removals: usize) -> usize {
+        stable(self, |s| {
+            let mut removed = 0;
+            let mut cursor = Cursor::new(s);
+            while removed < max_removals {
+                let Some(ch) = cursor.peek() else {
+                    break;
+                };
+                if f(ch) {
+                    cursor.keep(ch.len_utf8());
+                } else {
+                    cursor.remove(ch.len_utf8());
+                    removed += 1;
+                }
+            }
+            cursor.keep_rest();
+            removed
+        })
+    }
+
+    fn keep_limited<F: FnMut(char) -> bool>(&mut self, mut f: F, max_kept: usize) -> usize {
+        stable(self, |s| {
+            let mut kept = 0;
+            let mut cursor = Cursor::new(s);
+            while kept < max_kept {
+                let Some(ch) = cursor.peek() else {
+                    break;
+                };
+                if f(ch) {
+                    cursor.keep(ch.len_utf8());
+                    kept += 1;
+                } else {
+                    cursor.remove(ch.len_utf8());
+                }
+            }
+            // Dropping the cursor removes the rest of the string
+            kept
+        })
+    }
+
+    fn retain_protect_ends<F: FnMut(char) -> bool>(&mut self, head: usize, tail: usize, mut f: F) {
+        stable(self, |s| {
+            let head_end = s.char_indices().nth(head).map_or(s.len(), |(i, _)| i);
+            let tail_start = match tail.checked_sub(1) {
+                Some(n) => s.char_indices().nth_back(n).map_or(0, |(i, _)| i),
+                None => s.len(),
+            };
+            if head_end >= tail_start {
+                return;
+            }
+            let mut cursor = Cursor::new(s);
+            cursor.keep(head_end);
+            while cursor.offset() < tail_start {
+                let ch = cursor.peek().unwrap();
+                if f(ch) {
+                    cursor.keep(ch.len_utf8());
+                } else {
+                    cursor.remove(ch.len_utf8());
+                }
+            }
+            cursor.keep_rest();
+        })
+    }
+
+    fn retain_char_runs<F: FnMut(char, usize) -> usize>(&mut self, mut f: F) {
+        stable(self, |s| {
+            let mut cursor = Cursor::new(s);
+            while let Some(ch) = cursor.peek() {
+                let ch_len = ch.len_utf8();
+                let run = cursor.rest().chars().take_while(|&c| c == ch).count();
+                let keep = f(ch, run).min(run);
+                cursor.keep(keep * ch_len);
+                cursor.remove((run - keep) * ch_len);
+            }
+        })
+    }
+
+    fn normalize_indent_in_place(&mut self, opts: &IndentOptions) {
+        stable(self, |s| lines::normalize_indent_in_place(s, opts))
+    }
+
+    fn dedent_in_place(&mut self) -> usize {
+        stable(self, lines::dedent_in_place)
+    }
+
+    fn collapse_blank_lines_with(&mut self, opts: &BlankLineOptions) {
+        stable(self, |s| lines::collapse_blank_lines(s, opts))
+    }
+
+    fn minify_json_whitespace_in_place(&mut self) -> Result<(), JsonMinifyError> {
+        stable(self, json::minify_json_whitespace_in_place)
+    }
+
+    fn retain_with_last_kept_after<F: FnMut(Option<char>, char, &mut str) -> bool>(
+        &mut self,
+        mut f: F,
+    ) {
+        stable(self, |s| {
+            let mut last_kept = None;
+            let mut cursor = Cursor::new(s);
+            while let Some(ch) = cursor.peek() {
+                let ch_len = ch.len_utf8();
+                if f(last_kept, ch, cursor.after(ch_len)) {
+                    cursor.keep(ch_len);
+                    last_kept = Some(ch);
+                } else {
+                    cursor.remove(ch_len);
+                }
+            }
+        })
+    }
+
+    fn retain_adjacent<F: FnMut(Option<char>, char, Option<char>) -> bool>(&mut self, mut f: F) {
+        stable(self, |s| {
+            let mut cursor = Cursor::new(s);
+            let mut previous = None;
+            // The next character is cached, so it becomes the current character
+            // without being decoded again
+            let mut current = cursor.peek();
+            while let Some(ch) = current {
+                let ch_len = ch.len_utf8();
+                let next = cursor.rest()[ch_len..].chars().next();
+                if f(previous, ch, next) {
+                    cursor.keep(ch_len);
+                } else {
+                    cursor.remove(ch_len);
+                }
+                previous = Some(ch);
+                current = next;
+            }
+        })
+    }
+
+    fn retain_with_streak<F: FnMut(RemovalStreak, char) -> bool>(&mut self, mut f: F) {
+        stable(self, |s| {
+            let mut streak = RemovalStreak::default();
+            let mut cursor = Cursor::new(s);
+            while let Some(ch) = cursor.peek() {
+                if f(streak, ch) {
+                    cursor.keep(ch.len_utf8());
+                    streak.consecutive_removed = 0;
+                } else {
+                    cursor.remove(ch.len_utf8());
+                    streak.consecutive_removed += 1;
+                    streak.total_removed += 1;
+                }
+            }
+        })
+    }
+
+    fn retain_tidy<F: FnMut(char) -> bool>(&mut self, separators: &[char], mut f: F) {
+        stable(self, |s| {
+            // Whether a separator would be doubled or leading, so should be
+            // removed
+            let mut after_separator = true;
+            {
+                let mut cursor = Cursor::new(s);
+                while let Some(ch) = cursor.peek() {
+                    let is_separator = separators.contains(&ch);
+                    if f(ch) && !(is_separator && after_separator) {
+                        cursor.keep(ch.len_utf8());
+                        after_separator = is_separator;
+                    } else {
+                        cursor.remove(ch.len_utf8());
+                    }
+                }
+            }
+            if after_separator {
+                // Either nothing was kept, or the last kept character is a
+                // trailing separator
+                s.pop();
+            }
+        })
+    }
+
+    fn retain_route<F: FnMut(char) -> Route>(
+        &mut self,
+        diverted: &mut String,
+        f: F,
+    ) -> RouteCounts {
+        stable(self, |s| route::retain_route(s, diverted, f))
+    }
+
+    fn char_frequencies(&self) -> CharFrequencies {
+        frequency::char_frequencies(self)
+    }
+
+    fn retain_by_frequency<F: FnMut(char, usize) -> bool>(&mut self, f: F) {
+        stable(self, |s| frequency::retain_by_frequency(s, f))
+    }
+
+    fn dedup_chars_keep_last(&mut self) {
+        stable(self, frequency::dedup_chars_keep_last)
+    }
+
+    fn retain_words<F: FnMut(&str) -> bool>(&mut self, f: F) {
+        stable(self, |s| words::retain_words(s, f))
+    }
+
+    fn retain_words_by_len<R: RangeBounds<usize>>(&mut self, bounds: R) {
+        stable(self, |s| words::retain_words_by_len(s, bounds))
+    }
+
+    fn dedup_words_by<F: FnMut(&str, &str) -> bool>(&mut self, eq: F) {
+        stable(self, |s| words::dedup_words_by(s, eq))
+    }
+
+    fn collapse_whitespace(&mut self) {
+        stable(self, |s| {
+            let mut cursor = Cursor::new(s);
+            // Whether a space is owed before the next kept character
+            let mut pending = false;
+            while let Some(ch) = cursor.peek() {
+                let ch_len = ch.len_utf8();
+                if !ch.is_whitespace() {
+                    if pending {
+                        // The run was at least one byte, so this fits
+                        cursor.replace(0, " ");
+                        pending = false;
+                    }
+                    cursor.keep(ch_len);
+                    continue;
+                }
+                // Keep a lone space between words as it is, to avoid writes
+                let lone = ch == ' '
+                    && !pending
+                    && cursor.kept_len() > 0
+                    && cursor.rest()[1..]
+                        .chars()
+                        .next()
+                        .is_some_and(|next| !next.is_whitespace());
+                if lone {
+                    cursor.keep(ch_len);
+                } else {
+                    cursor.remove(ch_len);
+                    pending = cursor.kept_len() > 0;
+                }
+            }
+        })
+    }
+
+    fn remove_digit_separators_with(&mut self, separators: &[char], digits: DigitKind) {
+        stable(self, |s| {
+            numeric::remove_digit_separators(s, separators, digits)
+        })
+    }
+
+    fn remove_between_with(&mut self, open: &str, close: &str, opts: &BetweenOptions) -> usize {
+        stable(self, |s| between::remove_between(s, open, close, opts))
+    }
+
+    fn retain_between_with(&mut self, open: &str, close: &str, opts: &BetweenOptions) {
+        stable(self, |s| between::retain_between(s, open, close, opts))
+    }
+
+    fn retain_hash_removed<H: Hasher, F: FnMut(char) -> bool>(&mut self, hasher: &mut H, f: F) {
+        stable(self, |s| hash::retain_hash_removed(s, hasher, f))
+    }
+
+    fn retain_visit<V: RetainVisitor + ?Sized>(&mut self, visitor: &mut V) {
+        stable(self, |s| visitor::retain_visit(s, visitor))
+    }
+
+    #[cfg(feature = "unicode-width")]
+    fn retain_width<F: FnMut(char) -> bool>(&mut self, max_width: usize, f: F) {
+        stable(self, |s| width::retain_width(s, max_width, f))
+    }
+
+    #[cfg(feature = "unicode-width")]
+    fn truncate_width(&mut self, max_width: usize, ellipsis: Option<char>) {
+        stable(self, |s| width::truncate_width(s, max_width, ellipsis))
+    }
+
+    fn split_off_when<F: FnMut(char) -> ControlFlow<(), bool>>(&mut self, mut f: F) -> String {
+        stable(self, |s| {
+            let mut cursor = Cursor::new(s);
+            while let Some(ch) = cursor.peek() {
+                match f(ch) {
+                    ControlFlow::Continue(true) => cursor.keep(ch.len_utf8()),
+                    ControlFlow::Continue(false) => cursor.remove(ch.len_utf8()),
+                    // The rest of the string is still untouched, so can be copied
+                    // out directly. Dropping the cursor then leaves only the kept
+                    // region.
+                    ControlFlow::Break(()) => return String::from(cursor.rest()),
+                }
+            }
+            String::new()
+        })
+    }
+
+    fn split_when_with<F: FnMut(char) -> bool>(self, empty: EmptyPieces, f: F) -> Vec<String> {
+        split::split_when(self, empty, f)
+    }
+
+    fn filter_into_boxed_str<F: FnMut(char) -> bool>(mut self, f: F) -> Box<str> {
+        self.retain_default(f);
+        self.into_boxed_str()
+    }
+
+    fn filter_chars<F: FnMut(char) -> bool>(mut self, f: F) -> String {
+        self.retain_default(f);
+        self
+    }
+
+    fn filter_chars_ctx<F: FnMut(&mut str, char, &mut str) -> bool>(mut self, f: F) -> String {
+        self.retain_all(f);
+        self
+    }
+
+    fn take_span<F: FnMut(char) -> bool>(&mut self, mut f: F) -> Option<String> {
+        stable(self, |s| {
+            let mut chars = s.char_indices();
+            let start = chars.find(|&(_, ch)| f(ch))?.0;
+            let end = chars
+                .find(|&(_, ch)| !f(ch))
+                .map_or(s.len(), |(offset, _)| offset);
+            let span = String::from(&s[start..end]);
+            s.drain(start..end);
+            Some(span)
+        })
+    }
+
+    fn take_span_matches(&mut self, needle: &str) -> Option<String> {
+        stable(self, |s| {
+            let start = s.find(needle)?;
+            s.drain(start..start + needle.len());
+            Some(String::from(needle))
+        })
+    }
+
+    fn keep_matches(&mut self, needle: &str) -> usize {
+        stable(self, |s| {
+            if needle.is_empty() {
+                let count = s.chars().count() + 1;
+                s.clear();
+                return count;
+            }
+            let mut count = 0;
+            let mut cursor = Cursor::new(s);
+            while let Some(offset) = cursor.rest().find(needle) {
+                cursor.remove(offset);
+                cursor.keep(needle.len());
+                count += 1;
+            }
+            // Dropping the cursor removes everything after the last occurrence
+            count
+        })
+    }
+
+    fn remove_first_n_matches(&mut self, needle: &str, n: usize) -> usize {
+        stable(self, |s| {
+            if needle.is_empty() {
+                return n.min(s.chars().count() + 1);
+            }
+            let mut count = 0;
+            let mut cursor = Cursor::new(s);
+            while count < n {
+                let Some(offset) = cursor.rest().find(needle) else {
+                    break;
+                };
+                cursor.keep(offset);
+                cursor.remove(needle.len());
+                count += 1;
+            }
+            cursor.keep_rest();
+            count
+        })
+    }
+
+    fn remove_byte_ranges(&mut self, ranges: &[Range<usize>]) -> Result<usize, RangeError> {
+        stable(self, |s| ranges::remove_byte_ranges(s, ranges))
+    }
+
+    fn remove_matches_ignore_case(&mut self, needle: &str) -> usize {
+        stable(self, |s| {
+            if needle.is_empty() {
+                return s.chars().count() + 1;
+            }
+            let mut count = 0;
+            let mut cursor = Cursor::new(s);
+            while let Some(ch) = cursor.peek() {
+                let mut rest = cursor.rest().chars();
+                let matched = needle
+                    .chars()
+                    .all(|expected| rest.next().map(simple_fold) == Some(simple_fold(expected)));
+                if matched {
+                    cursor.remove(cursor.rest().len() - rest.as_str().len());
+                    count += 1;
+                } else {
+                    cursor.keep(ch.len_utf8());
+                }
+            }
+            count
+        })
+    }
+
+    fn strip_prefixes_in_place(&mut self, prefixes: &[&str]) -> usize {
+        stable(self, |s| {
+            let mut start = 0;
+            while let Some(len) = prefixes
+                .iter()
+                .filter(|prefix| !prefix.is_empty() && s[start..].starts_with(*prefix))
+                .map(|prefix| prefix.len())
+                .max()
+            {
+                start += len;
+            }
+            if start > 0 {
+                s.drain(..start);
+            }
+            start
+        })
+    }
+
+    fn strip_suffixes_in_place(&mut self, suffixes: &[&str]) -> usize {
+        stable(self, |s| {
+            let len = s.len();
+            let mut end = len;
+            while let Some(suffix_len) = suffixes
+                .iter()
+                .filter(|suffix| !suffix.is_empty() && s[..end].ends_with(*suffix))
+                .map(|suffix| suffix.len())
+                .max()
+            {
+                end -= suffix_len;
+            }
+            s.truncate(end);
+            len - end
+        })
+    }
+
+    fn extract_runs<F: FnMut(char) -> bool>(&mut self, f: F) -> Vec<String> {
+        stable(self, |s| {
+            let mut runs = Vec::new();
+            runs::for_each_run(s, f, |run| runs.push(String::from(run)));
+            runs
+        })
+    }
+
+    fn extract_runs_into<F: FnMut(char) -> bool>(&mut self, out: &mut String, sep: char, f: F) {
+        stable(self, |s| {
+            let mut first = true;
+            runs::for_each_run(s, f, |run| {
+                if !first {
+                    out.push(sep);
+                }
+                out.push_str(run);
+                first = false;
+            });
+        })
+    }
+
+    fn repair_balanced(&mut self, pairs: &[(char, char)]) -> usize {
+        stable(self, |s| {
+            balanced::repair_balanced(s, pairs, &mut RetainScratch::new())
+        })
+    }
+
+    fn repair_balanced_with_scratch(
+        &mut self,
+        pairs: &[(char, char)],
+        scratch: &mut RetainScratch,
+    ) -> usize {
+        stable(self, |s| balanced::repair_balanced(s, pairs, scratch))
+    }
+
+    #[cfg(feature = "rand")]
+    fn retain_sample<R: rand_core::RngCore + ?Sized>(
+        &mut self,
+        rng: &mut R,
+        keep_probability: f64,
+    ) {
+        stable(self, |s| sample::retain_sample(s, rng, keep_probability))
+    }
+
+    #[cfg(feature = "rand")]
+    fn retain_reservoir<R: rand_core::RngCore + ?Sized>(&mut self, rng: &mut R, k: usize) {
+        stable(self, |s| sample::retain_reservoir(s, rng, k))
+    }
+}
+
+/// The simple (1:1) case folding of `c`, as used by
+/// [`RetainMoreString::retain_folded`] and
+/// [`RetainMoreString::remove_matches_ignore_case`].
+///
+/// Lowercasing alone is not enough, since some characters have several
+/// lowercase forms (such as `'ς'` and `'σ'`, or `'ſ'` and `'s'`), which only
+/// agree once they have been through their shared uppercase form.
+fn simple_fold(c: char) -> char {
+    fn single(mut mapped: impl Iterator<Item = char>, c: char) -> char {
+        match (mapped.next(), mapped.next()) {
+            (Some(mapped), None) => mapped,
+            _ => c,
+        }
+    }
+    let upper = single(c.to_uppercase(), c);
+    single(upper.to_lowercase(), upper)
+}
+
+/// Run `f` on `s`, checking in debug builds that it keeps the allocation of
+/// `s`, as promised by [`RetainMoreString`].
+fn stable<R>(s: &mut String, f: impl FnOnce(&mut String) -> R) -> R {
+    let (ptr, capacity) = (s.as_ptr(), s.capacity());
+    let result = f(s);
+    debug_assert!(
+        s.as_ptr() == ptr && s.capacity() == capacity,
+        "the allocation of the string was changed"
+    );
+    result
+}
+
+/// Implementation of the sealed pattern for [`RetainMoreString`]
+/// See [C-SEALED] from rust-api-guidelines for explanation
+///
+/// [C-SEALED]: https://rust-lang.github.io/api-guidelines/future-proofing.html#sealed-traits-protect-against-downstream-implementations-c-sealed
+mod sealed {
+    use alloc::string::String;
+
+    pub trait Sealed {}
+    impl Sealed for String {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec::Vec};
+
+    fn redact(current: char, rest: &mut str) -> bool {
+        match (current, rest.chars().next()) {
+            ('-', Some(c)) => !c.is_ascii_digit(),
+            (c, _) => !c.is_ascii_digit(),
+        }
+    }
+
+    fn after_helper<F: FnMut(char, &mut str) -> bool>(input: &str, output: &str, f: F) {
+        let mut input = input.to_string();
+        input.retain_after(f);
+
+        assert_eq!(&input[..], output);
+    }
+    #[test]
+    fn retain_after() {
+        after_helper("this has no numbers", "this has no numbers", redact);
+        after_helper("54321", "", redact);
+        after_helper("-12345", "", redact);
+        after_helper("--12345", "-", redact);
+        after_helper("-12-3-45--", "--", redact);
+    }
+
+    #[test]
+    fn retain_report() {
+        fn report(input: &str, remove: char) -> (String, RetainReport) {
+            let mut s = input.to_string();
+            let report = s.retain_report(|_, it, _| it != remove);
+            (s, report)
+        }
+        let counts = |chars_removed, bytes_removed, chars_kept, bytes_kept| RetainReport {
+            chars_removed,
+            bytes_removed,
+            chars_kept,
+            bytes_kept,
+            ..RetainReport::default()
+        };
+        // Removing the final character
+        let (s, r) = report("ab\u{1F980}", '\u{1F980}');
+        assert_eq!((&*s, r), ("ab", counts(1, 4, 2, 2)));
+        let (s, r) = report("\u{e9}x\u{e9}", '\u{e9}');
+        assert_eq!((&*s, r), ("x", counts(2, 4, 1, 1)));
+        // Nothing removed, and removing everything
+        let (s, r) = report("\u{2603}a", 'x');
+        assert_eq!((&*s, r), ("\u{2603}a", counts(0, 0, 2, 4)));
+        assert!(!r.changed());
+        let (s, r) = report("xx", 'x');
+        assert_eq!((&*s, r), ("", counts(2, 2, 0, 0)));
+        let (s, r) = report("", 'x');
+        assert_eq!((&*s, r), ("", RetainReport::default()));
+    }
+
+    #[test]
+    fn retain_changed() {
+        fn changed(input: &str, remove: char) -> (String, bool, bool) {
+            let mut s = input.to_string();
+            let changed = s.retain_changed(|it| it != remove);
+            let mut all = input.to_string();
+            let all_changed = all.retain_all_changed(|_, it, _| it != remove);
+            assert_eq!(s, all);
+            (s, changed, all_changed)
+        }
+        // All kept, all removed, and empty
+        assert_eq!(
+            changed("ab\u{e9}", 'x'),
+            ("ab\u{e9}".to_string(), false, false)
+        );
+        assert_eq!(
+            changed("\u{1F980}\u{1F980}", '\u{1F980}'),
+            (String::new(), true, true)
+        );
+        assert_eq!(changed("", 'x'), (String::new(), false, false));
+        // Only the last character removed
+        assert_eq!(
+            changed("ab\u{e9}", '\u{e9}'),
+            ("ab".to_string(), true, true)
+        );
+    }
+
+    #[test]
+    fn retain_all_ref() {
+        let samples = [
+            "this has no numbers",
+            "54321",
+            "--12345",
+            "-12-3-45--",
+            "\u{e9}-1",
+        ];
+        for sample in samples {
+            let mut expected = sample.to_string();
+            expected.retain_all(|_, it, after| redact(it, after));
+            let mut s = sample.to_string();
+            s.retain_all_ref(|_, it, after| redact(it, &mut after.to_string()));
+            assert_eq!(s, expected);
+        }
+
+        // The predicate can capture shared borrows of other strings
+        let source = String::from("keep: b, d");
+        let kept: Vec<&str> = source["keep: ".len()..].split(", ").collect();
+        let mut s = "abcde".to_string();
+        let mut contexts = Vec::new();
+        s.retain_all_ref(|before, it, after| {
+            contexts.push((before.len(), after.len()));
+            kept.iter().any(|k| k.starts_with(it))
+        });
+        assert_eq!(s, "bd");
+        assert_eq!(contexts, [(0, 4), (0, 3), (1, 2), (1, 1), (2, 0)]);
+    }
+
+    #[test]
+    fn retain_all_indexed() {
+        let input = "\u{1F980}\u{e9}\u{1F980}a\u{2603}b";
+        let mut s = input.to_string();
+        let mut seen = Vec::new();
+        s.retain_all_indexed(|offset, before, it, _| {
+            seen.push((offset, before.len(), it));
+            it.is_ascii() || it == '\u{2603}'
+        });
+        assert_eq!(s, "a\u{2603}b");
+        // The offsets are those in the original string, even after the
+        // multibyte characters at the start were removed
+        let expected: Vec<_> = input
+            .char_indices()
+            .zip([0, 0, 0, 0, 1, 4])
+            .map(|((offset, it), kept)| (offset, kept, it))
+            .collect();
+        assert_eq!(seen, expected);
+        assert_eq!(seen[3], (10, 0, 'a'));
+    }
+
+    #[test]
+    fn retain_all_char_indexed() {
+        let input = "a\u{2603}\u{1F980}b\u{e9}\u{1F980}c";
+        let mut s = input.to_string();
+        let mut seen = Vec::new();
+        s.retain_all_char_indexed(|index, _, it, _| {
+            seen.push((index, it));
+            it.is_ascii()
+        });
+        assert_eq!(s, "abc");
+        let expected: Vec<_> = input.chars().enumerate().collect();
+        assert_eq!(seen, expected);
+        // The byte offset of `c` is 15, but it is the 7th character
+        assert_eq!(seen[6], (6, 'c'));
+        assert_eq!(input.find('c'), Some(15));
+    }
+
+    #[test]
+    fn try_retain_all() {
+        fn stop_at_bang(input: &str) -> (Result<(), usize>, String) {
+            let mut s = input.to_string();
+            let mut count = 0;
+            let result = s.try_retain_all(|_, it, _| {
+                count += 1;
+                match it {
+                    '!' => Err(count),
+                    it => Ok(it.is_ascii()),
+                }
+            });
+            (result, s)
+        }
+        // Stopping after a gap of multibyte characters
+        assert_eq!(
+            stop_at_bang("a\u{e9}\u{1F980}b!\u{e9}c\u{2603}"),
+            (Err(5), "ab!\u{e9}c\u{2603}".to_string())
+        );
+        // Stopping on the first character, and the last
+        assert_eq!(stop_at_bang("!\u{e9}"), (Err(1), "!\u{e9}".to_string()));
+        assert_eq!(stop_at_bang("\u{e9}\u{e9}!"), (Err(3), "!".to_string()));
+        // Not stopping
+        assert_eq!(stop_at_bang("a\u{e9}b"), (Ok(()), "ab".to_string()));
+    }
+
+    #[test]
+    fn retain_tidy() {
+        fn tidy(input: &str, removed: &str) -> String {
+            let mut s = input.to_string();
+            s.retain_tidy(&[' ', '\u{3000}'], |c| !removed.contains(c));
+            s
+        }
+        // Everything between two separators removed
+        assert_eq!(tidy("a x b", "x"), "a b");
+        assert_eq!(tidy("a xyz  b", "xyz"), "a b");
+        // Different separators are still adjacent separators
+        assert_eq!(tidy("a \u{3000}b\u{3000} c", ""), "a b\u{3000}c");
+        // Leading and trailing separators, before and after removals
+        assert_eq!(tidy("  a b  ", ""), "a b");
+        assert_eq!(tidy("x a b x", "x"), "a b");
+        assert_eq!(tidy("\u{e9} a \u{1F980}", "\u{e9}\u{1F980}"), "a");
+        // The predicate can remove separators itself
+        assert_eq!(tidy("a b\u{3000}c", " "), "ab\u{3000}c");
+        // Nothing kept, or only separators
+        assert_eq!(tidy("xyz", "xyz"), "");
+        assert_eq!(tidy(" \u{3000} ", ""), "");
+        assert_eq!(tidy("", ""), "");
+    }
+
+    #[test]
+    fn retain_control_flow() {
+        fn until(input: &str, stop: char) -> (String, usize) {
+            let mut s = input.to_string();
+            let mut calls = 0;
+            s.retain_control_flow(|_, it, _| {
+                calls += 1;
+                if it == stop {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(it.is_ascii())
+                }
+            });
+            (s, calls)
+        }
+        let stopped = |s: &str, calls: usize| (s.to_string(), calls);
+        // Multibyte characters removed before the break, and kept after it
+        assert_eq!(
+            until("\u{e9}a\u{1F980}|\u{1F980}b\u{e9}", '|'),
+            stopped("a|\u{1F980}b\u{e9}", 4)
+        );
+        // Breaking on a multibyte character, straight after a removal
+        assert_eq!(
+            until("a\u{e9}\u{2603}\u{e9}b", '\u{2603}'),
+            stopped("a\u{2603}\u{e9}b", 3)
+        );
+        // Breaking on the first and last characters
+        assert_eq!(until("|\u{e9}", '|'), stopped("|\u{e9}", 1));
+        assert_eq!(until("\u{e9}\u{e9}|", '|'), stopped("|", 3));
+        // Never breaking
+        assert_eq!(until("a\u{e9}b", '|'), stopped("ab", 3));
+    }
+
+    #[test]
+    fn retain_truncate() {
+        fn cut(input: &str) -> (String, usize, usize) {
+            let mut s = input.to_string();
+            let mut calls = 0;
+            let removed = s.retain_truncate(|_, it, _| {
+                calls += 1;
+                it.is_ascii()
+            });
+            (s, removed, calls)
+        }
+        let cut_to = |s: &str, removed: usize, calls: usize| (s.to_string(), removed, calls);
+        // The predicate isn't called after the first rejection
+        assert_eq!(cut("ab\u{e9}cd\u{e9}"), cut_to("ab", 6, 3));
+        assert_eq!(cut("\u{1F980}abc"), cut_to("", 7, 1));
+        assert_eq!(cut("abc\u{1F980}"), cut_to("abc", 4, 4));
+        assert_eq!(cut("abc"), cut_to("abc", 0, 3));
+        assert_eq!(cut(""), cut_to("", 0, 0));
+    }
+
+    #[test]
+    fn retain_scan() {
+        // Remove repeated characters, counting the removals in each run
+        let mut s = "aa\u{e9}\u{e9}\u{e9}b\u{1F980}\u{1F980}".to_string();
+        let (_, runs) = s.retain_scan((None, Vec::new()), |(last, runs), it, after| {
+            let repeat = *last == Some(it);
+            if repeat {
+                *runs.last_mut().unwrap() += 1;
+            } else if after.starts_with(it) {
+                runs.push(0);
+            }
+            *last = Some(it);
+            !repeat
+        });
+        assert_eq!(s, "a\u{e9}b\u{1F980}");
+        assert_eq!(runs, [1, 2, 1]);
+
+        let mut s = String::new();
+        assert_eq!(
+            s.retain_scan(3, |n, _, _| {
+                *n += 1;
+                false
+            }),
+            3
+        );
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn retain_counted() {
+        let mut s = "\u{1F980}a\u{1F980}\u{1F980}b\u{1F980}".to_string();
+        let mut counts = Vec::new();
+        // Keep at most two crabs
+        let mut crabs = 0;
+        s.retain_counted(|kept, it, _| {
+            counts.push(kept);
+            if it == '\u{1F980}' {
+                crabs += 1;
+                crabs <= 2
+            } else {
+                true
+            }
+        });
+        assert_eq!(s, "\u{1F980}a\u{1F980}b");
+        // Removed characters aren't counted
+        assert_eq!(counts, [0, 1, 2, 3, 3, 4]);
+    }
+
+    #[test]
+    fn retain_all_before_is_up_to_date() {
+        // `before` is always exactly the characters kept so far, including
+        // changes made through it, which `retain_after` doesn't promise
+        let mut s = "ab\u{e9}-cd\u{1F980}-e".to_string();
+        let mut expected_before = String::new();
+        s.retain_all(|before, it, after| {
+            assert_eq!(before, &expected_before[..]);
+            assert_eq!(before.chars().last(), expected_before.chars().last());
+            before.make_ascii_uppercase();
+            expected_before.make_ascii_uppercase();
+            // `after` is the untouched rest of the string
+            assert!(!after.contains(|c: char| c.is_ascii_uppercase()));
+            let keep = it != '-';
+            if keep {
+                expected_before.push(it);
+            }
+            keep
+        });
+        assert_eq!(s, "AB\u{e9}CD\u{1F980}e");
+
+        let mut s = "x-y\u{e9}".to_string();
+        let mut kept = String::new();
+        s.retain_ctx(|mut ctx| {
+            assert_eq!(ctx.kept(), &kept[..]);
+            assert_eq!(ctx.kept_chars(), kept.chars().count());
+            let keep = ctx.current() != '-';
+            if keep {
+                kept.push(ctx.current());
+            }
+            keep
+        });
+        assert_eq!(s, "xy\u{e9}");
+    }
+
+    #[test]
+    fn retain_after_sees_changes_to_after() {
+        let mut s = "a-b\u{e9}-c".to_string();
+        let mut seen = Vec::new();
+        s.retain_after(|it, after| {
+            seen.push(it);
+            after.make_ascii_uppercase();
+            it != '-'
+        });
+        assert_eq!(s, "aB\u{e9}C");
+        assert_eq!(seen, ['a', '-', 'B', '\u{e9}', '-', 'C']);
+    }
+
+    #[test]
+    fn retain_before() {
+        // Stop after the first multibyte character which follows a `#`
+        let mut s = "a#\u{e9}b\u{1F980}".to_string();
+        let mut seen = Vec::new();
+        s.retain_before(|before, it| {
+            seen.push((before.to_string(), it));
+            let mut chars = before.chars().rev();
+            !matches!((chars.next(), chars.next()), (Some(c), Some('#')) if !c.is_ascii())
+        });
+        assert_eq!(s, "a#\u{e9}");
+        let expected = [
+            ("", 'a'),
+            ("a", '#'),
+            ("a#", '\u{e9}'),
+            ("a#\u{e9}", 'b'),
+            ("a#\u{e9}", '\u{1F980}'),
+        ];
+        let expected: Vec<_> = expected.iter().map(|&(b, c)| (b.to_string(), c)).collect();
+        assert_eq!(seen, expected);
+
+        // The kept region can be modified
+        let mut s = "\u{e9}x\u{e9}".to_string();
+        s.retain_before(|before, it| {
+            before.make_ascii_uppercase();
+            it != 'x'
+        });
+        assert_eq!(s, "\u{e9}\u{e9}");
+        let mut s = "ab\u{1F980}".to_string();
+        s.retain_before(|before, _| {
+            before.make_ascii_uppercase();
+            true
+        });
+        assert_eq!(s, "AB\u{1F980}");
+    }
+
+    #[test]
+    fn retain_default() {
+        // Adapted from https://github.com/rust-lang/rust/blob/2ad5292aea63/library/alloc/tests/string.rs#L364-L396
+        let mut s = String::from("α_β_γ");
+
+        s.retain_default(|_| true);
+        assert_eq!(s, "α_β_γ");
+
+        s.retain_default(|c| c != '_');
+        assert_eq!(s, "αβγ");
+
+        s.retain_default(|c| c != 'β');
+        assert_eq!(s, "αγ");
+
+        s.retain_default(|c| c == 'α');
+        assert_eq!(s, "α");
+
+        s.retain_default(|_| false);
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn retain_with_sink() {
+        let events = core::cell::RefCell::new(Vec::new());
+        let mut s = "a1\u{e9}2\u{1F980}".to_string();
+        s.retain_with_sink(
+            |c| {
+                events.borrow_mut().push(('?', c));
+                !c.is_ascii_digit()
+            },
+            |c| events.borrow_mut().push(('-', c)),
+        );
+        assert_eq!(s, "a\u{e9}\u{1F980}");
+        // Each removed character is sunk once, straight after it is rejected
+        let expected = [
+            ('?', 'a'),
+            ('?', '1'),
+            ('-', '1'),
+            ('?', '\u{e9}'),
+            ('?', '2'),
+            ('-', '2'),
+            ('?', '\u{1F980}'),
+        ];
+        assert_eq!(events.into_inner(), expected);
+    }
+
+    #[test]
+    fn retain_folded() {
+        let vowels = |c: char| !"aeiou\u{e9}".contains(c);
+        let mut s = "AEIOU aeiou X\u{c9}\u{e9}Y".to_string();
+        s.retain_folded(vowels);
+        assert_eq!(s, "  XY");
+
+        // Each character is given to the predicate folded, and only once
+        let mut s = "Stra\u{df}E \u{c9}\u{130}".to_string();
+        let mut seen = Vec::new();
+        s.retain_folded(|c| {
+            seen.push(c);
+            c != 'e' && c != '\u{df}'
+        });
+        assert_eq!(
+            seen,
+            ['s', 't', 'r', 'a', '\u{df}', 'e', ' ', '\u{e9}', '\u{130}']
+        );
+        assert_eq!(s, "Stra \u{c9}\u{130}");
+
+        // Letters with several lowercase forms are given as the usual one
+        let mut s = "\u{3a3}\u{3c3}\u{3c2} \u{17f}S \u{3d0}".to_string();
+        let mut seen = Vec::new();
+        s.retain_folded(|c| {
+            seen.push(c);
+            c != '\u{3c3}'
+        });
+        assert_eq!(
+            seen,
+            ['\u{3c3}', '\u{3c3}', '\u{3c3}', ' ', 's', 's', ' ', '\u{3b2}']
+        );
+        assert_eq!(s, " \u{17f}S \u{3d0}");
+    }
+
+    #[test]
+    fn dyn_matches_generic() {
+        let samples = ["", "a b  c", "\u{e9}1\u{1F980} 2\u{2603}  x", "   "];
+        // Stateful predicates, which depend on every argument
+        fn all_predicate() -> impl FnMut(&mut str, char, &mut str) -> bool {
+            let mut calls = 0;
+            move |before, it, after| {
+                calls += 1;
+                !(it == ' ' && (before.ends_with(' ') || after.is_empty())) && calls % 5 != 0
+            }
+        }
+        fn default_predicate() -> impl FnMut(char) -> bool {
+            let mut calls = 0;
+            move |it| {
+                calls += 1;
+                !it.is_ascii_digit() && calls % 4 != 0
+            }
+        }
+        for sample in samples {
+            let mut expected = sample.to_string();
+            expected.retain_all(all_predicate());
+            let mut s = sample.to_string();
+            let mut f = all_predicate();
+            s.retain_all_dyn(&mut f);
+            assert_eq!(s, expected, "retain_all_dyn on {:?}", sample);
+
+            let mut expected = sample.to_string();
+            expected.retain(default_predicate());
+            let mut s = sample.to_string();
+            let mut f: alloc::boxed::Box<dyn FnMut(char) -> bool> =
+                alloc::boxed::Box::new(default_predicate());
+            s.retain_default_dyn(&mut f);
+            assert_eq!(s, expected, "retain_default_dyn on {:?}", sample);
+        }
+    }
+
+    #[test]
+    fn retain_by_lead_byte() {
+        // A table which removes every three and four byte character
+        let mut table = [true; 256];
+        table[0xE0..].iter_mut().for_each(|keep| *keep = false);
+        let mut s = "a\u{e9}\u{7ff}\u{800}b\u{ffff}\u{10000}\u{1F980}c\u{10FFFF}".to_string();
+        let mut seen = Vec::new();
+        s.retain_by_lead_byte(|b| {
+            seen.push(b);
+            table[usize::from(b)]
+        });
+        assert_eq!(s, "a\u{e9}\u{7ff}bc");
+        // Only lead bytes are seen, once per character
+        assert_eq!(
+            seen,
+            [b'a', 0xC3, 0xDF, 0xE0, b'b', 0xEF, 0xF0, 0xF0, b'c', 0xF4]
+        );
+
+        let mut s = String::new();
+        s.retain_by_lead_byte(|_| unreachable!());
+        assert_eq!(s, "");
+    }
+
+    /// Check that `f` leaves the pointer and capacity of a copy of `input`
+    /// (with spare capacity) unchanged.
+    fn assert_stable(name: &str, input: &str, f: impl FnOnce(&mut String)) {
+        let mut s = String::with_capacity(input.len() + 16);
+        s.push_str(input);
+        let (ptr, capacity) = (s.as_ptr(), s.capacity());
+        f(&mut s);
+        assert_eq!((s.as_ptr(), s.capacity()), (ptr, capacity), "{}", name);
+    }
+
+    #[test]
+    fn allocation_is_stable() {
+        const TEXT: &str = "  Hello,  w\u{f6}rld!! 1_000 (x [y) \n\n\n\t\u{1F980}-42 end end  ";
+        let odd = |c: char| u32::from(c) % 2 == 1;
+        let all = |_: &mut str, c: char, _: &mut str| odd(c);
+        let check = |name, f: &dyn Fn(&mut String)| assert_stable(name, TEXT, f);
+        /// A hasher which ignores what it is given.
+        struct Ignore;
+        impl Hasher for Ignore {
+            fn finish(&self) -> u64 {
+                0
+            }
+            fn write(&mut self, _: &[u8]) {}
+        }
+
+        check("retain_all", &|s| s.retain_all(all));
+        check("retain_all_dyn", &|s| s.retain_all_dyn(&mut { all }));
+        check("retain_report", &|s| {
+            s.retain_report(all);
+        });
+        check("retain_all_changed", &|s| {
+            s.retain_all_changed(all);
+        });
+        check("retain_changed", &|s| {
+            s.retain_changed(odd);
+        });
+        check("retain_ctx", &|s| s.retain_ctx(|ctx| odd(ctx.current())));
+        check("try_retain_all", &|s| {
+            let _ = s.try_retain_all(|_, c, _| if c == '!' { Err(()) } else { Ok(odd(c)) });
+        });
+        check("retain_control_flow", &|s| {
+            s.retain_control_flow(|_, c, _| match c {
+                '(' => ControlFlow::Break(()),
+                c => ControlFlow::Continue(odd(c)),
+            })
+        });
+        check("retain_truncate", &|s| {
+            s.retain_truncate(|_, c, _| c != '!');
+        });
+        check("retain_all_bytes_ctx", &|s| {
+            s.retain_all_bytes_ctx(|_, c, _| odd(c))
+        });
+        check("retain_all_ref", &|s| s.retain_all_ref(|_, c, _| odd(c)));
+        check("retain_all_indexed", &|s| {
+            s.retain_all_indexed(|_, _, c, _| odd(c))
+        });
+        check("retain_all_char_indexed", &|s| {
+            s.retain_all_char_indexed(|_, _, c, _| odd(c))
+        });
+        check("retain_traced", &|s| {
+            s.retain_traced(all);
+        });
+        check("retain_traced_with", &|s| {
+            s.retain_traced_with(4, all);
+        });
+        check("retain_all_mapped", &|s| {
+            s.retain_all_mapped(&mut OffsetMapper::new(), all)
+        });
+        check("retain_after", &|s| s.retain_after(|c, _| odd(c)));
+        check("retain_before", &|s| s.retain_before(|_, c| odd(c)));
+        check("retain_scan", &|s| {
+            s.retain_scan(0, |_, c, _| odd(c));
+        });
+        check("retain_counted", &|s| s.retain_counted(|_, c, _| odd(c)));
+        check("retain_default", &|s| s.retain_default(odd));
+        check("retain_default_dyn", &|s| {
+            s.retain_default_dyn(&mut { odd })
+        });
+        check("retain_matching", &|s| s.retain_matching('a'..='m'));
+        check("remove_matching", &|s| s.remove_matching("aeiou"));
+        check("retain_by_lead_byte", &|s| {
+            s.retain_by_lead_byte(|b| b % 2 == 1)
+        });
+        check("retain_folded", &|s| s.retain_folded(odd));
+        check("retain_char_bytes", &|s| {
+            s.retain_char_bytes(|_, c, _| odd(c))
+        });
+        check("retain_diff", &|s| {
+            s.retain_diff(odd);
+        });
+        check("retain_observed", &|s| s.retain_observed(odd, |_, _| {}));
+        check("retain_observed_until", &|s| {
+            s.retain_observed_until(
+                |c| match c {
+                    'x' => RetainAction::RemoveRest,
+                    c if odd(c) => RetainAction::Keep,
+                    _ => RetainAction::Remove,
+                },
+                |_, _| {},
+            )
+        });
+        check("retain_with_options", &|s| {
+            s.retain_with_options(RetainOptions::new(), odd);
+        });
+        check("retain_classified", &|s| {
+            s.retain_classified(odd);
+        });
+        check("retain_sparse", &|s| s.retain_sparse(2, odd));
+        check("retain_sparse_with_scratch", &|s| {
+            s.retain_sparse_with_scratch(100, &mut RetainScratch::new(), odd)
+        });
+        check("retain_budgeted", &|s| {
+            let mut retain = s.retain_budgeted(odd);
+            retain.step(3);
+        });
+        check("retain_with_min_len", &|s| s.retain_with_min_len(5, odd));
+        check("retain_protect_ends", &|s| s.retain_protect_ends(2, 2, odd));
+        check("retain_char_runs", &|s| {
+            s.retain_char_runs(|_, len| len / 2)
+        });
+        check("normalize_indent_in_place", &|s| {
+            s.normalize_indent_in_place(&IndentOptions::Cap(1))
+        });
+        check("dedent_in_place", &|s| {
+            s.dedent_in_place();
+        });
+        check("dedent", &|s| {
+            s.dedent();
+        });
+        check("collapse_blank_lines", &|s| s.collapse_blank_lines(1));
+        check("collapse_blank_lines_with", &|s| {
+            s.collapse_blank_lines_with(&BlankLineOptions::new(0))
+        });
+        assert_stable(
+            "minify_json_whitespace_in_place",
+            "{ \"a\" : [1, 2] }",
+            |s| s.minify_json_whitespace_in_place().unwrap(),
+        );
+        check("retain_with_last_kept", &|s| {
+            s.retain_with_last_kept(|_, c| odd(c))
+        });
+        check("retain_with_last_kept_after", &|s| {
+            s.retain_with_last_kept_after(|_, c, _| odd(c))
+        });
+        check("retain_adjacent", &|s| s.retain_adjacent(|_, c, _| odd(c)));
+        check("retain_with_streak", &|s| {
+            s.retain_with_streak(|_, c| odd(c))
+        });
+        check("retain_tidy", &|s| s.retain_tidy(&[' '], odd));
+        check("retain_route", &|s| {
+            s.retain_route(&mut String::new(), |c| {
+                if odd(c) {
+                    Route::Keep
+                } else {
+                    Route::Divert
+                }
+            });
+        });
+        check("extract_removed_into", &|s| {
+            s.extract_removed_into(odd, &mut String::new())
+        });
+        check("retain_with_sink", &|s| s.retain_with_sink(odd, |_| {}));
+        check("retain_logged", &|s| s.retain_logged(odd, &mut Vec::new()));
+        check("retain_removed_ranges", &|s| {
+            drop(s.retain_removed_ranges(odd))
+        });
+        check("retain_with_offset_map", &|s| {
+            drop(s.retain_with_offset_map(odd))
+        });
+        check("retain_adjust_spans", &|s| {
+            s.retain_adjust_spans(odd, &mut [0..1, 1..3])
+        });
+        check("retain_zip", &|s| {
+            let mut meta: Vec<()> = s.chars().map(drop).collect();
+            s.retain_zip(&mut meta, |c, _| odd(c)).unwrap();
+        });
+        check("retain_by_mask", &|s| {
+            let mask: Vec<bool> = s.chars().map(odd).collect();
+            s.retain_by_mask(&mask).unwrap();
+        });
+        check("retain_map", &|s| s.retain_map(|c, _| odd(c).then_some(c)));
+        check("retain_replace", &|s| {
+            s.retain_replace(|c, _| match c {
+                'a' => RetainDecision::Replace(""),
+                c if odd(c) => RetainDecision::Keep,
+                _ => RetainDecision::Remove,
+            })
+            .unwrap()
+        });
+        check("mask_retain", &|s| {
+            s.mask_retain(|c, _| odd(c), '#').unwrap()
+        });
+        check("retain_splice", &|s| {
+            s.retain_splice(|c, _| odd(c), "-".chars())
+        });
+        check("extract_if", &|s| {
+            s.extract_if(|c, _| !odd(c)).for_each(drop)
+        });
+        check("retain_by_frequency", &|s| {
+            s.retain_by_frequency(|_, count| count > 1)
+        });
+        check("retain_min_frequency", &|s| s.retain_min_frequency(2));
+        check("dedup_chars_keep_last", &|s| s.dedup_chars_keep_last());
+        check("retain_dedup", &|s| s.retain_dedup());
+        check("retain_dedup_by", &|s| {
+            s.retain_dedup_by(|a, b| odd(a) == odd(b))
+        });
+        check("collapse_whitespace", &|s| s.collapse_whitespace());
+        check("remove_all_matches", &|s| {
+            s.remove_all_matches("a");
+        });
+        check("remove_first_n_matches", &|s| {
+            s.remove_first_n_matches("a", 1);
+        });
+        check("remove_byte_ranges", &|s| {
+            let _ = s.remove_byte_ranges(&[2..3, 0..1]);
+        });
+        check("remove_limited", &|s| {
+            s.remove_limited(odd, 2);
+        });
+        check("keep_limited", &|s| {
+            s.keep_limited(odd, 2);
+        });
+        check("remove_matches_ignore_case", &|s| {
+            s.remove_matches_ignore_case("A");
+        });
+        check("retain_words", &|s| s.retain_words(|word| word.len() > 3));
+        check("retain_words_in", &|s| {
+            s.retain_words_in(&|word: &str| word == "end")
+        });
+        check("retain_words_by_len", &|s| s.retain_words_by_len(..4));
+        check("dedup_words", &|s| s.dedup_words());
+        check("dedup_words_by", &|s| {
+            s.dedup_words_by(|a, b| a.len() == b.len())
+        });
+        check("remove_digit_separators", &|s| {
+            s.remove_digit_separators(&['_'])
+        });
+        check("remove_digit_separators_with", &|s| {
+            s.remove_digit_separators_with(&['_'], DigitKind::Numeric)
+        });
+        check("remove_between", &|s| {
+            s.remove_between("a", "c", true);
+        });
+        check("remove_between_with", &|s| {
+            let nested = BetweenOptions {
+                nested: true,
+                ..BetweenOptions::new()
+            };
+            s.remove_between_with("a", "c", &nested);
+        });
+        check("retain_between", &|s| s.retain_between("a", "c"));
+        check("retain_between_with", &|s| {
+            s.retain_between_with("a", "c", &BetweenOptions::new())
+        });
+        check("retain_hash_removed", &|s| {
+            s.retain_hash_removed(&mut Ignore, odd)
+        });
+        check("retain_visit", &|s| {
+            s.retain_visit(&mut CountingVisitor::new(odd))
+        });
+        check("split_off_when", &|s| {
+            s.split_off_when(|c| match c {
+                '(' => ControlFlow::Break(()),
+                c => ControlFlow::Continue(odd(c)),
+            });
+        });
+        check("take_span", &|s| {
+            s.take_span(|c| c.is_alphabetic());
+        });
+        check("take_span_matches", &|s| {
+            s.take_span_matches("end");
+        });
+        check("keep_matches", &|s| {
+            s.keep_matches("end");
+        });
+        check("strip_prefixes_in_place", &|s| {
+            s.strip_prefixes_in_place(&[" ", "He"]);
+        });
+        check("strip_suffixes_in_place", &|s| {
+            s.strip_suffixes_in_place(&[" ", "end"]);
+        });
+        check("extract_runs", &|s| {
+            s.extract_runs(char::is_alphabetic);
+        });
+        check("extract_runs_into", &|s| {
+            s.extract_runs_into(&mut String::new(), ',', char::is_alphabetic)
+        });
+        check("repair_balanced", &|s| {
+            s.repair_balanced(&[('(', ')'), ('[', ']')]);
+        });
+        check("repair_balanced_with_scratch", &|s| {
+            s.repair_balanced_with_scratch(&[('(', ')')], &mut RetainScratch::new());
+        });
+        #[cfg(feature = "unicode-width")]
+        {
+            check("retain_width", &|s| s.retain_width(10, odd));
+            check("truncate_width", &|s| {
+                s.truncate_width(10, Some('\u{2026}'))
+            });
+        }
+        #[cfg(feature = "rand")]
+        {
+            /// A generator which always gives the same value.
+            struct Constant;
+            impl rand_core::RngCore for Constant {
+                fn next_u32(&mut self) -> u32 {
+                    u32::MAX / 3
+                }
+                fn next_u64(&mut self) -> u64 {
+                    u64::MAX / 3
+                }
+                fn fill_bytes(&mut self, dst: &mut [u8]) {
+                    rand_core::impls::fill_bytes_via_next(self, dst)
+                }
+            }
+            check("retain_sample", &|s| s.retain_sample(&mut Constant, 0.5));
+            check("retain_reservoir", &|s| {
+                s.retain_reservoir(&mut Constant, 5)
+            });
+        }
+    }
+
+    #[test]
+    fn retain_char_bytes() {
+        let input = "a\u{e9}\u{2603}\u{1F980}b";
+        let mut s = input.to_string();
+        let start = s.as_ptr() as usize;
+        let mut seen = Vec::new();
+        s.retain_char_bytes(|bytes, it, after| {
+            // The bytes are those in the original string, in place
+            seen.push((bytes.to_vec(), bytes.as_ptr() as usize - start, it));
+            assert_eq!(after, &input[input.len() - after.len()..]);
+            it != '\u{2603}'
+        });
+        assert_eq!(s, "a\u{e9}\u{1F980}b");
+        let expected: [(&[u8], usize, char); 5] = [
+            (b"a", 0, 'a'),
+            (b"\xC3\xA9", 1, '\u{e9}'),
+            (b"\xE2\x98\x83", 3, '\u{2603}'),
+            (b"\xF0\x9F\xA6\x80", 6, '\u{1F980}'),
+            (b"b", 10, 'b'),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(bytes, offset, it)| (bytes.to_vec(), offset, it))
+            .collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn retain_default_matches_retain() {
+        // `retain_default` accepts exactly the predicates `String::retain` does
+        let _: fn(&mut String, fn(char) -> bool) = String::retain;
+        let _: fn(&mut String, fn(char) -> bool) = String::retain_default;
+
+        let samples = ["", "a_b", "\u{e9}_\u{1F980}__x", "____"];
+        for sample in samples {
+            let mut expected = sample.to_string();
+            let mut visited = Vec::new();
+            expected.retain(|c| {
+                visited.push(c);
+                c != '_'
+            });
+            let mut s = sample.to_string();
+            let mut visited_default = Vec::new();
+            s.retain_default(|c| {
+                visited_default.push(c);
+                c != '_'
+            });
+            assert_eq!(s, expected);
+            assert_eq!(visited_default, visited);
+        }
+    }
+
+    #[test]
+    fn remove_limited() {
+        // The limit is reached exactly at the last character
+        let mut s = "a-b-\u{e9}-".to_string();
+        let mut calls = 0;
+        let removed = s.remove_limited(
+            |c| {
+                calls += 1;
+                c != '-'
+            },
+            3,
+        );
+        assert_eq!((s.as_str(), removed, calls), ("ab\u{e9}", 3, 6));
+
+        // The rest is kept without calling the predicate
+        let mut s = "--\u{1F980}-x".to_string();
+        let mut calls = 0;
+        let removed = s.remove_limited(
+            |c| {
+                calls += 1;
+                c != '-'
+            },
+            2,
+        );
+        assert_eq!((s.as_str(), removed, calls), ("\u{1F980}-x", 2, 2));
+
+        // Fewer rejected characters than the limit
+        let mut s = "a-b".to_string();
+        assert_eq!(s.remove_limited(|c| c != '-', 5), 1);
+        assert_eq!(s, "ab");
+        assert_eq!(s.remove_limited(|_| panic!(), 0), 0);
+        assert_eq!(s, "ab");
+    }
+
+    #[test]
+    fn keep_limited() {
+        // The quota is filled exactly at the last character
+        let mut s = "a1\u{e9}2b".to_string();
+        let mut calls = 0;
+        let kept = s.keep_limited(
+            |c| {
+                calls += 1;
+                c.is_alphabetic()
+            },
+            3,
+        );
+        assert_eq!((s.as_str(), kept, calls), ("a\u{e9}b", 3, 5));
+
+        // The rest is removed without calling the predicate
+        let mut s = "ab\u{1F980}cd".to_string();
+        let mut calls = 0;
+        let kept = s.keep_limited(
+            |_| {
+                calls += 1;
+                true
+            },
+            2,
+        );
+        assert_eq!((s.as_str(), kept, calls), ("ab", 2, 2));
+
+        // Fewer accepted characters than the quota
+        let mut s = "a1b".to_string();
+        assert_eq!(s.keep_limited(|c| c.is_alphabetic(), 5), 2);
+        assert_eq!(s, "ab");
+        assert_eq!(s.keep_limited(|_| panic!(), 0), 0);
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn retain_with_min_len() {
+        // Floor larger than the string
+        let mut s = "abc".to_string();
+        s.retain_with_min_len(10, |_| panic!("predicate should not be called"));
+        assert_eq!(s, "abc");
+
+        // Floor of zero is a plain retain
+        let mut s = "a1b2c3".to_string();
+        s.retain_with_min_len(0, |c| c.is_alphabetic());
+        assert_eq!(s, "abc");
+        s.retain_with_min_len(0, |_| false);
+        assert_eq!(s, "");
+
+        // The floor binds exactly at the last character
+        let mut s = "ab€d".to_string();
+        let mut calls = 0;
+        s.retain_with_min_len(1, |_| {
+            calls += 1;
+            false
+        });
+        assert_eq!(s, "d");
+        assert_eq!(calls, 3);
+
+        // Kept characters don't use up the allowance
+        let mut s = "a-b-c-d".to_string();
+        s.retain_with_min_len(4, |c| c != '-');
+        assert_eq!(s, "abcd");
+    }
+
+    #[test]
+    fn retain_protect_ends() {
+        // Overlapping ends keep the whole string
+        let mut s = "abcdef".to_string();
+        s.retain_protect_ends(3, 3, |_| panic!("predicate should not be called"));
+        assert_eq!(s, "abcdef");
+        s.retain_protect_ends(10, 0, |_| panic!("predicate should not be called"));
+        s.retain_protect_ends(0, 10, |_| panic!("predicate should not be called"));
+        assert_eq!(s, "abcdef");
+
+        // Multibyte characters at both boundaries
+        let mut s = "é\u{1F600}x€y\u{1F600}ü".to_string();
+        let mut seen = Vec::new();
+        s.retain_protect_ends(2, 2, |c| {
+            seen.push(c);
+            false
+        });
+        assert_eq!(seen, ['x', '€', 'y']);
+        assert_eq!(s, "é\u{1F600}\u{1F600}ü");
+
+        // No protection is a plain retain
+        let mut s = "a1b2".to_string();
+        s.retain_protect_ends(0, 0, |c| c.is_alphabetic());
+        assert_eq!(s, "ab");
+    }
+
+    #[test]
+    fn retain_adjacent() {
+        let mut s = "\u{e9}a\u{1F980}".to_string();
+        let mut seen = Vec::new();
+        s.retain_adjacent(|previous, it, next| {
+            seen.push((previous, it, next));
+            it == 'a'
+        });
+        assert_eq!(s, "a");
+        // Removed characters are still passed as the previous character
+        assert_eq!(
+            seen,
+            [
+                (None, '\u{e9}', Some('a')),
+                (Some('\u{e9}'), 'a', Some('\u{1F980}')),
+                (Some('a'), '\u{1F980}', None),
+            ]
+        );
+
+        let mut s = "\u{2603}".to_string();
+        s.retain_adjacent(|previous, _, next| previous.is_some() || next.is_some());
+        assert_eq!(s, "");
+
+        let mut s = String::new();
+        s.retain_adjacent(|_, _, _| unreachable!());
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn collapse_whitespace() {
+        let cases = [
+            ("a\tb\nc\r\nd", "a b c d"),
+            ("  \t lead and trail \n", "lead and trail"),
+            ("wide\u{3000}\u{3000}space\u{3000}", "wide space"),
+            ("a \u{3000}\u{85}b", "a b"),
+            (" \t\n\u{3000} ", ""),
+            ("", ""),
+            ("already normal", "already normal"),
+        ];
+        for (input, expected) in cases {
+            let mut s = input.to_string();
+            s.collapse_whitespace();
+            assert_eq!(s, expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn retain_dedup() {
+        // Runs of multi-byte characters, and a run at the start
+        let mut s = "\u{e9}\u{e9}\u{e9}a\u{1F980}\u{1F980}b\u{2603}\u{2603}".to_string();
+        s.retain_dedup();
+        assert_eq!(s, "\u{e9}a\u{1F980}b\u{2603}");
+
+        let mut s = "x".to_string();
+        s.retain_dedup_by(|_, _| unreachable!());
+        assert_eq!(s, "x");
+        let mut s = String::new();
+        s.retain_dedup();
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn retain_dedup_by_compares_with_last_kept() {
+        // Each character is compared with the first of its run, not the
+        // previous character
+        let mut s = "abcdgbc".to_string();
+        let mut seen = Vec::new();
+        s.retain_dedup_by(|last, it| {
+            seen.push((last, it));
+            (it as u32).abs_diff(last as u32) <= 2
+        });
+        assert_eq!(s, "adgb");
+        assert_eq!(
+            seen,
+            [
+                ('a', 'b'),
+                ('a', 'c'),
+                ('a', 'd'),
+                ('d', 'g'),
+                ('g', 'b'),
+                ('b', 'c')
+            ]
+        );
+    }
+
+    #[test]
+    fn retain_with_last_kept() {
+        // The last kept character is not the previous original character
+        let mut s = "aXbXXcé\u{1F600}éd".to_string();
+        let mut seen = Vec::new();
+        s.retain_with_last_kept(|last, it| {
+            seen.push(last);
+            it != 'X' && it != '\u{1F600}'
+        });
+        assert_eq!(s, "abcééd");
+        assert_eq!(
+            seen,
+            [
+                None,
+                Some('a'),
+                Some('a'),
+                Some('b'),
+                Some('b'),
+                Some('b'),
+                Some('c'),
+                Some('é'),
+                Some('é'),
+                Some('é'),
+            ]
+        );
+
+        // Matches lookbehind through `before`
+        let input = "a  b   c\t\t d";
+        let mut expected = input.to_string();
+        expected.retain_all(|before, it, _| {
+            !(it.is_whitespace() && before.ends_with(char::is_whitespace))
+        });
+        let mut s = input.to_string();
+        s.retain_with_last_kept(|last, it| {
+            !(it.is_whitespace() && last.is_some_and(char::is_whitespace))
+        });
+        assert_eq!(s, expected);
+        assert_eq!(s, "a b c\td");
+    }
+
+    #[test]
+    fn retain_with_last_kept_after() {
+        // The last kept character is tracked across removals, and is the
+        // character as it was given to the predicate
+        let mut s = "a  ^b  c".to_string();
+        let mut seen = Vec::new();
+        s.retain_with_last_kept_after(|last, it, after| {
+            seen.push(last);
+            if it == '^' {
+                after.make_ascii_uppercase();
+                return false;
+            }
+            !(it == ' ' && last == Some(' '))
+        });
+        assert_eq!(s, "a B C");
+        assert_eq!(
+            seen,
+            [
+                None,
+                Some('a'),
+                Some(' '),
+                Some(' '),
+                Some(' '),
+                Some('B'),
+                Some(' '),
+                Some(' '),
+            ]
+        );
+    }
+
+    #[test]
+    fn retain_with_streak() {
+        // Remove every digit, but at most 3 in a row
+        let max_three = |streak: RemovalStreak, it: char| {
+            !it.is_ascii_digit() || streak.consecutive_removed == 3
+        };
+        let mut s = "1234567x12y1234€12345678".to_string();
+        s.retain_with_streak(max_three);
+        // 123 removed, 4 forced, 567 removed, x kept, 12 removed, y kept,
+        // 123 removed, 4 forced, € kept, 123 removed, 4 forced, 567 removed,
+        // 8 forced
+        assert_eq!(s, "4xy4€48");
+
+        let mut s = "abcd".to_string();
+        let mut seen = Vec::new();
+        s.retain_with_streak(|streak, it| {
+            seen.push((streak.consecutive_removed, streak.total_removed));
+            it == 'c'
+        });
+        assert_eq!(s, "c");
+        assert_eq!(seen, [(0, 0), (1, 1), (2, 2), (0, 2)]);
+    }
+
+    /// The reference implementation of `retain_char_runs`, using a scalar
+    /// closure
+    fn char_runs_reference(input: &str, mut f: impl FnMut(char, usize) -> usize) -> String {
+        let chars: Vec<char> = input.chars().collect();
+        let mut keep = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let run = chars[i..].iter().take_while(|&&c| c == chars[i]).count();
+            let kept = f(chars[i], run).min(run);
+            keep.extend((0..run).map(|j| j < kept));
+            i += run;
+        }
+        let mut s = input.to_string();
+        let mut keep = keep.into_iter();
+        s.retain_default(|_| keep.next().unwrap());
+        s
+    }
+
+    #[test]
+    fn retain_char_runs() {
+        let inputs = [
+            "",
+            "a",
+            "aaabbbcccd",
+            "éééaéé\u{1F600}\u{1F600}\u{1F600}€",
+            "!!!???...   ",
+            "abababab",
+        ];
+        let policies: [fn(char, usize) -> usize; 4] = [
+            |_, _| 0,
+            |_, _| 1,
+            |_, len| len,
+            |c, len| if c.is_ascii() { 2 } else { len / 2 },
+        ];
+        for input in inputs {
+            for policy in policies {
+                let mut s = input.to_string();
+                s.retain_char_runs(policy);
+                assert_eq!(s, char_runs_reference(input, policy), "input {:?}", input);
+            }
+        }
+
+        let mut s = "\u{1F600}\u{1F600}\u{1F600}".to_string();
+        let mut calls = Vec::new();
+        s.retain_char_runs(|c, len| {
+            calls.push((c, len));
+            usize::MAX
+        });
+        assert_eq!(s, "\u{1F600}\u{1F600}\u{1F600}");
+        assert_eq!(calls, [('\u{1F600}', 3)]);
+    }
+
+    #[test]
+    fn split_off_when() {
+        fn split(input: &str, stop: char) -> (String, String) {
+            let mut s = input.to_string();
+            let tail = s.split_off_when(|it| match it {
+                it if it == stop => ControlFlow::Break(()),
+                it => ControlFlow::Continue(it != '-'),
+            });
+            (s, tail)
+        }
+        let pair = |a: &str, b: &str| (a.to_string(), b.to_string());
+        // Break on the first character
+        assert_eq!(split("|a-b", '|'), pair("", "|a-b"));
+        // Break on the last character, after removing some
+        assert_eq!(split("a-b-\u{e9}|", '|'), pair("ab\u{e9}", "|"));
+        // The tail is untouched by the earlier removals
+        assert_eq!(split("a-b|c-d", '|'), pair("ab", "|c-d"));
+        // Never breaking
+        let (s, tail) = split("a-b-c", '|');
+        assert_eq!(s, "abc");
+        assert_eq!(tail, "");
+        assert_eq!(tail.capacity(), 0);
+
+        let (_, tail) = split("ab|cdef", '|');
+        assert_eq!(tail.capacity(), tail.len());
+    }
+
+    #[test]
+    fn take_span() {
+        fn take(input: &str) -> (Option<String>, String) {
+            let mut s = input.to_string();
+            let span = s.take_span(|c| !c.is_ascii());
+            (span, s)
+        }
+        let some = |a: &str, b: &str| (Some(a.to_string()), b.to_string());
+        // At the start
+        assert_eq!(
+            take("\u{e9}\u{e8}ab\u{e9}"),
+            some("\u{e9}\u{e8}", "ab\u{e9}")
+        );
+        // At the end
+        assert_eq!(take("ab\u{1F980}"), some("\u{1F980}", "ab"));
+        // Spanning multibyte characters of different widths
+        assert_eq!(
+            take("a\u{e9}\u{1F980}\u{2603}b\u{e9}"),
+            some("\u{e9}\u{1F980}\u{2603}", "ab\u{e9}")
+        );
+        // The whole string
+        assert_eq!(take("\u{e9}\u{1F980}"), some("\u{e9}\u{1F980}", ""));
+        // Nothing matching
+        assert_eq!(take("abc"), (None, "abc".to_string()));
+        assert_eq!(take(""), (None, "".to_string()));
+    }
+
+    #[test]
+    fn take_span_matches() {
+        let mut s = "\u{e9}ab\u{e9}ab".to_string();
+        assert_eq!(s.take_span_matches("\u{e9}a").as_deref(), Some("\u{e9}a"));
+        assert_eq!(s, "b\u{e9}ab");
+        assert_eq!(s.take_span_matches("ab").as_deref(), Some("ab"));
+        assert_eq!(s, "b\u{e9}");
+        let capacity = s.capacity();
+        assert_eq!(s.take_span_matches("x"), None);
+        assert_eq!(s, "b\u{e9}");
+        assert_eq!(s.capacity(), capacity);
+        assert_eq!(s.take_span_matches("b\u{e9}").as_deref(), Some("b\u{e9}"));
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn keep_matches() {
+        // At the very ends
+        let mut s = "ab-x-ab".to_string();
+        assert_eq!(s.keep_matches("ab"), 2);
+        assert_eq!(s, "abab");
+        // Back to back
+        let mut s = "-\u{e9}\u{e9}\u{e9}-".to_string();
+        assert_eq!(s.keep_matches("\u{e9}"), 3);
+        assert_eq!(s, "\u{e9}\u{e9}\u{e9}");
+        // Overlapping
+        let mut s = "aaaaa".to_string();
+        assert_eq!(s.keep_matches("aa"), 2);
+        assert_eq!(s, "aaaa");
+        // Absent
+        let mut s = "abc".to_string();
+        assert_eq!(s.keep_matches("\u{1F980}"), 0);
+        assert_eq!(s, "");
+        let mut s = "".to_string();
+        assert_eq!(s.keep_matches("a"), 0);
+        assert_eq!(s, "");
+        // Empty
+        let mut s = "\u{e9}b".to_string();
+        assert_eq!(s.keep_matches(""), 3);
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn remove_all_matches() {
+        // At the very ends
+        let mut s = "ab-x-ab".to_string();
+        assert_eq!(s.remove_all_matches("ab"), 2);
+        assert_eq!(s, "-x-");
+        // Multi-byte needles, back to back
+        let mut s = "-\u{e9}\u{1F980}\u{e9}\u{1F980}-".to_string();
+        assert_eq!(s.remove_all_matches("\u{e9}\u{1F980}"), 2);
+        assert_eq!(s, "--");
+        // Overlapping
+        let mut s = "aaaaa".to_string();
+        assert_eq!(s.remove_all_matches("aa"), 2);
+        assert_eq!(s, "a");
+        // Longer than the string
+        let mut s = "ab".to_string();
+        assert_eq!(s.remove_all_matches("abc"), 0);
+        assert_eq!(s, "ab");
+        let mut s = "".to_string();
+        assert_eq!(s.remove_all_matches("a"), 0);
+        assert_eq!(s, "");
+        // Empty
+        let mut s = "\u{e9}b".to_string();
+        assert_eq!(s.remove_all_matches(""), 3);
+        assert_eq!(s, "\u{e9}b");
+    }
+
+    #[test]
+    fn remove_first_n_matches() {
+        let mut s = "a,b,c,d".to_string();
+        assert_eq!(s.remove_first_n_matches(",", 2), 2);
+        assert_eq!(s, "abc,d");
+        let mut s = "a,b".to_string();
+        assert_eq!(s.remove_first_n_matches(",", 5), 1);
+        assert_eq!(s, "ab");
+        let mut s = "a,b,c".to_string();
+        assert_eq!(s.remove_first_n_matches(",", usize::MAX), 2);
+        assert_eq!(s, "abc");
+        let mut s = "a,b".to_string();
+        assert_eq!(s.remove_first_n_matches(",", 0), 0);
+        assert_eq!(s, "a,b");
+        // Occurrences made adjacent by removals are not removed
+        let mut s = "aabb".to_string();
+        assert_eq!(s.remove_first_n_matches("ab", 2), 1);
+        assert_eq!(s, "ab");
+        let mut s = "x--y".to_string();
+        assert_eq!(s.remove_first_n_matches("-", 1), 1);
+        assert_eq!(s, "x-y");
+        // Empty
+        let mut s = "ab".to_string();
+        assert_eq!(s.remove_first_n_matches("", 2), 2);
+        assert_eq!(s, "ab");
+        let mut s = "ab".to_string();
+        assert_eq!(s.remove_first_n_matches("", 10), 3);
+        assert_eq!(s, "ab");
+    }
+
+    #[test]
+    fn remove_matches_ignore_case() {
+        let mut s = "Password, PASSWORD: pAsSwOrD".to_string();
+        assert_eq!(s.remove_matches_ignore_case("password"), 3);
+        assert_eq!(s, ", : ");
+        // Cyrillic and Greek
+        let mut s = "\u{41f}\u{410}\u{420}\u{41e}\u{41b}\u{42c}=1".to_string();
+        assert_eq!(
+            s.remove_matches_ignore_case("\u{43f}\u{430}\u{440}\u{43e}\u{43b}\u{44c}"),
+            1
+        );
+        assert_eq!(s, "=1");
+        let mut s = "\u{3b1}\u{391}\u{392}\u{3b2}".to_string();
+        assert_eq!(s.remove_matches_ignore_case("\u{3b1}\u{3b2}"), 1);
+        assert_eq!(s, "\u{3b1}\u{3b2}");
+        // Characters with several lowercase forms: final sigma, the long s and
+        // the beta symbol
+        let mut s = "\u{39f}\u{394}\u{39f}\u{3a3}".to_string();
+        assert_eq!(
+            s.remove_matches_ignore_case("\u{3bf}\u{3b4}\u{3bf}\u{3c2}"),
+            1
+        );
+        assert_eq!(s, "");
+        let mut s = "\u{3bf}\u{3b4}\u{3bf}\u{3c2}".to_string();
+        assert_eq!(
+            s.remove_matches_ignore_case("\u{3bf}\u{3b4}\u{3bf}\u{3c3}"),
+            1
+        );
+        assert_eq!(s, "");
+        let mut s = "\u{1f41}\u{3b4}\u{3cc}\u{3c2}".to_string();
+        assert_eq!(
+            s.remove_matches_ignore_case("\u{1f49}\u{394}\u{38c}\u{3a3}"),
+            1
+        );
+        assert_eq!(s, "");
+        let mut s = "\u{17f}top".to_string();
+        assert_eq!(s.remove_matches_ignore_case("stop"), 1);
+        assert_eq!(s, "");
+        let mut s = "\u{3d0}eta".to_string();
+        assert_eq!(s.remove_matches_ignore_case("\u{3b2}eta"), 1);
+        assert_eq!(s, "");
+        // Occurrences which are a different length in bytes from the needle:
+        // the Kelvin sign, the Ohm sign, and the capital sharp s
+        let mut s = "1\u{212a}m".to_string();
+        assert_eq!(s.remove_matches_ignore_case("k"), 1);
+        assert_eq!(s, "1m");
+        let mut s = "5\u{2126}".to_string();
+        assert_eq!(s.remove_matches_ignore_case("\u{3c9}"), 1);
+        assert_eq!(s, "5");
+        let mut s = "STRA\u{1e9e}E".to_string();
+        assert_eq!(s.remove_matches_ignore_case("stra\u{df}e"), 1);
+        assert_eq!(s, "");
+        // Only the simple folding is used
+        let mut s = "Stra\u{df}e".to_string();
+        assert_eq!(s.remove_matches_ignore_case("strasse"), 0);
+        assert_eq!(s, "Stra\u{df}e");
+        let mut s = "STRASSE".to_string();
+        assert_eq!(s.remove_matches_ignore_case("stra\u{df}e"), 0);
+        assert_eq!(s, "STRASSE");
+        // Overlapping, and longer than the string
+        let mut s = "AaAaA".to_string();
+        assert_eq!(s.remove_matches_ignore_case("aa"), 2);
+        assert_eq!(s, "A");
+        let mut s = "ab".to_string();
+        assert_eq!(s.remove_matches_ignore_case("ABC"), 0);
+        assert_eq!(s, "ab");
+        let mut s = "\u{e9}b".to_string();
+        assert_eq!(s.remove_matches_ignore_case(""), 3);
+        assert_eq!(s, "\u{e9}b");
+    }
+
+    #[test]
+    fn strip_prefixes_in_place() {
+        let mut s = "xml:ns2:id".to_string();
+        assert_eq!(s.strip_prefixes_in_place(&["xml:", "ns2:"]), 8);
+        assert_eq!(s, "id");
+        // The longest match is preferred
+        let mut s = "abcd".to_string();
+        assert_eq!(s.strip_prefixes_in_place(&["ab", "abc"]), 3);
+        assert_eq!(s, "d");
+        let mut s = "ababc".to_string();
+        assert_eq!(s.strip_prefixes_in_place(&["abc", "ab"]), 5);
+        assert_eq!(s, "");
+        // Multibyte
+        let mut s = "\u{e9}\u{e9}\u{1F980}x".to_string();
+        assert_eq!(s.strip_prefixes_in_place(&["\u{e9}", "\u{1F980}"]), 8);
+        assert_eq!(s, "x");
+        // The whole string
+        let mut s = "tmp".to_string();
+        assert_eq!(s.strip_prefixes_in_place(&["tmp"]), 3);
+        assert_eq!(s, "");
+        // Nothing matching, and empty prefixes
+        let mut s = "abc".to_string();
+        assert_eq!(s.strip_prefixes_in_place(&["", "b"]), 0);
+        assert_eq!(s, "abc");
+        let mut s = "".to_string();
+        assert_eq!(s.strip_prefixes_in_place(&["a"]), 0);
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn strip_suffixes_in_place() {
+        let mut s = "name_tmp~~".to_string();
+        assert_eq!(s.strip_suffixes_in_place(&["~", "_tmp"]), 6);
+        assert_eq!(s, "name");
+        // The longest match is preferred
+        let mut s = "xabc".to_string();
+        assert_eq!(s.strip_suffixes_in_place(&["bc", "abc"]), 3);
+        assert_eq!(s, "x");
+        let mut s = "xcabc".to_string();
+        assert_eq!(s.strip_suffixes_in_place(&["bc", "abc", "xca"]), 3);
+        assert_eq!(s, "xc");
+        // Multibyte
+        let mut s = "x\u{e9}\u{1F980}\u{e9}".to_string();
+        assert_eq!(s.strip_suffixes_in_place(&["\u{e9}", "\u{1F980}"]), 8);
+        assert_eq!(s, "x");
+        // The whole string
+        let mut s = "~~".to_string();
+        assert_eq!(s.strip_suffixes_in_place(&["~~"]), 2);
+        assert_eq!(s, "");
+        // Nothing matching, and empty suffixes
+        let mut s = "abc".to_string();
+        assert_eq!(s.strip_suffixes_in_place(&["", "b"]), 0);
+        assert_eq!(s, "abc");
+    }
+
+    #[test]
+    fn retain_all_bytes_ctx() {
+        // Remove everything between a `#` and the next `\n`, found with a
+        // bytewise search of the context
+        fn strip_comments(input: &str) -> String {
+            let mut s = input.to_string();
+            let mut in_comment = false;
+            s.retain_all_bytes_ctx(|_, it, after| {
+                if it == '#' && after.contains(&b'\n') {
+                    in_comment = true;
+                } else if it == '\n' {
+                    in_comment = false;
+                }
+                !in_comment
+            });
+            s
+        }
+        fn strip_comments_str(input: &str) -> String {
+            let mut s = input.to_string();
+            let mut in_comment = false;
+            s.retain_all(|_, it, after| {
+                if it == '#' && after.contains('\n') {
+                    in_comment = true;
+                } else if it == '\n' {
+                    in_comment = false;
+                }
+                !in_comment
+            });
+            s
+        }
+        let samples = [
+            "a # b\nc",
+            "\u{e9}#\u{1F980}\n#x\n\u{1F980}",
+            "# unterminated",
+            "##\n\n#\u{e9}\n",
+            "",
+        ];
+        for sample in samples {
+            assert_eq!(
+                strip_comments(sample),
+                strip_comments_str(sample),
+                "{:?}",
+                sample
+            );
+        }
+        assert_eq!(strip_comments("a # b\nc"), "a \nc");
+
+        // The context is the same as for `retain_all`
+        let mut contexts = Vec::new();
+        let mut s = "x\u{e9}y".to_string();
+        s.retain_all_bytes_ctx(|before, it, after| {
+            contexts.push((before.to_vec(), after.to_vec()));
+            it != '\u{e9}'
+        });
+        assert_eq!(s, "xy");
+        let expected: [(&[u8], &[u8]); 3] =
+            [(b"", "\u{e9}y".as_bytes()), (b"x", b"y"), (b"x", b"")];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|(a, b)| (a.to_vec(), b.to_vec()))
+            .collect();
+        assert_eq!(contexts, expected);
+    }
+
+    #[test]
+    fn filter_into_boxed_str() {
+        let samples = ["", "abc", "a b\u{e9} \u{1F980}c ", "   "];
+        for sample in samples {
+            let mut two_step = sample.to_string();
+            two_step.retain(|c| c != ' ');
+            let boxed = sample.to_string().filter_into_boxed_str(|c| c != ' ');
+            assert_eq!(&*boxed, two_step, "{:?}", sample);
+        }
+
+        // An empty result
+        let mut s = String::with_capacity(16);
+        s.push_str("xxx");
+        assert_eq!(&*s.filter_into_boxed_str(|_| false), "");
+
+        // Nothing removed, with no spare capacity, keeps the buffer
+        let s = "nospaces".to_string().into_boxed_str().into_string();
+        let ptr = s.as_ptr();
+        let boxed = s.filter_into_boxed_str(|c| c != ' ');
+        assert_eq!(&*boxed, "nospaces");
+        assert_eq!(boxed.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn filter_chars() {
+        let input = "a1 b\u{e9}2-\u{1F980} c3";
+        let mut sequential = input.to_string();
+        sequential.retain_default(|c| !c.is_ascii_digit());
+        sequential.retain_default(|c| c != ' ');
+        sequential.retain_default(|c| c.is_ascii());
+        let chained = input
+            .to_string()
+            .filter_chars(|c| !c.is_ascii_digit())
+            .filter_chars(|c| c != ' ')
+            .filter_chars(|c| c.is_ascii());
+        assert_eq!(chained, sequential);
+        assert_eq!(chained, "ab-c");
+
+        // The result can be used directly
+        let len = String::from("x y").filter_chars(|c| c != ' ').len();
+        assert_eq!(len, 2);
+        assert_eq!(
+            "aab"
+                .to_string()
+                .filter_chars_ctx(|before, it, _| !before.ends_with(it))
+                + "!",
+            "ab!"
+        );
     }
 }
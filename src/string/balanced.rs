@@ -0,0 +1,102 @@
+//! Removal of unmatched brackets, for
+//! [`RetainMoreString::repair_balanced`](crate::RetainMoreString::repair_balanced).
+
+use alloc::string::String;
+
+use super::{cursor::remove_ranges, scratch::RetainScratch};
+
+pub(super) fn repair_balanced(
+    s: &mut String,
+    pairs: &[(char, char)],
+    scratch: &mut RetainScratch,
+) -> usize {
+    scratch.clear();
+    // The kind of an open bracket, as the index of its pair
+    let kind = |open: char| pairs.iter().position(|&(it, _)| it == open);
+    // The open brackets which have not yet been closed are the stack in
+    // `runs`, and the unmatched brackets are marked in the bitmap
+    let mut removed = 0;
+    for (offset, ch) in s.char_indices() {
+        let range = offset..offset + ch.len_utf8();
+        let closes = pairs.iter().position(|&(_, close)| close == ch);
+        // Only the innermost open bracket can be closed. This is checked
+        // before opening, so that a quote closes an open quote
+        if let (Some(closes), Some(innermost)) = (closes, scratch.runs.last()) {
+            if Some(closes) == s[innermost.start..].chars().next().and_then(kind) {
+                scratch.runs.pop();
+                continue;
+            }
+        }
+        if kind(ch).is_some() {
+            scratch.runs.push(range);
+        } else if closes.is_some() {
+            scratch.mark(range);
+            removed += 1;
+        }
+    }
+    removed += scratch.runs.len();
+    if removed == 0 {
+        return 0;
+    }
+    while let Some(range) = scratch.runs.pop() {
+        scratch.mark(range);
+    }
+    // The unmatched closers and the unclosed openers are each in order, but
+    // are interleaved with each other, so are put in order by the bitmap
+    scratch.marked_runs();
+    remove_ranges(s, scratch.runs.drain(..));
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreString;
+    use alloc::string::{String, ToString};
+
+    const PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('"', '"'), ('\u{ab}', '\u{bb}')];
+
+    fn repair(input: &str) -> (String, usize) {
+        let mut s = input.to_string();
+        let removed = s.repair_balanced(PAIRS);
+        (s, removed)
+    }
+
+    #[test]
+    fn balanced_is_untouched() {
+        for input in [
+            "",
+            "plain",
+            "(a [b] \"c (d)\") [[]]",
+            "\u{ab}(\u{e9})\u{bb}",
+        ] {
+            assert_eq!(repair(input), (input.to_string(), 0));
+        }
+    }
+
+    #[test]
+    fn unmatched_removed() {
+        // Dangling openers, from truncated text
+        assert_eq!(repair("ok (see figure"), ("ok see figure".to_string(), 1));
+        assert_eq!(repair("[a (b"), ("a b".to_string(), 2));
+        // Stray closers at the start
+        assert_eq!(repair("))a(b)"), ("a(b)".to_string(), 2));
+        assert_eq!(repair("]\u{bb}\u{e9}"), ("\u{e9}".to_string(), 2));
+        // An odd quote
+        assert_eq!(repair("\"a\" \"b"), ("\"a\" b".to_string(), 1));
+    }
+
+    #[test]
+    fn interleaved() {
+        // A closer only matches the innermost open bracket
+        assert_eq!(repair("([)]"), ("[]".to_string(), 2));
+        assert_eq!(repair("(\"a)\""), ("\"a\"".to_string(), 2));
+        assert_eq!(repair("\u{ab}[\u{bb}]"), ("[]".to_string(), 2));
+    }
+
+    #[test]
+    fn other_characters_ignored() {
+        let mut s = "a(b)c]".to_string();
+        assert_eq!(s.repair_balanced(&[('[', ']')]), 1);
+        assert_eq!(s, "a(b)c");
+    }
+}
@@ -0,0 +1,200 @@
+use alloc::string::String;
+
+use super::{compiled::CompiledRetain, RetainMoreString};
+
+/// Configuration for [`retain_many_with`](crate::retain_many_with) and
+/// [`retain_many_by`](crate::retain_many_by).
+///
+/// Strings whose length in bytes is outside of the thresholds are skipped,
+/// and left untouched. By default, no strings are skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BatchOptions {
+    min_len: usize,
+    max_len: Option<usize>,
+}
+
+impl BatchOptions {
+    /// Options which skip no strings.
+    pub const fn new() -> Self {
+        BatchOptions {
+            min_len: 0,
+            max_len: None,
+        }
+    }
+
+    /// Skip strings shorter than `min` bytes.
+    pub const fn min_len(self, min: usize) -> Self {
+        BatchOptions {
+            min_len: min,
+            ..self
+        }
+    }
+
+    /// Skip strings longer than `max` bytes.
+    pub const fn max_len(self, max: usize) -> Self {
+        BatchOptions {
+            max_len: Some(max),
+            ..self
+        }
+    }
+
+    fn skips(&self, s: &str) -> bool {
+        s.len() < self.min_len || self.max_len.is_some_and(|max| s.len() > max)
+    }
+}
+
+/// Totals over a batch of strings filtered by [`retain_many`](crate::retain_many)
+/// and related functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BatchReport {
+    /// The number of strings which had at least one character removed.
+    pub strings_changed: usize,
+    /// The number of strings which were skipped because of their length.
+    pub strings_skipped: usize,
+    /// The total number of characters removed.
+    pub chars_removed: usize,
+}
+
+impl BatchReport {
+    fn record(&mut self, removed: usize) {
+        if removed > 0 {
+            self.strings_changed += 1;
+            self.chars_removed += removed;
+        }
+    }
+}
+
+/// Applies `compiled` to every string in `strings`.
+///
+/// This is [`retain_many_with`] with the default [`BatchOptions`].
+///
+/// # Usage
+///
+/// ```
+/// use retain_more::{retain_many, CompiledRetain};
+/// let digits = CompiledRetain::new(|c: char| c.is_ascii_digit());
+/// let mut phone_numbers = ["(555) 0100".to_string(), "555-0199".to_string(), "5550123".to_string()];
+/// let report = retain_many(&mut phone_numbers, &digits);
+/// assert_eq!(phone_numbers, ["5550100", "5550199", "5550123"]);
+/// assert_eq!((report.strings_changed, report.chars_removed), (2, 4));
+/// ```
+pub fn retain_many<F: Fn(char) -> bool>(
+    strings: &mut [String],
+    compiled: &CompiledRetain<F>,
+) -> BatchReport {
+    retain_many_with(strings, &BatchOptions::new(), compiled)
+}
+
+/// Applies `compiled` to every string in `strings` which is not skipped by
+/// `opts`.
+pub fn retain_many_with<F: Fn(char) -> bool>(
+    strings: &mut [String],
+    opts: &BatchOptions,
+    compiled: &CompiledRetain<F>,
+) -> BatchReport {
+    let mut report = BatchReport::default();
+    for s in strings {
+        if opts.skips(s) {
+            report.strings_skipped += 1;
+        } else {
+            report.record(compiled.apply(s));
+        }
+    }
+    report
+}
+
+/// Retains only the characters specified by the predicate in every string
+/// in `strings` which is not skipped by `opts`.
+///
+/// The same predicate is used for every string, in order, so it may
+/// accumulate state across the whole batch.
+///
+/// # Usage
+///
+/// ```
+/// use retain_more::{retain_many_by, BatchOptions};
+/// let mut lines = ["  short ".to_string(), "  much  longer  ".to_string()];
+/// let report = retain_many_by(&mut lines, &BatchOptions::new().min_len(10), |c| c != ' ');
+/// assert_eq!(lines, ["  short ", "muchlonger"]);
+/// assert_eq!(report.strings_skipped, 1);
+/// ```
+pub fn retain_many_by<F: FnMut(char) -> bool>(
+    strings: &mut [String],
+    opts: &BatchOptions,
+    mut f: F,
+) -> BatchReport {
+    let mut report = BatchReport::default();
+    for s in strings {
+        if opts.skips(s) {
+            report.strings_skipped += 1;
+            continue;
+        }
+        let mut removed = 0;
+        s.retain_default(|ch| {
+            let keep = f(ch);
+            removed += usize::from(!keep);
+            keep
+        });
+        report.record(removed);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec::Vec};
+
+    fn corpus() -> Vec<String> {
+        let mut strings: Vec<String> = ["", "abc", "a b c", "   ", "\u{e9}t\u{e9} !", "x"]
+            .iter()
+            .map(|it| it.to_string())
+            .collect();
+        for s in &mut strings {
+            s.reserve(32);
+        }
+        strings
+    }
+
+    #[test]
+    fn retain_many_corpus() {
+        let mut strings = corpus();
+        let capacities: Vec<usize> = strings.iter().map(String::capacity).collect();
+        let compiled = CompiledRetain::new(|c: char| !c.is_whitespace());
+        let report = retain_many(&mut strings, &compiled);
+        assert_eq!(strings, ["", "abc", "abc", "", "\u{e9}t\u{e9}!", "x"]);
+        assert_eq!(
+            report,
+            BatchReport {
+                strings_changed: 3,
+                strings_skipped: 0,
+                chars_removed: 6,
+            }
+        );
+        // Nothing reallocates, whether or not it changed
+        let after: Vec<usize> = strings.iter().map(String::capacity).collect();
+        assert_eq!(after, capacities);
+
+        let mut by_closure = corpus();
+        let report_by = retain_many_by(&mut by_closure, &BatchOptions::new(), |c| {
+            !c.is_whitespace()
+        });
+        assert_eq!(by_closure, strings);
+        assert_eq!(report_by, report);
+    }
+
+    #[test]
+    fn retain_many_skipped() {
+        let opts = BatchOptions::new().min_len(2).max_len(5);
+        let compiled = CompiledRetain::new(|c: char| c.is_alphabetic());
+        let mut strings = corpus();
+        let capacities: Vec<usize> = strings.iter().map(String::capacity).collect();
+        let report = retain_many_with(&mut strings, &opts, &compiled);
+        // "", "x" and "\u{e9}t\u{e9} !" (7 bytes) are skipped
+        assert_eq!(strings, ["", "abc", "abc", "", "\u{e9}t\u{e9} !", "x"]);
+        assert_eq!(report.strings_skipped, 3);
+        assert_eq!(report.strings_changed, 2);
+        let after: Vec<usize> = strings.iter().map(String::capacity).collect();
+        assert_eq!(after, capacities);
+    }
+}
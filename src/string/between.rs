@@ -0,0 +1,275 @@
+use alloc::string::String;
+
+use super::cursor::Cursor;
+
+/// Configuration for
+/// [`remove_between_with`](crate::RetainMoreString::remove_between_with) and
+/// [`retain_between_with`](crate::RetainMoreString::retain_between_with).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BetweenOptions {
+    /// Whether the delimiters of each section are kept, rather than removed
+    /// along with the text outside (or inside) of them.
+    pub keep_delimiters: bool,
+    /// Whether an opening delimiter inside a section starts a nested
+    /// section, so that the section only ends at the matching closing
+    /// delimiter. If `false`, a section ends at the first closing delimiter.
+    pub nested: bool,
+}
+
+impl BetweenOptions {
+    /// Options which remove the delimiters, and don't nest.
+    pub const fn new() -> Self {
+        BetweenOptions {
+            keep_delimiters: false,
+            nested: false,
+        }
+    }
+}
+
+/// The next complete section of `rest`, as the offset of its opening
+/// delimiter and the length of its contents.
+///
+/// This scans forward once, remembering the next opening and closing
+/// delimiters, so each byte is only searched again if it is part of an
+/// opening delimiter which overlaps a closing one.
+fn next_section(rest: &str, open: &str, close: &str, nested: bool) -> Option<(usize, usize)> {
+    if open.is_empty() || close.is_empty() {
+        return None;
+    }
+    let start = rest.find(open)?;
+    let inner = &rest[start + open.len()..];
+    let find_open = |from: usize| inner[from..].find(open).map(|i| from + i);
+    let mut next_open = if nested { find_open(0) } else { None };
+    let mut depth = 0_usize;
+    let mut end = inner.find(close)?;
+    loop {
+        match next_open {
+            // An opening delimiter which overlaps this close is ignored
+            Some(o) if o + open.len() <= end => {
+                depth += 1;
+                next_open = find_open(o + open.len());
+            }
+            _ if depth == 0 => return Some((start, end)),
+            _ => {
+                depth -= 1;
+                let pos = end + close.len();
+                end = pos + inner[pos..].find(close)?;
+                if next_open.is_some_and(|o| o < pos) {
+                    next_open = find_open(pos);
+                }
+            }
+        }
+    }
+}
+
+pub(super) fn remove_between(
+    s: &mut String,
+    open: &str,
+    close: &str,
+    opts: &BetweenOptions,
+) -> usize {
+    let mut cursor = Cursor::new(s);
+    let mut sections = 0;
+    while let Some((start, len)) = next_section(cursor.rest(), open, close, opts.nested) {
+        cursor.keep(start);
+        if opts.keep_delimiters {
+            cursor.keep(open.len());
+            cursor.remove(len);
+            cursor.keep(close.len());
+        } else {
+            cursor.remove(open.len() + len + close.len());
+        }
+        sections += 1;
+    }
+    // Including any unterminated section
+    cursor.keep_rest();
+    sections
+}
+
+pub(super) fn retain_between(s: &mut String, open: &str, close: &str, opts: &BetweenOptions) {
+    let mut cursor = Cursor::new(s);
+    while let Some((start, len)) = next_section(cursor.rest(), open, close, opts.nested) {
+        cursor.remove(start);
+        if opts.keep_delimiters {
+            cursor.keep(open.len() + len + close.len());
+        } else {
+            cursor.remove(open.len());
+            cursor.keep(len);
+            cursor.remove(close.len());
+        }
+    }
+    let rest = cursor.rest().len();
+    cursor.remove(rest);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BetweenOptions;
+    use crate::RetainMoreString;
+    use alloc::string::{String, ToString};
+
+    fn removed(input: &str, open: &str, close: &str, opts: BetweenOptions) -> (String, usize) {
+        let mut s = input.to_string();
+        let sections = s.remove_between_with(open, close, &opts);
+        (s, sections)
+    }
+
+    fn retained(input: &str, open: &str, close: &str, opts: BetweenOptions) -> String {
+        let mut s = input.to_string();
+        s.retain_between_with(open, close, &opts);
+        s
+    }
+
+    #[test]
+    fn back_to_back() {
+        let flat = BetweenOptions::new();
+        let input = "a(*x*)(*y*)b(**)c";
+        assert_eq!(removed(input, "(*", "*)", flat), ("abc".to_string(), 3));
+        assert_eq!(retained(input, "(*", "*)", flat), "xy");
+        let keep = BetweenOptions {
+            keep_delimiters: true,
+            ..flat
+        };
+        assert_eq!(
+            removed(input, "(*", "*)", keep),
+            ("a(**)(**)b(**)c".to_string(), 3)
+        );
+        assert_eq!(retained(input, "(*", "*)", keep), "(*x*)(*y*)(**)");
+
+        // The close can't overlap the open
+        assert_eq!(removed("(*)*)", "(*", "*)", flat), ("".to_string(), 1));
+        assert_eq!(
+            removed("say \"hi\" and \"bye", "\"", "\"", flat),
+            ("say  and \"bye".to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let input = "a(b(c)d)e(f)";
+        let flat = BetweenOptions::new();
+        let nested = BetweenOptions {
+            nested: true,
+            ..flat
+        };
+        assert_eq!(removed(input, "(", ")", flat), ("ad)e".to_string(), 2));
+        assert_eq!(removed(input, "(", ")", nested), ("ae".to_string(), 2));
+        assert_eq!(retained(input, "(", ")", flat), "b(cf");
+        assert_eq!(retained(input, "(", ")", nested), "b(c)df");
+        let keep = BetweenOptions {
+            keep_delimiters: true,
+            ..nested
+        };
+        assert_eq!(removed(input, "(", ")", keep), ("a()e()".to_string(), 2));
+
+        // Identical delimiters can't nest
+        assert_eq!(removed("'a'b'c'", "'", "'", nested), ("b".to_string(), 2));
+    }
+
+    #[test]
+    fn unterminated() {
+        let flat = BetweenOptions::new();
+        let nested = BetweenOptions {
+            nested: true,
+            ..flat
+        };
+        assert_eq!(removed("a[x]b[y", "[", "]", flat), ("ab[y".to_string(), 1));
+        assert_eq!(retained("a[x]b[y", "[", "]", flat), "x");
+        // The outer section is unterminated, so the inner one is kept
+        assert_eq!(
+            removed("a[b[c]d", "[", "]", nested),
+            ("a[b[c]d".to_string(), 0)
+        );
+        assert_eq!(retained("a[b[c]d", "[", "]", nested), "");
+
+        // Empty delimiters never match
+        assert_eq!(removed("a[b]", "", "]", flat), ("a[b]".to_string(), 0));
+        assert_eq!(retained("a[b]", "[", "", flat), "");
+    }
+
+    /// [`next_section`](super::next_section), searching again from each
+    /// nested open
+    fn naive_next_section(rest: &str, open: &str, close: &str) -> Option<(usize, usize)> {
+        let start = rest.find(open)?;
+        let inner = &rest[start + open.len()..];
+        let mut depth = 0_usize;
+        let mut pos = 0;
+        loop {
+            let end = pos + inner[pos..].find(close)?;
+            if let Some(i) = inner[pos..end].find(open) {
+                depth += 1;
+                pos += i + open.len();
+                continue;
+            }
+            if depth == 0 {
+                return Some((start, end));
+            }
+            depth -= 1;
+            pos = end + close.len();
+        }
+    }
+
+    #[test]
+    fn nested_matches_naive() {
+        let delimiters = [
+            ("(", ")"),
+            ("ab", "ba"),
+            ("aa", "a"),
+            ("a", "aa"),
+            ("ab", "b"),
+        ];
+        // Every string of up to 8 characters from the alphabet
+        for len in 0..=8 {
+            for n in 0..4_usize.pow(len) {
+                let input: String = (0..len)
+                    .map(|i| ['a', 'b', '(', ')'][n / 4_usize.pow(i) % 4])
+                    .collect();
+                for (open, close) in delimiters {
+                    assert_eq!(
+                        super::next_section(&input, open, close, true),
+                        naive_next_section(&input, open, close),
+                        "{:?} between {:?} and {:?}",
+                        input,
+                        open,
+                        close
+                    );
+                }
+            }
+        }
+
+        // Deeply nested
+        let deep = "(".repeat(10_000) + &")".repeat(10_000);
+        assert_eq!(
+            removed(
+                &deep,
+                "(",
+                ")",
+                BetweenOptions {
+                    nested: true,
+                    ..BetweenOptions::new()
+                }
+            ),
+            (String::new(), 1)
+        );
+    }
+
+    #[test]
+    fn multibyte() {
+        let input = "Der «schnelle» braune «Fuchs»\u{1F980}«x";
+        assert_eq!(
+            removed(input, "«", "»", BetweenOptions::new()),
+            ("Der  braune \u{1F980}«x".to_string(), 2)
+        );
+        assert_eq!(
+            retained(input, "«", "»", BetweenOptions::new()),
+            "schnelleFuchs"
+        );
+        let mut s = "\u{1F980}a\u{1F99E}\u{1F980}\u{1F980}b\u{1F99E}\u{1F99E}".to_string();
+        let opts = BetweenOptions {
+            keep_delimiters: false,
+            nested: true,
+        };
+        assert_eq!(s.remove_between_with("\u{1F980}", "\u{1F99E}", &opts), 2);
+        assert_eq!(s, "");
+    }
+}
@@ -0,0 +1,274 @@
+use core::{ptr, slice, str};
+
+use alloc::string::String;
+
+/// An in-progress retain, which does a bounded amount of copying each time
+/// it is [stepped](Self::step).
+///
+/// Created by
+/// [`retain_budgeted`](crate::RetainMoreString::retain_budgeted). Between
+/// steps, the string contains only the characters which have been kept and
+/// fully copied so far; the rest of its contents are held in its allocation
+/// until the retain is finished.
+///
+/// If this is dropped before it has finished, the pass is abandoned: the
+/// characters which have not yet been considered are kept without calling
+/// the predicate, which needs a single unbounded copy.
+pub struct RetainBudgeted<'a, F> {
+    string: &'a mut String,
+    f: F,
+    /// Pointer to the start of the allocation of `string`, which is never
+    /// resized whilst this exists.
+    ptr: *mut u8,
+    /// The length of the string before the retain started.
+    len: usize,
+    /// The offset of the first character which has not yet been considered.
+    read: usize,
+    /// The number of bytes which have been kept and copied into place.
+    write: usize,
+    /// The offset in the original string of the next byte of kept
+    /// characters still to be copied to `write`. The bytes to copy are
+    /// `pending..read`.
+    pending: usize,
+}
+
+/// The progress made by [`RetainBudgeted::step`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StepResult {
+    /// The number of bytes copied during this step.
+    pub bytes_moved: usize,
+    /// The number of bytes of the original string which have been considered.
+    pub bytes_processed: usize,
+    /// The number of bytes of the original string which have not yet been
+    /// considered.
+    pub bytes_remaining: usize,
+    /// Whether the retain has finished, so further steps do nothing.
+    pub finished: bool,
+}
+
+impl<'a, F: FnMut(char) -> bool> RetainBudgeted<'a, F> {
+    pub(super) fn new(string: &'a mut String, f: F) -> Self {
+        let len = string.len();
+        // SAFETY: Until the retain has finished, the length of the string is
+        // only ever set to a prefix of the kept bytes, which is valid UTF-8.
+        let ptr = unsafe { string.as_mut_vec().as_mut_ptr() };
+        RetainBudgeted {
+            string,
+            f,
+            ptr,
+            len,
+            read: 0,
+            write: 0,
+            pending: 0,
+        }
+    }
+
+    /// Continue the retain, copying at most `max_bytes_moved` bytes.
+    ///
+    /// Characters are considered until one is kept whose copy does not fit
+    /// in the remaining budget, so a step which moves nothing (e.g. because
+    /// nothing has been removed yet) may consider many characters. A kept
+    /// character which does not fit is copied over several steps.
+    pub fn step(&mut self, max_bytes_moved: usize) -> StepResult {
+        let mut budget = max_bytes_moved;
+        loop {
+            let to_copy = self.read - self.pending;
+            if to_copy > 0 {
+                let n = to_copy.min(budget);
+                // SAFETY: `write < pending`, so the destination is before the
+                // source, and both are within the allocation. `ptr::copy`
+                // handles any overlap.
+                unsafe { ptr::copy(self.ptr.add(self.pending), self.ptr.add(self.write), n) };
+                self.pending += n;
+                self.write += n;
+                budget -= n;
+                if n < to_copy {
+                    break;
+                }
+            }
+            let ch = match self.rest().chars().next() {
+                Some(ch) => ch,
+                None => break,
+            };
+            let ch_len = ch.len_utf8();
+            // Everything kept so far has been copied, so `pending == read`
+            if !(self.f)(ch) {
+                self.pending += ch_len;
+            } else if self.write == self.read {
+                // Nothing has been removed, so the character is in place
+                self.write += ch_len;
+                self.pending += ch_len;
+            }
+            // Otherwise, the kept character is copied by the next iteration
+            self.read += ch_len;
+        }
+        self.sync_len();
+        StepResult {
+            bytes_moved: max_bytes_moved - budget,
+            bytes_processed: self.read,
+            bytes_remaining: self.len - self.read,
+            finished: self.is_finished(),
+        }
+    }
+
+    /// Run the retain to completion, without a budget.
+    pub fn finish(mut self) {
+        self.step(usize::MAX);
+    }
+
+    fn is_finished(&self) -> bool {
+        self.read == self.len && self.pending == self.read
+    }
+
+    /// The characters which have not yet been considered.
+    fn rest(&self) -> &str {
+        // SAFETY: `read..len` is within the allocation, and is untouched
+        // since only bytes before `write <= read` are written. `read` is on a
+        // character boundary, so this is valid UTF-8.
+        unsafe {
+            str::from_utf8_unchecked(slice::from_raw_parts(
+                self.ptr.add(self.read),
+                self.len - self.read,
+            ))
+        }
+    }
+
+    /// Set the length of the string to the longest prefix of the copied
+    /// bytes which ends on a character boundary.
+    fn sync_len(&mut self) {
+        // SAFETY: `0..write` is within the allocation, and has been written
+        // with the kept characters in order, the last of which may be
+        // incomplete.
+        let copied = unsafe { slice::from_raw_parts(self.ptr, self.write) };
+        // Only the last character can be incomplete, so only its first byte
+        // needs to be found, at most 3 bytes before the end
+        let last_start = copied
+            .iter()
+            .rev()
+            .take(4)
+            .position(|&b| b & 0b1100_0000 != 0b1000_0000)
+            .map_or(0, |back| self.write - 1 - back);
+        let valid = match copied.get(last_start) {
+            Some(&lead) if last_start + utf8_len(lead) > self.write => last_start,
+            _ => self.write,
+        };
+        // SAFETY: `0..valid` is valid UTF-8, and within the allocation.
+        unsafe { self.string.as_mut_vec().set_len(valid) };
+    }
+}
+
+/// The length of the UTF-8 encoded character which starts with `lead`.
+fn utf8_len(lead: u8) -> usize {
+    match lead {
+        0..=0x7F => 1,
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        _ => 4,
+    }
+}
+
+impl<F> Drop for RetainBudgeted<'_, F> {
+    fn drop(&mut self) {
+        // Keep the unconsidered rest, along with any kept bytes which are
+        // still to be copied
+        let rest = self.len - self.pending;
+        // SAFETY: As in `step`. The result is the kept characters followed
+        // by the untouched rest, so is valid UTF-8.
+        unsafe {
+            ptr::copy(self.ptr.add(self.pending), self.ptr.add(self.write), rest);
+            self.string.as_mut_vec().set_len(self.write + rest);
+        }
+        debug_assert!(
+            core::ptr::eq(self.string.as_ptr(), self.ptr),
+            "the allocation of the string was changed"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreString;
+    use alloc::string::{String, ToString};
+
+    const INPUT: &str = "\u{1F980}a\u{e9}\u{2603}b \u{1F980}\u{1F980}c\u{e9} \u{2603}\u{2603}";
+
+    fn keep(c: char) -> bool {
+        c != 'a' && c != '\u{2603}' && c != ' '
+    }
+
+    #[test]
+    fn one_byte_steps() {
+        let mut expected = INPUT.to_string();
+        expected.retain(keep);
+
+        let mut s = INPUT.to_string();
+        let mut retain = s.retain_budgeted(keep);
+        let mut moved = 0;
+        loop {
+            let result = retain.step(1);
+            assert!(result.bytes_moved <= 1);
+            assert_eq!(result.bytes_processed + result.bytes_remaining, INPUT.len());
+            moved += result.bytes_moved;
+            if result.finished {
+                break;
+            }
+        }
+        drop(retain);
+        assert_eq!(s, expected);
+        // Every kept byte after the first removal is moved
+        assert_eq!(moved, expected.len() - "\u{1F980}".len());
+    }
+
+    #[test]
+    fn valid_between_steps() {
+        for budget in [1, 2, 3, 5] {
+            let mut s = INPUT.to_string();
+            let mut retain = s.retain_budgeted(keep);
+            while !retain.step(budget).finished {
+                // The string is only accessible through the retain, but its
+                // length is kept on a character boundary, excluding only an
+                // incomplete last character
+                assert!(core::str::from_utf8(retain.string.as_bytes()).is_ok());
+                assert!(retain.write - retain.string.len() < 4);
+            }
+            drop(retain);
+            let mut expected = INPUT.to_string();
+            expected.retain(keep);
+            assert_eq!(s, expected, "budget {}", budget);
+        }
+    }
+
+    #[test]
+    fn nothing_removed_moves_nothing() {
+        let mut s = "\u{e9}abc".to_string();
+        let mut retain = s.retain_budgeted(|_| true);
+        let result = retain.step(0);
+        assert_eq!(result.bytes_moved, 0);
+        assert!(result.finished);
+        drop(retain);
+        assert_eq!(s, "\u{e9}abc");
+    }
+
+    #[test]
+    fn abandoned() {
+        let mut s = "a-b-\u{e9}-c".to_string();
+        let mut retain = s.retain_budgeted(|c| c != '-');
+        // Copying `b` uses the budget, but the next `-` and the `\u{e9}` are
+        // still considered, since no more bytes need to be moved to do so
+        retain.step(1);
+        drop(retain);
+        assert_eq!(s, "ab\u{e9}-c");
+
+        let mut s = "-\u{1F980}-x".to_string();
+        let mut retain = s.retain_budgeted(|c| c != '-');
+        // Part way through copying the crab
+        let result = retain.step(2);
+        assert!(!result.finished);
+        drop(retain);
+        assert_eq!(s, "\u{1F980}-x");
+
+        let mut s = String::from("a-b");
+        s.retain_budgeted(|c| c != '-').finish();
+        assert_eq!(s, "ab");
+    }
+}
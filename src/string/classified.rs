@@ -0,0 +1,156 @@
+//! Per character class statistics, for
+//! [`retain_classified`](crate::RetainMoreString::retain_classified).
+
+use core::ops::AddAssign;
+
+use alloc::string::String;
+
+/// The number of characters in each class, as classified by
+/// [`retain_classified`](crate::RetainMoreString::retain_classified).
+///
+/// Each character is counted in exactly one class, checked in the order of
+/// the fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ClassCounts {
+    /// Characters for which [`char::is_alphabetic`] is true.
+    pub letters: usize,
+    /// Characters for which [`char::is_numeric`] is true.
+    pub digits: usize,
+    /// Characters for which [`char::is_whitespace`] is true.
+    pub whitespace: usize,
+    /// Characters for which [`char::is_ascii_punctuation`] is true. Non-ASCII
+    /// punctuation is counted in [`other`](Self::other).
+    pub punctuation: usize,
+    /// Every other character.
+    pub other: usize,
+}
+
+impl ClassCounts {
+    /// The total number of characters counted.
+    pub fn total(&self) -> usize {
+        self.letters + self.digits + self.whitespace + self.punctuation + self.other
+    }
+
+    fn record(&mut self, ch: char) {
+        let count = if ch.is_alphabetic() {
+            &mut self.letters
+        } else if ch.is_numeric() {
+            &mut self.digits
+        } else if ch.is_whitespace() {
+            &mut self.whitespace
+        } else if ch.is_ascii_punctuation() {
+            &mut self.punctuation
+        } else {
+            &mut self.other
+        };
+        *count += 1;
+    }
+}
+
+impl AddAssign for ClassCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.letters += other.letters;
+        self.digits += other.digits;
+        self.whitespace += other.whitespace;
+        self.punctuation += other.punctuation;
+        self.other += other.other;
+    }
+}
+
+/// Statistics about the characters kept and removed by
+/// [`retain_classified`](crate::RetainMoreString::retain_classified).
+///
+/// Reports from several passes can be combined with `+=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ClassifiedReport {
+    /// The characters which were kept, by class.
+    pub kept: ClassCounts,
+    /// The characters which were removed, by class.
+    pub removed: ClassCounts,
+    /// The number of bytes which were removed, i.e. how much shorter the
+    /// string became.
+    pub bytes_removed: usize,
+}
+
+impl AddAssign for ClassifiedReport {
+    fn add_assign(&mut self, other: Self) {
+        self.kept += other.kept;
+        self.removed += other.removed;
+        self.bytes_removed += other.bytes_removed;
+    }
+}
+
+pub(super) fn retain_classified<F: FnMut(char) -> bool>(
+    s: &mut String,
+    mut f: F,
+) -> ClassifiedReport {
+    let mut report = ClassifiedReport::default();
+    s.retain(|ch| {
+        let keep = f(ch);
+        if keep {
+            report.kept.record(ch);
+        } else {
+            report.removed.record(ch);
+            report.bytes_removed += ch.len_utf8();
+        }
+        keep
+    });
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClassCounts, ClassifiedReport};
+    use crate::RetainMoreString;
+    use alloc::string::{String, ToString};
+
+    fn sanitize(input: &str) -> (String, ClassifiedReport) {
+        let mut s = input.to_string();
+        let report = s.retain_classified(|c| c.is_ascii_alphanumeric() || c == ' ');
+        (s, report)
+    }
+
+    #[test]
+    fn counts_each_class() {
+        let (s, report) = sanitize("Hi \u{e9}t\u{e9}, 42\u{663}!\t\u{2014}\u{1F980}");
+        assert_eq!(s, "Hi t 42");
+        let expected = ClassifiedReport {
+            kept: ClassCounts {
+                letters: 3,
+                digits: 2,
+                whitespace: 2,
+                ..ClassCounts::default()
+            },
+            removed: ClassCounts {
+                // 'é' twice
+                letters: 2,
+                // '٣', the Arabic-Indic digit three
+                digits: 1,
+                // '\t'
+                whitespace: 1,
+                // ',' and '!'
+                punctuation: 2,
+                // The em dash and the crab
+                other: 2,
+            },
+            bytes_removed: 2 + 2 + 2 + 1 + 1 + 1 + 3 + 4,
+        };
+        assert_eq!(report, expected);
+        assert_eq!(report.kept.total() + report.removed.total(), 15);
+    }
+
+    #[test]
+    fn merging_matches_concatenation() {
+        let first = "a-b 1\u{e9}";
+        let second = "\u{1F980}?? z9\n";
+        let (_, mut merged) = sanitize(first);
+        merged += sanitize(second).1;
+
+        let (_, concatenated) = sanitize(&(first.to_string() + second));
+        assert_eq!(merged, concatenated);
+
+        let mut empty = ClassifiedReport::default();
+        empty += ClassifiedReport::default();
+        assert_eq!(empty, sanitize("").1);
+    }
+}
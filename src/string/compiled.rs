@@ -0,0 +1,197 @@
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use alloc::{boxed::Box, string::String};
+
+use super::cursor::Cursor;
+
+/// The first character which is encoded in two bytes of UTF-8.
+const TWO_BYTE_START: u32 = 0x80;
+/// The number of characters which are encoded in two bytes of UTF-8.
+const TWO_BYTE_LEN: usize = 0x800 - 0x80;
+
+/// The states of each entry of the lazily filled table.
+const UNKNOWN: u8 = 0;
+const KEEP: u8 = 1;
+const REMOVE: u8 = 2;
+
+/// A character predicate which has been compiled into lookup tables, for
+/// applying the same filter to many strings.
+///
+/// The decision for every ASCII character is computed when the
+/// `CompiledRetain` is created. Decisions for the other characters which are
+/// encoded in two bytes of UTF-8 (`'\u{80}'..'\u{800}'`, which covers most
+/// Latin, Greek and Cyrillic text) are cached the first time each is seen.
+/// All other characters call the predicate each time they occur.
+///
+/// [`apply`](Self::apply) only needs `&self`, so a `CompiledRetain` can be
+/// shared between threads (if the predicate can). The cache is filled with
+/// atomic operations, and as the predicate must be pure, it doesn't matter
+/// if two threads race to fill the same entry.
+///
+/// # Usage
+///
+/// ```
+/// use retain_more::CompiledRetain;
+/// let alphanumeric = CompiledRetain::new(|c: char| c.is_alphanumeric());
+/// let mut names = vec!["ab-c".to_string(), "Zoë!".to_string()];
+/// for name in &mut names {
+///     alphanumeric.apply(name);
+/// }
+/// assert_eq!(names, ["abc", "Zoë"]);
+/// ```
+pub struct CompiledRetain<F> {
+    f: F,
+    ascii: [bool; 128],
+    two_byte: Box<[AtomicU8; TWO_BYTE_LEN]>,
+}
+
+impl<F: Fn(char) -> bool> CompiledRetain<F> {
+    /// Compile `f`, which must be pure: it must always return the same
+    /// result for the same character.
+    pub fn new(f: F) -> Self {
+        let mut ascii = [false; 128];
+        for (byte, keep) in (0..128u8).zip(ascii.iter_mut()) {
+            *keep = f(char::from(byte));
+        }
+        CompiledRetain {
+            f,
+            ascii,
+            two_byte: Box::new([const { AtomicU8::new(UNKNOWN) }; TWO_BYTE_LEN]),
+        }
+    }
+
+    /// Whether `ch` is kept.
+    pub fn keeps(&self, ch: char) -> bool {
+        if ch.is_ascii() {
+            return self.ascii[ch as usize];
+        }
+        let Some(entry) = (ch as u32)
+            .checked_sub(TWO_BYTE_START)
+            .and_then(|idx| self.two_byte.get(idx as usize))
+        else {
+            return (self.f)(ch);
+        };
+        match entry.load(Ordering::Relaxed) {
+            KEEP => true,
+            REMOVE => false,
+            _ => {
+                let keep = (self.f)(ch);
+                entry.store(if keep { KEEP } else { REMOVE }, Ordering::Relaxed);
+                keep
+            }
+        }
+    }
+
+    /// Retains only the characters of `s` which the predicate keeps,
+    /// returning the number of characters removed.
+    ///
+    /// This is equivalent to `s.retain(f)`, but ASCII characters are decided
+    /// by a table lookup on each byte, and consecutive kept characters are
+    /// moved as a single block.
+    pub fn apply(&self, s: &mut String) -> usize {
+        let mut removed = 0;
+        let mut cursor = Cursor::new(s);
+        loop {
+            // The length of the run of kept characters at the start of the
+            // unconsidered region, and the length of the first removed
+            // character after it
+            let mut run = 0;
+            let mut removed_len = 0;
+            let rest = cursor.rest();
+            let bytes = rest.as_bytes();
+            while run < bytes.len() {
+                let byte = bytes[run];
+                let (keep, len) = if byte.is_ascii() {
+                    (self.ascii[byte as usize], 1)
+                } else {
+                    let ch = rest[run..].chars().next().unwrap();
+                    (self.keeps(ch), ch.len_utf8())
+                };
+                if !keep {
+                    removed_len = len;
+                    break;
+                }
+                run += len;
+            }
+            cursor.keep(run);
+            if removed_len == 0 {
+                return removed;
+            }
+            cursor.remove(removed_len);
+            removed += 1;
+        }
+    }
+}
+
+impl<F> core::fmt::Debug for CompiledRetain<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CompiledRetain").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetainMoreString;
+    use alloc::{string::ToString, vec::Vec};
+
+    /// A deterministic mix of characters from each UTF-8 width.
+    fn random_strings() -> Vec<String> {
+        let pool: Vec<char> =
+            "aZ9 -_.\t\u{e9}\u{df}\u{3a9}\u{416}\u{7ff}\u{800}\u{65e5}\u{1F980}\u{10FFFF}"
+                .chars()
+                .collect();
+        let mut state: u32 = 0x2545_f491;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as usize
+        };
+        (0..200)
+            .map(|_| {
+                let len = next() % 40;
+                (0..len).map(|_| pool[next() % pool.len()]).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn apply_matches_retain_default() {
+        let predicates: [fn(char) -> bool; 3] = [
+            |c| c.is_alphanumeric(),
+            |c| !c.is_whitespace() && c != '\u{7ff}',
+            |c| (c as u32).is_multiple_of(3),
+        ];
+        for predicate in predicates {
+            let compiled = CompiledRetain::new(predicate);
+            for input in random_strings() {
+                let mut expected = input.clone();
+                expected.retain_default(predicate);
+                let mut s = input.clone();
+                let removed = compiled.apply(&mut s);
+                assert_eq!(s, expected, "{:?}", input);
+                assert_eq!(removed, input.chars().count() - expected.chars().count());
+            }
+        }
+    }
+
+    #[test]
+    fn shareable() {
+        fn assert_sync<T: Sync>(_: &T) {}
+        assert_sync(&CompiledRetain::new(|c: char| c.is_alphabetic()));
+    }
+
+    #[test]
+    fn apply_edges() {
+        let compiled = CompiledRetain::new(|c: char| c != 'x');
+        let mut s = String::new();
+        assert_eq!(compiled.apply(&mut s), 0);
+        let mut s = "xxx".to_string();
+        assert_eq!(compiled.apply(&mut s), 3);
+        assert_eq!(s, "");
+        let mut s = "axbx".to_string();
+        assert_eq!(compiled.apply(&mut s), 2);
+        assert_eq!(s, "ab");
+    }
+}
@@ -0,0 +1,141 @@
+//! The argument of [`retain_ctx`](crate::RetainMoreString::retain_ctx).
+
+use alloc::string::String;
+
+use super::cursor::Cursor;
+
+/// Everything known about the current character of a
+/// [`retain_ctx`](crate::RetainMoreString::retain_ctx) pass.
+///
+/// This only has accessors, so more information can be added in future
+/// without breaking existing predicates.
+#[derive(Debug)]
+pub struct RetainContext<'a> {
+    kept: &'a mut str,
+    rest: &'a mut str,
+    current: char,
+    byte_offset: usize,
+    kept_chars: usize,
+}
+
+impl<'a> RetainContext<'a> {
+    /// The contents of the string which have already been retained.
+    ///
+    /// This is argument 0 of [`retain_all`](crate::RetainMoreString::retain_all).
+    pub fn kept(&mut self) -> &mut str {
+        self.kept
+    }
+
+    /// The contents of the string which are yet to be considered, after the
+    /// current character.
+    ///
+    /// This is argument 2 of [`retain_all`](crate::RetainMoreString::retain_all).
+    pub fn rest(&mut self) -> &mut str {
+        self.rest
+    }
+
+    /// Both [`kept`](Self::kept) and [`rest`](Self::rest), for predicates
+    /// which need to hold on to both at once.
+    pub fn split(&mut self) -> (&mut str, &mut str) {
+        (self.kept, self.rest)
+    }
+
+    /// The character being considered.
+    pub fn current(&self) -> char {
+        self.current
+    }
+
+    /// The byte offset of the current character in the original string.
+    ///
+    /// This is unaffected by the characters which have been removed, so is
+    /// generally not the length of [`kept`](Self::kept).
+    pub fn byte_offset(&self) -> usize {
+        self.byte_offset
+    }
+
+    /// The number of characters which have been retained so far, i.e. the
+    /// number of characters in [`kept`](Self::kept).
+    pub fn kept_chars(&self) -> usize {
+        self.kept_chars
+    }
+}
+
+pub(super) fn retain_ctx<F: FnMut(RetainContext<'_>) -> bool>(s: &mut String, mut f: F) {
+    let mut cursor = Cursor::new(s);
+    let mut kept_chars = 0;
+    while let Some(ch) = cursor.peek() {
+        let ch_len = ch.len_utf8();
+        let byte_offset = cursor.offset();
+        let (kept, rest) = cursor.split(ch_len);
+        let ctx = RetainContext {
+            kept,
+            rest,
+            current: ch,
+            byte_offset,
+            kept_chars,
+        };
+        if f(ctx) {
+            cursor.keep(ch_len);
+            kept_chars += 1;
+        } else {
+            cursor.remove(ch_len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreString;
+    use alloc::{
+        string::{String, ToString},
+        vec::Vec,
+    };
+
+    #[test]
+    fn accessors() {
+        let mut s = "a\u{e9}-\u{1F980}b".to_string();
+        let mut seen = Vec::new();
+        s.retain_ctx(|mut ctx| {
+            let current = ctx.current();
+            seen.push((
+                ctx.byte_offset(),
+                ctx.kept_chars(),
+                ctx.kept().to_string(),
+                current,
+                ctx.rest().to_string(),
+            ));
+            current != '-'
+        });
+        assert_eq!(s, "a\u{e9}\u{1F980}b");
+        let expected = [
+            (0, 0, "", 'a', "\u{e9}-\u{1F980}b"),
+            (1, 1, "a", '\u{e9}', "-\u{1F980}b"),
+            (3, 2, "a\u{e9}", '-', "\u{1F980}b"),
+            (4, 2, "a\u{e9}", '\u{1F980}', "b"),
+            (8, 3, "a\u{e9}\u{1F980}", 'b', ""),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(offset, count, kept, c, rest)| {
+                (offset, count, String::from(kept), c, String::from(rest))
+            })
+            .collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn mutation_through_split() {
+        // Uppercase the rest of the string after a `^`, and drop the `^`
+        let mut s = "ab^cd\u{e9}".to_string();
+        s.retain_ctx(|mut ctx| {
+            if ctx.current() != '^' {
+                return true;
+            }
+            let (kept, rest) = ctx.split();
+            assert_eq!(kept, "ab");
+            rest.make_ascii_uppercase();
+            false
+        });
+        assert_eq!(s, "abCD\u{e9}");
+    }
+}
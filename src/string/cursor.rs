@@ -0,0 +1,480 @@
+//! The in-place compaction machinery shared by the [`String`] implementation
+//! of [`RetainMoreString`](crate::RetainMoreString).
+
+use core::{
+    convert::Infallible, ops::Range, slice, str::from_utf8_unchecked, str::from_utf8_unchecked_mut,
+};
+
+use alloc::{collections::TryReserveError, string::String};
+
+/// A cursor which walks over a [`String`], deciding for each region whether
+/// it should be kept or removed, and compacting the kept regions towards the
+/// start of the allocation.
+///
+/// The string is split into three regions:
+///  - `0..kept_len()`: The bytes which have been kept so far. This is always
+///    valid UTF-8.
+///  - `kept_len()..offset()`: A gap of bytes which have been removed. The
+///    bytes in this region have not yet been overwritten, except where they
+///    were the destination of a copy of retained bytes (which can only happen
+///    below `kept_len()`).
+///  - `offset()..original_len()`: The bytes which have not yet been
+///    considered. These are untouched, so are valid UTF-8.
+///
+/// Bytes are only ever written once something has been removed, so a pass
+/// which removes nothing makes no writes to the buffer, and neither does the
+/// part of a pass before its first removal. This is a documented guarantee of
+/// [`RetainMoreString::retain_all`](crate::RetainMoreString::retain_all), so
+/// must be kept by any change to how bytes are copied. (Setting the length of
+/// the string does not write to the buffer.)
+///
+/// If the cursor is dropped before reaching the end of the string (e.g.
+/// because a predicate panicked), the string is left containing only the
+/// kept region. If the cursor is leaked, the string is left empty.
+///
+/// The allocation is only replaced by [`Self::grow`] and [`Self::try_grow`],
+/// which move the unconsidered region to the end of a larger allocation. Offsets are still
+/// reported relative to the original string afterwards.
+pub(crate) struct Cursor<'a> {
+    string: &'a mut String,
+    /// Pointer to the start of the allocation of `string`.
+    ///
+    /// The allocation is only replaced by `grow`, which updates this pointer.
+    /// It is obtained from [`alloc::vec::Vec::as_mut_ptr`], so it is valid
+    /// for the whole allocation, not just `0..string.len()`.
+    ptr: *mut u8,
+    /// The end of the unconsidered region in the allocation. This is the
+    /// length of the string before the cursor was created, plus `shift`.
+    len: usize,
+    /// The index of the start of the region which has not yet been
+    /// considered. This is always at a UTF-8 character boundary.
+    idx: usize,
+    /// How far the unconsidered region has been moved by `grow`.
+    shift: usize,
+    /// The number of bytes removed so far.
+    del_bytes: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub(crate) fn new(string: &'a mut String) -> Self {
+        let len = string.len();
+        // This is required for panic safety, see https://github.com/rust-lang/rust/issues/78498
+        // SAFETY: 0..0 is empty and hence that region is valid UTF-8
+        // SAFETY: 0 <= self.len(), since self.len() is a usize
+        let ptr = unsafe {
+            let vec = string.as_mut_vec();
+            vec.set_len(0);
+            vec.as_mut_ptr()
+        };
+        Cursor {
+            string,
+            ptr,
+            len,
+            idx: 0,
+            shift: 0,
+            del_bytes: 0,
+        }
+    }
+
+    /// The byte offset in the original string of the first byte which has not
+    /// yet been considered.
+    pub(crate) fn offset(&self) -> usize {
+        self.idx - self.shift
+    }
+
+    /// The length of the string before this cursor was created.
+    pub(crate) fn original_len(&self) -> usize {
+        self.len - self.shift
+    }
+
+    /// The number of bytes which have been kept so far.
+    pub(crate) fn kept_len(&self) -> usize {
+        self.idx - self.del_bytes
+    }
+
+    /// The region of the string which has not yet been considered.
+    pub(crate) fn rest(&self) -> &str {
+        // SAFETY: `idx <= len`, so `idx..len` is within the allocation. This
+        // region is untouched, and `idx` is on a character boundary, so it is
+        // valid UTF-8.
+        unsafe {
+            from_utf8_unchecked(slice::from_raw_parts(
+                self.ptr.add(self.idx),
+                self.len - self.idx,
+            ))
+        }
+    }
+
+    /// The last `n` bytes which were removed, which must all have been removed
+    /// since the last time anything was kept.
+    ///
+    /// # Panics
+    ///
+    /// If fewer than `n` bytes have been removed, or `n` is not on a
+    /// character boundary of the removed bytes.
+    pub(crate) fn removed_run(&self, n: usize) -> &str {
+        assert!(n <= self.del_bytes);
+        // SAFETY: `idx - n >= idx - del_bytes = kept_len()`, so this region
+        // is within the gap, which has not been overwritten. `idx` is on a
+        // character boundary of the original string.
+        let run = unsafe { slice::from_raw_parts(self.ptr.add(self.idx - n), n) };
+        core::str::from_utf8(run).expect("removed run must start at a character boundary")
+    }
+
+    /// The next character which has not yet been considered.
+    pub(crate) fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    /// Mutable access to the kept region, and to the unconsidered region
+    /// excluding its first `skip` bytes (normally the current character).
+    ///
+    /// # Panics
+    ///
+    /// If `skip` is not on a character boundary of [`Self::rest`].
+    pub(crate) fn split(&mut self, skip: usize) -> (&mut str, &mut str) {
+        assert!(self.rest().is_char_boundary(skip));
+        let kept_len = self.kept_len();
+        let after = self.idx + skip;
+        // SAFETY: The kept region is `0..kept_len`, and `kept_len <= idx <=
+        // after`, so these regions do not overlap. Both are within the
+        // allocation and valid UTF-8, since `after` is on a character
+        // boundary of the untouched region. Their lifetimes are tied to the
+        // mutable borrow of `self`, so there is no other access to them.
+        unsafe {
+            (
+                from_utf8_unchecked_mut(slice::from_raw_parts_mut(self.ptr, kept_len)),
+                from_utf8_unchecked_mut(slice::from_raw_parts_mut(
+                    self.ptr.add(after),
+                    self.len - after,
+                )),
+            )
+        }
+    }
+
+    /// Mutable access to the unconsidered region excluding its first `skip`
+    /// bytes, as in [`Self::split`], without constructing the kept region.
+    ///
+    /// # Panics
+    ///
+    /// If `skip` is not on a character boundary of [`Self::rest`].
+    pub(crate) fn after(&mut self, skip: usize) -> &mut str {
+        assert!(self.rest().is_char_boundary(skip));
+        let after = self.idx + skip;
+        // SAFETY: As in `split`, this region is within the allocation, valid
+        // UTF-8, and only accessible through the mutable borrow of `self`.
+        unsafe {
+            from_utf8_unchecked_mut(slice::from_raw_parts_mut(
+                self.ptr.add(after),
+                self.len - after,
+            ))
+        }
+    }
+
+    /// Shared access to the first `skip` bytes of the unconsidered region
+    /// (normally the current character), and mutable access to the rest of
+    /// it.
+    ///
+    /// # Panics
+    ///
+    /// If `skip` is not on a character boundary of [`Self::rest`].
+    pub(crate) fn current_and_after(&mut self, skip: usize) -> (&[u8], &mut str) {
+        assert!(self.rest().is_char_boundary(skip));
+        // SAFETY: The regions `idx..idx + skip` and `idx + skip..len` do not
+        // overlap, and are both within the untouched region. The second
+        // starts on a character boundary, so is valid UTF-8.
+        unsafe {
+            (
+                slice::from_raw_parts(self.ptr.add(self.idx), skip),
+                from_utf8_unchecked_mut(slice::from_raw_parts_mut(
+                    self.ptr.add(self.idx + skip),
+                    self.len - self.idx - skip,
+                )),
+            )
+        }
+    }
+
+    /// Shared access to the kept region, and to the unconsidered region
+    /// excluding its first `skip` bytes, as in [`Self::split`].
+    ///
+    /// # Panics
+    ///
+    /// If `skip` is not on a character boundary of [`Self::rest`].
+    pub(crate) fn split_ref(&self, skip: usize) -> (&str, &str) {
+        assert!(self.rest().is_char_boundary(skip));
+        let (kept, rest) = self.split_bytes(skip);
+        // SAFETY: The kept region is valid UTF-8, and the unconsidered region
+        // is valid UTF-8 after a character boundary.
+        unsafe { (from_utf8_unchecked(kept), from_utf8_unchecked(rest)) }
+    }
+
+    /// Shared access to the bytes of the kept region, and of the unconsidered
+    /// region excluding its first `skip` bytes, as in [`Self::split`].
+    ///
+    /// # Panics
+    ///
+    /// If `skip` is greater than the length of [`Self::rest`].
+    pub(crate) fn split_bytes(&self, skip: usize) -> (&[u8], &[u8]) {
+        let after = self.idx + skip;
+        assert!(after <= self.len);
+        // SAFETY: As in `split`, both regions are within the allocation and
+        // do not overlap. They are only borrowed immutably, so their contents
+        // cannot be changed through them.
+        unsafe {
+            (
+                slice::from_raw_parts(self.ptr, self.kept_len()),
+                slice::from_raw_parts(self.ptr.add(after), self.len - after),
+            )
+        }
+    }
+
+    /// Keep the next `n` bytes, copying them to the end of the kept region.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is not on a character boundary of [`Self::rest`].
+    pub(crate) fn keep(&mut self, n: usize) {
+        assert!(self.rest().is_char_boundary(n));
+        if self.del_bytes > 0 {
+            #[cfg(test)]
+            BYTES_WRITTEN.with(|written| written.set(written.get() + n));
+            // SAFETY: Both regions are within the allocation, since `idx + n
+            // <= len`. We copy a region which starts and ends on character
+            // boundaries, so the kept region stays valid UTF-8. We can't use
+            // copy_nonoverlapping here in case `del_bytes < n`.
+            unsafe {
+                core::ptr::copy(
+                    self.ptr.add(self.idx),
+                    self.ptr.add(self.idx - self.del_bytes),
+                    n,
+                );
+            }
+        }
+        self.idx += n;
+    }
+
+    /// Consume the next `n` bytes, appending `replacement` to the kept region
+    /// in their place.
+    ///
+    /// This returns `false` without consuming anything if `replacement` is
+    /// longer than those bytes and the gap together.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is not on a character boundary of [`Self::rest`].
+    pub(crate) fn replace(&mut self, n: usize, replacement: &str) -> bool {
+        let bytes = replacement.as_bytes();
+        if self.rest().as_bytes().get(..n) == Some(bytes) {
+            self.keep(n);
+            return true;
+        }
+        assert!(self.rest().is_char_boundary(n));
+        if bytes.len() > self.del_bytes + n {
+            return false;
+        }
+        #[cfg(test)]
+        BYTES_WRITTEN.with(|written| written.set(written.get() + bytes.len()));
+        // SAFETY: The destination is `kept_len()..kept_len() + bytes.len()`,
+        // which is within the gap and the `n` consumed bytes, so is within
+        // the allocation, and holds nothing which will be read again. We
+        // write a whole string, so the kept region stays valid UTF-8. The
+        // source can't overlap the destination, as `replacement` is borrowed
+        // immutably whilst we hold the string mutably.
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                self.ptr.add(self.kept_len()),
+                bytes.len(),
+            );
+        }
+        self.idx += n;
+        self.del_bytes = self.del_bytes + n - bytes.len();
+        true
+    }
+
+    /// Remove the next `n` bytes.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is not on a character boundary of [`Self::rest`].
+    pub(crate) fn remove(&mut self, n: usize) {
+        assert!(self.rest().is_char_boundary(n));
+        self.idx += n;
+        self.del_bytes += n;
+    }
+
+    /// Keep every byte which has not yet been considered.
+    pub(crate) fn keep_rest(&mut self) {
+        self.keep(self.len - self.idx);
+    }
+
+    /// Move the string into a larger allocation, so that the gap is at least
+    /// `min_gap` bytes, e.g. so that a [`Self::replace`] which didn't fit
+    /// will.
+    ///
+    /// The new allocation is at least twice the size of the old one, so the
+    /// unconsidered region is moved `O(log len)` times however many times
+    /// this is called.
+    pub(crate) fn grow(&mut self, min_gap: usize) {
+        let Ok(()) = self.reallocate(min_gap, |capacity| {
+            Ok::<_, Infallible>(String::with_capacity(capacity))
+        });
+    }
+
+    /// [`Self::grow`], reporting allocation failure instead of aborting.
+    ///
+    /// If this fails, the cursor is unchanged.
+    pub(crate) fn try_grow(&mut self, min_gap: usize) -> Result<(), TryReserveError> {
+        self.reallocate(min_gap, |capacity| {
+            let mut string = String::new();
+            string.try_reserve_exact(capacity)?;
+            Ok(string)
+        })
+    }
+
+    fn reallocate<E>(
+        &mut self,
+        min_gap: usize,
+        alloc: impl FnOnce(usize) -> Result<String, E>,
+    ) -> Result<(), E> {
+        if self.del_bytes >= min_gap {
+            return Ok(());
+        }
+        let kept_len = self.kept_len();
+        let rest_len = self.len - self.idx;
+        let required = kept_len.saturating_add(min_gap).saturating_add(rest_len);
+        let mut string = alloc(required.max(self.string.capacity().saturating_mul(2)))?;
+        // The new allocation is larger, so this is at least `idx`
+        let idx = string.capacity() - rest_len;
+        #[cfg(test)]
+        BYTES_WRITTEN.with(|written| written.set(written.get() + kept_len + rest_len));
+        // SAFETY: The kept region and the unconsidered region are valid
+        // UTF-8 within the old allocation, and are copied to the start and
+        // end of the new one, which is at least as large as both together, so
+        // they don't overlap. The new string has length 0, as does the old
+        // one, so the old allocation is freed without reading it.
+        unsafe {
+            let ptr = string.as_mut_vec().as_mut_ptr();
+            core::ptr::copy_nonoverlapping(self.ptr, ptr, kept_len);
+            core::ptr::copy_nonoverlapping(self.ptr.add(self.idx), ptr.add(idx), rest_len);
+            *self.string = string;
+            self.ptr = self.string.as_mut_vec().as_mut_ptr();
+        }
+        self.shift += idx - self.idx;
+        self.len = idx + rest_len;
+        self.idx = idx;
+        self.del_bytes = idx - kept_len;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+std::thread_local! {
+    /// The number of bytes copied into string buffers by cursors on this
+    /// thread, so that tests can check that a pass made no writes.
+    static BYTES_WRITTEN: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+}
+
+impl Drop for Cursor<'_> {
+    fn drop(&mut self) {
+        // SAFETY: `kept_len() <= len <= capacity`, and the kept region is
+        // valid UTF-8.
+        let kept_len = self.kept_len();
+        unsafe {
+            self.string.as_mut_vec().set_len(kept_len);
+        }
+    }
+}
+
+/// Remove every range in `ranges` from `string` in a single compaction pass.
+///
+/// The ranges must be in ascending order, must not overlap, and must lie on
+/// character boundaries of `string`.
+///
+/// # Panics
+///
+/// If the ranges are out of order, overlapping, or not on character
+/// boundaries. `string` is left valid (but partially modified) in that case.
+pub(crate) fn remove_ranges<I: IntoIterator<Item = Range<usize>>>(string: &mut String, ranges: I) {
+    let mut cursor = Cursor::new(string);
+    for range in ranges {
+        assert!(
+            range.start >= cursor.offset(),
+            "ranges must be sorted and disjoint"
+        );
+        cursor.keep(range.start - cursor.offset());
+        cursor.remove(range.end - range.start);
+    }
+    cursor.keep_rest();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BYTES_WRITTEN;
+    use crate::RetainMoreString;
+    use alloc::string::{String, ToString};
+
+    /// The number of bytes written to string buffers by `f`.
+    fn bytes_written(f: impl FnOnce()) -> usize {
+        let before = BYTES_WRITTEN.with(|written| written.get());
+        f();
+        BYTES_WRITTEN.with(|written| written.get()) - before
+    }
+
+    /// Run `f` on a copy of `input`, checking that it made no writes and
+    /// left the string exactly as it was.
+    fn assert_clean(input: &str, f: impl FnOnce(&mut String)) {
+        let mut s = String::with_capacity(input.len() + 8);
+        s.push_str(input);
+        let (ptr, capacity) = (s.as_ptr(), s.capacity());
+        assert_eq!(bytes_written(|| f(&mut s)), 0);
+        assert_eq!(s, input);
+        assert_eq!((s.as_ptr(), s.capacity()), (ptr, capacity));
+    }
+
+    #[test]
+    fn zero_removals_make_no_writes() {
+        let input = "clean \u{e9}\u{1F980} input";
+        assert_clean(input, |s| s.retain_all(|_, _, _| true));
+        assert_clean(input, |s| s.retain_after(|_, _| true));
+        assert_clean(input, |s| s.retain_before(|_, _| true));
+        assert_clean(input, |s| s.retain_all_indexed(|_, _, _, _| true));
+        assert_clean(input, |s| s.retain_all_bytes_ctx(|_, _, _| true));
+        assert_clean(input, |s| s.retain_with_last_kept(|_, _| true));
+        assert_clean(input, |s| {
+            s.retain_diff(|_| true);
+        });
+        assert_clean(input, |s| s.retain_map(|c, _| Some(c)));
+        assert_clean(input, |s| s.collapse_whitespace());
+        assert_clean(input, |s| {
+            let mask: alloc::vec::Vec<bool> = input.chars().map(|_| true).collect();
+            s.retain_by_mask(&mask).unwrap();
+        });
+        assert_clean(input, |s| {
+            s.remove_first_n_matches("clean", 0);
+        });
+    }
+
+    #[test]
+    fn growth_is_amortised() {
+        // Every character grows, so the string is reallocated repeatedly,
+        // but each reallocation at least doubles the capacity
+        let mut s = "a".repeat(10_000);
+        let written = bytes_written(|| s.retain_map(|_, _| Some('\u{e9}')));
+        assert_eq!(s, "\u{e9}".repeat(10_000));
+        assert!(written < 5 * s.len(), "{} bytes written", written);
+    }
+
+    #[test]
+    fn writes_start_at_first_removal() {
+        // Only the bytes after the first removal are moved
+        let mut s = "abc-\u{e9}f".to_string();
+        assert_eq!(bytes_written(|| s.retain_all(|_, c, _| c != '-')), 3);
+        assert_eq!(s, "abc\u{e9}f");
+
+        // Removing only the last character moves nothing
+        let mut s = "abc-".to_string();
+        assert_eq!(bytes_written(|| s.retain_all(|_, c, _| c != '-')), 0);
+        assert_eq!(s, "abc");
+    }
+}
@@ -0,0 +1,269 @@
+use core::fmt;
+
+use alloc::{string::String, vec::Vec};
+
+use super::cursor::{remove_ranges, Cursor};
+
+/// A description of the characters removed from a string by
+/// [`retain_diff`](crate::RetainMoreString::retain_diff).
+///
+/// The script is a list of `(original_byte_offset, byte_len)` deletions, in
+/// ascending order of offset, where adjacent removed characters are
+/// coalesced into a single deletion. It also records the length of the
+/// string it was created from, so that it can detect being applied to a
+/// string which has diverged.
+///
+/// The script only contains plain integers, so it can be sent to other
+/// machines by any means; [`DeletionScript::new`] reconstructs it from its
+/// parts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct DeletionScript {
+    original_len: usize,
+    deletions: Vec<(usize, usize)>,
+}
+
+impl DeletionScript {
+    /// Create a script from its parts, as returned by
+    /// [`original_len`](Self::original_len) and
+    /// [`deletions`](Self::deletions).
+    ///
+    /// The deletions are not validated until the script is
+    /// [applied](Self::apply).
+    pub fn new(original_len: usize, deletions: Vec<(usize, usize)>) -> Self {
+        DeletionScript {
+            original_len,
+            deletions,
+        }
+    }
+
+    /// The length in bytes of the string this script was created from.
+    pub fn original_len(&self) -> usize {
+        self.original_len
+    }
+
+    /// The `(original_byte_offset, byte_len)` pairs of the deletions in this
+    /// script.
+    pub fn deletions(&self) -> &[(usize, usize)] {
+        &self.deletions
+    }
+
+    /// Convert this script into its list of deletions.
+    pub fn into_deletions(self) -> Vec<(usize, usize)> {
+        self.deletions
+    }
+
+    /// Whether this script removes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.deletions.is_empty()
+    }
+
+    /// Perform the deletions described by this script on `s`, in a single
+    /// compaction pass.
+    ///
+    /// Every deletion is validated against `s` before any of them are
+    /// performed, so `s` is left untouched if an error is returned.
+    ///
+    /// The validation catches strings of a different length, and strings
+    /// where a deletion would not lie on character boundaries. It cannot
+    /// detect a diverged string which happens to have the same length, and
+    /// the same character boundaries at each deletion.
+    pub fn apply(&self, s: &mut String) -> Result<(), ApplyError> {
+        if s.len() != self.original_len {
+            return Err(ApplyError::LengthMismatch {
+                expected: self.original_len,
+                found: s.len(),
+            });
+        }
+        let mut previous_end = 0;
+        for (index, &(offset, len)) in self.deletions.iter().enumerate() {
+            if offset < previous_end {
+                return Err(ApplyError::Unordered { index });
+            }
+            let end = match offset.checked_add(len) {
+                Some(end) if end <= s.len() => end,
+                _ => return Err(ApplyError::OutOfBounds { index }),
+            };
+            if !s.is_char_boundary(offset) || !s.is_char_boundary(end) {
+                return Err(ApplyError::NotCharBoundary { index });
+            }
+            previous_end = end;
+        }
+        remove_ranges(
+            s,
+            self.deletions
+                .iter()
+                .map(|&(offset, len)| offset..offset + len),
+        );
+        Ok(())
+    }
+}
+
+/// The error returned by [`DeletionScript::apply`] when the script does not
+/// fit the target string.
+///
+/// `index` is the position of the offending deletion within
+/// [`DeletionScript::deletions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApplyError {
+    /// The target string does not have the same length as the string the
+    /// script was created from.
+    LengthMismatch { expected: usize, found: usize },
+    /// The deletion starts before the end of the previous deletion.
+    Unordered { index: usize },
+    /// The deletion extends past the end of the target string.
+    OutOfBounds { index: usize },
+    /// The deletion does not start and end on character boundaries of the
+    /// target string.
+    NotCharBoundary { index: usize },
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplyError::LengthMismatch { expected, found } => write!(
+                f,
+                "deletion script is for a string of length {}, but the target has length {}",
+                expected, found
+            ),
+            ApplyError::Unordered { index } => {
+                write!(
+                    f,
+                    "deletion {} overlaps or precedes the previous deletion",
+                    index
+                )
+            }
+            ApplyError::OutOfBounds { index } => {
+                write!(f, "deletion {} extends past the end of the target", index)
+            }
+            ApplyError::NotCharBoundary { index } => write!(
+                f,
+                "deletion {} does not lie on character boundaries of the target",
+                index
+            ),
+        }
+    }
+}
+
+pub(super) fn retain_diff<F: FnMut(char) -> bool>(s: &mut String, mut f: F) -> DeletionScript {
+    let mut deletions: Vec<(usize, usize)> = Vec::new();
+    let mut cursor = Cursor::new(s);
+    let original_len = cursor.original_len();
+    while let Some(ch) = cursor.peek() {
+        let ch_len = ch.len_utf8();
+        if f(ch) {
+            cursor.keep(ch_len);
+        } else {
+            match deletions.last_mut() {
+                Some((offset, len)) if *offset + *len == cursor.offset() => *len += ch_len,
+                _ => deletions.push((cursor.offset(), ch_len)),
+            }
+            cursor.remove(ch_len);
+        }
+    }
+    DeletionScript {
+        original_len,
+        deletions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetainMoreString;
+    use alloc::{string::ToString, vec};
+
+    #[test]
+    fn apply_to_identical_copy() {
+        let original = "ab€€c  d\u{1F600}e-1-2".to_string();
+        let mut s = original.clone();
+        let script = s.retain_diff(|c| c.is_ascii_alphabetic());
+        assert_eq!(&s, "abcde");
+        assert_eq!(script.original_len(), original.len());
+
+        let mut replica = original.clone();
+        script.apply(&mut replica).unwrap();
+        assert_eq!(replica, s);
+
+        // Round trip through the plain parts of the script
+        let rebuilt = DeletionScript::new(script.original_len(), script.deletions().to_vec());
+        let mut replica = original;
+        rebuilt.apply(&mut replica).unwrap();
+        assert_eq!(replica, s);
+    }
+
+    #[test]
+    fn coalesced() {
+        let mut s = "--a--b".to_string();
+        let script = s.retain_diff(|c| c != '-');
+        assert_eq!(script.deletions(), &[(0, 2), (3, 2)]);
+        let mut s = "abc".to_string();
+        assert!(s.retain_diff(|_| true).is_empty());
+    }
+
+    #[test]
+    fn removed_ranges() {
+        // At the very start and the very end
+        let mut s = "xxabcxx".to_string();
+        assert_eq!(s.retain_removed_ranges(|c| c != 'x'), [0..2, 5..7]);
+        assert_eq!(s, "abc");
+
+        // A run spanning multi-byte characters
+        let mut s = "a\u{e9}\u{1F980}-\u{2603}b\u{e9}".to_string();
+        let ranges = s.retain_removed_ranges(|c| c.is_ascii_alphabetic());
+        assert_eq!(s, "ab");
+        assert_eq!(ranges, [1..11, 12..14]);
+
+        let mut s = "\u{1F980}".to_string();
+        assert_eq!(s.retain_removed_ranges(|_| false), vec![0..4]);
+        assert!(s.retain_removed_ranges(|_| false).is_empty());
+    }
+
+    #[test]
+    fn apply_to_diverged() {
+        let mut s = "hello, world".to_string();
+        let script = s.retain_diff(|c| c.is_alphabetic());
+
+        let mut shorter = "hello world".to_string();
+        assert_eq!(
+            script.apply(&mut shorter),
+            Err(ApplyError::LengthMismatch {
+                expected: 12,
+                found: 11
+            })
+        );
+        assert_eq!(shorter, "hello world");
+
+        // Same length, but the deleted comma is now in the middle of a
+        // multibyte character
+        let mut diverged = "hell\u{e9}, world".to_string();
+        diverged.remove(7);
+        assert_eq!(diverged.len(), 12);
+        let before = diverged.clone();
+        assert_eq!(
+            script.apply(&mut diverged),
+            Err(ApplyError::NotCharBoundary { index: 0 })
+        );
+        assert_eq!(diverged, before);
+    }
+
+    #[test]
+    fn invalid_scripts() {
+        let mut s = "abcdef".to_string();
+        let unordered = DeletionScript::new(6, vec![(2, 2), (3, 1)]);
+        assert_eq!(
+            unordered.apply(&mut s),
+            Err(ApplyError::Unordered { index: 1 })
+        );
+        let out_of_bounds = DeletionScript::new(6, vec![(0, 1), (5, 2)]);
+        assert_eq!(
+            out_of_bounds.apply(&mut s),
+            Err(ApplyError::OutOfBounds { index: 1 })
+        );
+        let overflow = DeletionScript::new(6, vec![(1, usize::MAX)]);
+        assert_eq!(
+            overflow.apply(&mut s),
+            Err(ApplyError::OutOfBounds { index: 0 })
+        );
+        assert_eq!(s, "abcdef");
+    }
+}
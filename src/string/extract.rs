@@ -0,0 +1,118 @@
+use core::iter::FusedIterator;
+
+use alloc::string::String;
+
+use super::cursor::Cursor;
+
+/// An iterator which removes the characters of a string matching a
+/// predicate, and yields them along with their offset in the original string.
+///
+/// Created by [`extract_if`](crate::RetainMoreString::extract_if).
+///
+/// If the iterator is dropped before it is exhausted, the characters which
+/// have not yet been considered are kept. If the iterator is leaked (e.g.
+/// with [`core::mem::forget`]), the string may be truncated, but is still
+/// valid UTF-8.
+pub struct ExtractIf<'a, F> {
+    cursor: Cursor<'a>,
+    pred: F,
+}
+
+impl<'a, F> ExtractIf<'a, F> {
+    pub(super) fn new(string: &'a mut String, pred: F) -> Self {
+        ExtractIf {
+            cursor: Cursor::new(string),
+            pred,
+        }
+    }
+}
+
+impl<F: FnMut(char, &mut str) -> bool> Iterator for ExtractIf<'_, F> {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<(usize, char)> {
+        while let Some(ch) = self.cursor.peek() {
+            let ch_len = ch.len_utf8();
+            let offset = self.cursor.offset();
+            if (self.pred)(ch, self.cursor.after(ch_len)) {
+                self.cursor.remove(ch_len);
+                return Some((offset, ch));
+            }
+            self.cursor.keep(ch_len);
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Each character is at least one byte
+        (0, Some(self.cursor.original_len() - self.cursor.offset()))
+    }
+}
+
+impl<F: FnMut(char, &mut str) -> bool> FusedIterator for ExtractIf<'_, F> {}
+
+impl<F> Drop for ExtractIf<'_, F> {
+    fn drop(&mut self) {
+        // The cursor then sets the length of the string to the kept region
+        self.cursor.keep_rest();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreString as _;
+    use alloc::{string::ToString, vec::Vec};
+
+    #[test]
+    fn extract_if() {
+        let mut s = "a1\u{e9}22\u{1F980}3".to_string();
+        let digits: Vec<_> = s.extract_if(|c, _| c.is_ascii_digit()).collect();
+        assert_eq!(digits, [(1, '1'), (4, '2'), (5, '2'), (10, '3')]);
+        assert_eq!(s, "a\u{e9}\u{1F980}");
+    }
+
+    #[test]
+    fn extract_if_sees_changes_to_after() {
+        // Extract every character after a `!`, including a second `!`
+        let mut s = "a!!b!c".to_string();
+        let mut escaped = false;
+        let extracted: Vec<_> = s
+            .extract_if(|c, after| {
+                let extract = core::mem::take(&mut escaped);
+                if c == '!' && !extract {
+                    escaped = true;
+                    after.make_ascii_uppercase();
+                }
+                extract
+            })
+            .map(|(_, c)| c)
+            .collect();
+        assert_eq!(extracted, ['!', 'C']);
+        assert_eq!(s, "a!B!");
+    }
+
+    #[test]
+    fn extract_if_early_drop() {
+        let mut s = "1a2\u{e9}3b4".to_string();
+        let mut iter = s.extract_if(|c, _| c.is_ascii_digit());
+        assert_eq!(iter.next(), Some((0, '1')));
+        assert_eq!(iter.next(), Some((2, '2')));
+        drop(iter);
+        // Only the yielded characters are removed
+        assert_eq!(s, "a\u{e9}3b4");
+
+        // Dropping without calling `next` removes nothing
+        drop(s.extract_if(|_, _| true));
+        assert_eq!(s, "a\u{e9}3b4");
+    }
+
+    #[test]
+    fn extract_if_leaked() {
+        let mut s = "ab\u{e9}c".to_string();
+        let mut iter = s.extract_if(|c, _| c == 'a');
+        assert_eq!(iter.next(), Some((0, 'a')));
+        core::mem::forget(iter);
+        assert!(s.len() <= 4);
+        assert!(core::str::from_utf8(s.as_bytes()).is_ok());
+    }
+}
@@ -0,0 +1,193 @@
+use alloc::{collections::BTreeMap, string::String};
+
+use super::cursor::Cursor;
+
+/// A map from characters to values, where ASCII characters are stored in a
+/// fixed table, and all other characters in a [`BTreeMap`], so that it needs
+/// neither hashing nor a large table.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CharMap<V> {
+    ascii: [V; 128],
+    other: BTreeMap<char, V>,
+}
+
+impl<V: Copy + Default + PartialEq> CharMap<V> {
+    fn new() -> Self {
+        CharMap {
+            ascii: [V::default(); 128],
+            other: BTreeMap::new(),
+        }
+    }
+
+    fn get(&self, ch: char) -> V {
+        if ch.is_ascii() {
+            self.ascii[ch as usize]
+        } else {
+            self.other.get(&ch).copied().unwrap_or_default()
+        }
+    }
+
+    fn get_mut(&mut self, ch: char) -> &mut V {
+        if ch.is_ascii() {
+            &mut self.ascii[ch as usize]
+        } else {
+            self.other.entry(ch).or_default()
+        }
+    }
+
+    /// The characters whose value is not the default, in ascending order.
+    fn iter(&self) -> impl Iterator<Item = (char, V)> + '_ {
+        let ascii = (0..128u8)
+            .map(char::from)
+            .zip(self.ascii.iter().copied())
+            .filter(|&(_, v)| v != V::default());
+        ascii.chain(self.other.iter().map(|(&ch, &v)| (ch, v)))
+    }
+}
+
+/// The number of times each character occurs in a string.
+///
+/// Returned by [`char_frequencies`](crate::RetainMoreString::char_frequencies).
+///
+/// ASCII characters are counted in a fixed table, and all other characters
+/// in a [`BTreeMap`], so counting needs neither hashing nor a large table.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CharFrequencies {
+    counts: CharMap<usize>,
+}
+
+impl CharFrequencies {
+    fn count(s: &str) -> Self {
+        let mut counts = CharMap::new();
+        for ch in s.chars() {
+            *counts.get_mut(ch) += 1;
+        }
+        CharFrequencies { counts }
+    }
+
+    /// The number of times `ch` occurs.
+    pub fn get(&self, ch: char) -> usize {
+        self.counts.get(ch)
+    }
+
+    /// The number of distinct characters.
+    pub fn distinct(&self) -> usize {
+        self.counts.iter().count()
+    }
+
+    /// Each character which occurs, with the number of times it occurs, in
+    /// ascending order of character.
+    pub fn iter(&self) -> impl Iterator<Item = (char, usize)> + '_ {
+        self.counts.iter()
+    }
+}
+
+pub(super) fn char_frequencies(s: &str) -> CharFrequencies {
+    CharFrequencies::count(s)
+}
+
+pub(super) fn retain_by_frequency<F: FnMut(char, usize) -> bool>(s: &mut String, mut f: F) {
+    let frequencies = CharFrequencies::count(s);
+    let mut cursor = Cursor::new(s);
+    while let Some(ch) = cursor.peek() {
+        if f(ch, frequencies.get(ch)) {
+            cursor.keep(ch.len_utf8());
+        } else {
+            cursor.remove(ch.len_utf8());
+        }
+    }
+}
+
+pub(super) fn dedup_chars_keep_last(s: &mut String) {
+    // The byte offset of the last occurrence of each character, plus one so
+    // that the default of 0 means "not seen"
+    let mut last = CharMap::<usize>::new();
+    for (offset, ch) in s.char_indices() {
+        *last.get_mut(ch) = offset + 1;
+    }
+    let mut cursor = Cursor::new(s);
+    while let Some(ch) = cursor.peek() {
+        if last.get(ch) == cursor.offset() + 1 {
+            cursor.keep(ch.len_utf8());
+        } else {
+            cursor.remove(ch.len_utf8());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreString;
+    use alloc::{collections::BTreeSet, string::String, string::ToString, vec, vec::Vec};
+
+    #[test]
+    fn char_frequencies() {
+        let frequencies = "b\u{e9}ab\u{e9}\u{1F980}b".to_string().char_frequencies();
+        assert_eq!(frequencies.get('a'), 1);
+        assert_eq!(frequencies.get('b'), 3);
+        assert_eq!(frequencies.get('\u{e9}'), 2);
+        assert_eq!(frequencies.get('z'), 0);
+        assert_eq!(frequencies.distinct(), 4);
+        assert_eq!(
+            frequencies.iter().collect::<Vec<_>>(),
+            vec![('a', 1), ('b', 3), ('\u{e9}', 2), ('\u{1F980}', 1)]
+        );
+    }
+
+    #[test]
+    fn retain_min_frequency() {
+        // Ties at the threshold are kept
+        let mut s = "aabbbc\u{e9}\u{e9}".to_string();
+        s.retain_min_frequency(2);
+        assert_eq!(s, "aabbb\u{e9}\u{e9}");
+        s.retain_min_frequency(3);
+        assert_eq!(s, "bbb");
+
+        // Every character is unique
+        let mut s = "abc\u{1F980}".to_string();
+        s.retain_min_frequency(1);
+        assert_eq!(s, "abc\u{1F980}");
+        s.retain_min_frequency(2);
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn retain_by_frequency() {
+        // The counts are of the original string, not of what has been kept
+        let mut s = "abacad".to_string();
+        let mut seen = Vec::new();
+        s.retain_by_frequency(|ch, n| {
+            seen.push((ch, n));
+            n == 1
+        });
+        assert_eq!(s, "bcd");
+        assert_eq!(seen[4], ('a', 3));
+    }
+
+    /// Keep the last occurrence of each character, by keeping the first
+    /// occurrence in the reversed string.
+    fn dedup_keep_last_reference(input: &str) -> String {
+        let mut seen = BTreeSet::new();
+        let mut reversed: Vec<char> = input.chars().rev().filter(|&ch| seen.insert(ch)).collect();
+        reversed.reverse();
+        reversed.into_iter().collect()
+    }
+
+    #[test]
+    fn dedup_chars_keep_last() {
+        let samples = [
+            "",
+            "a",
+            "abcabc",
+            "abracadabra",
+            "\u{e9}a\u{e9}b\u{1F980}\u{e9}\u{1F980}",
+            "\u{65e5}\u{672c}\u{65e5}\u{672c}\u{8a9e}\u{65e5}",
+            "aaaa\u{e9}\u{e9}a",
+        ];
+        for sample in samples {
+            let mut s = sample.to_string();
+            s.dedup_chars_keep_last();
+            assert_eq!(s, dedup_keep_last_reference(sample), "{:?}", sample);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use core::hash::{Hash, Hasher};
+
+use alloc::string::String;
+
+use super::cursor::Cursor;
+
+pub(super) fn retain_hash_removed<H: Hasher, F: FnMut(char) -> bool>(
+    s: &mut String,
+    hasher: &mut H,
+    mut f: F,
+) {
+    let mut cursor = Cursor::new(s);
+    // The length in bytes of the current run of removed characters
+    let mut run = 0;
+    while let Some(ch) = cursor.peek() {
+        if f(ch) {
+            if run > 0 {
+                cursor.removed_run(run).hash(hasher);
+                run = 0;
+            }
+            cursor.keep(ch.len_utf8());
+        } else {
+            cursor.remove(ch.len_utf8());
+            run += ch.len_utf8();
+        }
+    }
+    if run > 0 {
+        cursor.removed_run(run).hash(hasher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreString;
+    use alloc::{string::String, string::ToString, vec::Vec};
+    use core::hash::{Hash, Hasher};
+
+    /// A hasher which records exactly what it was given.
+    #[derive(Default, PartialEq, Debug)]
+    struct Recorder(Vec<u8>);
+
+    impl Hasher for Recorder {
+        fn finish(&self) -> u64 {
+            0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            self.0.extend_from_slice(bytes);
+            // Mark the boundaries of each call
+            self.0.push(b'|');
+        }
+    }
+
+    /// A simple FNV-1a hasher, which is sensitive to the order of the bytes,
+    /// but not to how they are split between calls.
+    struct Fnv(u64);
+
+    impl Hasher for Fnv {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for &b in bytes {
+                self.0 ^= u64::from(b);
+                self.0 = self.0.wrapping_mul(0x100_0000_01b3);
+            }
+        }
+    }
+
+    impl Default for Fnv {
+        fn default() -> Self {
+            Fnv(0xcbf2_9ce4_8422_2325)
+        }
+    }
+
+    /// Hash the runs of removed characters, by collecting them first.
+    fn reference<H: Hasher + Default>(input: &str, mut f: impl FnMut(char) -> bool) -> H {
+        let mut runs: Vec<String> = Vec::new();
+        let mut in_run = false;
+        for ch in input.chars() {
+            if f(ch) {
+                in_run = false;
+            } else {
+                if !in_run {
+                    runs.push(String::new());
+                    in_run = true;
+                }
+                runs.last_mut().unwrap().push(ch);
+            }
+        }
+        let mut hasher = H::default();
+        for run in &runs {
+            run.as_str().hash(&mut hasher);
+        }
+        hasher
+    }
+
+    fn hashed<H: Hasher + Default>(input: &str, f: impl FnMut(char) -> bool) -> (String, H) {
+        let mut s = input.to_string();
+        let mut hasher = H::default();
+        s.retain_hash_removed(&mut hasher, f);
+        (s, hasher)
+    }
+
+    #[test]
+    fn retain_hash_removed() {
+        let samples = ["", "abc", "--a-b--", "1\u{e9}2\u{1F980}\u{1F980}3", "----"];
+        for sample in samples {
+            let f = |c: char| c.is_ascii_alphanumeric();
+            let mut expected = sample.to_string();
+            expected.retain(f);
+
+            let (s, recorder) = hashed::<Recorder>(sample, f);
+            assert_eq!(s, expected);
+            assert_eq!(recorder, reference::<Recorder>(sample, f));
+
+            let (_, fnv) = hashed::<Fnv>(sample, f);
+            assert_eq!(fnv.finish(), reference::<Fnv>(sample, f).finish());
+
+            #[allow(deprecated)]
+            let (_, sip) = hashed::<core::hash::SipHasher>(sample, f);
+            #[allow(deprecated)]
+            let sip_reference = reference::<core::hash::SipHasher>(sample, f);
+            assert_eq!(sip.finish(), sip_reference.finish());
+        }
+    }
+
+    #[test]
+    fn retain_hash_removed_framing() {
+        // Removing "ab" then "c" is distinguished from "a" then "bc"
+        let (_, first) = hashed::<Fnv>("abXc", |c| c == 'X');
+        let (_, second) = hashed::<Fnv>("aXbc", |c| c == 'X');
+        assert_ne!(first.finish(), second.finish());
+
+        // Nothing is hashed if nothing is removed
+        let (_, recorder) = hashed::<Recorder>("abc", |_| true);
+        assert_eq!(recorder, Recorder::default());
+    }
+}
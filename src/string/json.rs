@@ -0,0 +1,131 @@
+use core::fmt;
+
+use alloc::string::String;
+
+use super::cursor::Cursor;
+
+/// The error returned by
+/// [`minify_json_whitespace_in_place`](crate::RetainMoreString::minify_json_whitespace_in_place)
+/// when a string literal is not terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JsonMinifyError {
+    offset: usize,
+}
+
+impl JsonMinifyError {
+    /// The byte offset in the original string of the `'"'` which starts the
+    /// unterminated string literal.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for JsonMinifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unterminated JSON string starting at byte {}",
+            self.offset
+        )
+    }
+}
+
+/// Whitespace as defined by [RFC 8259](https://www.rfc-editor.org/rfc/rfc8259#section-2)
+fn is_json_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+/// The length of the string literal at the start of `rest`, including both
+/// quotes, or `None` if it is unterminated.
+fn string_literal_len(rest: &[u8]) -> Option<usize> {
+    let mut i = 1;
+    while i < rest.len() {
+        match rest[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+pub(super) fn minify_json_whitespace_in_place(s: &mut String) -> Result<(), JsonMinifyError> {
+    let mut cursor = Cursor::new(s);
+    loop {
+        let rest = cursor.rest().as_bytes();
+        let other = rest
+            .iter()
+            .position(|&b| b == b'"' || is_json_whitespace(b))
+            .unwrap_or(rest.len());
+        cursor.keep(other);
+        let rest = cursor.rest().as_bytes();
+        match rest.first() {
+            None => return Ok(()),
+            Some(b'"') => match string_literal_len(rest) {
+                Some(len) => cursor.keep(len),
+                None => {
+                    let offset = cursor.offset();
+                    cursor.keep_rest();
+                    return Err(JsonMinifyError { offset });
+                }
+            },
+            Some(_) => {
+                let whitespace = rest.iter().take_while(|&&b| is_json_whitespace(b)).count();
+                cursor.remove(whitespace);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetainMoreString;
+    use alloc::string::ToString;
+
+    fn minified(input: &str) -> String {
+        let mut s = input.to_string();
+        s.minify_json_whitespace_in_place().unwrap();
+        s
+    }
+
+    #[test]
+    fn round_trip() {
+        let documents = [
+            "{}",
+            " [ 1 , 2 ,\n 3 ] ",
+            r#"{ "key" : "value with  spaces", "nested": { "a" : [ true, false, null ] } }"#,
+            r#"{"escaped \" quote": "a \\" , "tab\t": "\\\" still a string \" " }"#,
+            "{\r\n\t\"unicode\": \"h\u{e9}llo \u{1F600} w\u{f6}rld\",\r\n\t\"num\": -1.5e10\r\n}",
+            r#"  "just a string with \\\\ backslashes"  "#,
+        ];
+        assert_eq!(minified(documents[1]), "[1,2,3]");
+        for document in documents {
+            let result = minified(document);
+            let original: serde_json::Value = serde_json::from_str(document).unwrap();
+            let minified: serde_json::Value = serde_json::from_str(&result).unwrap();
+            assert_eq!(original, minified);
+        }
+    }
+
+    #[test]
+    fn strings_untouched() {
+        assert_eq!(minified(r#"[ "a \" ] " , "\\" ]"#), r#"["a \" ] ","\\"]"#);
+        assert_eq!(minified("\"\u{3000}\"\u{3000}"), "\"\u{3000}\"\u{3000}");
+    }
+
+    #[test]
+    fn unterminated() {
+        let mut s = r#"{ "a" : 1, "b : [ 2 ] }"#.to_string();
+        assert_eq!(
+            s.minify_json_whitespace_in_place(),
+            Err(JsonMinifyError { offset: 11 })
+        );
+        assert_eq!(s, r#"{"a":1,"b : [ 2 ] }"#);
+
+        // An escape at the very end doesn't end the string
+        let mut s = r#" "\"#.to_string();
+        assert_eq!(s.minify_json_whitespace_in_place().unwrap_err().offset(), 1);
+        assert_eq!(s, r#""\"#);
+    }
+}
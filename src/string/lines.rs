@@ -0,0 +1,386 @@
+//! Line-oriented operations, which treat `'\n'` as the line terminator.
+//!
+//! A `'\r'` before the `'\n'` is never treated as part of the indentation of
+//! a line, so `"\r\n"` line endings are preserved.
+
+use alloc::string::String;
+
+use super::cursor::Cursor;
+
+/// How [`normalize_indent_in_place`](crate::RetainMoreString::normalize_indent_in_place)
+/// changes the indentation of each line.
+///
+/// The indentation of a line is the run of spaces (`' '`) and tabs (`'\t'`)
+/// at its start. Other whitespace is not considered to be indentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndentOptions {
+    /// Remove all of the indentation.
+    StripAll,
+    /// Keep at most the first `n` characters of the indentation.
+    Cap(usize),
+    /// Remove the tabs from the indentation, keeping the spaces.
+    RemoveTabs,
+}
+
+/// What [`collapse_blank_lines_with`](crate::RetainMoreString::collapse_blank_lines_with)
+/// does with blank lines at the start or end of the string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlankEdges {
+    /// Remove them entirely.
+    Strip,
+    /// Treat them like any other run of blank lines.
+    Cap,
+}
+
+/// Configuration for
+/// [`collapse_blank_lines_with`](crate::RetainMoreString::collapse_blank_lines_with).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlankLineOptions {
+    /// The maximum number of consecutive blank lines to keep.
+    pub max_consecutive: usize,
+    /// Whether a line containing only whitespace is blank. If `false`, only
+    /// empty lines are blank.
+    pub whitespace_is_blank: bool,
+    /// What to do with the blank lines before the first non-blank line.
+    pub leading: BlankEdges,
+    /// What to do with the blank lines after the last non-blank line.
+    pub trailing: BlankEdges,
+}
+
+impl BlankLineOptions {
+    /// The options used by
+    /// [`collapse_blank_lines`](crate::RetainMoreString::collapse_blank_lines).
+    ///
+    /// Whitespace-only lines are blank, and blank lines at the edges are
+    /// capped like every other run.
+    pub const fn new(max_consecutive: usize) -> Self {
+        BlankLineOptions {
+            max_consecutive,
+            whitespace_is_blank: true,
+            leading: BlankEdges::Cap,
+            trailing: BlankEdges::Cap,
+        }
+    }
+}
+
+fn is_indent(b: u8) -> bool {
+    b == b' ' || b == b'\t'
+}
+
+/// The length of the line at the start of `rest`, including its terminator.
+fn line_len(rest: &str) -> usize {
+    rest.find('\n').map_or(rest.len(), |i| i + 1)
+}
+
+pub(super) fn normalize_indent_in_place(s: &mut String, opts: &IndentOptions) {
+    let mut cursor = Cursor::new(s);
+    while !cursor.rest().is_empty() {
+        let indent = cursor.rest().bytes().take_while(|&b| is_indent(b)).count();
+        match *opts {
+            IndentOptions::StripAll => cursor.remove(indent),
+            IndentOptions::Cap(n) => {
+                let kept = indent.min(n);
+                cursor.keep(kept);
+                cursor.remove(indent - kept);
+            }
+            IndentOptions::RemoveTabs => {
+                for _ in 0..indent {
+                    if cursor.rest().as_bytes()[0] == b'\t' {
+                        cursor.remove(1);
+                    } else {
+                        cursor.keep(1);
+                    }
+                }
+            }
+        }
+        let line = line_len(cursor.rest());
+        cursor.keep(line);
+    }
+}
+
+/// Whether the line at the start of `line` (which may include its
+/// terminator) contains only whitespace.
+fn is_blank(line: &str) -> bool {
+    line.chars().all(char::is_whitespace)
+}
+
+/// The common leading indentation of every non-blank line in `s`.
+fn common_indent(s: &str) -> &str {
+    let mut prefix: Option<&str> = None;
+    for line in s.split('\n').filter(|line| !is_blank(line)) {
+        let indent = line.bytes().take_while(|&b| is_indent(b)).count();
+        let common = match prefix {
+            Some(prefix) => prefix
+                .bytes()
+                .zip(line.bytes())
+                .take_while(|(a, b)| a == b)
+                .count(),
+            None => indent,
+        };
+        prefix = Some(&line[..common.min(indent)]);
+    }
+    prefix.unwrap_or("")
+}
+
+pub(super) fn collapse_blank_lines(s: &mut String, opts: &BlankLineOptions) {
+    let is_blank_line = |line: &str| {
+        let content = line.strip_suffix('\n').unwrap_or(line);
+        let content = content.strip_suffix('\r').unwrap_or(content);
+        content.is_empty() || (opts.whitespace_is_blank && is_blank(content))
+    };
+    let mut at_start = true;
+    let mut cursor = Cursor::new(s);
+    while !cursor.rest().is_empty() {
+        let line = line_len(cursor.rest());
+        if !is_blank_line(&cursor.rest()[..line]) {
+            cursor.keep(line);
+            at_start = false;
+            continue;
+        }
+        // Measure the whole run of blank lines, and the bytes in the lines
+        // which might be kept
+        let rest = cursor.rest();
+        let mut run_len = 0;
+        let mut run_lines = 0;
+        let mut capped_len = 0;
+        while run_len < rest.len() {
+            let line = line_len(&rest[run_len..]);
+            if !is_blank_line(&rest[run_len..run_len + line]) {
+                break;
+            }
+            run_len += line;
+            run_lines += 1;
+            if run_lines == opts.max_consecutive {
+                capped_len = run_len;
+            }
+        }
+        if run_lines < opts.max_consecutive {
+            capped_len = run_len;
+        }
+        let at_end = run_len == rest.len();
+        let strip = (at_start && opts.leading == BlankEdges::Strip)
+            || (at_end && opts.trailing == BlankEdges::Strip);
+        let kept = if strip { 0 } else { capped_len };
+        cursor.keep(kept);
+        cursor.remove(run_len - kept);
+    }
+}
+
+pub(super) fn dedent_in_place(s: &mut String) -> usize {
+    let width = common_indent(s).len();
+    if width == 0 {
+        return 0;
+    }
+    let mut cursor = Cursor::new(s);
+    while !cursor.rest().is_empty() {
+        let line = line_len(cursor.rest());
+        if is_blank(&cursor.rest()[..line]) {
+            let indent = cursor.rest().bytes().take_while(|&b| is_indent(b)).count();
+            cursor.remove(indent.min(width));
+        } else {
+            cursor.remove(width);
+        }
+        let line = line_len(cursor.rest());
+        cursor.keep(line);
+    }
+    width
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BlankEdges, BlankLineOptions, IndentOptions, RetainMoreString};
+    use alloc::string::ToString;
+
+    fn normalized(input: &str, opts: IndentOptions) -> alloc::string::String {
+        let mut s = input.to_string();
+        s.normalize_indent_in_place(&opts);
+        s
+    }
+
+    #[test]
+    fn strip_all() {
+        assert_eq!(
+            normalized("  a\n\t\tb \n \t c\td\n", IndentOptions::StripAll),
+            "a\nb \nc\td\n"
+        );
+        assert_eq!(
+            normalized("  a\r\n\t \r\n   \r\n  b", IndentOptions::StripAll),
+            "a\r\n\r\n\r\nb"
+        );
+        assert_eq!(normalized("   ", IndentOptions::StripAll), "");
+    }
+
+    #[test]
+    fn cap() {
+        assert_eq!(
+            normalized("    a\n  b\n\t\t\tc\n d", IndentOptions::Cap(2)),
+            "  a\n  b\n\t\tc\n d"
+        );
+        assert_eq!(
+            normalized("   \r\n\u{3000} x\r\n", IndentOptions::Cap(1)),
+            " \r\n\u{3000} x\r\n"
+        );
+        assert_eq!(normalized("    a", IndentOptions::Cap(0)), "a");
+    }
+
+    #[test]
+    fn remove_tabs() {
+        assert_eq!(
+            normalized("\t a\t\n \t\tb\n\t\r\n", IndentOptions::RemoveTabs),
+            " a\t\n b\n\r\n"
+        );
+    }
+
+    #[test]
+    fn already_normalized() {
+        let input = "a\r\nb\n\nc d\t\n";
+        for opts in [
+            IndentOptions::StripAll,
+            IndentOptions::Cap(0),
+            IndentOptions::RemoveTabs,
+        ] {
+            let mut s = input.to_string();
+            let ptr = s.as_ptr();
+            s.normalize_indent_in_place(&opts);
+            assert_eq!(s, input);
+            assert_eq!(s.as_ptr(), ptr);
+        }
+        assert_eq!(normalized("  a\n  b", IndentOptions::Cap(2)), "  a\n  b");
+    }
+
+    fn collapsed(input: &str, opts: BlankLineOptions) -> alloc::string::String {
+        let mut s = input.to_string();
+        s.collapse_blank_lines_with(&opts);
+        s
+    }
+
+    #[test]
+    fn collapse_blank_lines() {
+        let mut s = "a\n\n\n\nb\n \t\n\nc\n".to_string();
+        s.collapse_blank_lines(1);
+        assert_eq!(s, "a\n\nb\n \t\nc\n");
+
+        let mut s = "a\r\n\r\n  \r\n\r\nb\r\n".to_string();
+        s.collapse_blank_lines(2);
+        assert_eq!(s, "a\r\n\r\n  \r\nb\r\n");
+
+        // No newline at the end of the string, including after a blank line
+        let mut s = "a\n\n\nb".to_string();
+        s.collapse_blank_lines(1);
+        assert_eq!(s, "a\n\nb");
+        let mut s = "a\n\n  ".to_string();
+        s.collapse_blank_lines(1);
+        assert_eq!(s, "a\n\n");
+
+        // Already collapsed
+        let mut s = "a\n\nb\n".to_string();
+        s.collapse_blank_lines(1);
+        assert_eq!(s, "a\n\nb\n");
+    }
+
+    #[test]
+    fn collapse_blank_lines_zero() {
+        let mut s = "\n\na\n\nb\r\n   \r\nc\n\n".to_string();
+        s.collapse_blank_lines(0);
+        assert_eq!(s, "a\nb\r\nc\n");
+
+        let mut s = "\n \n\r\n".to_string();
+        s.collapse_blank_lines(0);
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn collapse_blank_lines_options() {
+        let input = "\n\n\na\n \n \nb\n\n\n";
+        let opts = BlankLineOptions::new(1);
+        assert_eq!(collapsed(input, opts), "\na\n \nb\n\n");
+        let strip = BlankLineOptions {
+            leading: BlankEdges::Strip,
+            trailing: BlankEdges::Strip,
+            ..opts
+        };
+        assert_eq!(collapsed(input, strip), "a\n \nb\n");
+        let strip_leading = BlankLineOptions {
+            leading: BlankEdges::Strip,
+            ..opts
+        };
+        assert_eq!(collapsed(input, strip_leading), "a\n \nb\n\n");
+
+        // Whitespace-only lines aren't blank
+        let empty_only = BlankLineOptions {
+            whitespace_is_blank: false,
+            ..opts
+        };
+        assert_eq!(collapsed(input, empty_only), "\na\n \n \nb\n\n");
+    }
+
+    #[test]
+    fn dedent_matches_textwrap() {
+        let corpus = [
+            "",
+            "no indentation\nat all",
+            "    four\n    spaces\n",
+            "    nested\n        more\n      less\n",
+            "\n    leading blank line\n    here",
+            "  a\n\n  b\n  \n  c\n",
+            "\t\ttabs\n\t\t\tdeeper\n\tshallower",
+            "\t mixed\n\t  is\n\t\tcompared literally",
+            "    trailing blank\n  \n",
+            "   only one line",
+        ];
+        for input in corpus {
+            let mut s = input.to_string();
+            s.dedent_in_place();
+            assert_eq!(s, textwrap::dedent(input), "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn dedent_width() {
+        let mut s = "    a\n      b\n".to_string();
+        assert_eq!(s.dedent_in_place(), 4);
+        assert_eq!(s, "a\n  b\n");
+        assert_eq!(s.dedent_in_place(), 0);
+
+        // Blank lines don't constrain the width, and lose at most that much
+        // whitespace
+        let mut s = "\t\ta\n\t\t\t\t\n \n\t\tb".to_string();
+        assert_eq!(s.dedent_in_place(), 2);
+        assert_eq!(s, "a\n\t\t\n\nb");
+
+        // Unlike `textwrap::dedent`, whitespace beyond the width is kept
+        let mut s = "  \n  \n".to_string();
+        assert_eq!(s.dedent_in_place(), 0);
+        assert_eq!(s, "  \n  \n");
+
+        // CRLF line endings are preserved
+        let mut s = "  a\r\n  \r\n   b\r\n".to_string();
+        assert_eq!(s.dedent_in_place(), 2);
+        assert_eq!(s, "a\r\n\r\n b\r\n");
+    }
+
+    #[test]
+    fn dedent_literal() {
+        // The first line of a raw literal is usually empty
+        let mut s = "\n    a\n\n      b\n    c".to_string();
+        assert_eq!(s.dedent(), 4);
+        assert_eq!(s, "\na\n\n  b\nc");
+
+        // A tab is not the same as four spaces
+        let mut s = "\n\ta\n    b\n".to_string();
+        assert_eq!(s.dedent(), 0);
+        assert_eq!(s, "\n\ta\n    b\n");
+        let mut s = "\r\n \t a\r\n\r\n \t\tb\r\n".to_string();
+        assert_eq!(s.dedent(), 2);
+        assert_eq!(s, "\r\n a\r\n\r\n\tb\r\n");
+
+        // A whitespace-only line keeps what is beyond the common indentation
+        let mut s = "    a\n        \n    b".to_string();
+        assert_eq!(s.dedent(), 4);
+        assert_eq!(s, "a\n    \nb");
+        // ..which is none at all if another line isn't indented
+        let mut s = "    a\n        \nb".to_string();
+        assert_eq!(s.dedent(), 0);
+        assert_eq!(s, "    a\n        \nb");
+    }
+}
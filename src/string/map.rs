@@ -0,0 +1,117 @@
+use alloc::string::String;
+
+use super::cursor::Cursor;
+
+/// Retain and replace the characters of `s` in place, growing the string
+/// when a replacement doesn't fit in the gap.
+pub(super) fn retain_map<F: FnMut(char, &mut str) -> Option<char>>(s: &mut String, mut f: F) {
+    let mut cursor = Cursor::new(s);
+    while let Some(ch) = cursor.peek() {
+        let ch_len = ch.len_utf8();
+        match f(ch, cursor.after(ch_len)) {
+            Some(mapped) => {
+                let mut buf = [0; 4];
+                let mapped = mapped.encode_utf8(&mut buf);
+                if !cursor.replace(ch_len, mapped) {
+                    cursor.grow(mapped.len() - ch_len);
+                    let replaced = cursor.replace(ch_len, mapped);
+                    debug_assert!(replaced);
+                }
+            }
+            None => cursor.remove(ch_len),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreString;
+    use alloc::{
+        string::{String, ToString},
+        vec::Vec,
+    };
+
+    #[test]
+    fn shrinking() {
+        let mut s = "\u{1F980}a\u{1F980}\u{e9}".to_string();
+        s.retain_map(|c, _| Some(if c == '\u{1F980}' { 'c' } else { c }));
+        assert_eq!(s, "cac\u{e9}");
+    }
+
+    #[test]
+    fn equal_length() {
+        let mut s = "\u{201c}quoted\u{201d}\ttab".to_string();
+        let capacity = s.capacity();
+        s.retain_map(|c, _| match c {
+            '\u{201c}' | '\u{201d}' => Some('"'),
+            '\t' => Some(' '),
+            c => Some(c),
+        });
+        assert_eq!(s, "\"quoted\" tab");
+        assert_eq!(s.capacity(), capacity);
+
+        let mut s = "a\u{e9}b".to_string();
+        s.retain_map(|c, _| Some(if c == '\u{e9}' { '\u{f1}' } else { c }));
+        assert_eq!(s, "a\u{f1}b");
+    }
+
+    #[test]
+    fn growing() {
+        let mut s = "banana".to_string();
+        s.retain_map(|c, _| Some(if c == 'a' { '\u{e9}' } else { c }));
+        assert_eq!(s, "b\u{e9}n\u{e9}n\u{e9}");
+
+        // Removals make room for later growth to happen in place
+        let mut s = "---ab".to_string();
+        let ptr = s.as_ptr();
+        s.retain_map(|c, _| match c {
+            '-' => None,
+            'a' => Some('\u{1F980}'),
+            c => Some(c),
+        });
+        assert_eq!(s, "\u{1F980}b");
+        assert_eq!(s.as_ptr(), ptr);
+
+        // Characters after a replacement which didn't fit keep their order
+        let mut s = "a-b-\u{1F980}c".to_string();
+        s.retain_map(|c, _| match c {
+            'a' => Some('\u{e9}'),
+            '-' => None,
+            '\u{1F980}' => Some('d'),
+            c => Some(c),
+        });
+        assert_eq!(s, "\u{e9}bdc");
+    }
+
+    #[test]
+    fn growing_matches_filter_map() {
+        // Growth in the middle of removals and replacements of every width
+        let input = "a\u{e9}-\u{1F980}b--\u{20ac}c".repeat(40);
+        let f = |c: char| match c {
+            '-' => None,
+            'a' => Some('\u{1F980}'),
+            '\u{e9}' => Some('e'),
+            'b' => Some('\u{20ac}'),
+            c => Some(c),
+        };
+        let mut s = input.clone();
+        s.retain_map(|c, _| f(c));
+        assert_eq!(s, input.chars().filter_map(f).collect::<String>());
+    }
+
+    #[test]
+    fn sees_changes_to_after() {
+        let mut s = "a^bc".to_string();
+        let mut seen = Vec::new();
+        s.retain_map(|c, after| {
+            seen.push(c);
+            if c == '^' {
+                after.make_ascii_uppercase();
+                return None;
+            }
+            Some(c)
+        });
+        assert_eq!(s, "aBC");
+        assert_eq!(seen, ['a', '^', 'B', 'C']);
+    }
+}
@@ -0,0 +1,93 @@
+use core::fmt;
+
+use alloc::string::String;
+
+use super::cursor::Cursor;
+
+/// The error returned by
+/// [`mask_retain`](crate::RetainMoreString::mask_retain) when the fill
+/// character could be longer than a character it replaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FillTooWide {
+    /// The fill character.
+    pub fill: char,
+    /// The byte offset of the first character of the string which is
+    /// shorter than the fill character.
+    pub offset: usize,
+}
+
+impl fmt::Display for FillTooWide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the fill character {:?} is longer than the character at byte {}",
+            self.fill, self.offset
+        )
+    }
+}
+
+pub(super) fn mask_retain<F: FnMut(char, &mut str) -> bool>(
+    s: &mut String,
+    mut f: F,
+    fill: char,
+) -> Result<(), FillTooWide> {
+    let fill_len = fill.len_utf8();
+    if fill_len > 1 {
+        if let Some((offset, _)) = s.char_indices().find(|(_, c)| c.len_utf8() < fill_len) {
+            return Err(FillTooWide { fill, offset });
+        }
+    }
+    let mut buf = [0; 4];
+    let fill = &*fill.encode_utf8(&mut buf);
+    let mut cursor = Cursor::new(s);
+    while let Some(ch) = cursor.peek() {
+        let ch_len = ch.len_utf8();
+        if f(ch, cursor.after(ch_len)) {
+            cursor.keep(ch_len);
+        } else {
+            let replaced = cursor.replace(ch_len, fill);
+            // Every character is at least as long as the fill
+            debug_assert!(replaced);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FillTooWide;
+    use crate::RetainMoreString;
+    use alloc::string::ToString;
+
+    #[test]
+    fn mask_multi_byte() {
+        let mut s = "\u{e9}t\u{e9} \u{1F980}\u{2603}".to_string();
+        assert_eq!(s.len(), 13);
+        s.mask_retain(|c, _| c.is_ascii(), '#').unwrap();
+        assert_eq!(s, "#t# ##");
+        assert_eq!(s.len(), 6);
+
+        // A fill which is no longer than any character
+        let mut s = "\u{1F980}\u{e9}\u{2603}".to_string();
+        s.mask_retain(|c, _| c == '\u{e9}', '\u{e9}').unwrap();
+        assert_eq!(s, "\u{e9}\u{e9}\u{e9}");
+    }
+
+    #[test]
+    fn fill_too_wide() {
+        let mut s = "\u{1F980}\u{e9}ab".to_string();
+        let err = s.mask_retain(|_, _| false, '\u{2603}').unwrap_err();
+        assert_eq!(
+            err,
+            FillTooWide {
+                fill: '\u{2603}',
+                offset: 4
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "the fill character '\u{2603}' is longer than the character at byte 4"
+        );
+        assert_eq!(s, "\u{1F980}\u{e9}ab");
+    }
+}
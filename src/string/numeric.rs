@@ -0,0 +1,89 @@
+use alloc::string::String;
+
+use super::cursor::Cursor;
+
+/// Which characters
+/// [`remove_digit_separators_with`](crate::RetainMoreString::remove_digit_separators_with)
+/// treats as digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DigitKind {
+    /// Only `'0'..='9'`, as [`char::is_ascii_digit`].
+    #[default]
+    Ascii,
+    /// Any numeric character, as [`char::is_numeric`]. This includes digits
+    /// from other scripts, such as `'٣'`, and characters such as `'½'`.
+    Numeric,
+}
+
+impl DigitKind {
+    fn is_digit(self, ch: char) -> bool {
+        match self {
+            DigitKind::Ascii => ch.is_ascii_digit(),
+            DigitKind::Numeric => ch.is_numeric(),
+        }
+    }
+}
+
+pub(super) fn remove_digit_separators(s: &mut String, separators: &[char], digits: DigitKind) {
+    let mut cursor = Cursor::new(s);
+    // The previous character of the original string, which may have been
+    // removed
+    let mut previous = None;
+    while let Some(ch) = cursor.peek() {
+        let remove = separators.contains(&ch)
+            && previous.is_some_and(|previous| digits.is_digit(previous))
+            && cursor.rest()[ch.len_utf8()..]
+                .chars()
+                .next()
+                .is_some_and(|next| digits.is_digit(next));
+        if remove {
+            cursor.remove(ch.len_utf8());
+        } else {
+            cursor.keep(ch.len_utf8());
+        }
+        previous = Some(ch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DigitKind;
+    use crate::RetainMoreString;
+    use alloc::string::{String, ToString};
+
+    fn without_separators(input: &str, digits: DigitKind) -> String {
+        let mut s = input.to_string();
+        s.remove_digit_separators_with(&[',', '_'], digits);
+        s
+    }
+
+    #[test]
+    fn remove_digit_separators() {
+        let ascii = |input| without_separators(input, DigitKind::Ascii);
+        assert_eq!(ascii("1,234,567"), "1234567");
+        assert_eq!(ascii("0xFF_FF 1_000"), "0xFF_FF 1000");
+        assert_eq!(ascii("hello, world"), "hello, world");
+        assert_eq!(ascii("1, 2, 3"), "1, 2, 3");
+        // At the edges of the string
+        assert_eq!(ascii(",1,"), ",1,");
+        assert_eq!(ascii(",12_"), ",12_");
+        // Two separators in a row are both kept
+        assert_eq!(ascii("1,,2"), "1,,2");
+        assert_eq!(ascii("1,_2"), "1,_2");
+        // Characters which are not in the list are never removed
+        let mut s = "1.234.567".to_string();
+        s.remove_digit_separators(&[',']);
+        assert_eq!(s, "1.234.567");
+    }
+
+    #[test]
+    fn remove_digit_separators_non_ascii() {
+        // Arabic-Indic digits
+        let input = "\u{663},\u{664}\u{665} 1,\u{bd}";
+        assert_eq!(without_separators(input, DigitKind::Ascii), input);
+        assert_eq!(
+            without_separators(input, DigitKind::Numeric),
+            "\u{663}\u{664}\u{665} 1\u{bd}"
+        );
+    }
+}
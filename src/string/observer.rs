@@ -0,0 +1,194 @@
+use alloc::string::String;
+
+use super::cursor::Cursor;
+
+/// An observer of the characters removed by
+/// [`retain_observed`](crate::RetainMoreString::retain_observed).
+///
+/// This is implemented for every `FnMut(usize, char)`, and can also be
+/// implemented for your own types (or mutable references to them), for
+/// example to count removals for metrics:
+///
+/// ```
+/// use retain_more::{RemovalObserver, RetainMoreString as _};
+///
+/// #[derive(Default)]
+/// struct Counter {
+///     removed: usize,
+///     removed_bytes: usize,
+/// }
+///
+/// impl RemovalObserver for &mut Counter {
+///     fn removed(&mut self, _offset: usize, ch: char) {
+///         self.removed += 1;
+///         self.removed_bytes += ch.len_utf8();
+///     }
+/// }
+///
+/// let mut counter = Counter::default();
+/// let mut s = "a€b€c".to_string();
+/// s.retain_observed(|c| c.is_ascii(), &mut counter);
+/// assert_eq!(s, "abc");
+/// assert_eq!((counter.removed, counter.removed_bytes), (2, 6));
+/// ```
+pub trait RemovalObserver {
+    /// Called for every removed character, with the byte offset of `ch` in
+    /// the original string.
+    fn removed(&mut self, offset: usize, ch: char);
+}
+
+impl<F: FnMut(usize, char)> RemovalObserver for F {
+    fn removed(&mut self, offset: usize, ch: char) {
+        self(offset, ch)
+    }
+}
+
+/// What to do with a character, and possibly the rest of the string, in
+/// [`retain_observed_until`](crate::RetainMoreString::retain_observed_until).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RetainAction {
+    /// Keep the character.
+    Keep,
+    /// Remove the character.
+    Remove,
+    /// Keep the character and the rest of the string, without considering
+    /// them.
+    KeepRest,
+    /// Remove the character and the rest of the string, without considering
+    /// them.
+    RemoveRest,
+}
+
+pub(super) fn retain_observed_until<F, O>(s: &mut String, mut f: F, mut observer: O)
+where
+    F: FnMut(char) -> RetainAction,
+    O: RemovalObserver,
+{
+    let mut cursor = Cursor::new(s);
+    while let Some(ch) = cursor.peek() {
+        let ch_len = ch.len_utf8();
+        let offset = cursor.offset();
+        match f(ch) {
+            RetainAction::Keep => cursor.keep(ch_len),
+            RetainAction::Remove => {
+                cursor.remove(ch_len);
+                observer.removed(offset, ch);
+            }
+            RetainAction::KeepRest => break,
+            RetainAction::RemoveRest => {
+                let rest = cursor.rest().len();
+                cursor.remove(rest);
+                // The removed bytes are still in the gap, so the calls for
+                // the run are made from there
+                for (i, ch) in cursor.removed_run(rest).char_indices() {
+                    observer.removed(offset + i, ch);
+                }
+            }
+        }
+    }
+    cursor.keep_rest();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetainAction;
+    use crate::RetainMoreString;
+    use alloc::{string::ToString, vec, vec::Vec};
+
+    #[test]
+    fn observes_removed_in_order() {
+        let mut s = "x1ü2€3\u{1F600}y".to_string();
+        let mut seen = Vec::new();
+        s.retain_observed(|c| !c.is_ascii_digit(), |offset, c| seen.push((offset, c)));
+        assert_eq!(s, "xü€\u{1F600}y");
+        assert_eq!(seen, vec![(1, '1'), (4, '2'), (8, '3')]);
+    }
+
+    #[test]
+    fn retain_logged_is_lossless() {
+        let original = "\u{1F980}a1\u{e9}\u{e9}2-\u{2603}3b\u{1F980}";
+        let mut s = original.to_string();
+        let mut log = vec![(usize::MAX, '!')];
+        s.retain_logged(|c| c.is_ascii_alphanumeric(), &mut log);
+        assert_eq!(s, "a123b");
+        // Existing entries are left alone
+        assert_eq!(log[0], (usize::MAX, '!'));
+
+        // The offsets are into the original, so inserting the removed
+        // characters in order restores it
+        for &(offset, c) in &log[1..] {
+            s.insert(offset, c);
+        }
+        assert_eq!(s, original);
+    }
+
+    #[test]
+    fn nothing_removed() {
+        let mut s = "keep everything".to_string();
+        let mut calls = 0;
+        s.retain_observed(|_| true, |_, _| calls += 1);
+        assert_eq!(s, "keep everything");
+        assert_eq!(calls, 0);
+    }
+
+    #[test]
+    fn observes_bulk_removals() {
+        let input = "a1\u{e9}2;b3\u{1F980}4|c5";
+        let decide = |c: char| match c {
+            ';' => RetainAction::RemoveRest,
+            '|' => RetainAction::KeepRest,
+            c if c.is_ascii_digit() => RetainAction::Remove,
+            _ => RetainAction::Keep,
+        };
+        let mut s = input.to_string();
+        let mut seen = Vec::new();
+        s.retain_observed_until(decide, |offset, c| seen.push((offset, c)));
+        assert_eq!(s, "a\u{e9}");
+        // Every character from the `;` on is reported, in order
+        let removed: Vec<_> = input
+            .char_indices()
+            .filter(|&(i, _)| i == 1 || i >= 4)
+            .collect();
+        assert_eq!(seen, removed);
+
+        let mut s = "x1|2y3".to_string();
+        let mut seen = Vec::new();
+        s.retain_observed_until(decide, |offset, c| seen.push((offset, c)));
+        assert_eq!(s, "x|2y3");
+        assert_eq!(seen, [(1, '1')]);
+
+        // The observed characters and the result describe the original
+        let mut s = "5;".to_string();
+        let mut seen = Vec::new();
+        s.retain_observed_until(decide, |offset, c| seen.push((offset, c)));
+        assert_eq!(s, "");
+        assert_eq!(seen, [(0, '5'), (1, ';')]);
+    }
+
+    #[test]
+    fn observer_runs_before_next_decision() {
+        // Each observer call happens before the predicate sees the following
+        // character
+        let mut s = "abcd".to_string();
+        let events = core::cell::RefCell::new(Vec::new());
+        s.retain_observed(
+            |c| {
+                events.borrow_mut().push(('?', c));
+                c == 'a' || c == 'd'
+            },
+            |_, c| events.borrow_mut().push(('-', c)),
+        );
+        assert_eq!(s, "ad");
+        assert_eq!(
+            events.into_inner(),
+            vec![
+                ('?', 'a'),
+                ('?', 'b'),
+                ('-', 'b'),
+                ('?', 'c'),
+                ('-', 'c'),
+                ('?', 'd')
+            ]
+        );
+    }
+}
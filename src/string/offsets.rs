@@ -0,0 +1,492 @@
+use core::ops::Range;
+
+use alloc::{string::String, vec::Vec};
+
+use super::cursor::Cursor;
+
+/// A run of bytes removed from the original string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Run {
+    /// The range of the run, in offsets of the original string.
+    original: Range<usize>,
+    /// The number of bytes removed before `original.start`.
+    removed_before: usize,
+}
+
+impl Run {
+    /// The offset in the current string where this run was removed.
+    fn current(&self) -> usize {
+        self.original.start - self.removed_before
+    }
+
+    /// The number of bytes removed up to the end of this run.
+    fn removed_through(&self) -> usize {
+        self.removed_before + self.original.len()
+    }
+}
+
+/// A map between byte offsets in a string before and after a sequence of
+/// retain passes.
+///
+/// Pass the same mapper to each call of
+/// [`retain_all_mapped`](crate::RetainMoreString::retain_all_mapped), and it
+/// will accumulate the deletions made by all of them, relative to the string
+/// before the first pass. Mappers recorded separately for consecutive passes
+/// can be combined with [`compose`](Self::compose).
+///
+/// The deletions are stored as sorted, coalesced runs, so each lookup is a
+/// binary search.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct OffsetMapper {
+    runs: Vec<Run>,
+}
+
+impl OffsetMapper {
+    /// A mapper for no deletions, which maps every offset to itself.
+    pub fn new() -> Self {
+        OffsetMapper::default()
+    }
+
+    /// The total number of bytes removed.
+    pub fn bytes_removed(&self) -> usize {
+        self.runs.last().map_or(0, Run::removed_through)
+    }
+
+    /// The ranges of the original string which have been removed, in
+    /// ascending order. Adjacent removals are coalesced.
+    pub fn removed_ranges(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.runs.iter().map(|run| run.original.clone())
+    }
+
+    /// The offset in the current string of the byte at `original`, or `None`
+    /// if that byte has been removed.
+    ///
+    /// An offset at or past the end of the original string maps past the
+    /// end of the current string in the same way, so the original length
+    /// maps to the current length.
+    pub fn map_forward(&self, original: usize) -> Option<usize> {
+        let idx = self
+            .runs
+            .partition_point(|run| run.original.end <= original);
+        if let Some(run) = self.runs.get(idx) {
+            if run.original.start <= original {
+                return None;
+            }
+        }
+        let removed = idx
+            .checked_sub(1)
+            .map_or(0, |prev| self.runs[prev].removed_through());
+        Some(original - removed)
+    }
+
+    /// The offset in the current string of the byte at `original`, or of
+    /// the first kept byte after it if that byte has been removed.
+    ///
+    /// This is [`map_forward`](Self::map_forward), except that offsets in a
+    /// removed run snap forward to the end of the run, which is where the run
+    /// was removed from in the current string.
+    pub fn map_forward_clamped(&self, original: usize) -> usize {
+        let idx = self
+            .runs
+            .partition_point(|run| run.original.end <= original);
+        match self.runs.get(idx) {
+            Some(run) if run.original.start <= original => run.current(),
+            _ => {
+                let removed = idx
+                    .checked_sub(1)
+                    .map_or(0, |prev| self.runs[prev].removed_through());
+                original - removed
+            }
+        }
+    }
+
+    /// The offset in the original string of the byte at `current`.
+    ///
+    /// This is the inverse of [`map_forward`](Self::map_forward) for the
+    /// bytes which were kept. An offset at or past the end of the current
+    /// string maps past the end of the original string.
+    pub fn map_backward(&self, current: usize) -> usize {
+        let idx = self.runs.partition_point(|run| run.current() <= current);
+        let removed = idx
+            .checked_sub(1)
+            .map_or(0, |prev| self.runs[prev].removed_through());
+        current + removed
+    }
+
+    /// The mapper for applying the deletions of `self`, followed by those
+    /// of `other`, whose offsets are relative to the string after `self`.
+    pub fn compose(&self, other: &OffsetMapper) -> OffsetMapper {
+        let mut ranges: Vec<Range<usize>> = self.removed_ranges().collect();
+        // Each run of `other` covers consecutive kept bytes, so in the
+        // original string it covers those bytes and any runs of `self`
+        // between them
+        ranges.extend(
+            other
+                .removed_ranges()
+                .map(|range| self.map_backward(range.start)..self.map_backward(range.end - 1) + 1),
+        );
+        ranges.sort_unstable_by_key(|range| range.start);
+        let mut composed = OffsetMapper::new();
+        for range in ranges {
+            composed.push_original(range);
+        }
+        composed
+    }
+
+    /// Record the removal of `range`, in offsets of the original string,
+    /// which must not start before any recorded removal.
+    fn push_original(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        match self.runs.last_mut() {
+            Some(last) if range.start <= last.original.end => {
+                last.original.end = last.original.end.max(range.end);
+            }
+            _ => {
+                let removed_before = self.bytes_removed();
+                self.runs.push(Run {
+                    original: range,
+                    removed_before,
+                });
+            }
+        }
+    }
+}
+
+pub(super) fn retain_all_mapped<F: FnMut(&mut str, char, &mut str) -> bool>(
+    s: &mut String,
+    mapper: &mut OffsetMapper,
+    mut f: F,
+) {
+    // The deletions of this pass, relative to the string before it
+    let mut pass = OffsetMapper::new();
+    {
+        let mut cursor = Cursor::new(s);
+        while let Some(ch) = cursor.peek() {
+            let ch_len = ch.len_utf8();
+            let (before, after) = cursor.split(ch_len);
+            if f(before, ch, after) {
+                cursor.keep(ch_len);
+            } else {
+                pass.push_original(cursor.offset()..cursor.offset() + ch_len);
+                cursor.remove(ch_len);
+            }
+        }
+    }
+    *mapper = mapper.compose(&pass);
+}
+
+pub(super) fn retain_adjust_spans<F: FnMut(char) -> bool>(
+    s: &mut String,
+    f: F,
+    spans: &mut [Range<usize>],
+) {
+    let in_order = spans
+        .windows(2)
+        .all(|pair| pair[0].start <= pair[1].start && pair[0].end <= pair[1].end);
+    if in_order {
+        adjust_ordered_spans(s, f, spans)
+    } else {
+        adjust_unordered_spans(s, f, spans)
+    }
+}
+
+/// [`retain_adjust_spans`] where the starts and the ends of `spans` are each
+/// in increasing order, so each endpoint can be adjusted once the pass
+/// reaches it.
+fn adjust_ordered_spans<F: FnMut(char) -> bool>(
+    s: &mut String,
+    mut f: F,
+    spans: &mut [Range<usize>],
+) {
+    // The index of the first span whose start (or end) is not yet adjusted
+    let (mut starts, mut ends) = (0, 0);
+    let mut removed_before = 0;
+    let mut cursor = Cursor::new(s);
+    while let Some(ch) = cursor.peek() {
+        let offset = cursor.offset();
+        let next = offset + ch.len_utf8();
+        let kept = f(ch);
+        // An endpoint within a removed character loses the part of it before
+        // the endpoint
+        let adjust = |endpoint: &mut usize| {
+            let within = if kept {
+                0
+            } else {
+                *endpoint - offset.min(*endpoint)
+            };
+            *endpoint -= removed_before + within;
+        };
+        while let Some(span) = spans[starts..].first_mut().filter(|span| span.start < next) {
+            adjust(&mut span.start);
+            starts += 1;
+        }
+        while let Some(span) = spans[ends..].first_mut().filter(|span| span.end < next) {
+            adjust(&mut span.end);
+            ends += 1;
+        }
+        if kept {
+            cursor.keep(next - offset);
+        } else {
+            cursor.remove(next - offset);
+            removed_before += next - offset;
+        }
+    }
+    for span in &mut spans[starts..] {
+        span.start -= removed_before;
+    }
+    for span in &mut spans[ends..] {
+        span.end -= removed_before;
+    }
+}
+
+/// [`retain_adjust_spans`] for spans in any order, where each run of removed
+/// characters adjusts every span.
+fn adjust_unordered_spans<F: FnMut(char) -> bool>(
+    s: &mut String,
+    mut f: F,
+    spans: &mut [Range<usize>],
+) {
+    // The current run of removed bytes, in offsets of the original string
+    let mut run = 0..0;
+    // The number of bytes removed before `run`
+    let mut removed_before = 0;
+    let mut cursor = Cursor::new(s);
+    while let Some(ch) = cursor.peek() {
+        let ch_len = ch.len_utf8();
+        if f(ch) {
+            cursor.keep(ch_len);
+        } else {
+            if run.end != cursor.offset() {
+                shift_spans(spans, run.start - removed_before, run.len());
+                removed_before += run.len();
+                run.start = cursor.offset();
+            }
+            cursor.remove(ch_len);
+            run.end = cursor.offset();
+        }
+    }
+    shift_spans(spans, run.start - removed_before, run.len());
+}
+
+/// Adjust the endpoints of `spans` for the removal of the `len` bytes at
+/// `at`, where the spans have already been adjusted for every earlier
+/// removal.
+fn shift_spans(spans: &mut [Range<usize>], at: usize, len: usize) {
+    if len == 0 {
+        return;
+    }
+    let shift = |offset: &mut usize| {
+        if *offset > at {
+            *offset -= len.min(*offset - at);
+        }
+    };
+    for span in spans {
+        shift(&mut span.start);
+        shift(&mut span.end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetainMoreString;
+    use alloc::{string::ToString, vec};
+
+    #[test]
+    fn successive_passes() {
+        let original = "a1 b\u{e9}2 \u{1F980}c3  d\u{e9}\u{e9}4e";
+        let mut s = original.to_string();
+        // The original offsets of the bytes still in `s`
+        let mut alive: Vec<usize> = (0..original.len()).collect();
+        let mut mapper = OffsetMapper::new();
+
+        let passes: [fn(char) -> bool; 3] = [
+            |c| !c.is_ascii_digit(),
+            |c| c != ' ',
+            |c| !matches!(c, '\u{e9}' | 'b'),
+        ];
+        for keep in passes {
+            let mut offset = 0;
+            let mut removed = vec![false; s.len()];
+            s.retain_all_mapped(&mut mapper, |_, ch, _| {
+                let kept = keep(ch);
+                if !kept {
+                    removed[offset..offset + ch.len_utf8()].fill(true);
+                }
+                offset += ch.len_utf8();
+                kept
+            });
+            let mut removed = removed.into_iter();
+            alive.retain(|_| !removed.next().unwrap());
+
+            for offset in 0..original.len() {
+                assert_eq!(
+                    mapper.map_forward(offset),
+                    alive.binary_search(&offset).ok(),
+                    "{}",
+                    offset
+                );
+            }
+            assert_eq!(mapper.map_forward(original.len()), Some(s.len()));
+            for (current, &offset) in alive.iter().enumerate() {
+                assert_eq!(mapper.map_backward(current), offset);
+            }
+            assert_eq!(mapper.map_backward(s.len()), original.len());
+            assert_eq!(mapper.bytes_removed(), original.len() - s.len());
+        }
+        assert_eq!(s, "a\u{1F980}cde");
+    }
+
+    #[test]
+    fn retain_with_offset_map() {
+        // The removed runs are `\u{e9}\u{1F980}` at 1..7, and `-` at 8..9
+        let mut s = "a\u{e9}\u{1F980}b-c".to_string();
+        let map = s.retain_with_offset_map(|c| c.is_ascii_alphabetic());
+        assert_eq!(s, "abc");
+        let ranges: Vec<_> = map.removed_ranges().collect();
+        assert_eq!(ranges, [1..7, 8..9]);
+
+        // Before the first run
+        assert_eq!(map.map_forward(0), Some(0));
+        assert_eq!(map.map_forward_clamped(0), 0);
+        // At the start of a run, and part way into removed multi-byte
+        // characters
+        for offset in 1..7 {
+            assert_eq!(map.map_forward(offset), None);
+            assert_eq!(map.map_forward_clamped(offset), 1);
+        }
+        // Between and after the runs
+        assert_eq!(map.map_forward(7), Some(1));
+        assert_eq!(map.map_forward(8), None);
+        assert_eq!(map.map_forward_clamped(8), 2);
+        assert_eq!(map.map_forward(9), Some(2));
+        assert_eq!(map.map_forward_clamped(10), 3);
+
+        // A run at the end snaps to the end of the string
+        let mut s = "ab\u{2603}".to_string();
+        let map = s.retain_with_offset_map(|c| c.is_ascii());
+        assert_eq!(map.map_forward(3), None);
+        assert_eq!(map.map_forward_clamped(3), s.len());
+    }
+
+    #[test]
+    fn retain_adjust_spans() {
+        let mut s = "fn \u{e9}(x: u8)".to_string();
+        let mut spans = [
+            // `fn`, before any removal
+            0..2,
+            // The whole string
+            0..12,
+            // `\u{e9}(`, which is entirely removed
+            3..6,
+            // `(x`, which is clipped
+            5..7,
+            // `u8)`, after the removals
+            9..12,
+            // An empty span in a removed run
+            4..4,
+        ];
+        s.retain_adjust_spans(|c| c.is_ascii_alphanumeric(), &mut spans);
+        assert_eq!(s, "fnxu8");
+        assert_eq!(spans, [0..2, 0..5, 2..2, 2..3, 3..5, 2..2]);
+
+        // The same spans, in order
+        let mut s = "fn \u{e9}(x: u8)".to_string();
+        let mut spans = [0..2, 3..6, 4..4, 5..7, 9..12];
+        s.retain_adjust_spans(|c| c.is_ascii_alphanumeric(), &mut spans);
+        assert_eq!(spans, [0..2, 2..2, 2..2, 2..3, 3..5]);
+    }
+
+    /// A seeded SplitMix64 generator.
+    fn next(state: &mut u64) -> u64 {
+        *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    #[test]
+    fn retain_adjust_spans_matches_recomputing() {
+        let alphabet = ['a', 'b', '\u{e9}', '\u{2603}', '\u{1F980}', ' '];
+        let mut state = 0;
+        for _ in 0..500 {
+            let len = (next(&mut state) % 12) as usize;
+            let original: String = (0..len)
+                .map(|_| alphabet[(next(&mut state) % 6) as usize])
+                .collect();
+            let removed_chars = next(&mut state);
+            let keep = |c: char| removed_chars & (1 << (c as u32 % 64)) == 0;
+
+            // Spans between arbitrary character boundaries, in any order
+            let boundaries: Vec<usize> = original
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain([original.len()])
+                .collect();
+            let mut spans: Vec<Range<usize>> = (0..4)
+                .map(|_| {
+                    let mut bound = || boundaries[next(&mut state) as usize % boundaries.len()];
+                    let (a, b) = (bound(), bound());
+                    a.min(b)..a.max(b)
+                })
+                .collect();
+            // Half of the time, with the starts and the ends each in order
+            if next(&mut state) & 1 == 0 {
+                let mut starts: Vec<usize> = spans.iter().map(|span| span.start).collect();
+                let mut ends: Vec<usize> = spans.iter().map(|span| span.end).collect();
+                starts.sort_unstable();
+                ends.sort_unstable();
+                spans = starts
+                    .into_iter()
+                    .zip(ends)
+                    .map(|(start, end)| start..end)
+                    .collect();
+            }
+
+            // The offset of each original boundary in the rebuilt string
+            let mut kept_before = Vec::new();
+            let mut rebuilt = String::new();
+            for c in original.chars() {
+                kept_before.push(rebuilt.len());
+                if keep(c) {
+                    rebuilt.push(c);
+                }
+            }
+            kept_before.push(rebuilt.len());
+            let recompute = |offset: usize| kept_before[boundaries.binary_search(&offset).unwrap()];
+            let expected: Vec<_> = spans
+                .iter()
+                .map(|span| recompute(span.start)..recompute(span.end))
+                .collect();
+
+            let mut s = original.clone();
+            s.retain_adjust_spans(keep, &mut spans);
+            assert_eq!(s, rebuilt);
+            assert_eq!(spans, expected, "{:?}", original);
+        }
+    }
+
+    #[test]
+    fn compose() {
+        let mut s = "0123456789".to_string();
+        let mut first = OffsetMapper::new();
+        s.retain_all_mapped(&mut first, |_, c, _| !matches!(c, '2' | '3' | '7'));
+        let mut second = OffsetMapper::new();
+        s.retain_all_mapped(&mut second, |_, c, _| !matches!(c, '1' | '4' | '6'));
+        assert_eq!(s, "0589");
+        let composed = first.compose(&second);
+        // Removing `4` and `6` joins the runs around them
+        let ranges: Vec<_> = composed.removed_ranges().collect();
+        assert_eq!(ranges, [1..5, 6..8]);
+        assert_eq!(composed.map_forward(5), Some(1));
+        assert_eq!(composed.map_forward(6), None);
+        assert_eq!(composed.map_backward(2), 8);
+
+        // Composing with the identity changes nothing
+        assert_eq!(first.compose(&OffsetMapper::new()), first);
+        assert_eq!(OffsetMapper::new().compose(&first), first);
+    }
+}
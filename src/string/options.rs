@@ -0,0 +1,270 @@
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use alloc::string::String;
+
+use super::{cursor::Cursor, observer::RemovalObserver, report::RetainReport};
+
+/// What to do with the spare capacity of a string after a retain pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ShrinkPolicy {
+    /// Leave the capacity unchanged. This never reallocates.
+    #[default]
+    Never,
+    /// Call [`String::shrink_to_fit`] if anything was removed.
+    ToFit,
+}
+
+/// Configuration for
+/// [`retain_with_options`](crate::RetainMoreString::retain_with_options).
+///
+/// Every knob is independent, and the default value of each leaves the
+/// behaviour of the retain unchanged. The builder methods are `const`, so a
+/// configuration can be stored in a `const`:
+///
+/// ```
+/// use retain_more::{RetainMoreString as _, RetainOptions, ShrinkPolicy};
+/// const OPTS: RetainOptions<'static> = RetainOptions::new()
+///     .max_removals(2)
+///     .shrink(ShrinkPolicy::ToFit);
+///
+/// let mut s = "a b c d".to_string();
+/// let report = s.retain_with_options(OPTS, |c| c != ' ');
+/// assert_eq!(s, "abc d");
+/// assert!(report.budget_exhausted);
+/// ```
+pub struct RetainOptions<'a> {
+    protected: &'a [Range<usize>],
+    max_removals: Option<usize>,
+    observer: Option<&'a mut dyn RemovalObserver>,
+    shrink: ShrinkPolicy,
+    cancel: Option<&'a AtomicBool>,
+}
+
+impl<'a> RetainOptions<'a> {
+    /// Options which leave the retain unchanged.
+    pub const fn new() -> Self {
+        RetainOptions {
+            protected: &[],
+            max_removals: None,
+            observer: None,
+            shrink: ShrinkPolicy::Never,
+            cancel: None,
+        }
+    }
+
+    /// Unconditionally keep the characters which start within any of
+    /// `ranges`, without calling the predicate for them.
+    ///
+    /// The ranges are byte offsets into the original string. They must be
+    /// sorted by their start and must not overlap; characters in ranges
+    /// which break this rule may not be protected.
+    pub const fn protected(self, ranges: &'a [Range<usize>]) -> Self {
+        RetainOptions {
+            protected: ranges,
+            ..self
+        }
+    }
+
+    /// Remove at most `max` characters. Once that many have been removed, the
+    /// rest of the string is kept without calling the predicate.
+    pub const fn max_removals(self, max: usize) -> Self {
+        RetainOptions {
+            max_removals: Some(max),
+            ..self
+        }
+    }
+
+    /// Notify `observer` of every removed character, with its original byte
+    /// offset, immediately after it is removed.
+    ///
+    /// Any `FnMut(usize, char)` is a [`RemovalObserver`], so a closure can be
+    /// used directly.
+    pub const fn observer(self, observer: &'a mut dyn RemovalObserver) -> Self {
+        RetainOptions {
+            observer: Some(observer),
+            ..self
+        }
+    }
+
+    /// Choose what happens to the spare capacity after the pass.
+    pub const fn shrink(self, policy: ShrinkPolicy) -> Self {
+        RetainOptions {
+            shrink: policy,
+            ..self
+        }
+    }
+
+    /// Stop the pass once `token` is set, keeping the characters which have
+    /// not yet been considered. The token is checked before every character.
+    pub const fn cancel_token(self, token: &'a AtomicBool) -> Self {
+        RetainOptions {
+            cancel: Some(token),
+            ..self
+        }
+    }
+}
+
+impl Default for RetainOptions<'_> {
+    fn default() -> Self {
+        RetainOptions::new()
+    }
+}
+
+impl core::fmt::Debug for RetainOptions<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RetainOptions")
+            .field("protected", &self.protected)
+            .field("max_removals", &self.max_removals)
+            .field("observer", &self.observer.as_ref().map(|_| ".."))
+            .field("shrink", &self.shrink)
+            .field("cancel", &self.cancel)
+            .finish()
+    }
+}
+
+pub(super) fn retain_with_options<F: FnMut(char) -> bool>(
+    s: &mut String,
+    mut opts: RetainOptions<'_>,
+    mut f: F,
+) -> RetainReport {
+    // The string is only reallocated by the shrink at the end
+    let (ptr, capacity) = (s.as_ptr(), s.capacity());
+    let mut report = RetainReport::default();
+    let mut protected = opts.protected.iter().peekable();
+    {
+        let mut cursor = Cursor::new(s);
+        while let Some(ch) = cursor.peek() {
+            if opts.cancel.is_some_and(|c| c.load(Ordering::Relaxed)) {
+                report.cancelled = true;
+                break;
+            }
+            if opts.max_removals == Some(report.chars_removed) {
+                report.budget_exhausted = true;
+                break;
+            }
+            let offset = cursor.offset();
+            while protected.peek().is_some_and(|r| r.end <= offset) {
+                protected.next();
+            }
+            let is_protected = protected.peek().is_some_and(|r| r.contains(&offset));
+            let ch_len = ch.len_utf8();
+            if is_protected || f(ch) {
+                cursor.keep(ch_len);
+                report.record_kept(ch);
+            } else {
+                cursor.remove(ch_len);
+                report.record_removed(ch);
+                if let Some(observer) = opts.observer.as_deref_mut() {
+                    observer.removed(offset, ch);
+                }
+            }
+        }
+        report.record_kept_str(cursor.rest());
+        cursor.keep_rest();
+    }
+    debug_assert!(
+        s.as_ptr() == ptr && s.capacity() == capacity,
+        "the allocation of the string was changed"
+    );
+    if opts.shrink == ShrinkPolicy::ToFit && report.changed() {
+        s.shrink_to_fit();
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetainMoreString;
+    use alloc::{string::ToString, vec, vec::Vec};
+
+    #[test]
+    fn default_is_plain_retain() {
+        let mut s = "a1b2c3".to_string();
+        let report = s.retain_with_options(RetainOptions::new(), |c| c.is_alphabetic());
+        assert_eq!(s, "abc");
+        assert_eq!(
+            report,
+            RetainReport {
+                chars_removed: 3,
+                bytes_removed: 3,
+                chars_kept: 3,
+                bytes_kept: 3,
+                cancelled: false,
+                budget_exhausted: false,
+            }
+        );
+    }
+
+    #[test]
+    fn protected_and_budget() {
+        // Protected digits don't count towards the budget
+        let protected = [0..2, 6..8];
+        let opts = || RetainOptions::new().protected(&protected).max_removals(2);
+        let mut s = "12a34b56c78".to_string();
+        let report = s.retain_with_options(opts(), |c| !c.is_ascii_digit());
+        assert_eq!(s, "12ab56c78");
+        assert_eq!(report.chars_removed, 2);
+        assert_eq!(report.chars_kept, 9);
+        assert!(report.budget_exhausted);
+
+        // The budget is not exhausted if the string ends at the same time
+        let mut s = "12a3".to_string();
+        let report = s.retain_with_options(opts(), |c| !c.is_ascii_digit());
+        assert_eq!(s, "12a");
+        assert!(!report.budget_exhausted);
+    }
+
+    #[test]
+    fn protected_multibyte() {
+        // A protected range starting mid-character doesn't protect that
+        // character
+        let protected = 1..4;
+        let opts = RetainOptions::new().protected(core::slice::from_ref(&protected));
+        let mut s = "ééé".to_string();
+        s.retain_with_options(opts, |_| false);
+        assert_eq!(s, "é");
+    }
+
+    #[test]
+    fn observer_and_budget() {
+        let mut seen = Vec::new();
+        let mut observer = |offset, c| seen.push((offset, c));
+        let opts = RetainOptions::new().observer(&mut observer).max_removals(2);
+        let mut s = "x€y€z€".to_string();
+        s.retain_with_options(opts, |c| c.is_ascii());
+        assert_eq!(s, "xyz€");
+        assert_eq!(seen, vec![(1, '€'), (5, '€')]);
+    }
+
+    #[test]
+    fn cancellation() {
+        let token = AtomicBool::new(false);
+        let opts = RetainOptions::new().cancel_token(&token);
+        let mut s = "a-b-c-d".to_string();
+        let report = s.retain_with_options(opts, |c| {
+            if c == 'c' {
+                token.store(true, Ordering::Relaxed);
+            }
+            c != '-'
+        });
+        assert_eq!(s, "abc-d");
+        assert!(report.cancelled);
+        assert_eq!((report.chars_removed, report.chars_kept), (2, 5));
+    }
+
+    #[test]
+    fn shrink() {
+        let mut s = String::with_capacity(64);
+        s.push_str("aaaa");
+        const OPTS: RetainOptions<'static> = RetainOptions::new().shrink(ShrinkPolicy::ToFit);
+        s.retain_with_options(OPTS, |_| true);
+        assert_eq!(s.capacity(), 64);
+        s.retain_with_options(OPTS, |_| false);
+        assert!(s.capacity() < 64);
+        assert_eq!(s, "");
+    }
+}
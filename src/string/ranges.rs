@@ -0,0 +1,124 @@
+use core::{fmt, ops::Range};
+
+use alloc::{string::String, vec::Vec};
+
+use super::cursor::remove_ranges;
+
+/// The error returned by
+/// [`remove_byte_ranges`](crate::RetainMoreString::remove_byte_ranges) when
+/// one of the ranges is invalid for the string.
+///
+/// Each variant holds the first offending range.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RangeError {
+    /// The range starts after it ends.
+    Reversed { range: Range<usize> },
+    /// The range ends past the end of the string.
+    OutOfBounds { range: Range<usize> },
+    /// The range does not start and end on character boundaries of the
+    /// string.
+    NotCharBoundary { range: Range<usize> },
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeError::Reversed { range } => {
+                write!(f, "range {:?} starts after it ends", range)
+            }
+            RangeError::OutOfBounds { range } => {
+                write!(f, "range {:?} extends past the end of the string", range)
+            }
+            RangeError::NotCharBoundary { range } => write!(
+                f,
+                "range {:?} does not lie on character boundaries of the string",
+                range
+            ),
+        }
+    }
+}
+
+pub(super) fn remove_byte_ranges(
+    s: &mut String,
+    ranges: &[Range<usize>],
+) -> Result<usize, RangeError> {
+    for range in ranges {
+        let range = range.clone();
+        if range.start > range.end {
+            return Err(RangeError::Reversed { range });
+        }
+        if range.end > s.len() {
+            return Err(RangeError::OutOfBounds { range });
+        }
+        if !s.is_char_boundary(range.start) || !s.is_char_boundary(range.end) {
+            return Err(RangeError::NotCharBoundary { range });
+        }
+    }
+    let mut sorted: Vec<Range<usize>> = ranges.iter().filter(|r| !r.is_empty()).cloned().collect();
+    sorted.sort_unstable_by_key(|range| range.start);
+    // Merge overlapping and touching ranges
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(sorted.len());
+    for range in sorted {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    let removed = merged.iter().map(|range| range.len()).sum();
+    remove_ranges(s, merged);
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RangeError;
+    use crate::RetainMoreString;
+    use alloc::string::ToString;
+
+    #[test]
+    fn unsorted_overlapping_and_touching() {
+        let mut s = "0123456789\u{e9}xyz".to_string();
+        let removed = s
+            .remove_byte_ranges(&[
+                // Touches 3..5
+                5..6,
+                8..12,
+                // Overlaps 3..5, and is empty
+                4..4,
+                3..5,
+                // Within 8..12
+                9..10,
+                1..2,
+            ])
+            .unwrap();
+        assert_eq!(s, "0267xyz");
+        assert_eq!(removed, 1 + 3 + 4);
+
+        let mut s = "abc".to_string();
+        assert_eq!(s.remove_byte_ranges(&[]), Ok(0));
+        assert_eq!(s.remove_byte_ranges(&[0..3, 0..1]), Ok(3));
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn invalid_ranges() {
+        let mut s = "a\u{e9}b".to_string();
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 2..1;
+        let cases = [
+            (reversed.clone(), RangeError::Reversed { range: reversed }),
+            (1..5, RangeError::OutOfBounds { range: 1..5 }),
+            (0..2, RangeError::NotCharBoundary { range: 0..2 }),
+            (2..3, RangeError::NotCharBoundary { range: 2..3 }),
+        ];
+        for (range, expected) in cases {
+            assert_eq!(s.remove_byte_ranges(&[0..1, range]), Err(expected));
+            // Nothing is removed if any range is invalid
+            assert_eq!(s, "a\u{e9}b");
+        }
+        assert_eq!(
+            RangeError::NotCharBoundary { range: 0..2 }.to_string(),
+            "range 0..2 does not lie on character boundaries of the string"
+        );
+    }
+}
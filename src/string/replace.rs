@@ -0,0 +1,126 @@
+use alloc::{collections::TryReserveError, string::String};
+
+use super::cursor::Cursor;
+
+/// What [`retain_replace`](crate::RetainMoreString::retain_replace) does with
+/// a character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RetainDecision<'a> {
+    /// Keep the character in the string.
+    Keep,
+    /// Remove the character.
+    Remove,
+    /// Remove the character, and insert this string in its place.
+    Replace(&'a str),
+}
+
+/// Retain and replace the characters of `s` in place, growing the string
+/// when a replacement doesn't fit in the gap, as in `map::retain_map`.
+///
+/// If growing fails, the current character and the rest of the string are
+/// kept.
+pub(super) fn retain_replace<'r, F: FnMut(char, &mut str) -> RetainDecision<'r>>(
+    s: &mut String,
+    mut f: F,
+) -> Result<(), TryReserveError> {
+    let mut cursor = Cursor::new(s);
+    while let Some(ch) = cursor.peek() {
+        let ch_len = ch.len_utf8();
+        match f(ch, cursor.after(ch_len)) {
+            RetainDecision::Keep => cursor.keep(ch_len),
+            RetainDecision::Remove => cursor.remove(ch_len),
+            RetainDecision::Replace(replacement) => {
+                if !cursor.replace(ch_len, replacement) {
+                    if let Err(err) = cursor.try_grow(replacement.len() - ch_len) {
+                        cursor.keep_rest();
+                        return Err(err);
+                    }
+                    let replaced = cursor.replace(ch_len, replacement);
+                    debug_assert!(replaced);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetainDecision;
+    use crate::RetainMoreString;
+    use alloc::{string::ToString, vec::Vec};
+
+    fn escape(c: char, _: &mut str) -> RetainDecision<'static> {
+        match c {
+            '<' => RetainDecision::Replace("&lt;"),
+            '>' => RetainDecision::Replace("&gt;"),
+            c if c.is_control() => RetainDecision::Remove,
+            _ => RetainDecision::Keep,
+        }
+    }
+
+    #[test]
+    fn escaping() {
+        let mut s = "<b>bold\u{7}</b>".to_string();
+        s.retain_replace(escape).unwrap();
+        assert_eq!(s, "&lt;b&gt;bold&lt;/b&gt;");
+
+        // Removals leave room for the replacements to be written in place
+        let mut s = "\0\0\0<\u{1F980}".to_string();
+        let ptr = s.as_ptr();
+        s.retain_replace(escape).unwrap();
+        assert_eq!(s, "&lt;\u{1F980}");
+        assert_eq!(s.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn shorter_replacements() {
+        let mut s = "a&amp;b&lt;".to_string();
+        let mut in_entity = false;
+        s.retain_replace(|c, rest| {
+            if in_entity {
+                in_entity = c != ';';
+                return RetainDecision::Remove;
+            }
+            if c != '&' {
+                return RetainDecision::Keep;
+            }
+            in_entity = true;
+            if rest.starts_with("amp;") {
+                RetainDecision::Replace("&")
+            } else {
+                RetainDecision::Replace("<")
+            }
+        })
+        .unwrap();
+        assert_eq!(s, "a&b<");
+
+        // An empty replacement is a removal
+        let mut s = "a\u{e9}b".to_string();
+        s.retain_replace(|c, _| {
+            if c == '\u{e9}' {
+                RetainDecision::Replace("")
+            } else {
+                RetainDecision::Keep
+            }
+        })
+        .unwrap();
+        assert_eq!(s, "ab");
+    }
+
+    #[test]
+    fn replacements_are_not_rescanned() {
+        let mut s = "a-b-".to_string();
+        let mut seen = Vec::new();
+        s.retain_replace(|c, _| {
+            seen.push(c);
+            match c {
+                '-' => RetainDecision::Replace("--"),
+                _ => RetainDecision::Keep,
+            }
+        })
+        .unwrap();
+        assert_eq!(s, "a--b--");
+        assert_eq!(seen, ['a', '-', 'b', '-']);
+    }
+}
@@ -0,0 +1,54 @@
+/// Statistics about a single retain pass over a string.
+///
+/// Returned by [`retain_with_options`](crate::RetainMoreString::retain_with_options)
+/// and [`retain_report`](crate::RetainMoreString::retain_report).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RetainReport {
+    /// The number of characters which were removed.
+    pub chars_removed: usize,
+    /// The number of bytes which were removed.
+    pub bytes_removed: usize,
+    /// The number of characters which were kept.
+    pub chars_kept: usize,
+    /// The number of bytes which were kept.
+    pub bytes_kept: usize,
+    /// Whether the pass stopped early because it was cancelled. The
+    /// characters which had not been considered were kept.
+    pub cancelled: bool,
+    /// Whether the pass stopped early because the removal budget was used up.
+    /// The characters which had not been considered were kept.
+    pub budget_exhausted: bool,
+}
+
+impl RetainReport {
+    /// Whether any characters were removed.
+    pub fn changed(&self) -> bool {
+        self.chars_removed > 0
+    }
+
+    pub(crate) fn record_kept(&mut self, ch: char) {
+        self.chars_kept += 1;
+        self.bytes_kept += ch.len_utf8();
+    }
+
+    pub(crate) fn record_removed(&mut self, ch: char) {
+        self.chars_removed += 1;
+        self.bytes_removed += ch.len_utf8();
+    }
+
+    pub(crate) fn record_kept_str(&mut self, s: &str) {
+        self.chars_kept += s.chars().count();
+        self.bytes_kept += s.len();
+    }
+}
+
+/// The removals made so far by
+/// [`retain_with_streak`](crate::RetainMoreString::retain_with_streak).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RemovalStreak {
+    /// The number of characters removed since the last kept character (or
+    /// since the start of the string).
+    pub consecutive_removed: usize,
+    /// The total number of characters removed so far.
+    pub total_removed: usize,
+}
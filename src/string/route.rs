@@ -0,0 +1,141 @@
+use alloc::string::String;
+
+use super::cursor::Cursor;
+
+/// What [`retain_route`](crate::RetainMoreString::retain_route) does with a
+/// character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Route {
+    /// Keep the character in the string.
+    Keep,
+    /// Remove the character.
+    Drop,
+    /// Remove the character, and append it to the secondary string.
+    Divert,
+}
+
+/// The number of characters sent to each [`Route`] by
+/// [`retain_route`](crate::RetainMoreString::retain_route).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RouteCounts {
+    /// The number of characters which were kept.
+    pub kept: usize,
+    /// The number of characters which were removed.
+    pub dropped: usize,
+    /// The number of characters which were moved to the secondary string.
+    pub diverted: usize,
+}
+
+pub(super) fn retain_route<F: FnMut(char) -> Route>(
+    s: &mut String,
+    diverted: &mut String,
+    mut f: F,
+) -> RouteCounts {
+    let mut counts = RouteCounts::default();
+    let mut cursor = Cursor::new(s);
+    while let Some(ch) = cursor.peek() {
+        match f(ch) {
+            Route::Keep => {
+                cursor.keep(ch.len_utf8());
+                counts.kept += 1;
+            }
+            Route::Drop => {
+                cursor.remove(ch.len_utf8());
+                counts.dropped += 1;
+            }
+            Route::Divert => {
+                // Pushing before the next call of `f` means that a later
+                // panic leaves exactly the diverted characters
+                diverted.push(ch);
+                cursor.remove(ch.len_utf8());
+                counts.diverted += 1;
+            }
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetainMoreString;
+    use alloc::{string::ToString, vec::Vec};
+
+    #[test]
+    fn retain_route() {
+        let mut s = "a\u{e9}1\u{1F980}b\u{2603}2c".to_string();
+        let mut quarantine = "old:".to_string();
+        let counts = s.retain_route(&mut quarantine, |c| match c {
+            c if c.is_ascii_digit() => Route::Drop,
+            c if !c.is_ascii() => Route::Divert,
+            _ => Route::Keep,
+        });
+        assert_eq!(s, "abc");
+        assert_eq!(quarantine, "old:\u{e9}\u{1F980}\u{2603}");
+        assert_eq!(
+            counts,
+            RouteCounts {
+                kept: 3,
+                dropped: 2,
+                diverted: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn retain_route_single() {
+        let route = |input: &str, to: Route| {
+            let mut s = input.to_string();
+            let mut diverted = String::new();
+            s.retain_route(&mut diverted, |_| to);
+            (s, diverted)
+        };
+        let input = "x\u{e9}y";
+        assert_eq!(
+            route(input, Route::Keep),
+            (input.to_string(), String::new())
+        );
+        assert_eq!(route(input, Route::Drop), (String::new(), String::new()));
+        assert_eq!(
+            route(input, Route::Divert),
+            (String::new(), input.to_string())
+        );
+    }
+
+    #[test]
+    fn extract_removed_into() {
+        let input = "a1\u{e9}22\u{1F980}3b";
+        let mut s = input.to_string();
+        let mut sink = String::with_capacity(16);
+        sink.push_str("> ");
+        let ptr = sink.as_ptr();
+        let mut kept = Vec::new();
+        s.extract_removed_into(
+            |c| {
+                kept.push(!c.is_numeric());
+                !c.is_numeric()
+            },
+            &mut sink,
+        );
+        assert_eq!(s, "a\u{e9}\u{1F980}b");
+        assert_eq!(sink, "> 1223");
+        // The reserved capacity was reused
+        assert_eq!(sink.as_ptr(), ptr);
+
+        // Taking characters from each half in order of the original
+        // indices reproduces the input
+        let (mut kept_chars, mut removed_chars) = (s.chars(), sink[2..].chars());
+        let merged: String = kept
+            .iter()
+            .map(|&kept| {
+                if kept {
+                    kept_chars.next()
+                } else {
+                    removed_chars.next()
+                }
+                .unwrap()
+            })
+            .collect();
+        assert_eq!(merged, input);
+    }
+}
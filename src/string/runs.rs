@@ -0,0 +1,104 @@
+//! Extraction of the runs of characters matching a predicate, for
+//! [`RetainMoreString::extract_runs`](crate::RetainMoreString::extract_runs).
+
+use alloc::string::String;
+
+use super::cursor::Cursor;
+
+/// Remove every maximal run of characters specified by `f` from `s`, passing
+/// each run to `on_run` in order.
+///
+/// Each run is read out of the gap left behind by the cursor before anything
+/// after it is kept, so extraction and compaction happen in the same pass.
+pub(super) fn for_each_run<F, R>(s: &mut String, mut f: F, mut on_run: R)
+where
+    F: FnMut(char) -> bool,
+    R: FnMut(&str),
+{
+    let mut cursor = Cursor::new(s);
+    // The length of the run removed since the last kept character
+    let mut run = 0;
+    while let Some(ch) = cursor.peek() {
+        let ch_len = ch.len_utf8();
+        if f(ch) {
+            cursor.remove(ch_len);
+            run += ch_len;
+        } else {
+            if run > 0 {
+                // Keeping the next character would overwrite the run
+                on_run(cursor.removed_run(run));
+                run = 0;
+            }
+            cursor.keep(ch_len);
+        }
+    }
+    if run > 0 {
+        on_run(cursor.removed_run(run));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreString;
+    use alloc::{
+        string::{String, ToString},
+        vec::Vec,
+    };
+
+    fn sorted_chars(s: &str) -> Vec<char> {
+        let mut chars: Vec<char> = s.chars().collect();
+        chars.sort_unstable();
+        chars
+    }
+
+    #[test]
+    fn extract_runs() {
+        let cases: [(&str, &[&str], &str); 6] = [
+            ("a12b345c", &["12", "345"], "abc"),
+            ("12ab", &["12"], "ab"),
+            ("ab12", &["12"], "ab"),
+            ("1\u{e9}2\u{1F980}", &["1", "2"], "\u{e9}\u{1F980}"),
+            ("123", &["123"], ""),
+            ("", &[], ""),
+        ];
+        for (input, runs, rest) in cases {
+            let mut s = input.to_string();
+            assert_eq!(s.extract_runs(|c| c.is_ascii_digit()), runs, "{:?}", input);
+            assert_eq!(s, rest, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn extract_runs_is_permutation() {
+        let samples = [
+            "[a] b [cd]e",
+            "\u{e9}[\u{1F980}]x[]",
+            "no brackets",
+            "[[[",
+            "]]x[[",
+        ];
+        for sample in samples {
+            let is_bracketed = |c: char| c == '[' || c == ']' || c.is_ascii_lowercase();
+            let mut s = sample.to_string();
+            let mut all = s.extract_runs(is_bracketed).concat();
+            all.push_str(&s);
+            assert_eq!(sorted_chars(&all), sorted_chars(sample), "{:?}", sample);
+            assert!(!s.contains(is_bracketed));
+
+            let mut s = sample.to_string();
+            let mut out = String::new();
+            s.extract_runs_into(&mut out, '\n', is_bracketed);
+            let expected = sample.to_string().extract_runs(is_bracketed).join("\n");
+            assert_eq!(out, expected, "{:?}", sample);
+        }
+    }
+
+    #[test]
+    fn extract_runs_into_appends() {
+        let mut s = "x1y22z".to_string();
+        let mut out = "runs:".to_string();
+        s.extract_runs_into(&mut out, ',', |c| c.is_ascii_digit());
+        assert_eq!(out, "runs:1,22");
+        assert_eq!(s, "xyz");
+    }
+}
@@ -0,0 +1,179 @@
+//! Random sampling of characters, using any [`RngCore`] from the
+//! [`rand_core`] crate.
+
+use alloc::string::String;
+use rand_core::RngCore;
+
+use super::cursor::Cursor;
+
+/// A uniformly distributed float in `0.0..1.0`, from the top 53 bits of a
+/// random `u64`.
+fn unit_f64<R: RngCore + ?Sized>(rng: &mut R) -> f64 {
+    (rng.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
+/// A uniformly distributed integer in `0..bound`, using Lemire's widening
+/// multiplication with rejection.
+fn below<R: RngCore + ?Sized>(rng: &mut R, bound: u64) -> u64 {
+    debug_assert!(bound > 0);
+    let threshold = bound.wrapping_neg() % bound;
+    loop {
+        let product = u128::from(rng.next_u64()) * u128::from(bound);
+        if product as u64 >= threshold {
+            return (product >> 64) as u64;
+        }
+    }
+}
+
+pub(super) fn retain_sample<R: RngCore + ?Sized>(
+    s: &mut String,
+    rng: &mut R,
+    keep_probability: f64,
+) {
+    assert!(
+        (0.0..=1.0).contains(&keep_probability),
+        "keep_probability must be between 0 and 1, got {}",
+        keep_probability
+    );
+    let mut cursor = Cursor::new(s);
+    while let Some(ch) = cursor.peek() {
+        if unit_f64(rng) < keep_probability {
+            cursor.keep(ch.len_utf8());
+        } else {
+            cursor.remove(ch.len_utf8());
+        }
+    }
+}
+
+pub(super) fn retain_reservoir<R: RngCore + ?Sized>(s: &mut String, rng: &mut R, k: usize) {
+    let mut remaining = s.chars().count();
+    if k >= remaining {
+        return;
+    }
+    // Selection sampling (Knuth's Algorithm S): since the number of
+    // characters is known up front, each character can be kept with
+    // probability `needed / remaining`, which gives every subset of `k`
+    // characters the same probability, without storing the chosen indices.
+    let mut needed = k;
+    let mut cursor = Cursor::new(s);
+    while let Some(ch) = cursor.peek() {
+        if needed == 0 {
+            break;
+        }
+        if below(rng, remaining as u64) < needed as u64 {
+            cursor.keep(ch.len_utf8());
+            needed -= 1;
+        } else {
+            cursor.remove(ch.len_utf8());
+        }
+        remaining -= 1;
+    }
+    // Dropping the cursor removes everything after the last kept character
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreString;
+    use alloc::{string::ToString, vec};
+    use rand_core::RngCore;
+
+    /// SplitMix64, so the tests have a seeded generator without needing
+    /// another dependency.
+    struct SplitMix64(u64);
+
+    impl RngCore for SplitMix64 {
+        fn next_u32(&mut self) -> u32 {
+            (self.next_u64() >> 32) as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+
+        fn fill_bytes(&mut self, dst: &mut [u8]) {
+            rand_core::impls::fill_bytes_via_next(self, dst)
+        }
+    }
+
+    #[test]
+    fn retain_sample_seeded() {
+        let mut s = "the quick brown \u{e9}\u{1F980} fox".to_string();
+        s.retain_sample(&mut SplitMix64(7), 0.5);
+        assert_eq!(s, "thquick b\u{1F980}x");
+    }
+
+    #[test]
+    fn retain_sample_extremes() {
+        let mut rng = SplitMix64(0);
+        let mut s = "abc\u{e9}".to_string();
+        s.retain_sample(&mut rng, 1.0);
+        assert_eq!(s, "abc\u{e9}");
+        s.retain_sample(&mut rng, 0.0);
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    #[should_panic]
+    fn retain_sample_nan() {
+        "abc"
+            .to_string()
+            .retain_sample(&mut SplitMix64(0), f64::NAN);
+    }
+
+    #[test]
+    fn retain_sample_statistics() {
+        let mut rng = SplitMix64(42);
+        let mut s = "a\u{e9}".repeat(5_000);
+        s.retain_sample(&mut rng, 0.3);
+        // The count is binomial with mean 3000 and standard deviation ~46
+        let kept = s.chars().count();
+        assert!((2770..=3230).contains(&kept), "{}", kept);
+    }
+
+    #[test]
+    fn retain_reservoir_seeded() {
+        let mut s = "the quick brown \u{e9}\u{1F980} fox".to_string();
+        s.retain_reservoir(&mut SplitMix64(7), 6);
+        assert_eq!(s, "hukb\u{1F980}x");
+    }
+
+    #[test]
+    fn retain_reservoir_exact_count() {
+        let mut rng = SplitMix64(1);
+        for k in 0..=12 {
+            let mut s = "0123456789\u{e9}\u{1F980}".to_string();
+            s.retain_reservoir(&mut rng, k);
+            assert_eq!(s.chars().count(), k.min(12));
+            // The kept characters are still in order
+            let mut chars: vec::Vec<char> = s.chars().collect();
+            let unsorted = chars.clone();
+            chars.sort_unstable();
+            assert_eq!(chars, unsorted);
+        }
+    }
+
+    #[test]
+    fn retain_reservoir_uniform() {
+        // Every subset of 2 from 4 characters should be chosen about 1/6 of
+        // the time
+        let mut rng = SplitMix64(3);
+        let mut counts = [0usize; 16];
+        for _ in 0..6_000 {
+            let mut s = "abcd".to_string();
+            s.retain_reservoir(&mut rng, 2);
+            let mask = s.bytes().fold(0, |mask, b| mask | 1 << (b - b'a'));
+            counts[mask] += 1;
+        }
+        for (mask, &count) in counts.iter().enumerate() {
+            if mask.count_ones() == 2 {
+                assert!((850..=1150).contains(&count), "{:04b}: {}", mask, count);
+            } else {
+                assert_eq!(count, 0);
+            }
+        }
+    }
+}
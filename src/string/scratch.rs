@@ -0,0 +1,145 @@
+//! Reusable temporary storage, for the `_with_scratch` methods of
+//! [`RetainMoreString`](crate::RetainMoreString).
+
+use core::{mem::size_of, ops::Range};
+
+use alloc::vec::Vec;
+
+/// Reusable temporary storage for the methods of
+/// [`RetainMoreString`](crate::RetainMoreString) which need it.
+///
+/// Each method with a `_with_scratch` variant, such as
+/// [`retain_sparse_with_scratch`](crate::RetainMoreString::retain_sparse_with_scratch),
+/// borrows the scratch for the duration of the call, clearing it first. The
+/// buffers keep their capacity between calls, so once a scratch has grown
+/// to fit the inputs it is used with, later calls don't allocate.
+///
+/// # Usage
+///
+/// ```
+/// use retain_more::{RetainMoreString as _, RetainScratch};
+/// let mut scratch = RetainScratch::new();
+/// let mut lines = ["a (b".to_string(), "c) d".to_string(), "(e)".to_string()];
+/// for line in &mut lines {
+///     line.repair_balanced_with_scratch(&[('(', ')')], &mut scratch);
+/// }
+/// assert_eq!(lines, ["a b", "c d", "(e)"]);
+/// scratch.shrink_to_fit();
+/// assert_eq!(scratch.footprint(), 0);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RetainScratch {
+    /// One bit per byte of the string.
+    pub(super) bitmap: Vec<u64>,
+    /// Characters gathered before being written to the string.
+    pub(super) staging: Vec<u8>,
+    /// Byte ranges of the string.
+    pub(super) runs: Vec<Range<usize>>,
+}
+
+impl RetainScratch {
+    /// An empty scratch, which has not allocated.
+    pub fn new() -> Self {
+        RetainScratch::default()
+    }
+
+    /// Empty the buffers, keeping their capacity for reuse.
+    pub fn clear(&mut self) {
+        self.bitmap.clear();
+        self.staging.clear();
+        self.runs.clear();
+    }
+
+    /// Clear the scratch, and release all of its memory.
+    pub fn shrink_to_fit(&mut self) {
+        self.clear();
+        self.bitmap.shrink_to_fit();
+        self.staging.shrink_to_fit();
+        self.runs.shrink_to_fit();
+    }
+
+    /// The number of bytes of memory owned by the scratch's buffers.
+    pub fn footprint(&self) -> usize {
+        self.bitmap.capacity() * size_of::<u64>()
+            + self.staging.capacity()
+            + self.runs.capacity() * size_of::<Range<usize>>()
+    }
+
+    /// Set the bits for the bytes in `range`, growing the bitmap as needed.
+    pub(super) fn mark(&mut self, range: Range<usize>) {
+        let words = range.end.div_ceil(64);
+        if self.bitmap.len() < words {
+            self.bitmap.resize(words, 0);
+        }
+        for byte in range {
+            self.bitmap[byte / 64] |= 1 << (byte % 64);
+        }
+    }
+
+    /// Replace `runs` with the ranges of consecutive set bits in the bitmap,
+    /// in ascending order.
+    pub(super) fn marked_runs(&mut self) {
+        self.runs.clear();
+        let mut start = None;
+        for (index, &word) in self.bitmap.iter().enumerate() {
+            // Skip words which can't start or end a run
+            if (word == 0 && start.is_none()) || (word == u64::MAX && start.is_some()) {
+                continue;
+            }
+            for bit in 0..64 {
+                let byte = index * 64 + bit;
+                match (word & (1 << bit) != 0, start) {
+                    (true, None) => start = Some(byte),
+                    (false, Some(from)) => {
+                        self.runs.push(from..byte);
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if let Some(from) = start {
+            self.runs.push(from..self.bitmap.len() * 64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetainScratch;
+
+    #[test]
+    fn marked_runs() {
+        let mut scratch = RetainScratch::new();
+        scratch.mark(1..3);
+        scratch.mark(3..4);
+        scratch.mark(60..130);
+        scratch.mark(200..201);
+        scratch.marked_runs();
+        assert_eq!(scratch.runs, [1..4, 60..130, 200..201]);
+
+        // A run up to the end of the bitmap
+        scratch.clear();
+        scratch.mark(10..64);
+        scratch.marked_runs();
+        assert_eq!(scratch.runs.len(), 1);
+        assert_eq!(scratch.runs[0], 10..64);
+
+        scratch.clear();
+        scratch.marked_runs();
+        assert!(scratch.runs.is_empty());
+    }
+
+    #[test]
+    fn footprint() {
+        let mut scratch = RetainScratch::new();
+        assert_eq!(scratch.footprint(), 0);
+        scratch.mark(0..65);
+        scratch.staging.push(0);
+        assert!(scratch.footprint() > 2 * 8);
+        scratch.clear();
+        assert!(scratch.footprint() > 2 * 8);
+        scratch.shrink_to_fit();
+        assert_eq!(scratch.footprint(), 0);
+    }
+}
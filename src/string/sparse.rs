@@ -0,0 +1,98 @@
+use core::str::from_utf8_unchecked;
+
+use alloc::string::String;
+
+use super::{cursor::Cursor, scratch::RetainScratch};
+
+pub(super) fn retain_sparse<F: FnMut(char) -> bool>(
+    s: &mut String,
+    expected_kept: usize,
+    scratch: &mut RetainScratch,
+    mut f: F,
+) {
+    scratch.clear();
+    // Gather the kept characters, without writing to `s` at all
+    let staging = &mut scratch.staging;
+    staging.reserve(expected_kept.min(s.len()));
+    let mut kept = 0;
+    let mut fallback = None;
+    for (offset, ch) in s.char_indices() {
+        if !f(ch) {
+            continue;
+        }
+        if kept == expected_kept {
+            fallback = Some(offset);
+            break;
+        }
+        staging.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+        kept += 1;
+    }
+    // SAFETY: Only whole characters are pushed to `staging`
+    let staged = unsafe { from_utf8_unchecked(staging) };
+    let offset = match fallback {
+        Some(offset) => offset,
+        None => {
+            // The kept characters are written to the front of `s` in one go
+            s.clear();
+            s.push_str(staged);
+            return;
+        }
+    };
+    // The hint was too small. The character at `offset` was already accepted
+    // by the predicate, so it is kept without calling it again, and the rest
+    // is compacted as usual
+    let ch_len = s[offset..].chars().next().map_or(0, char::len_utf8);
+    s.replace_range(..offset, staged);
+    let mut cursor = Cursor::new(s);
+    cursor.keep(staged.len() + ch_len);
+    while let Some(ch) = cursor.peek() {
+        if f(ch) {
+            cursor.keep(ch.len_utf8());
+        } else {
+            cursor.remove(ch.len_utf8());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreString;
+    use alloc::{string::ToString, vec::Vec};
+
+    #[test]
+    fn retain_sparse_matches_retain() {
+        let samples = [
+            "",
+            "no digits here",
+            "a1b2c3",
+            "\u{e9}1\u{1F980}23\u{2603}4",
+            "0123456789",
+        ];
+        for sample in samples {
+            let mut expected = sample.to_string();
+            expected.retain(|c| c.is_ascii_digit());
+            for hint in [0, 1, 2, 3, 10, 1000, usize::MAX] {
+                let mut s = sample.to_string();
+                let mut visited = Vec::new();
+                s.retain_sparse(hint, |c| {
+                    visited.push(c);
+                    c.is_ascii_digit()
+                });
+                assert_eq!(s, expected, "{:?} with hint {}", sample, hint);
+                // Each character is visited exactly once, in order
+                assert_eq!(visited, sample.chars().collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[test]
+    fn retain_sparse_keeps_allocation() {
+        let mut s = "x".repeat(1000);
+        s.push_str("42");
+        let ptr = s.as_ptr();
+        s.retain_sparse(2, |c| c.is_ascii_digit());
+        assert_eq!(s, "42");
+        assert_eq!(s.as_ptr(), ptr);
+        assert!(s.capacity() >= 1002);
+    }
+}
@@ -0,0 +1,89 @@
+use alloc::string::String;
+
+use super::cursor::Cursor;
+
+/// Append `ch` to the kept region, growing the string if it doesn't fit in
+/// the gap.
+fn insert(cursor: &mut Cursor<'_>, ch: char) {
+    let mut buf = [0; 4];
+    let ch = ch.encode_utf8(&mut buf);
+    if !cursor.replace(0, ch) {
+        cursor.grow(ch.len());
+        let inserted = cursor.replace(0, ch);
+        debug_assert!(inserted);
+    }
+}
+
+/// Retain the characters of `s`, inserting `replacement` in place of each
+/// run of removed characters, growing the string as in `map::retain_map`.
+pub(super) fn retain_splice<F, I>(s: &mut String, mut f: F, replacement: I)
+where
+    F: FnMut(char, &mut str) -> bool,
+    I: IntoIterator<Item = char> + Clone,
+{
+    let mut cursor = Cursor::new(s);
+    let mut in_run = false;
+    while let Some(ch) = cursor.peek() {
+        let ch_len = ch.len_utf8();
+        if !f(ch, cursor.after(ch_len)) {
+            cursor.remove(ch_len);
+            in_run = true;
+            continue;
+        }
+        if in_run {
+            for replacement in replacement.clone() {
+                insert(&mut cursor, replacement);
+            }
+            in_run = false;
+        }
+        cursor.keep(ch_len);
+    }
+    if in_run {
+        for replacement in replacement {
+            insert(&mut cursor, replacement);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreString;
+    use alloc::string::ToString;
+
+    #[test]
+    fn runs_at_the_ends() {
+        let mut s = "12ab3c45".to_string();
+        s.retain_splice(|c, _| !c.is_ascii_digit(), "_".chars());
+        assert_eq!(s, "_ab_c_");
+
+        let mut s = "123".to_string();
+        s.retain_splice(|_, _| false, "<>".chars());
+        assert_eq!(s, "<>");
+
+        let mut s = "abc".to_string();
+        s.retain_splice(|_, _| true, "<>".chars());
+        assert_eq!(s, "abc");
+    }
+
+    #[test]
+    fn multi_byte_runs() {
+        // Shorter and longer replacements for a two byte run
+        let mut s = "a\u{e9}b".to_string();
+        s.retain_splice(|c, _| c.is_ascii(), Some('?'));
+        assert_eq!(s, "a?b");
+
+        let mut s = "a\u{e9}b\u{e9}\u{e9}".to_string();
+        s.retain_splice(|c, _| c.is_ascii(), "[\u{2026}]".chars());
+        assert_eq!(s, "a[\u{2026}]b[\u{2026}]");
+
+        // Growing early keeps the later characters in order
+        let mut s = "1abc2de3f".repeat(20);
+        s.retain_splice(|c, _| !c.is_ascii_digit(), "<\u{1F980}>".chars());
+        assert_eq!(s, "<\u{1F980}>abc<\u{1F980}>de<\u{1F980}>f".repeat(20));
+
+        // Removing a run makes no room for a replacement of nothing
+        let mut s = "\u{1F980}x\u{1F980}".to_string();
+        s.retain_splice(|c, _| c.is_ascii(), None);
+        assert_eq!(s, "x");
+    }
+}
@@ -0,0 +1,93 @@
+use core::ops::Range;
+
+use alloc::{string::String, vec::Vec};
+
+/// Whether [`split_when_with`](crate::RetainMoreString::split_when_with)
+/// returns the empty pieces between adjacent delimiters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EmptyPieces {
+    /// Return every piece, including empty ones, as [`str::split`] does.
+    #[default]
+    Keep,
+    /// Leave out the empty pieces.
+    Skip,
+}
+
+pub(super) fn split_when<F: FnMut(char) -> bool>(
+    mut s: String,
+    empty: EmptyPieces,
+    mut f: F,
+) -> Vec<String> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut start = 0;
+    for (offset, ch) in s.char_indices() {
+        if f(ch) {
+            ranges.push(start..offset);
+            start = offset + ch.len_utf8();
+        }
+    }
+    ranges.push(start..s.len());
+    if empty == EmptyPieces::Skip {
+        ranges.retain(|range| !range.is_empty());
+    }
+    let Some(first) = ranges.first().cloned() else {
+        return Vec::new();
+    };
+    let mut pieces = Vec::with_capacity(ranges.len());
+    // A placeholder for the first piece, which reuses the allocation of `s`
+    // once the other pieces have been copied out of it
+    pieces.push(String::new());
+    pieces.extend(
+        ranges[1..]
+            .iter()
+            .map(|range| String::from(&s[range.clone()])),
+    );
+    s.truncate(first.end);
+    s.drain(..first.start);
+    pieces[0] = s;
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetainMoreString;
+    use alloc::{string::ToString, vec::Vec};
+
+    #[test]
+    fn split_when_matches_str_split() {
+        let samples = ["", "a", ",", "a,b", ",a,,b,", "\u{e9},\u{1F980};x", ";;;"];
+        for sample in samples {
+            let is_delimiter = |c: char| c == ',' || c == ';';
+            let expected: Vec<&str> = sample.split(is_delimiter).collect();
+            let pieces = sample.to_string().split_when(is_delimiter);
+            assert_eq!(pieces, expected, "{:?}", sample);
+
+            let expected: Vec<&str> = expected.into_iter().filter(|it| !it.is_empty()).collect();
+            let pieces = sample
+                .to_string()
+                .split_when_with(EmptyPieces::Skip, is_delimiter);
+            assert_eq!(pieces, expected, "{:?}", sample);
+        }
+    }
+
+    #[test]
+    fn split_when_reuses_allocation() {
+        let mut s = String::with_capacity(64);
+        s.push_str("first second third");
+        let ptr = s.as_ptr();
+        let pieces = s.split_when(|c| c == ' ');
+        assert_eq!(pieces, ["first", "second", "third"]);
+        assert_eq!(pieces[0].as_ptr(), ptr);
+        assert!(pieces[0].capacity() >= 64);
+        assert_eq!(pieces[1].capacity(), "second".len());
+
+        // When skipping a leading empty piece, the first kept piece reuses it
+        let mut s = String::with_capacity(64);
+        s.push_str("  a b");
+        let ptr = s.as_ptr();
+        let pieces = s.split_when_with(EmptyPieces::Skip, |c| c == ' ');
+        assert_eq!(pieces, ["a", "b"]);
+        assert_eq!(pieces[0].as_ptr(), ptr);
+    }
+}
@@ -0,0 +1,305 @@
+use core::fmt;
+
+use alloc::{string::String, vec::Vec};
+
+use super::cursor::{remove_ranges, Cursor};
+
+/// A single predicate call recorded by
+/// [`retain_traced`](crate::RetainMoreString::retain_traced).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TraceEntry {
+    /// The byte offset of the character in the original string.
+    pub offset: usize,
+    /// The character the predicate was called with.
+    pub ch: char,
+    /// Whether the predicate kept the character.
+    pub kept: bool,
+    /// The end of the retained region when the predicate was called, if
+    /// snapshots were enabled.
+    pub before: String,
+    /// The start of the unconsidered region after the character when the
+    /// predicate was called, if snapshots were enabled.
+    pub after: String,
+}
+
+/// A record of every decision made by the predicate in
+/// [`retain_traced`](crate::RetainMoreString::retain_traced).
+///
+/// The [`Debug`] output is a table with a row for each predicate call. The
+/// trace can be [replayed](Self::replay_on) on an identical copy of the
+/// original string, to reproduce the result without calling the predicate.
+#[derive(Clone, PartialEq, Eq, Hash, Default)]
+pub struct RetainTrace {
+    original_len: usize,
+    snapshot_len: usize,
+    entries: Vec<TraceEntry>,
+}
+
+impl RetainTrace {
+    /// The length in bytes of the string the trace was recorded on.
+    pub fn original_len(&self) -> usize {
+        self.original_len
+    }
+
+    /// The maximum length in bytes of each snapshot.
+    pub fn snapshot_len(&self) -> usize {
+        self.snapshot_len
+    }
+
+    /// The recorded predicate calls, in order.
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+
+    /// Apply the recorded decisions to `s`, which must be identical to the
+    /// string the trace was recorded on.
+    ///
+    /// Every character of `s` is checked against the trace before anything
+    /// is removed, so `s` is left untouched if an error is returned.
+    pub fn replay_on(&self, s: &mut String) -> Result<(), DivergenceError> {
+        let mut entries = self.entries.iter();
+        for (offset, found) in s.char_indices() {
+            match entries.next() {
+                Some(entry) if entry.offset == offset && entry.ch == found => {}
+                entry => {
+                    return Err(DivergenceError {
+                        offset,
+                        expected: entry.map(|entry| entry.ch),
+                        found: Some(found),
+                    })
+                }
+            }
+        }
+        if let Some(entry) = entries.next() {
+            return Err(DivergenceError {
+                offset: s.len(),
+                expected: Some(entry.ch),
+                found: None,
+            });
+        }
+        remove_ranges(
+            s,
+            self.entries
+                .iter()
+                .filter(|entry| !entry.kept)
+                .map(|entry| entry.offset..entry.offset + entry.ch.len_utf8()),
+        );
+        Ok(())
+    }
+}
+
+impl fmt::Debug for RetainTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "RetainTrace ({} calls over {} bytes)",
+            self.entries.len(),
+            self.original_len
+        )?;
+        write!(f, "{:>8}  {:<12}  {:<7}", "offset", "char", "kept")?;
+        if self.snapshot_len > 0 {
+            write!(f, "  before | after")?;
+        }
+        for entry in &self.entries {
+            writeln!(f)?;
+            // Pad the `Debug` form of the character, which is not padded
+            // by the width
+            let ch = alloc::format!("{:?}", entry.ch);
+            let kept = if entry.kept { "kept" } else { "removed" };
+            write!(f, "{:>8}  {:<12}  {:<7}", entry.offset, ch, kept)?;
+            if self.snapshot_len > 0 {
+                write!(f, "  {:?} | {:?}", entry.before, entry.after)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The error returned by [`RetainTrace::replay_on`] when the target string
+/// differs from the string the trace was recorded on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DivergenceError {
+    /// The byte offset in the target string of the first difference.
+    pub offset: usize,
+    /// The character recorded in the trace at that point, or `None` if the
+    /// target string is longer than the original.
+    pub expected: Option<char>,
+    /// The character in the target string at that point, or `None` if the
+    /// target string is shorter than the original.
+    pub found: Option<char>,
+}
+
+impl fmt::Display for DivergenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the target diverges from the trace at byte {}: ",
+            self.offset
+        )?;
+        match (self.expected, self.found) {
+            (Some(expected), Some(found)) => {
+                write!(f, "expected {:?}, found {:?}", expected, found)
+            }
+            (Some(expected), None) => write!(f, "expected {:?}, found the end", expected),
+            (None, Some(found)) => write!(f, "expected the end, found {:?}", found),
+            (None, None) => write!(f, "no difference"),
+        }
+    }
+}
+
+/// The longest prefix of `s` which is at most `len` bytes, ending on a
+/// character boundary.
+fn head(s: &str, mut len: usize) -> &str {
+    if len >= s.len() {
+        return s;
+    }
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+    &s[..len]
+}
+
+/// The longest suffix of `s` which is at most `len` bytes, starting on a
+/// character boundary.
+fn tail(s: &str, len: usize) -> &str {
+    let Some(mut start) = s.len().checked_sub(len) else {
+        return s;
+    };
+    while !s.is_char_boundary(start) {
+        start += 1;
+    }
+    &s[start..]
+}
+
+pub(super) fn retain_traced<F: FnMut(&mut str, char, &mut str) -> bool>(
+    s: &mut String,
+    snapshot_len: usize,
+    mut f: F,
+) -> RetainTrace {
+    let mut entries = Vec::new();
+    let mut cursor = Cursor::new(s);
+    let original_len = cursor.original_len();
+    while let Some(ch) = cursor.peek() {
+        let ch_len = ch.len_utf8();
+        let offset = cursor.offset();
+        let (before, after) = cursor.split(ch_len);
+        let kept = f(before, ch, after);
+        entries.push(TraceEntry {
+            offset,
+            ch,
+            kept,
+            // The snapshots are taken after the call, since the predicate
+            // can modify both regions
+            before: String::from(tail(before, snapshot_len)),
+            after: String::from(head(after, snapshot_len)),
+        });
+        if kept {
+            cursor.keep(ch_len);
+        } else {
+            cursor.remove(ch_len);
+        }
+    }
+    RetainTrace {
+        original_len,
+        snapshot_len,
+        entries,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetainMoreString;
+    use alloc::{format, string::ToString};
+
+    /// A stateful predicate, which removes every third character it sees.
+    fn every_third() -> impl FnMut(&mut str, char, &mut str) -> bool {
+        let mut count = 0;
+        move |_, _, _| {
+            count += 1;
+            count % 3 != 0
+        }
+    }
+
+    #[test]
+    fn replay() {
+        let original = "ab\u{e9}cd\u{1F980}ef".to_string();
+        let mut s = original.clone();
+        let trace = s.retain_traced(every_third());
+        assert_eq!(s, "abcdef");
+        assert_eq!(trace.entries().len(), 8);
+        assert_eq!(trace.original_len(), original.len());
+
+        let mut replica = original.clone();
+        trace.replay_on(&mut replica).unwrap();
+        assert_eq!(replica, s);
+    }
+
+    #[test]
+    fn divergence() {
+        let original = "ab\u{e9}cd";
+        let trace = original.to_string().retain_traced(every_third());
+
+        // A character changed
+        let mut diverged = "ab\u{e8}cd".to_string();
+        let err = trace.replay_on(&mut diverged).unwrap_err();
+        assert_eq!(
+            err,
+            DivergenceError {
+                offset: 2,
+                expected: Some('\u{e9}'),
+                found: Some('\u{e8}'),
+            }
+        );
+        // Untouched on error
+        assert_eq!(diverged, "ab\u{e8}cd");
+
+        let mut longer = format!("{}!", original);
+        let err = trace.replay_on(&mut longer).unwrap_err();
+        assert_eq!((err.offset, err.expected, err.found), (6, None, Some('!')));
+
+        let mut shorter = "ab\u{e9}c".to_string();
+        let err = trace.replay_on(&mut shorter).unwrap_err();
+        assert_eq!((err.offset, err.expected, err.found), (5, Some('d'), None));
+        assert_eq!(
+            err.to_string(),
+            "the target diverges from the trace at byte 5: expected 'd', found the end"
+        );
+    }
+
+    #[test]
+    fn snapshots() {
+        let mut s = "ab\u{1F980}cd".to_string();
+        let trace = s.retain_traced_with(3, |_, c, _| c != 'b');
+        let snapshots: Vec<_> = trace
+            .entries()
+            .iter()
+            .map(|entry| (entry.before.as_str(), entry.after.as_str()))
+            .collect();
+        // The snapshots are cut back to character boundaries, so the crab
+        // never fits
+        assert_eq!(
+            snapshots,
+            [("", "b"), ("a", ""), ("a", "cd"), ("", "d"), ("c", ""),]
+        );
+        // Without snapshots, nothing is stored
+        let trace = "abc".to_string().retain_traced(|_, _, _| true);
+        assert!(trace
+            .entries()
+            .iter()
+            .all(|entry| entry.before.capacity() == 0));
+    }
+
+    #[test]
+    fn debug_table() {
+        let trace = "a\u{e9}"
+            .to_string()
+            .retain_traced_with(4, |_, c, _| c == 'a');
+        let expected = "\
+RetainTrace (2 calls over 3 bytes)
+  offset  char          kept     before | after
+       0  'a'           kept     \"\" | \"\u{e9}\"
+       1  '\u{e9}'           removed  \"a\" | \"\"";
+        assert_eq!(format!("{:?}", trace), expected);
+    }
+}
@@ -0,0 +1,304 @@
+use alloc::string::String;
+
+use super::{cursor::Cursor, observer::RetainAction};
+
+/// An event-style alternative to a predicate, used by
+/// [`retain_visit`](crate::RetainMoreString::retain_visit).
+///
+/// For each character, [`decide_action`](Self::decide_action) (which calls
+/// [`decide`](Self::decide) by default) is called first, and then exactly one
+/// of [`on_keep`](Self::on_keep) or [`on_remove`](Self::on_remove) is called
+/// for that character, before the next character is decided. If the
+/// decision applies to the rest of the string, the rest is kept or removed
+/// at once, and then the same notification is made for each of its
+/// characters in order, so the notifications are the same as if each had
+/// been decided individually. The notifications do nothing by default.
+///
+/// ```
+/// use retain_more::{RetainMoreString as _, RetainVisitor};
+///
+/// /// Removes digits, remembering where they were
+/// #[derive(Default)]
+/// struct Digits {
+///     offsets: Vec<usize>,
+/// }
+///
+/// impl RetainVisitor for Digits {
+///     fn decide(&mut self, ch: char) -> bool {
+///         !ch.is_ascii_digit()
+///     }
+///
+///     fn on_remove(&mut self, _ch: char, original_offset: usize) {
+///         self.offsets.push(original_offset);
+///     }
+/// }
+///
+/// let mut visitor = Digits::default();
+/// let mut s = "a1b€2".to_string();
+/// s.retain_visit(&mut visitor);
+/// assert_eq!(s, "ab€");
+/// assert_eq!(visitor.offsets, [1, 6]);
+/// ```
+pub trait RetainVisitor {
+    /// Whether `ch` should be kept.
+    fn decide(&mut self, ch: char) -> bool;
+
+    /// What to do with `ch`, which can also end the pass early by keeping or
+    /// removing the rest of the string.
+    ///
+    /// By default, this keeps or removes just `ch`, as chosen by
+    /// [`decide`](Self::decide).
+    fn decide_action(&mut self, ch: char) -> RetainAction {
+        if self.decide(ch) {
+            RetainAction::Keep
+        } else {
+            RetainAction::Remove
+        }
+    }
+
+    /// Called after `ch` has been kept.
+    fn on_keep(&mut self, ch: char) {
+        let _ = ch;
+    }
+
+    /// Called after `ch` has been removed, with its byte offset in the
+    /// original string.
+    fn on_remove(&mut self, ch: char, original_offset: usize) {
+        let _ = (ch, original_offset);
+    }
+}
+
+impl<V: RetainVisitor + ?Sized> RetainVisitor for &mut V {
+    fn decide(&mut self, ch: char) -> bool {
+        (**self).decide(ch)
+    }
+
+    fn decide_action(&mut self, ch: char) -> RetainAction {
+        (**self).decide_action(ch)
+    }
+
+    fn on_keep(&mut self, ch: char) {
+        (**self).on_keep(ch)
+    }
+
+    fn on_remove(&mut self, ch: char, original_offset: usize) {
+        (**self).on_remove(ch, original_offset)
+    }
+}
+
+/// A [`RetainVisitor`] which decides using a predicate, and counts the
+/// characters kept and removed.
+///
+/// ```
+/// use retain_more::{CountingVisitor, RetainMoreString as _};
+/// let mut visitor = CountingVisitor::new(|c: char| c.is_alphabetic());
+/// let mut s = "a, b, c".to_string();
+/// s.retain_visit(&mut visitor);
+/// assert_eq!(s, "abc");
+/// assert_eq!((visitor.kept(), visitor.removed()), (3, 4));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CountingVisitor<F> {
+    f: F,
+    kept: usize,
+    removed: usize,
+}
+
+impl<F: FnMut(char) -> bool> CountingVisitor<F> {
+    /// Create a visitor which keeps the characters for which `f` returns
+    /// `true`.
+    pub fn new(f: F) -> Self {
+        CountingVisitor {
+            f,
+            kept: 0,
+            removed: 0,
+        }
+    }
+}
+
+impl<F> CountingVisitor<F> {
+    /// The number of characters kept so far.
+    pub fn kept(&self) -> usize {
+        self.kept
+    }
+
+    /// The number of characters removed so far.
+    pub fn removed(&self) -> usize {
+        self.removed
+    }
+}
+
+impl<F: FnMut(char) -> bool> RetainVisitor for CountingVisitor<F> {
+    fn decide(&mut self, ch: char) -> bool {
+        (self.f)(ch)
+    }
+
+    fn on_keep(&mut self, _ch: char) {
+        self.kept += 1;
+    }
+
+    fn on_remove(&mut self, _ch: char, _original_offset: usize) {
+        self.removed += 1;
+    }
+}
+
+pub(super) fn retain_visit<V: RetainVisitor + ?Sized>(s: &mut String, visitor: &mut V) {
+    let mut cursor = Cursor::new(s);
+    while let Some(ch) = cursor.peek() {
+        let ch_len = ch.len_utf8();
+        let offset = cursor.offset();
+        match visitor.decide_action(ch) {
+            RetainAction::Keep => {
+                cursor.keep(ch_len);
+                visitor.on_keep(ch);
+            }
+            RetainAction::Remove => {
+                cursor.remove(ch_len);
+                visitor.on_remove(ch, offset);
+            }
+            RetainAction::KeepRest => {
+                let rest = cursor.rest().len();
+                cursor.keep_rest();
+                let (kept, _) = cursor.split(0);
+                for ch in kept[kept.len() - rest..].chars() {
+                    visitor.on_keep(ch);
+                }
+            }
+            RetainAction::RemoveRest => {
+                let rest = cursor.rest().len();
+                cursor.remove(rest);
+                for (i, ch) in cursor.removed_run(rest).char_indices() {
+                    visitor.on_remove(ch, offset + i);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetainMoreString;
+    use alloc::{string::ToString, vec::Vec};
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Decide(char),
+        Keep(char),
+        Remove(char, usize),
+    }
+
+    #[derive(Default)]
+    struct Recorder(Vec<Event>);
+
+    impl RetainVisitor for Recorder {
+        fn decide(&mut self, ch: char) -> bool {
+            self.0.push(Event::Decide(ch));
+            ch != '-'
+        }
+
+        fn on_keep(&mut self, ch: char) {
+            self.0.push(Event::Keep(ch));
+        }
+
+        fn on_remove(&mut self, ch: char, original_offset: usize) {
+            self.0.push(Event::Remove(ch, original_offset));
+        }
+    }
+
+    #[test]
+    fn retain_visit_event_order() {
+        let mut recorder = Recorder::default();
+        let mut s = "\u{e9}-a-".to_string();
+        s.retain_visit(&mut recorder);
+        assert_eq!(s, "\u{e9}a");
+        assert_eq!(
+            recorder.0,
+            [
+                Event::Decide('\u{e9}'),
+                Event::Keep('\u{e9}'),
+                Event::Decide('-'),
+                Event::Remove('-', 2),
+                Event::Decide('a'),
+                Event::Keep('a'),
+                Event::Decide('-'),
+                Event::Remove('-', 4),
+            ]
+        );
+    }
+
+    /// Ends the pass at the first `;` or `|`
+    struct Until(Recorder);
+
+    impl RetainVisitor for Until {
+        fn decide(&mut self, ch: char) -> bool {
+            self.0.decide(ch)
+        }
+
+        fn decide_action(&mut self, ch: char) -> RetainAction {
+            match ch {
+                ';' => RetainAction::RemoveRest,
+                '|' => RetainAction::KeepRest,
+                ch if self.decide(ch) => RetainAction::Keep,
+                _ => RetainAction::Remove,
+            }
+        }
+
+        fn on_keep(&mut self, ch: char) {
+            self.0.on_keep(ch);
+        }
+
+        fn on_remove(&mut self, ch: char, original_offset: usize) {
+            self.0.on_remove(ch, original_offset);
+        }
+    }
+
+    #[test]
+    fn bulk_events() {
+        // Each character of the rest gets the notification it would have had
+        // if it was decided alone, but is never decided
+        let mut visitor = Until(Recorder::default());
+        let mut s = "a-;\u{e9}-".to_string();
+        s.retain_visit(&mut visitor);
+        assert_eq!(s, "a");
+        assert_eq!(
+            visitor.0 .0,
+            [
+                Event::Decide('a'),
+                Event::Keep('a'),
+                Event::Decide('-'),
+                Event::Remove('-', 1),
+                Event::Remove(';', 2),
+                Event::Remove('\u{e9}', 3),
+                Event::Remove('-', 5),
+            ]
+        );
+
+        let mut visitor = Until(Recorder::default());
+        let mut s = "-|\u{1F980}-".to_string();
+        s.retain_visit(&mut visitor);
+        assert_eq!(s, "|\u{1F980}-");
+        assert_eq!(
+            visitor.0 .0,
+            [
+                Event::Decide('-'),
+                Event::Remove('-', 0),
+                Event::Keep('|'),
+                Event::Keep('\u{1F980}'),
+                Event::Keep('-'),
+            ]
+        );
+    }
+
+    #[test]
+    fn counting_visitor() {
+        let mut visitor = CountingVisitor::new(|c: char| c != ' ');
+        let mut s = "a b c".to_string();
+        s.retain_visit(&mut visitor);
+        let mut t = "d  e".to_string();
+        // The counts accumulate when a visitor is reused
+        t.retain_visit(&mut visitor);
+        assert_eq!((s.as_str(), t.as_str()), ("abc", "de"));
+        assert_eq!((visitor.kept(), visitor.removed()), (5, 4));
+    }
+}
@@ -0,0 +1,182 @@
+//! Display width based operations, using the widths from the
+//! [`unicode-width`](unicode_width) crate.
+//!
+//! The width of each character is measured on its own, using
+//! [`UnicodeWidthChar::width`], with control characters counted as zero
+//! columns. Sequences which a terminal may render differently from the sum
+//! of their parts (such as emoji joined by a zero width joiner) are counted
+//! as that sum.
+
+use alloc::string::String;
+use unicode_width::UnicodeWidthChar;
+
+use super::cursor::Cursor;
+
+/// The zero width joiner, which should not be left dangling at the end of a
+/// truncated string.
+const ZWJ: char = '\u{200d}';
+
+fn char_width(ch: char) -> usize {
+    ch.width().unwrap_or(0)
+}
+
+pub(super) fn retain_width<F: FnMut(char) -> bool>(s: &mut String, max_width: usize, mut f: F) {
+    let mut width = 0;
+    let mut truncated = false;
+    {
+        let mut cursor = Cursor::new(s);
+        while let Some(ch) = cursor.peek() {
+            if !f(ch) {
+                cursor.remove(ch.len_utf8());
+                continue;
+            }
+            let ch_width = char_width(ch);
+            if width + ch_width > max_width {
+                let rest = cursor.rest().len();
+                cursor.remove(rest);
+                truncated = true;
+                break;
+            }
+            width += ch_width;
+            cursor.keep(ch.len_utf8());
+        }
+    }
+    if truncated && s.ends_with(ZWJ) {
+        s.pop();
+    }
+}
+
+pub(super) fn truncate_width(s: &mut String, max_width: usize, ellipsis: Option<char>) {
+    let total: usize = s.chars().map(char_width).sum();
+    if total <= max_width {
+        return;
+    }
+    // The ellipsis must fit within the width and the original length
+    let ellipsis = ellipsis
+        .filter(|&ellipsis| char_width(ellipsis) <= max_width && ellipsis.len_utf8() <= s.len());
+    let ellipsis_width = ellipsis.map_or(0, char_width);
+    let budget = max_width.saturating_sub(ellipsis_width);
+    let mut width = 0;
+    let mut cut = s.len();
+    for (offset, ch) in s.char_indices() {
+        width += char_width(ch);
+        if width > budget {
+            cut = offset;
+            break;
+        }
+    }
+    if s[..cut].ends_with(ZWJ) {
+        cut -= ZWJ.len_utf8();
+    }
+    match ellipsis {
+        // Write the ellipsis into the freed space, removing more characters
+        // if there is not enough of it, so the string never grows beyond its
+        // original length
+        Some(ellipsis) => {
+            while s.len() - cut < ellipsis.len_utf8() {
+                cut = s[..cut]
+                    .char_indices()
+                    .next_back()
+                    .map_or(0, |(offset, _)| offset);
+            }
+            s.truncate(cut);
+            s.push(ellipsis);
+        }
+        None => s.truncate(cut),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreString;
+    use alloc::string::{String, ToString};
+    use unicode_width::UnicodeWidthStr;
+
+    fn truncated(input: &str, max_width: usize, ellipsis: Option<char>) -> String {
+        let mut s = input.to_string();
+        let capacity = s.capacity();
+        s.truncate_width(max_width, ellipsis);
+        assert_eq!(s.capacity(), capacity);
+        s
+    }
+
+    #[test]
+    fn retain_width_wide_chars() {
+        // Each of these is 2 columns wide
+        let mut s = "\u{65e5}\u{672c}\u{8a9e}".to_string();
+        s.retain_width(5, |_| true);
+        assert_eq!(s, "\u{65e5}\u{672c}");
+
+        // The predicate is applied before the budget
+        let mut s = "a-\u{65e5}-b-c".to_string();
+        s.retain_width(4, |c| c != '-');
+        assert_eq!(s, "a\u{65e5}b");
+
+        // Zero width characters after the last kept character still fit
+        let mut s = "e\u{301}\u{65e5}".to_string();
+        s.retain_width(1, |_| true);
+        assert_eq!(s, "e\u{301}");
+    }
+
+    #[test]
+    fn retain_width_zwj() {
+        // Family emoji: man, ZWJ, woman, ZWJ, girl
+        let family = "\u{1F468}\u{200d}\u{1F469}\u{200d}\u{1F467}";
+        let mut s = family.to_string();
+        s.retain_width(3, |_| true);
+        assert_eq!(s, "\u{1F468}");
+        let mut s = family.to_string();
+        s.retain_width(6, |_| true);
+        assert_eq!(s, family);
+        let mut s = family.to_string();
+        s.retain_width(6, |c| c != '\u{200d}');
+        assert_eq!(s, "\u{1F468}\u{1F469}\u{1F467}");
+    }
+
+    #[test]
+    fn truncate_width() {
+        assert_eq!(
+            truncated("hello world", 20, Some('\u{2026}')),
+            "hello world"
+        );
+        assert_eq!(
+            truncated("hello world", 11, Some('\u{2026}')),
+            "hello world"
+        );
+        assert_eq!(truncated("hello world", 8, None), "hello wo");
+        // The ellipsis takes the place of the last column
+        assert_eq!(
+            truncated("hello world", 8, Some('\u{2026}')),
+            "hello w\u{2026}"
+        );
+        // A wide character which straddles the budget is removed entirely
+        assert_eq!(truncated("ab\u{65e5}\u{672c}", 3, None), "ab");
+        assert_eq!(truncated("ab\u{65e5}\u{672c}", 5, Some('~')), "ab\u{65e5}~");
+        assert_eq!(truncated("ab\u{65e5}\u{672c}", 4, Some('~')), "ab~");
+        assert_eq!(truncated("ab\u{65e5}\u{672c}", 0, Some('~')), "");
+        assert_eq!(
+            truncated("\u{1F468}\u{200d}\u{1F469}", 3, None),
+            "\u{1F468}"
+        );
+
+        let long = "The quick brown fox jumps over the lazy dog";
+        for max_width in 0..long.len() {
+            let s = truncated(long, max_width, Some('\u{2026}'));
+            assert!(s.width() <= max_width, "{:?} is too wide", s);
+        }
+    }
+
+    #[test]
+    fn truncate_width_within_freed_space() {
+        // Only one byte would be freed, but the ellipsis needs three, so an
+        // extra character is removed
+        let mut s = String::with_capacity(4);
+        s.push_str("abcd");
+        s.truncate_width(3, Some('\u{2026}'));
+        assert_eq!(s, "a\u{2026}");
+        assert_eq!(s.capacity(), 4);
+
+        // The whole string is too short to hold the ellipsis
+        assert_eq!(truncated("ab", 1, Some('\u{2026}')), "a");
+    }
+}
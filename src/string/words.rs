@@ -0,0 +1,215 @@
+//! Word segmentation shared by the word based methods of
+//! [`RetainMoreString`](crate::RetainMoreString).
+//!
+//! A word is a maximal run of non-whitespace characters, so punctuation is
+//! part of the word it is attached to. The whitespace between two words is
+//! their separator. When words are removed, separators are merged so that
+//! the remaining words are separated as they were:
+//!  - The whitespace before the first word and after the last word is always
+//!    kept.
+//!  - The separator before a word is kept only if that word is kept, and
+//!    some earlier word was kept.
+
+use core::ops::{Range, RangeBounds};
+
+use alloc::string::String;
+
+use super::cursor::Cursor;
+
+/// The length of the whitespace at the start of `s`.
+fn whitespace_len(s: &str) -> usize {
+    s.len() - s.trim_start().len()
+}
+
+pub(super) fn retain_words<F: FnMut(&str) -> bool>(s: &mut String, mut f: F) {
+    let mut cursor = Cursor::new(s);
+    cursor.keep(whitespace_len(cursor.rest()));
+    let mut any_kept = false;
+    // The length of the separator before the next word
+    let mut separator = 0;
+    loop {
+        let rest = &cursor.rest()[separator..];
+        let word_len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if word_len == 0 {
+            // Only the trailing whitespace remains
+            cursor.keep_rest();
+            return;
+        }
+        // The word is still in the unconsidered region, so is untouched
+        let keep = f(&rest[..word_len]);
+        if keep && any_kept {
+            cursor.keep(separator);
+        } else {
+            cursor.remove(separator);
+        }
+        if keep {
+            cursor.keep(word_len);
+            any_kept = true;
+        } else {
+            cursor.remove(word_len);
+        }
+        separator = whitespace_len(cursor.rest());
+    }
+}
+
+pub(super) fn retain_words_by_len<R: RangeBounds<usize>>(s: &mut String, bounds: R) {
+    retain_words(s, |word| bounds.contains(&word.chars().count()))
+}
+
+pub(super) fn dedup_words_by<F: FnMut(&str, &str) -> bool>(s: &mut String, mut eq: F) {
+    let mut cursor = Cursor::new(s);
+    cursor.keep(whitespace_len(cursor.rest()));
+    // The range of the last kept word, within the kept region. That region
+    // is only ever appended to, so the range stays valid.
+    let mut previous: Option<Range<usize>> = None;
+    // The length of the separator before the next word
+    let mut separator = 0;
+    loop {
+        let (kept, rest) = cursor.split(0);
+        let rest = &rest[separator..];
+        let word_len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if word_len == 0 {
+            // Only the trailing whitespace remains
+            cursor.keep_rest();
+            return;
+        }
+        let duplicate = match &previous {
+            Some(previous) => eq(&kept[previous.clone()], &rest[..word_len]),
+            None => false,
+        };
+        if duplicate {
+            cursor.remove(separator + word_len);
+        } else {
+            cursor.keep(separator);
+            let start = cursor.kept_len();
+            cursor.keep(word_len);
+            previous = Some(start..cursor.kept_len());
+        }
+        separator = whitespace_len(cursor.rest());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreString;
+    use alloc::{collections::BTreeSet, string::ToString, vec::Vec};
+
+    #[test]
+    fn retain_words_separators() {
+        let cases = [
+            ("a b c", "b", "a c"),
+            ("a b c", "a", "b c"),
+            ("a b c", "c", "a b"),
+            ("a  b\tc", "b", "a\tc"),
+            ("  a b  ", "b", "  a  "),
+            ("  a b  ", "a", "  b  "),
+            ("  a b  ", "ab", "    "),
+            ("a\nb\n\nc", "bc", "a"),
+            ("", "", ""),
+            ("   ", "", "   "),
+        ];
+        for (input, removed, expected) in cases {
+            let mut s = input.to_string();
+            s.retain_words(|word| !removed.contains(word));
+            assert_eq!(s, expected, "removing {:?} from {:?}", removed, input);
+        }
+    }
+
+    #[test]
+    fn retain_words_visits_each_word() {
+        let mut s = " one, two\u{a0}three  four ".to_string();
+        let mut words = Vec::new();
+        s.retain_words(|word| {
+            words.push(word.to_string());
+            true
+        });
+        assert_eq!(words, ["one,", "two", "three", "four"]);
+        assert_eq!(s, " one, two\u{a0}three  four ");
+    }
+
+    #[test]
+    fn retain_words_in() {
+        let dictionary: BTreeSet<&str> = ["the", "cat", "sat", "mat."].iter().copied().collect();
+        let contains = |word: &str| dictionary.contains(word);
+
+        // The lookup is case sensitive, and punctuation is part of the word
+        let mut s = "The cat, the cat sat on the mat.".to_string();
+        s.retain_words_in(&contains);
+        assert_eq!(s, "the cat sat the mat.");
+
+        let mut s = "  nothing here  ".to_string();
+        s.retain_words_in(&|_: &str| false);
+        assert_eq!(s, "    ");
+    }
+
+    fn by_len<R: core::ops::RangeBounds<usize>>(input: &str, bounds: R) -> alloc::string::String {
+        let mut s = input.to_string();
+        s.retain_words_by_len(bounds);
+        s
+    }
+
+    #[test]
+    fn retain_words_by_len() {
+        let input = "a bb ccc dddd eeeee";
+        assert_eq!(by_len(input, 2..4), "bb ccc");
+        assert_eq!(by_len(input, 2..=4), "bb ccc dddd");
+        assert_eq!(by_len(input, ..3), "a bb");
+        assert_eq!(by_len(input, 4..), "dddd eeeee");
+        assert_eq!(by_len(input, ..), input);
+        assert_eq!(by_len(input, 6..), "");
+    }
+
+    #[test]
+    fn retain_words_by_len_multibyte() {
+        // 3 characters, but 12 bytes
+        let crabs = "\u{1F980}\u{1F980}\u{1F980}";
+        let mut s = alloc::format!("ab {} \u{e9}t\u{e9} abcdefgh", crabs);
+        s.retain_words_by_len(3..=4);
+        assert_eq!(s, alloc::format!("{} \u{e9}t\u{e9}", crabs));
+    }
+
+    fn dedup(input: &str) -> alloc::string::String {
+        let mut s = input.to_string();
+        s.dedup_words();
+        s
+    }
+
+    #[test]
+    fn dedup_words() {
+        assert_eq!(dedup("the the cat"), "the cat");
+        assert_eq!(dedup("a a a b"), "a b");
+        assert_eq!(dedup("b a a a"), "b a");
+        assert_eq!(dedup("a b a b"), "a b a b");
+        // The separator before the removed word goes with it
+        assert_eq!(dedup("  x\t\tx\ny  "), "  x\ny  ");
+        assert_eq!(dedup("x \n x"), "x");
+        // Punctuation is part of the word
+        assert_eq!(dedup("so so, so"), "so so, so");
+        assert_eq!(dedup("\u{e9}\u{1F980} \u{e9}\u{1F980}"), "\u{e9}\u{1F980}");
+        assert_eq!(dedup(""), "");
+        assert_eq!(dedup("   "), "   ");
+    }
+
+    #[test]
+    fn dedup_words_by() {
+        let mut s = "The the THE end  End".to_string();
+        s.dedup_words_by(|previous, word| previous.eq_ignore_ascii_case(word));
+        assert_eq!(s, "The end");
+
+        // Each word is compared with the previous kept word, not the
+        // previous word
+        let mut s = "a ab abc abd b".to_string();
+        let mut calls = Vec::new();
+        s.dedup_words_by(|previous, word| {
+            calls.push((previous.to_string(), word.to_string()));
+            word.starts_with(previous)
+        });
+        assert_eq!(s, "a b");
+        let pairs = [("a", "ab"), ("a", "abc"), ("a", "abd"), ("a", "b")];
+        let pairs: Vec<_> = pairs
+            .iter()
+            .map(|&(a, b)| (a.to_string(), b.to_string()))
+            .collect();
+        assert_eq!(calls, pairs);
+    }
+}
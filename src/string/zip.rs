@@ -0,0 +1,198 @@
+use core::fmt;
+
+use alloc::{string::String, vec::Vec};
+
+use super::cursor::Cursor;
+
+/// The error returned by
+/// [`retain_zip`](crate::RetainMoreString::retain_zip) when the metadata does
+/// not have one element per character of the string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ZipLengthError {
+    /// The number of characters in the string.
+    pub chars: usize,
+    /// The number of elements of metadata.
+    pub meta: usize,
+}
+
+impl fmt::Display for ZipLengthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the string has {} characters, but there are {} elements of metadata",
+            self.chars, self.meta
+        )
+    }
+}
+
+/// The error returned by
+/// [`retain_by_mask`](crate::RetainMoreString::retain_by_mask) when the mask
+/// does not have one element per character of the string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaskLenError {
+    /// The number of characters in the string.
+    pub chars: usize,
+    /// The length of the mask.
+    pub mask: usize,
+}
+
+impl fmt::Display for MaskLenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the string has {} characters, but the mask has length {}",
+            self.chars, self.mask
+        )
+    }
+}
+
+/// A cursor which keeps the characters which have not been considered when
+/// dropped, to match [`Vec::retain`] if the predicate panics.
+struct KeepRest<'a>(Cursor<'a>);
+
+impl Drop for KeepRest<'_> {
+    fn drop(&mut self) {
+        self.0.keep_rest();
+    }
+}
+
+pub(super) fn retain_zip<T, F: FnMut(char, &T) -> bool>(
+    s: &mut String,
+    meta: &mut Vec<T>,
+    mut f: F,
+) -> Result<(), ZipLengthError> {
+    let chars = s.chars().count();
+    if chars != meta.len() {
+        return Err(ZipLengthError {
+            chars,
+            meta: meta.len(),
+        });
+    }
+    let mut cursor = KeepRest(Cursor::new(s));
+    meta.retain(|it| {
+        let ch = cursor.0.peek().expect("one character per element");
+        let ch_len = ch.len_utf8();
+        let keep = f(ch, it);
+        if keep {
+            cursor.0.keep(ch_len);
+        } else {
+            cursor.0.remove(ch_len);
+        }
+        keep
+    });
+    Ok(())
+}
+
+pub(super) fn retain_by_mask(s: &mut String, mask: &[bool]) -> Result<(), MaskLenError> {
+    let chars = s.chars().count();
+    if chars != mask.len() {
+        return Err(MaskLenError {
+            chars,
+            mask: mask.len(),
+        });
+    }
+    let mut cursor = Cursor::new(s);
+    // Each run of equal decisions is kept or removed as a single block
+    for run in mask.chunk_by(|a, b| a == b) {
+        let len: usize = cursor
+            .rest()
+            .chars()
+            .take(run.len())
+            .map(char::len_utf8)
+            .sum();
+        if run[0] {
+            cursor.keep(len);
+        } else {
+            cursor.remove(len);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MaskLenError, ZipLengthError};
+    use crate::RetainMoreString;
+    use alloc::{string::ToString, vec, vec::Vec};
+
+    #[test]
+    fn retain_zip() {
+        // One colour per character, where byte and character indices differ
+        let mut s = "a\u{e9}\u{1F980}b\u{2603}".to_string();
+        let mut colours = vec![1, 2, 3, 4, 5];
+        let ptr = colours.as_ptr();
+        let mut seen = Vec::new();
+        s.retain_zip(&mut colours, |c, &colour| {
+            seen.push((c, colour));
+            colour % 2 == 1
+        })
+        .unwrap();
+        assert_eq!(s, "a\u{1F980}\u{2603}");
+        assert_eq!(colours, [1, 3, 5]);
+        assert_eq!(colours.as_ptr(), ptr);
+        assert_eq!(
+            seen,
+            [
+                ('a', 1),
+                ('\u{e9}', 2),
+                ('\u{1F980}', 3),
+                ('b', 4),
+                ('\u{2603}', 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn retain_by_mask() {
+        // Alternating over multi-byte characters
+        let mut s = "a\u{e9}\u{1F980}b\u{2603}c".to_string();
+        s.retain_by_mask(&[true, false, true, false, true, false])
+            .unwrap();
+        assert_eq!(s, "a\u{1F980}\u{2603}");
+
+        let mut s = "ab\u{e9}\u{e9}cd".to_string();
+        s.retain_by_mask(&[true, true, false, false, true, true])
+            .unwrap();
+        assert_eq!(s, "abcd");
+        s.retain_by_mask(&[true; 4]).unwrap();
+        assert_eq!(s, "abcd");
+        s.retain_by_mask(&[false; 4]).unwrap();
+        assert_eq!(s, "");
+        s.retain_by_mask(&[]).unwrap();
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn retain_by_mask_length_mismatch() {
+        let mut s = "\u{e9}b".to_string();
+        let err = s.retain_by_mask(&[false]).unwrap_err();
+        assert_eq!(err, MaskLenError { chars: 2, mask: 1 });
+        let err = s.retain_by_mask(&[false; 3]).unwrap_err();
+        assert_eq!(err, MaskLenError { chars: 2, mask: 3 });
+        assert_eq!(
+            err.to_string(),
+            "the string has 2 characters, but the mask has length 3"
+        );
+        assert_eq!(s, "\u{e9}b");
+    }
+
+    #[test]
+    fn retain_zip_length_mismatch() {
+        // Four bytes, but only one character
+        let mut s = "\u{1F980}".to_string();
+        let mut meta = vec![0; 4];
+        let err = s.retain_zip(&mut meta, |_, _| false).unwrap_err();
+        assert_eq!(err, ZipLengthError { chars: 1, meta: 4 });
+        assert_eq!(
+            err.to_string(),
+            "the string has 1 characters, but there are 4 elements of metadata"
+        );
+        // Neither is changed
+        assert_eq!(s, "\u{1F980}");
+        assert_eq!(meta.len(), 4);
+
+        let mut meta: Vec<()> = Vec::new();
+        assert!(s.retain_zip(&mut meta, |_, _| true).is_err());
+        assert!("".to_string().retain_zip(&mut meta, |_, _| true).is_ok());
+    }
+}
@@ -0,0 +1,799 @@
+use core::{
+    array,
+    ops::{Bound, Range, RangeBounds},
+    ptr, slice,
+};
+
+use alloc::vec::Vec;
+
+mod extract;
+mod report;
+mod utf8;
+
+pub use extract::ExtractIfCtx;
+pub use report::VecRetainReport;
+pub use utf8::{retain_str_in_vec, Utf8RetainError};
+
+/// More advanced versions of [`Vec::retain`], implemented as extension
+/// methods on [`Vec`].
+///
+/// This trait is sealed and cannot be implemented for types outside of
+/// `retain_more`
+pub trait RetainMoreVec<T>: sealed::Sealed {
+    /// Retains only the elements specified by the predicate, visiting the
+    /// elements from the back to the front.
+    ///
+    /// In other words, remove all elements for which `f` returns false. This
+    /// method operates in place, visiting each element exactly once in
+    /// reverse order, and preserves the order of the retained elements.
+    ///
+    /// The arguments of the predicate are:
+    ///  - 0: `&mut [T]`; The elements which have already been retained, i.e.
+    ///    those after the current element for which the predicate returned
+    ///    `true`.
+    ///  - 1: `&mut T`; The current element being considered.
+    ///  - 2: `&mut [T]`; The elements yet to be considered, i.e. all of the
+    ///    elements before the current element.
+    ///
+    /// If the predicate (or the destructor of a removed element) panics, the
+    /// vector is left containing the elements not yet considered, followed by
+    /// the elements which were retained. No element is dropped twice, and
+    /// none are leaked.
+    ///
+    /// # Usage
+    ///
+    /// Keep only the last occurrence of each key:
+    ///
+    /// ```
+    /// use retain_more::RetainMoreVec as _;
+    /// let mut entries = vec![("a", 1), ("b", 2), ("a", 3), ("c", 4), ("b", 5)];
+    /// entries.retain_all_rev(|kept, (key, _), _| !kept.iter().any(|(k, _)| k == key));
+    /// assert_eq!(entries, [("a", 3), ("c", 4), ("b", 5)]);
+    /// ```
+    fn retain_all_rev<F: FnMut(&mut [T], &mut T, &mut [T]) -> bool>(&mut self, f: F);
+
+    /// Retains only the elements within `range` specified by the predicate,
+    /// returning the number of elements removed.
+    ///
+    /// Elements outside of `range` are never passed to `f`, and are always
+    /// kept. The elements after the range are moved down to close the gap in
+    /// a single bulk move once the range has been visited, rather than being
+    /// shifted for each removal.
+    ///
+    /// If the predicate (or the destructor of a removed element) panics, the
+    /// elements which have not yet been removed are kept, in their original
+    /// order.
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of bounds, in the same cases as slice indexing.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreVec as _;
+    /// let mut v: Vec<u32> = (0..10).collect();
+    /// assert_eq!(v.retain_range(2..8, |it| *it % 3 == 0), 4);
+    /// assert_eq!(v, [0, 1, 3, 6, 8, 9]);
+    /// ```
+    fn retain_range<R: RangeBounds<usize>, F: FnMut(&mut T) -> bool>(
+        &mut self,
+        range: R,
+        f: F,
+    ) -> usize;
+
+    /// Retains only the elements specified by the predicate, rewriting
+    /// `indices` to keep pointing at the same elements.
+    ///
+    /// Each entry of `indices` is treated as an index into this vector. After
+    /// the retain, an index of a retained element is replaced with that
+    /// element's new position, and an index of a removed element (or an index
+    /// which was out of bounds to begin with) is replaced with `usize::MAX`.
+    /// `usize::MAX` is never a valid index into a vector of non-zero-sized
+    /// elements, so it cannot be confused with a real position.
+    ///
+    /// The elements are visited in order, and the indices are fixed up after
+    /// the pass, using a table of the runs of removed elements. That table
+    /// has one entry per run, so it stays small when removals are clustered.
+    ///
+    /// If the predicate (or the destructor of a removed element) panics, the
+    /// vector is left as by [`retain_range`](Self::retain_range), and
+    /// `indices` is left unchanged.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreVec as _;
+    /// let mut nodes = vec!['a', 'b', 'c', 'd', 'e'];
+    /// let mut parents = [4, 0, 2, 7];
+    /// nodes.retain_adjust_indices(&mut parents, |it| *it != 'c');
+    /// assert_eq!(nodes, ['a', 'b', 'd', 'e']);
+    /// assert_eq!(parents, [3, 0, usize::MAX, usize::MAX]);
+    /// ```
+    fn retain_adjust_indices<F: FnMut(&mut T) -> bool>(&mut self, indices: &mut [usize], f: F);
+
+    /// Retains only the elements specified by the predicate, returning
+    /// statistics about what was removed.
+    ///
+    /// This is equivalent to [`Vec::retain_mut`], with the report assembled
+    /// during the same pass. [`VecRetainReport::sorted`] is always `None`;
+    /// use [`retain_report_sorted`](Self::retain_report_sorted) to compute
+    /// it.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreVec as _;
+    /// let mut v = vec![1, 2, 3, 4, 5, 6];
+    /// let report = v.retain_report(|it| *it != 2 && *it != 5);
+    /// assert_eq!(v, [1, 3, 4, 6]);
+    /// assert_eq!((report.removed, report.kept), (2, 4));
+    /// assert_eq!((report.first_removed, report.last_removed), (Some(1), Some(4)));
+    /// assert_eq!(report.sorted, None);
+    /// ```
+    fn retain_report<F: FnMut(&mut T) -> bool>(&mut self, f: F) -> VecRetainReport;
+
+    /// Retains only the elements specified by the predicate, returning
+    /// statistics about what was removed, including whether the retained
+    /// elements are sorted.
+    ///
+    /// This is [`retain_report`](Self::retain_report), which also compares
+    /// each retained element with the previous retained element, in the same
+    /// way as [`slice::is_sorted`]. The result is recorded in
+    /// [`VecRetainReport::sorted`]. As the predicate can modify the elements,
+    /// a vector which was sorted before the pass is not necessarily sorted
+    /// after it. This costs one comparison per retained element, so is
+    /// separate from `retain_report`.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreVec as _;
+    /// let mut v = vec![3, 1, 4, 1, 5, 9, 2, 6];
+    /// let report = v.retain_report_sorted(|it| *it > 2);
+    /// assert_eq!(v, [3, 4, 5, 9, 6]);
+    /// assert_eq!(report.sorted, Some(false));
+    ///
+    /// let report = v.retain_report_sorted(|it| *it != 6);
+    /// assert_eq!(report.sorted, Some(true));
+    /// ```
+    fn retain_report_sorted<F: FnMut(&mut T) -> bool>(&mut self, f: F) -> VecRetainReport
+    where
+        T: PartialOrd;
+
+    /// Creates an iterator which removes the elements specified by the
+    /// predicate, and yields them by value.
+    ///
+    /// This is [`Vec::extract_if`] over the whole vector, where the predicate
+    /// is also given the context of the current element. The arguments of the
+    /// predicate are:
+    ///  - 0: `&[T]`; The elements which have been retained so far, i.e. those
+    ///    before the current element for which the predicate returned
+    ///    `false`.
+    ///  - 1: `&mut T`; The current element being considered.
+    ///  - 2: `&[T]`; The elements after the current element, which have not
+    ///    yet been considered.
+    ///
+    /// The predicate returns `true` to extract the current element. Note that
+    /// this is the opposite of the `retain` methods.
+    ///
+    /// Elements are only considered as the iterator is advanced. If the
+    /// iterator is dropped before it is exhausted, or if the predicate
+    /// panics, the elements which have not yet been considered are kept.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreVec as _;
+    /// let mut v = vec![1, 4, 2, 8, 5, 7];
+    /// // Extract the elements which are smaller than an earlier retained element
+    /// let out_of_order: Vec<_> = v
+    ///     .extract_if_ctx(|retained, it, _| retained.last().is_some_and(|last| *it < *last))
+    ///     .collect();
+    /// assert_eq!(v, [1, 4, 8]);
+    /// assert_eq!(out_of_order, [2, 5, 7]);
+    /// ```
+    fn extract_if_ctx<F: FnMut(&[T], &mut T, &[T]) -> bool>(
+        &mut self,
+        f: F,
+    ) -> ExtractIfCtx<'_, T, F>;
+
+    /// Retains only the elements specified by the predicate, which is also
+    /// given the next `N` elements.
+    ///
+    /// The arguments of the predicate are:
+    ///  - 0: `&T`; The current element being considered.
+    ///  - 1: `[Option<&T>; N]`; The `N` elements after the current element,
+    ///    in order. Near the end of the vector, where there are fewer than
+    ///    `N` elements remaining, the array is padded with `None`.
+    ///
+    /// The upcoming elements are always taken from the region which has not
+    /// yet been considered, so they are the original elements, and are never
+    /// affected by the removal of the current or earlier elements. This
+    /// region is never moved during the pass, so the references are valid for
+    /// the whole call to the predicate.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreVec as _;
+    /// let mut v = vec![1, 1, 2, 3, 3, 3, 1];
+    /// // Remove an element if it is the same as the next one
+    /// v.retain_window(|it, [next]| next != Some(it));
+    /// assert_eq!(v, [1, 2, 3, 1]);
+    /// ```
+    fn retain_window<const N: usize, F: FnMut(&T, [Option<&T>; N]) -> bool>(&mut self, f: F);
+}
+
+/// Resolve `range` against a slice of length `len`, panicking in the same
+/// cases as slice indexing.
+fn resolve_range<R: RangeBounds<usize>>(range: R, len: usize) -> Range<usize> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start
+            .checked_add(1)
+            .expect("attempted to index slice from after maximum usize"),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end
+            .checked_add(1)
+            .expect("attempted to index slice up to maximum usize"),
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    assert!(
+        start <= end,
+        "slice index starts at {} but ends at {}",
+        start,
+        end
+    );
+    assert!(
+        end <= len,
+        "range end index {} out of range for slice of length {}",
+        end,
+        len
+    );
+    start..end
+}
+
+/// Restores the vector during a forward retain, by moving the elements which
+/// have not yet been considered down to directly after the retained elements.
+///
+/// The vector is split into three regions:
+///  - `0..idx - deleted`: The elements which have been retained (or were
+///    never considered)
+///  - `idx - deleted..idx`: Elements which have been moved or dropped
+///  - `idx..len`: The elements which have not yet been considered
+struct FrontCompact<'a, T> {
+    vec: &'a mut Vec<T>,
+    idx: usize,
+    deleted: usize,
+    len: usize,
+}
+
+impl<'a, T> FrontCompact<'a, T> {
+    /// Start compacting `vec`, with the first `start` elements already
+    /// considered and retained.
+    fn new(vec: &'a mut Vec<T>, start: usize) -> Self {
+        let len = vec.len();
+        // Avoid double drops if the predicate or a destructor panics, see
+        // `FrontCompact`
+        // SAFETY: 0 <= len
+        unsafe { vec.set_len(0) };
+        FrontCompact {
+            vec,
+            idx: start,
+            deleted: 0,
+            len,
+        }
+    }
+
+    /// A mutable reference to the next element to be considered.
+    ///
+    /// # Safety
+    ///
+    /// `idx < len` must hold, and the reference must not be used after the
+    /// next call to [`Self::keep`] or [`Self::remove`].
+    unsafe fn current<'b>(&mut self) -> &'b mut T {
+        &mut *self.vec.as_mut_ptr().add(self.idx)
+    }
+
+    /// The last element of the retained region, if there is one.
+    fn last_kept(&self) -> Option<&T> {
+        let kept = self.idx - self.deleted;
+        // SAFETY: `0..idx - deleted` is initialised
+        (kept > 0).then(|| unsafe { &*self.vec.as_ptr().add(kept - 1) })
+    }
+
+    /// Keep the next element, moving it to the end of the retained region.
+    ///
+    /// # Safety
+    ///
+    /// `idx < len` must hold.
+    unsafe fn keep(&mut self) {
+        if self.deleted > 0 {
+            let ptr = self.vec.as_mut_ptr();
+            // SAFETY: `idx - deleted` is in the gap, so is distinct from
+            // `idx` and can be overwritten.
+            ptr::copy_nonoverlapping(ptr.add(self.idx), ptr.add(self.idx - self.deleted), 1);
+        }
+        self.idx += 1;
+    }
+
+    /// Drop the next element.
+    ///
+    /// # Safety
+    ///
+    /// `idx < len` must hold.
+    unsafe fn remove(&mut self) {
+        let ptr = self.vec.as_mut_ptr().add(self.idx);
+        // Move the element into the gap before dropping it, so it is not
+        // touched again even if its destructor panics.
+        self.idx += 1;
+        self.deleted += 1;
+        ptr::drop_in_place(ptr);
+    }
+}
+
+impl<T> Drop for FrontCompact<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: Both regions are within the allocation, and the
+        // unconsidered region is moved into the gap, so every element is
+        // initialised exactly once in `0..len - deleted`.
+        unsafe {
+            if self.deleted > 0 {
+                let ptr = self.vec.as_mut_ptr();
+                ptr::copy(
+                    ptr.add(self.idx),
+                    ptr.add(self.idx - self.deleted),
+                    self.len - self.idx,
+                );
+            }
+            self.vec.set_len(self.len - self.deleted);
+        }
+    }
+}
+
+/// Restores the vector during [`RetainMoreVec::retain_all_rev`], by moving
+/// the retained elements down to directly after the unvisited elements.
+///
+/// The vector is split into three regions:
+///  - `0..unvisited`: The elements not yet considered
+///  - `unvisited..kept_start`: Elements which have been moved or dropped
+///  - `kept_start..len`: The elements which have been retained
+struct BackCompact<'a, T> {
+    vec: &'a mut Vec<T>,
+    unvisited: usize,
+    kept_start: usize,
+    len: usize,
+}
+
+impl<T> Drop for BackCompact<'_, T> {
+    fn drop(&mut self) {
+        let kept = self.len - self.kept_start;
+        // SAFETY: Both regions are within the allocation, and the kept region
+        // is moved into the gap, so every element is initialised exactly
+        // once in `0..unvisited + kept`.
+        unsafe {
+            let ptr = self.vec.as_mut_ptr();
+            if self.unvisited != self.kept_start {
+                ptr::copy(ptr.add(self.kept_start), ptr.add(self.unvisited), kept);
+            }
+            self.vec.set_len(self.unvisited + kept);
+        }
+    }
+}
+
+impl<T> RetainMoreVec<T> for Vec<T> {
+    fn retain_all_rev<F: FnMut(&mut [T], &mut T, &mut [T]) -> bool>(&mut self, mut f: F) {
+        let len = self.len();
+        // Avoid double drops if the predicate or a destructor panics, see
+        // `BackCompact`
+        // SAFETY: 0 <= len
+        unsafe { self.set_len(0) };
+        let mut guard = BackCompact {
+            vec: self,
+            unvisited: len,
+            kept_start: len,
+            len,
+        };
+        while guard.unvisited > 0 {
+            let idx = guard.unvisited - 1;
+            let ptr = guard.vec.as_mut_ptr();
+            // SAFETY: `0..idx`, `idx` and `kept_start..len` are disjoint, since
+            // `idx < unvisited <= kept_start`, and each is initialised.
+            let keep = unsafe {
+                let kept =
+                    slice::from_raw_parts_mut(ptr.add(guard.kept_start), len - guard.kept_start);
+                let unvisited = slice::from_raw_parts_mut(ptr, idx);
+                f(kept, &mut *ptr.add(idx), unvisited)
+            };
+            guard.unvisited = idx;
+            if keep {
+                guard.kept_start -= 1;
+                if guard.kept_start != idx {
+                    // SAFETY: `kept_start` was in the gap, so is distinct from
+                    // `idx` and can be overwritten.
+                    unsafe { ptr::copy_nonoverlapping(ptr.add(idx), ptr.add(guard.kept_start), 1) };
+                }
+            } else {
+                // SAFETY: `idx` is no longer in the unvisited region, so will
+                // not be touched again, even if this panics.
+                unsafe { ptr::drop_in_place(ptr.add(idx)) };
+            }
+        }
+    }
+
+    fn retain_range<R: RangeBounds<usize>, F: FnMut(&mut T) -> bool>(
+        &mut self,
+        range: R,
+        mut f: F,
+    ) -> usize {
+        let range = resolve_range(range, self.len());
+        let mut compact = FrontCompact::new(self, range.start);
+        while compact.idx < range.end {
+            // SAFETY: `idx < range.end <= len`, and `current` is not used
+            // after the call to `keep` or `remove`
+            unsafe {
+                if f(compact.current()) {
+                    compact.keep();
+                } else {
+                    compact.remove();
+                }
+            }
+        }
+        compact.deleted
+    }
+
+    fn retain_adjust_indices<F: FnMut(&mut T) -> bool>(&mut self, indices: &mut [usize], mut f: F) {
+        let len = self.len();
+        // The runs of removed elements, as `(end, deleted)`, where `end` is
+        // the original index after the run, and `deleted` is the number of
+        // elements removed up to and including the run.
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        {
+            let mut compact = FrontCompact::new(self, 0);
+            while compact.idx < len {
+                // SAFETY: `idx < len`, and `current` is not used after the
+                // call to `keep` or `remove`
+                unsafe {
+                    if f(compact.current()) {
+                        compact.keep();
+                    } else {
+                        compact.remove();
+                        match runs.last_mut() {
+                            Some((end, deleted)) if *end == compact.idx - 1 => {
+                                *end += 1;
+                                *deleted += 1;
+                            }
+                            _ => runs.push((compact.idx, compact.deleted)),
+                        }
+                    }
+                }
+            }
+        }
+        for index in indices {
+            if *index >= len {
+                *index = usize::MAX;
+                continue;
+            }
+            // The first run which ends after `index` is the only one which
+            // could contain it.
+            let run = runs.partition_point(|&(end, _)| end <= *index);
+            let deleted_before = match run.checked_sub(1) {
+                Some(previous) => runs[previous].1,
+                None => 0,
+            };
+            *index = match runs.get(run) {
+                Some(&(end, deleted)) if end - (deleted - deleted_before) <= *index => usize::MAX,
+                _ => *index - deleted_before,
+            };
+        }
+    }
+
+    fn retain_report<F: FnMut(&mut T) -> bool>(&mut self, f: F) -> VecRetainReport {
+        retain_report(self, f, None::<fn(&T, &T) -> bool>)
+    }
+
+    fn extract_if_ctx<F: FnMut(&[T], &mut T, &[T]) -> bool>(
+        &mut self,
+        f: F,
+    ) -> ExtractIfCtx<'_, T, F> {
+        ExtractIfCtx::new(self, f)
+    }
+
+    fn retain_window<const N: usize, F: FnMut(&T, [Option<&T>; N]) -> bool>(&mut self, mut f: F) {
+        let len = self.len();
+        let mut compact = FrontCompact::new(self, 0);
+        while compact.idx < len {
+            let idx = compact.idx;
+            let ptr = compact.vec.as_ptr();
+            // SAFETY: `idx..len` is the unconsidered region, which is
+            // initialised and is not written to until `keep` or `remove`.
+            let keep = unsafe {
+                let upcoming = array::from_fn(|i| {
+                    let next = idx + 1 + i;
+                    (next < len).then(|| &*ptr.add(next))
+                });
+                f(&*ptr.add(idx), upcoming)
+            };
+            // SAFETY: `idx < len`
+            unsafe {
+                if keep {
+                    compact.keep();
+                } else {
+                    compact.remove();
+                }
+            }
+        }
+    }
+
+    fn retain_report_sorted<F: FnMut(&mut T) -> bool>(&mut self, f: F) -> VecRetainReport
+    where
+        T: PartialOrd,
+    {
+        retain_report(self, f, Some(|a: &T, b: &T| a <= b))
+    }
+}
+
+/// The shared implementation of [`RetainMoreVec::retain_report`] and
+/// [`RetainMoreVec::retain_report_sorted`], where `in_order` is the
+/// comparison used to check whether the retained elements are sorted.
+fn retain_report<T, F, O>(vec: &mut Vec<T>, mut f: F, mut in_order: Option<O>) -> VecRetainReport
+where
+    F: FnMut(&mut T) -> bool,
+    O: FnMut(&T, &T) -> bool,
+{
+    let len = vec.len();
+    let mut report = VecRetainReport {
+        sorted: in_order.as_ref().map(|_| true),
+        ..VecRetainReport::default()
+    };
+    let mut compact = FrontCompact::new(vec, 0);
+    while compact.idx < len {
+        // SAFETY: `idx < len`, and `current` is not used after the call to
+        // `keep` or `remove`
+        unsafe {
+            let current = compact.current();
+            if f(current) {
+                if let (Some(in_order), Some(true), Some(previous)) =
+                    (&mut in_order, report.sorted, compact.last_kept())
+                {
+                    report.sorted = Some(in_order(previous, current));
+                }
+                compact.keep();
+            } else {
+                report.record_removed(compact.idx);
+                compact.remove();
+            }
+        }
+    }
+    report.kept = len - compact.deleted;
+    report
+}
+
+/// Implementation of the sealed pattern for [`RetainMoreVec`]
+/// See [C-SEALED] from rust-api-guidelines for explanation
+///
+/// [C-SEALED]: https://rust-lang.github.io/api-guidelines/future-proofing.html#sealed-traits-protect-against-downstream-implementations-c-sealed
+mod sealed {
+    use alloc::vec::Vec;
+
+    pub trait Sealed {}
+    impl<T> Sealed for Vec<T> {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{rc::Rc, vec};
+
+    #[test]
+    fn retain_all_rev() {
+        // Keep at most 3 elements, preferring those at the end
+        let mut v: Vec<u32> = (0..10).collect();
+        v.retain_all_rev(|kept, _, _| kept.len() < 3);
+        assert_eq!(v, [7, 8, 9]);
+
+        // The context slices are the kept suffix and unvisited prefix
+        let mut v = vec![1, 2, 3, 4, 5];
+        let mut seen = Vec::new();
+        v.retain_all_rev(|kept, it, unvisited| {
+            seen.push((kept.to_vec(), *it, unvisited.to_vec()));
+            *it % 2 == 1
+        });
+        assert_eq!(v, [1, 3, 5]);
+        assert_eq!(
+            seen,
+            [
+                (vec![], 5, vec![1, 2, 3, 4]),
+                (vec![5], 4, vec![1, 2, 3]),
+                (vec![5], 3, vec![1, 2]),
+                (vec![3, 5], 2, vec![1]),
+                (vec![3, 5], 1, vec![]),
+            ]
+        );
+
+        let mut v: Vec<u8> = Vec::new();
+        v.retain_all_rev(|_, _, _| unreachable!());
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn retain_all_rev_drops() {
+        let tracker = Rc::new(());
+        let mut v: Vec<(usize, Rc<()>)> = (0..6).map(|i| (i, tracker.clone())).collect();
+        v.retain_all_rev(|_, (i, _), _| *i % 3 == 0);
+        assert_eq!(v.iter().map(|(i, _)| *i).collect::<Vec<_>>(), [0, 3]);
+        assert_eq!(Rc::strong_count(&tracker), 3);
+        drop(v);
+        assert_eq!(Rc::strong_count(&tracker), 1);
+    }
+
+    #[test]
+    fn retain_range() {
+        let mut v: Vec<u32> = (0..10).collect();
+        let mut visited = Vec::new();
+        let removed = v.retain_range(3..=6, |it| {
+            visited.push(*it);
+            *it % 2 == 0
+        });
+        assert_eq!(removed, 2);
+        assert_eq!(visited, [3, 4, 5, 6]);
+        assert_eq!(v, [0, 1, 2, 4, 6, 7, 8, 9]);
+
+        assert_eq!(v.retain_range(..2, |_| false), 2);
+        assert_eq!(v, [2, 4, 6, 7, 8, 9]);
+        assert_eq!(v.retain_range(4.., |_| false), 2);
+        assert_eq!(v, [2, 4, 6, 7]);
+        assert_eq!(v.retain_range(2..2, |_| unreachable!()), 0);
+        assert_eq!(v.retain_range(.., |_| true), 0);
+        assert_eq!(v, [2, 4, 6, 7]);
+    }
+
+    #[test]
+    fn retain_range_drops() {
+        let tracker = Rc::new(());
+        let mut v: Vec<(usize, Rc<()>)> = (0..8).map(|i| (i, tracker.clone())).collect();
+        assert_eq!(v.retain_range(2..6, |(i, _)| *i == 4), 3);
+        assert_eq!(
+            v.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            [0, 1, 4, 6, 7]
+        );
+        assert_eq!(Rc::strong_count(&tracker), 6);
+    }
+
+    #[test]
+    fn retain_adjust_indices() {
+        let mut v: Vec<usize> = (0..12).collect();
+        let keep = |it: &usize| !matches!(it, 0 | 3 | 4 | 5 | 8 | 11);
+        let mut indices: Vec<usize> = (0..14).chain([usize::MAX]).collect();
+        let mut expected = indices.clone();
+        for index in &mut expected {
+            *index = match v.get(*index) {
+                Some(it) if keep(it) => (0..*index).filter(|i| keep(i)).count(),
+                _ => usize::MAX,
+            };
+        }
+        v.retain_adjust_indices(&mut indices, |it| keep(it));
+        assert_eq!(v, [1, 2, 6, 7, 9, 10]);
+        assert_eq!(indices, expected);
+        for (index, &adjusted) in indices.iter().enumerate() {
+            if adjusted != usize::MAX {
+                assert_eq!(v[adjusted], index);
+            }
+        }
+
+        // Nothing removed
+        let mut v = vec!['a', 'b'];
+        let mut indices = [1, 0, 2];
+        v.retain_adjust_indices(&mut indices, |_| true);
+        assert_eq!(indices, [1, 0, usize::MAX]);
+    }
+
+    #[test]
+    fn retain_report() {
+        let mut v = vec![1, 2, 3];
+        let report = v.retain_report(|_| true);
+        assert_eq!(
+            report,
+            VecRetainReport {
+                kept: 3,
+                ..VecRetainReport::default()
+            }
+        );
+        assert!(!report.changed());
+
+        let report = v.retain_report(|_| false);
+        assert_eq!(
+            report,
+            VecRetainReport {
+                removed: 3,
+                kept: 0,
+                first_removed: Some(0),
+                last_removed: Some(2),
+                sorted: None,
+            }
+        );
+        assert!(v.is_empty());
+
+        let report = v.retain_report_sorted(|_| unreachable!());
+        assert_eq!(report.sorted, Some(true));
+    }
+
+    #[test]
+    fn retain_report_sorted() {
+        // Floats are only `PartialOrd`; NaN is never in order
+        let mut v = vec![0.5, f64::NAN, 1.5, 1.0, 2.0];
+        let report = v.retain_report_sorted(|it| !it.is_nan());
+        assert_eq!(v, [0.5, 1.5, 1.0, 2.0]);
+        assert_eq!(report.sorted, Some(false));
+        assert_eq!(
+            (report.first_removed, report.last_removed),
+            (Some(1), Some(1))
+        );
+
+        let report = v.retain_report_sorted(|it| *it != 1.0);
+        assert_eq!(v, [0.5, 1.5, 2.0]);
+        assert_eq!(report.sorted, Some(true));
+
+        let mut v = vec![1.0, f64::NAN];
+        assert_eq!(v.retain_report_sorted(|_| true).sorted, Some(false));
+
+        // The comparison sees the elements after the predicate modified them
+        let mut v = vec![1, 2, 3];
+        let report = v.retain_report_sorted(|it| {
+            *it = 10 - *it;
+            true
+        });
+        assert_eq!(report.sorted, Some(false));
+    }
+
+    #[test]
+    fn retain_window_dedup() {
+        let samples: [&[u8]; 5] = [&[], &[1], &[1, 1, 1], &[1, 2, 2, 3, 1, 1], &[4, 4, 5, 5, 4]];
+        for sample in samples {
+            let mut expected = sample.to_vec();
+            expected.dedup();
+            let mut v = sample.to_vec();
+            v.retain_window(|it, [next]| next != Some(it));
+            assert_eq!(v, expected);
+        }
+
+        // Unlike `Vec::dedup`, the last element of each run is the one kept
+        let mut v = vec![(1, 'a'), (1, 'b'), (2, 'c'), (2, 'd')];
+        v.retain_window(|(it, _), [next]| next.map(|(next, _)| next) != Some(it));
+        assert_eq!(v, [(1, 'b'), (2, 'd')]);
+    }
+
+    #[test]
+    fn retain_window_local_maximum() {
+        // Keep the elements which are larger than each of the next 3
+        let mut v = vec![5, 1, 4, 2, 3, 0, 7, 6];
+        v.retain_window(|it, upcoming: [Option<&i32>; 3]| {
+            upcoming.iter().flatten().all(|next| *next < it)
+        });
+        assert_eq!(v, [5, 4, 7, 6]);
+
+        // An empty window sees nothing
+        let mut v = vec![1, 2, 3];
+        v.retain_window(|it, []| *it != 2);
+        assert_eq!(v, [1, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "range end index 5 out of range for slice of length 4")]
+    fn retain_range_out_of_bounds() {
+        let mut v = vec![1, 2, 3, 4];
+        v.retain_range(1..5, |_| true);
+    }
+
+    #[test]
+    #[should_panic(expected = "slice index starts at 3 but ends at 2")]
+    fn retain_range_inverted() {
+        let mut v = vec![1, 2, 3, 4];
+        #[allow(clippy::reversed_empty_ranges)]
+        v.retain_range(3..2, |_| true);
+    }
+}
@@ -0,0 +1,143 @@
+use core::{iter::FusedIterator, ptr, slice};
+
+use alloc::vec::Vec;
+
+/// An iterator which removes the elements of a vector matching a predicate,
+/// and yields them by value.
+///
+/// Created by [`extract_if_ctx`](crate::RetainMoreVec::extract_if_ctx).
+///
+/// If the iterator is dropped before it is exhausted, the elements which
+/// have not yet been considered are kept. If the iterator is leaked (e.g.
+/// with [`core::mem::forget`]), the vector may lose any number of elements.
+pub struct ExtractIfCtx<'a, T, F> {
+    vec: &'a mut Vec<T>,
+    /// The index of the next element to consider.
+    idx: usize,
+    /// The number of elements yielded so far.
+    deleted: usize,
+    /// The length of the vector before the iterator was created.
+    old_len: usize,
+    pred: F,
+}
+
+impl<'a, T, F> ExtractIfCtx<'a, T, F> {
+    pub(super) fn new(vec: &'a mut Vec<T>, pred: F) -> Self {
+        let old_len = vec.len();
+        // Avoid double drops (and exposing moved out elements) if the
+        // predicate panics or the iterator is leaked
+        // SAFETY: 0 <= old_len
+        unsafe { vec.set_len(0) };
+        ExtractIfCtx {
+            vec,
+            idx: 0,
+            deleted: 0,
+            old_len,
+            pred,
+        }
+    }
+}
+
+impl<T, F: FnMut(&[T], &mut T, &[T]) -> bool> Iterator for ExtractIfCtx<'_, T, F> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        while self.idx < self.old_len {
+            let ptr = self.vec.as_mut_ptr();
+            // SAFETY: The retained prefix `0..idx - deleted`, the current
+            // element `idx` and the unvisited suffix `idx + 1..old_len` are
+            // disjoint and initialised. The slices are only borrowed for the
+            // call to the predicate, so the current element is not aliased
+            // when it is moved out.
+            let extract = unsafe {
+                let retained = slice::from_raw_parts(ptr, self.idx - self.deleted);
+                let current = &mut *ptr.add(self.idx);
+                let rest =
+                    slice::from_raw_parts(ptr.add(self.idx + 1), self.old_len - self.idx - 1);
+                (self.pred)(retained, current, rest)
+            };
+            let idx = self.idx;
+            self.idx += 1;
+            if extract {
+                self.deleted += 1;
+                // SAFETY: `idx` is now in the gap, so will not be read again
+                return Some(unsafe { ptr::read(ptr.add(idx)) });
+            } else if self.deleted > 0 {
+                // SAFETY: `idx - deleted` is in the gap, so is distinct from
+                // `idx` and can be overwritten.
+                unsafe { ptr::copy_nonoverlapping(ptr.add(idx), ptr.add(idx - self.deleted), 1) };
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.old_len - self.idx))
+    }
+}
+
+impl<T, F: FnMut(&[T], &mut T, &[T]) -> bool> FusedIterator for ExtractIfCtx<'_, T, F> {}
+
+impl<T, F> Drop for ExtractIfCtx<'_, T, F> {
+    fn drop(&mut self) {
+        // SAFETY: Both regions are within the allocation, and the
+        // unconsidered region is moved into the gap, so every element is
+        // initialised exactly once in `0..old_len - deleted`.
+        unsafe {
+            if self.deleted > 0 {
+                let ptr = self.vec.as_mut_ptr();
+                ptr::copy(
+                    ptr.add(self.idx),
+                    ptr.add(self.idx - self.deleted),
+                    self.old_len - self.idx,
+                );
+            }
+            self.vec.set_len(self.old_len - self.deleted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RetainMoreVec as _;
+    use alloc::{rc::Rc, vec, vec::Vec};
+
+    #[test]
+    fn extract_if_ctx() {
+        // Extract every element equal to one which has already been retained
+        let mut v = vec![1, 2, 1, 3, 2, 1];
+        let duplicates: Vec<i32> = v
+            .extract_if_ctx(|retained, it, _| retained.contains(it))
+            .collect();
+        assert_eq!(v, [1, 2, 3]);
+        assert_eq!(duplicates, [1, 2, 1]);
+
+        // The unvisited suffix excludes the current element
+        let mut v = vec![3, 1, 2];
+        let extracted: Vec<i32> = v
+            .extract_if_ctx(|_, it, rest| rest.iter().any(|other| other < it))
+            .collect();
+        assert_eq!(extracted, [3]);
+        assert_eq!(v, [1, 2]);
+    }
+
+    #[test]
+    fn extract_if_ctx_early_drop() {
+        let tracker = Rc::new(());
+        let mut v: Vec<(usize, Rc<()>)> = (0..8).map(|i| (i, tracker.clone())).collect();
+        let mut iter = v.extract_if_ctx(|_, (i, _), _| *i % 2 == 1);
+        assert_eq!(iter.next().map(|(i, _)| i), Some(1));
+        assert_eq!(iter.next().map(|(i, _)| i), Some(3));
+        drop(iter);
+        // Only the yielded elements are removed, even though 4 had been kept
+        assert_eq!(
+            v.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            [0, 2, 4, 5, 6, 7]
+        );
+        assert_eq!(Rc::strong_count(&tracker), 7);
+
+        // Dropping without calling `next` removes nothing
+        drop(v.extract_if_ctx(|_, _, _| true));
+        assert_eq!(v.len(), 6);
+    }
+}
@@ -0,0 +1,34 @@
+/// Statistics about a single retain pass over a vector.
+///
+/// Returned by [`retain_report`](crate::RetainMoreVec::retain_report) and
+/// [`retain_report_sorted`](crate::RetainMoreVec::retain_report_sorted).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct VecRetainReport {
+    /// The number of elements which were removed.
+    pub removed: usize,
+    /// The number of elements which were kept.
+    pub kept: usize,
+    /// The original index of the first element which was removed.
+    pub first_removed: Option<usize>,
+    /// The original index of the last element which was removed.
+    pub last_removed: Option<usize>,
+    /// Whether the retained elements are in ascending order.
+    ///
+    /// This is only computed by
+    /// [`retain_report_sorted`](crate::RetainMoreVec::retain_report_sorted),
+    /// and is `None` otherwise.
+    pub sorted: Option<bool>,
+}
+
+impl VecRetainReport {
+    /// Whether any elements were removed.
+    pub fn changed(&self) -> bool {
+        self.removed > 0
+    }
+
+    pub(crate) fn record_removed(&mut self, index: usize) {
+        self.removed += 1;
+        self.first_removed.get_or_insert(index);
+        self.last_removed = Some(index);
+    }
+}
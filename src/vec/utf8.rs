@@ -0,0 +1,167 @@
+use core::{fmt, str};
+
+use alloc::vec::Vec;
+
+/// The error returned by [`retain_str_in_vec`](crate::retain_str_in_vec)
+/// when the vector does not contain valid UTF-8.
+///
+/// This mirrors [`core::str::Utf8Error`], describing the first invalid
+/// sequence in the vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Utf8RetainError {
+    valid_up_to: usize,
+    error_len: Option<usize>,
+}
+
+impl Utf8RetainError {
+    /// The byte offset of the first invalid sequence, which is also the
+    /// number of bytes at the start of the vector which are valid UTF-8.
+    pub fn valid_up_to(&self) -> usize {
+        self.valid_up_to
+    }
+
+    /// The length of the invalid byte sequence, or `None` if the vector ends
+    /// part way through a character. See [`core::str::Utf8Error::error_len`].
+    pub fn error_len(&self) -> Option<usize> {
+        self.error_len
+    }
+}
+
+impl fmt::Display for Utf8RetainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.error_len {
+            Some(len) => write!(
+                f,
+                "invalid utf-8 sequence of {} bytes from index {}",
+                len, self.valid_up_to
+            ),
+            None => write!(
+                f,
+                "incomplete utf-8 byte sequence from index {}",
+                self.valid_up_to
+            ),
+        }
+    }
+}
+
+/// Truncates the vector to the kept bytes when dropped, so that a panicking
+/// predicate leaves only the characters kept before it.
+struct Compactor<'a> {
+    v: &'a mut Vec<u8>,
+    kept: usize,
+}
+
+impl Drop for Compactor<'_> {
+    fn drop(&mut self) {
+        self.v.truncate(self.kept);
+    }
+}
+
+/// Retains only the characters specified by the predicate, in a vector of
+/// bytes which should contain UTF-8.
+///
+/// On success, this is the same as converting `v` to a
+/// [`String`](alloc::string::String), calling
+/// [`String::retain`](alloc::string::String::retain) and converting back,
+/// without needing to take ownership of `v`. If `v` is not valid UTF-8, an
+/// error describing the first invalid sequence is returned, and `v` is left
+/// untouched.
+///
+/// The whole vector is validated before the predicate is first called, so
+/// invalid input is never partially filtered. This costs one extra read of
+/// the vector, using the fast validation of [`core::str::from_utf8`], which
+/// is small compared to calling the predicate on each character.
+///
+/// If the predicate panics, `v` is left containing the characters which had
+/// been kept before the panic.
+///
+/// [`String`]: alloc::string::String
+///
+/// # Usage
+///
+/// ```
+/// use retain_more::retain_str_in_vec;
+/// let mut bytes = b"caf\xc3\xa9 au lait".to_vec();
+/// retain_str_in_vec(&mut bytes, |c| c != ' ').unwrap();
+/// assert_eq!(bytes, "caf\u{e9}aulait".as_bytes());
+///
+/// let mut invalid = b"ok \xff".to_vec();
+/// let err = retain_str_in_vec(&mut invalid, |c| c != ' ').unwrap_err();
+/// assert_eq!((err.valid_up_to(), err.error_len()), (3, Some(1)));
+/// assert_eq!(invalid, b"ok \xff");
+/// ```
+pub fn retain_str_in_vec<F: FnMut(char) -> bool>(
+    v: &mut Vec<u8>,
+    mut f: F,
+) -> Result<(), Utf8RetainError> {
+    if let Err(e) = str::from_utf8(v) {
+        return Err(Utf8RetainError {
+            valid_up_to: e.valid_up_to(),
+            error_len: e.error_len(),
+        });
+    }
+    let len = v.len();
+    let mut compactor = Compactor { v, kept: 0 };
+    let mut idx = 0;
+    while idx < len {
+        // SAFETY: The whole vector was valid UTF-8, and `idx` is on a
+        // character boundary. Bytes are only written below `kept <= idx`, so
+        // `idx..` is untouched.
+        let ch = unsafe { str::from_utf8_unchecked(&compactor.v[idx..]) }
+            .chars()
+            .next()
+            .expect("idx is before the end of the vector");
+        let ch_len = ch.len_utf8();
+        if f(ch) {
+            let kept = compactor.kept;
+            compactor.v.copy_within(idx..idx + ch_len, kept);
+            compactor.kept += ch_len;
+        }
+        idx += ch_len;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::{String, ToString};
+
+    #[test]
+    fn matches_string_retain() {
+        let samples = ["", "a b c", "\u{e9} \u{1F980} x ", "   "];
+        for sample in samples {
+            let mut expected = String::from(sample);
+            expected.retain(|c| c != ' ');
+            let mut v = sample.as_bytes().to_vec();
+            retain_str_in_vec(&mut v, |c| c != ' ').unwrap();
+            assert_eq!(v, expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn untouched_on_error() {
+        // Removable content before an invalid byte
+        let input = b"a b \xc3\xa9 \xe2\x28\xa1 c";
+        let mut v = input.to_vec();
+        let mut calls = 0;
+        let err = retain_str_in_vec(&mut v, |c| {
+            calls += 1;
+            c != ' '
+        })
+        .unwrap_err();
+        assert_eq!(v, input);
+        assert_eq!(calls, 0);
+        assert_eq!((err.valid_up_to(), err.error_len()), (7, Some(1)));
+
+        // Ending part way through a character
+        let mut v = b"a \xf0\x9f\xa6".to_vec();
+        let err = retain_str_in_vec(&mut v, |_| false).unwrap_err();
+        assert_eq!((err.valid_up_to(), err.error_len()), (2, None));
+        assert_eq!(v, b"a \xf0\x9f\xa6");
+        assert_eq!(
+            err.to_string(),
+            "incomplete utf-8 byte sequence from index 2"
+        );
+    }
+}
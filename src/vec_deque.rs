@@ -0,0 +1,187 @@
+use alloc::collections::VecDeque;
+
+mod utf8;
+
+pub use utf8::Utf8Error;
+
+/// More advanced versions of [`VecDeque::retain`], implemented as extension
+/// methods on [`VecDeque`].
+///
+/// This trait is sealed and cannot be implemented for types outside of
+/// `retain_more`
+pub trait RetainMoreVecDeque<T>: sealed::Sealed {
+    /// Replaces each element with the result of `f`, removing the elements
+    /// for which it returns `None`.
+    ///
+    /// The elements are passed to `f` by value, in order from front to back,
+    /// and the order of the results is preserved. This allows transforming
+    /// elements which cannot be cheaply cloned or modified in place.
+    ///
+    /// This is implemented by rotating the ring buffer: each element is popped
+    /// from the front, and its replacement (if any) is pushed to the back.
+    /// This moves each element once, the same as a compaction, but works
+    /// directly across the wrap point of the buffer, without first making it
+    /// contiguous. As the deque never grows, the buffer is never reallocated.
+    ///
+    /// If `f` panics, the element being considered is dropped as part of
+    /// unwinding. The deque is left containing the results produced so far,
+    /// followed by the elements which have not yet been considered, in their
+    /// original order.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreVecDeque as _;
+    /// use std::collections::VecDeque;
+    /// let mut queue: VecDeque<String> = ["a", "", "bc", ""].iter().map(|it| it.to_string()).collect();
+    /// queue.retain_map(|mut it| {
+    ///     if it.is_empty() {
+    ///         None
+    ///     } else {
+    ///         it.push('!');
+    ///         Some(it)
+    ///     }
+    /// });
+    /// assert_eq!(queue, ["a!", "bc!"]);
+    /// ```
+    fn retain_map<F: FnMut(T) -> Option<T>>(&mut self, f: F);
+}
+
+/// Text processing for [`VecDeque<u8>`], treating it as a ring buffer of
+/// UTF-8 text.
+///
+/// This trait is sealed and cannot be implemented for types outside of
+/// `retain_more`
+pub trait RetainMoreByteDeque: sealed::Sealed {
+    /// Retains only the characters specified by the predicate, returning the
+    /// number of characters removed.
+    ///
+    /// The contents of the deque are decoded as UTF-8, including characters
+    /// whose bytes are split across the wrap point of the ring buffer, and the
+    /// kept characters are compacted within the buffer, without making it
+    /// contiguous.
+    ///
+    /// If the deque does not contain valid UTF-8, an error is returned and
+    /// the deque is left untouched. If the predicate panics, the deque is left
+    /// containing the kept characters followed by the characters which have
+    /// not yet been considered, so is still valid UTF-8.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// use retain_more::RetainMoreByteDeque as _;
+    /// use std::collections::VecDeque;
+    /// let mut buffer: VecDeque<u8> = "caf\u{e9} \u{1F980}!".bytes().collect();
+    /// assert_eq!(buffer.retain_utf8(|c| c.is_alphabetic()), Ok(3));
+    /// assert_eq!(buffer, "caf\u{e9}".as_bytes());
+    ///
+    /// buffer.push_back(0xFF);
+    /// assert_eq!(buffer.retain_utf8(|_| true).unwrap_err().valid_up_to(), 5);
+    /// ```
+    fn retain_utf8<F: FnMut(char) -> bool>(&mut self, f: F) -> Result<usize, Utf8Error>;
+}
+
+impl RetainMoreByteDeque for VecDeque<u8> {
+    fn retain_utf8<F: FnMut(char) -> bool>(&mut self, f: F) -> Result<usize, Utf8Error> {
+        utf8::retain_utf8(self, f)
+    }
+}
+
+/// Restores the order of the deque during
+/// [`RetainMoreVecDeque::retain_map`].
+///
+/// The deque contains the `remaining` elements which have not yet been
+/// considered, followed by the results produced so far.
+struct Rotation<'a, T> {
+    deque: &'a mut VecDeque<T>,
+    remaining: usize,
+}
+
+impl<T> Drop for Rotation<'_, T> {
+    fn drop(&mut self) {
+        self.deque.rotate_left(self.remaining);
+    }
+}
+
+impl<T> RetainMoreVecDeque<T> for VecDeque<T> {
+    // This is safe code, rather than a compaction over `as_mut_slices` using
+    // raw pointers, since `VecDeque` provides no way to shorten itself without
+    // dropping the elements in the gap. The `retain_map` benchmark shows this
+    // to be around twice as slow as `make_contiguous` followed by a
+    // `Vec::retain_mut`, but it needs neither an extra pass over the buffer
+    // nor a way to modify the elements in place.
+    fn retain_map<F: FnMut(T) -> Option<T>>(&mut self, mut f: F) {
+        let mut rotation = Rotation {
+            remaining: self.len(),
+            deque: self,
+        };
+        while rotation.remaining > 0 {
+            let Some(it) = rotation.deque.pop_front() else {
+                unreachable!("the deque has at least `remaining` elements");
+            };
+            rotation.remaining -= 1;
+            if let Some(it) = f(it) {
+                rotation.deque.push_back(it);
+            }
+        }
+    }
+}
+
+/// Implementation of the sealed pattern for [`RetainMoreVecDeque`] and
+/// [`RetainMoreByteDeque`]
+/// See [C-SEALED] from rust-api-guidelines for explanation
+///
+/// [C-SEALED]: https://rust-lang.github.io/api-guidelines/future-proofing.html#sealed-traits-protect-against-downstream-implementations-c-sealed
+mod sealed {
+    use alloc::collections::VecDeque;
+
+    pub trait Sealed {}
+    impl<T> Sealed for VecDeque<T> {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{rc::Rc, vec::Vec};
+
+    /// A deque whose contents wrap around the end of its buffer.
+    fn wrapped(len: u32) -> VecDeque<u32> {
+        let mut deque = VecDeque::with_capacity(len as usize);
+        for i in (0..len / 2).rev() {
+            deque.push_front(i);
+        }
+        for i in len / 2..len {
+            deque.push_back(i);
+        }
+        deque
+    }
+
+    #[test]
+    fn retain_map_wrapped() {
+        let mut deque = wrapped(10);
+        let (front, back) = deque.as_slices();
+        assert!(!front.is_empty() && !back.is_empty());
+        let capacity = deque.capacity();
+
+        deque.retain_map(|it| (it % 3 != 0).then(|| it * 10));
+        assert_eq!(deque, [10, 20, 40, 50, 70, 80]);
+        assert_eq!(deque.capacity(), capacity);
+
+        deque.retain_map(Some);
+        assert_eq!(deque, [10, 20, 40, 50, 70, 80]);
+    }
+
+    #[test]
+    fn retain_map_remove_all() {
+        let tracker = Rc::new(());
+        let mut deque: VecDeque<Rc<()>> = (0..5).map(|_| tracker.clone()).collect();
+        deque.rotate_left(2);
+        deque.retain_map(|_| None);
+        assert!(deque.is_empty());
+        assert_eq!(Rc::strong_count(&tracker), 1);
+
+        let mut empty: VecDeque<Vec<u8>> = VecDeque::new();
+        empty.retain_map(|_| unreachable!());
+        assert!(empty.is_empty());
+    }
+}
@@ -0,0 +1,250 @@
+use core::{fmt, str};
+
+use alloc::collections::VecDeque;
+
+/// The error returned by
+/// [`retain_utf8`](crate::RetainMoreByteDeque::retain_utf8) when the deque
+/// does not contain valid UTF-8.
+///
+/// This mirrors [`core::str::Utf8Error`], with the offsets measured from the
+/// front of the deque rather than the start of a slice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Utf8Error {
+    valid_up_to: usize,
+    error_len: Option<usize>,
+}
+
+impl Utf8Error {
+    /// The number of bytes from the front of the deque which are valid UTF-8.
+    pub fn valid_up_to(&self) -> usize {
+        self.valid_up_to
+    }
+
+    /// The length of the invalid byte sequence, or `None` if the deque ends
+    /// part way through a character. See [`core::str::Utf8Error::error_len`].
+    pub fn error_len(&self) -> Option<usize> {
+        self.error_len
+    }
+}
+
+impl fmt::Display for Utf8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.error_len {
+            Some(len) => write!(
+                f,
+                "invalid utf-8 sequence of {} bytes from index {}",
+                len, self.valid_up_to
+            ),
+            None => write!(
+                f,
+                "incomplete utf-8 byte sequence from index {}",
+                self.valid_up_to
+            ),
+        }
+    }
+}
+
+/// The length of the character which starts with the byte `lead`. `lead` must
+/// be the first byte of a (possibly incomplete) valid UTF-8 sequence.
+fn char_width(lead: u8) -> usize {
+    match lead {
+        0x00..=0x7F => 1,
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        _ => 4,
+    }
+}
+
+/// Check that `front` followed by `back` is valid UTF-8, where a character's
+/// bytes may be split between the two.
+fn validate(front: &[u8], back: &[u8]) -> Result<(), Utf8Error> {
+    let straddling = match str::from_utf8(front) {
+        Ok(_) => 0,
+        // `front` ends part way through a character, which may be completed
+        // by the start of `back`
+        Err(e) if e.error_len().is_none() => {
+            let start = e.valid_up_to();
+            let head = &front[start..];
+            let width = char_width(head[0]);
+            // If `back` is too short to complete the character, what there is
+            // of it is still checked, so that an invalid byte is reported as
+            // it would be without the wrap
+            let needed = (width - head.len()).min(back.len());
+            let mut buf = [0; 4];
+            buf[..head.len()].copy_from_slice(head);
+            buf[head.len()..head.len() + needed].copy_from_slice(&back[..needed]);
+            if let Err(e) = str::from_utf8(&buf[..head.len() + needed]) {
+                return Err(Utf8Error {
+                    valid_up_to: start,
+                    error_len: e.error_len(),
+                });
+            }
+            needed
+        }
+        Err(e) => {
+            return Err(Utf8Error {
+                valid_up_to: e.valid_up_to(),
+                error_len: e.error_len(),
+            })
+        }
+    };
+    match str::from_utf8(&back[straddling..]) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(Utf8Error {
+            valid_up_to: front.len() + straddling + e.valid_up_to(),
+            error_len: e.error_len(),
+        }),
+    }
+}
+
+/// Closes the gap left by the removed characters during
+/// [`retain_utf8`](crate::RetainMoreByteDeque::retain_utf8).
+///
+/// The deque is split into three regions:
+///  - `0..write`: The bytes which have been kept so far
+///  - `write..read`: A gap of bytes which have been removed or copied
+///  - `read..`: The bytes which have not yet been considered
+///
+/// Each region is valid UTF-8, so removing the gap (even after a panic)
+/// leaves the deque valid.
+struct Gap<'a> {
+    deque: &'a mut VecDeque<u8>,
+    write: usize,
+    read: usize,
+}
+
+impl Drop for Gap<'_> {
+    fn drop(&mut self) {
+        self.deque.drain(self.write..self.read);
+    }
+}
+
+pub(super) fn retain_utf8<F: FnMut(char) -> bool>(
+    deque: &mut VecDeque<u8>,
+    mut f: F,
+) -> Result<usize, Utf8Error> {
+    let (front, back) = deque.as_slices();
+    validate(front, back)?;
+    let mut removed = 0;
+    let mut gap = Gap {
+        deque,
+        write: 0,
+        read: 0,
+    };
+    while gap.read < gap.deque.len() {
+        let width = char_width(gap.deque[gap.read]);
+        let mut buf = [0; 4];
+        for (i, b) in buf[..width].iter_mut().enumerate() {
+            *b = gap.deque[gap.read + i];
+        }
+        let ch = str::from_utf8(&buf[..width])
+            .ok()
+            .and_then(|it| it.chars().next())
+            .expect("the deque was validated as UTF-8");
+        if f(ch) {
+            if gap.write != gap.read {
+                for (i, &b) in buf[..width].iter().enumerate() {
+                    gap.deque[gap.write + i] = b;
+                }
+            }
+            gap.write += width;
+        } else {
+            removed += 1;
+        }
+        gap.read += width;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetainMoreByteDeque as _;
+    use alloc::vec::Vec;
+
+    /// Deques containing `bytes`, with the start of the buffer at each
+    /// possible position, so that the wrap point falls between every pair of
+    /// bytes.
+    fn rotations(bytes: &[u8]) -> impl Iterator<Item = VecDeque<u8>> + '_ {
+        let capacity = VecDeque::<u8>::with_capacity(bytes.len()).capacity();
+        (0..capacity).map(move |shift| {
+            let mut deque = VecDeque::with_capacity(bytes.len());
+            assert_eq!(deque.capacity(), capacity);
+            for _ in 0..shift {
+                deque.push_back(0);
+                deque.pop_front();
+            }
+            deque.extend(bytes);
+            deque
+        })
+    }
+
+    #[test]
+    fn retain_utf8_wrapped() {
+        let text = "a\u{1F980}b\u{e9}c\u{1F980}";
+        let mut straddled = [false; 4];
+        for mut deque in rotations(text.as_bytes()) {
+            // Record where the first crab is split by the wrap point
+            let front_len = deque.as_slices().0.len();
+            if (2..5).contains(&front_len) {
+                straddled[front_len - 1] = true;
+            }
+            let removed = deque.retain_utf8(|c| c != 'b' && c != '\u{1F980}');
+            assert_eq!(removed, Ok(3));
+            assert_eq!(
+                deque.iter().copied().collect::<Vec<u8>>(),
+                "a\u{e9}c".as_bytes()
+            );
+        }
+        assert_eq!(straddled, [false, true, true, true]);
+    }
+
+    #[test]
+    fn retain_utf8_invalid() {
+        let mut invalid = "ab\u{1F980}".as_bytes().to_vec();
+        invalid[4] = b'x';
+        for mut deque in rotations(&invalid) {
+            let before = deque.clone();
+            let err = deque.retain_utf8(|_| false).unwrap_err();
+            assert_eq!((err.valid_up_to(), err.error_len()), (2, Some(2)));
+            assert_eq!(deque, before);
+        }
+
+        let truncated = &"ab\u{1F980}".as_bytes()[..5];
+        for mut deque in rotations(truncated) {
+            let err = deque.retain_utf8(|_| false).unwrap_err();
+            assert_eq!((err.valid_up_to(), err.error_len()), (2, None));
+        }
+
+        // An invalid sequence split by the wrap point, with too few bytes
+        // after it to complete the character, is reported as it is by
+        // `str::from_utf8`
+        for invalid in [&b"a\xF0A"[..], b"a\xF0\x9FA", b"\xE2\x98"] {
+            let expected = core::str::from_utf8(invalid).unwrap_err();
+            for mut deque in rotations(invalid) {
+                let err = deque.retain_utf8(|_| false).unwrap_err();
+                assert_eq!(
+                    (err.valid_up_to(), err.error_len()),
+                    (expected.valid_up_to(), expected.error_len()),
+                    "{:?}",
+                    deque.as_slices()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn retain_utf8_matches_string() {
+        let text = "Hello, w\u{f6}rld! \u{1F980} \u{65e5}\u{672c}";
+        let predicate = |c: char| c.is_alphabetic() || c == ' ';
+        let mut expected = alloc::string::String::from(text);
+        expected.retain(predicate);
+        for mut deque in rotations(text.as_bytes()) {
+            deque.retain_utf8(predicate).unwrap();
+            assert_eq!(
+                deque.iter().copied().collect::<Vec<u8>>(),
+                expected.as_bytes()
+            );
+        }
+    }
+}
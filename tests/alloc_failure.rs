@@ -0,0 +1,73 @@
+//! The tests which require a global allocator which can be made to fail, to
+//! check the state of a string after a fallible method reports an error
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    ptr,
+};
+
+use retain_more::{RetainDecision, RetainMoreString as _};
+
+struct FailingAllocator;
+
+thread_local! {
+    /// Whether allocations made by this thread fail. Tests run on separate
+    /// threads, so they don't affect each other.
+    static FAIL: Cell<bool> = const { Cell::new(false) };
+}
+
+fn failing() -> bool {
+    FAIL.try_with(Cell::get).unwrap_or(false)
+}
+
+unsafe impl GlobalAlloc for FailingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if failing() {
+            return ptr::null_mut();
+        }
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if failing() {
+            return ptr::null_mut();
+        }
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: FailingAllocator = FailingAllocator;
+
+/// Run `f` with every allocation on this thread failing.
+fn without_allocation<R>(f: impl FnOnce() -> R) -> R {
+    FAIL.with(|fail| fail.set(true));
+    let result = f();
+    FAIL.with(|fail| fail.set(false));
+    result
+}
+
+#[test]
+fn retain_replace_keeps_the_rest() {
+    let escape = |c: char, _: &mut str| match c {
+        '<' => RetainDecision::Replace("&lt;"),
+        '-' => RetainDecision::Remove,
+        '\u{e9}' => RetainDecision::Replace("e"),
+        _ => RetainDecision::Keep,
+    };
+    let mut s = "-\u{e9}a<b>-<c".to_string();
+    let result = without_allocation(|| s.retain_replace(escape));
+    assert!(result.is_err());
+    // The first `<` doesn't fit in the gap of 2 bytes, so it and everything
+    // after it are kept as they were
+    assert_eq!(s, "ea<b>-<c");
+
+    // Nothing is lost, so the pass can be finished once memory is available
+    s.retain_replace(escape).unwrap();
+    assert_eq!(s, "ea&lt;b>&lt;c");
+}
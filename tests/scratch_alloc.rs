@@ -0,0 +1,101 @@
+//! The tests which require a counting global allocator, to check that a
+//! warmed [`RetainScratch`] avoids allocating
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use retain_more::{RetainMoreString as _, RetainScratch};
+
+struct CountingAllocator;
+
+thread_local! {
+    /// The number of allocations made by this thread. Tests run on
+    /// separate threads, so they don't see each other's allocations.
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// The number of allocations made by `f`.
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn retain_sparse_with_warm_scratch() {
+    let inputs: Vec<String> = ["x1y2z3", "no digits", "\u{e9}4\u{1F980}5", "12345678"]
+        .iter()
+        .map(|s| s.repeat(20))
+        .collect();
+    // Without a scratch, the characters are gathered in a new buffer
+    let mut s = inputs[0].clone();
+    assert_ne!(
+        allocations(|| s.retain_sparse(4, |c| c.is_ascii_digit())),
+        0
+    );
+
+    let mut scratch = RetainScratch::new();
+    // Both the gathering and the fallback paths
+    for hint in [4, 1000] {
+        for input in &inputs {
+            let mut s = input.clone();
+            s.retain_sparse_with_scratch(hint, &mut scratch, |c| c.is_ascii_digit());
+        }
+        for input in &inputs {
+            let mut s = input.clone();
+            let count = allocations(|| {
+                s.retain_sparse_with_scratch(hint, &mut scratch, |c| c.is_ascii_digit())
+            });
+            assert_eq!(count, 0, "{:?} with hint {}", input, hint);
+            let mut expected = input.clone();
+            expected.retain(|c| c.is_ascii_digit());
+            assert_eq!(s, expected);
+        }
+    }
+}
+
+#[test]
+fn repair_balanced_with_warm_scratch() {
+    let pairs = [('(', ')'), ('[', ']')];
+    let inputs: Vec<String> = ["(a [b)] c)", "((((", "]]]] x", "[balanced (text)]"]
+        .iter()
+        .map(|s| s.repeat(30))
+        .collect();
+    let mut scratch = RetainScratch::new();
+    for input in &inputs {
+        input
+            .clone()
+            .repair_balanced_with_scratch(&pairs, &mut scratch);
+    }
+    for input in &inputs {
+        let mut s = input.clone();
+        let mut expected = input.clone();
+        let count = allocations(|| {
+            s.repair_balanced_with_scratch(&pairs, &mut scratch);
+        });
+        assert_eq!(count, 0, "{:?}", input);
+        expected.repair_balanced(&pairs);
+        assert_eq!(s, expected);
+    }
+}
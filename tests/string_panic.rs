@@ -1,7 +1,7 @@
 //! The tests which require access to [`std::panic::catch_unwind`], which is
 //! unavailable in a `no_std` crate
 
-use retain_more::RetainMoreString as _;
+use retain_more::{RetainDecision, RetainMoreString as _, Route};
 
 #[test]
 fn retain_default_safety() {
@@ -23,6 +23,7 @@ fn retain_default_safety() {
 /// Independently discovered reproduction of
 /// https://github.com/rust-lang/rust/issues/78498
 #[test]
+#[allow(clippy::needless_return)]
 fn retain_all_safety_78498() {
     let mut index = 0;
     let mut input = "૱uu".to_string();
@@ -40,3 +41,162 @@ fn retain_all_safety_78498() {
     .unwrap_err();
     assert!(std::str::from_utf8(input.as_bytes()).is_ok());
 }
+
+#[test]
+fn retain_observed_panic() {
+    let mut s = "a1b2c3".to_string();
+    let mut removed = Vec::new();
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        s.retain_observed(
+            |c| {
+                if c == 'c' {
+                    panic!("stop here")
+                }
+                c.is_alphabetic()
+            },
+            |offset, c| removed.push((offset, c)),
+        )
+    }))
+    .unwrap_err();
+    assert_eq!(removed, [(1, '1'), (3, '2')]);
+    assert!(std::str::from_utf8(s.as_bytes()).is_ok());
+}
+
+#[test]
+fn retain_route_panic() {
+    let mut s = "a\u{e9}b\u{1F980}c\u{2603}d".to_string();
+    let mut quarantine = String::new();
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        s.retain_route(&mut quarantine, |c| match c {
+            'c' => panic!("stop here"),
+            '\u{e9}' => Route::Drop,
+            c if c.is_ascii() => Route::Keep,
+            _ => Route::Divert,
+        })
+    }))
+    .unwrap_err();
+    assert_eq!(s, "ab");
+    assert_eq!(quarantine, "\u{1F980}");
+}
+
+#[test]
+fn extract_if_panic() {
+    let mut s = "1\u{e9}2\u{1F980}3x4".to_string();
+    let mut extracted = Vec::new();
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        for item in s.extract_if(|c, _| {
+            if c == 'x' {
+                panic!("stop here")
+            }
+            c.is_ascii_digit()
+        }) {
+            extracted.push(item);
+        }
+    }))
+    .unwrap_err();
+    assert_eq!(extracted, [(0, '1'), (3, '2'), (8, '3')]);
+    // The unvisited characters are kept, including the one being considered
+    assert_eq!(s, "\u{e9}\u{1F980}x4");
+}
+
+/// As in `retain_all_safety_78498`, but where the iterator is dropped whilst
+/// unwinding after some characters have been removed.
+#[test]
+fn extract_if_safety_78498() {
+    let mut index = 0;
+    let mut input = "૱uu".to_string();
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        input
+            .extract_if(|_, _| {
+                let ret = match index {
+                    0 => true,
+                    2 => panic!("What happens here"),
+                    _ => false,
+                };
+                index += 1;
+                ret
+            })
+            .for_each(drop)
+    }))
+    .unwrap_err();
+    assert_eq!(input, "uu");
+}
+
+#[test]
+fn retain_zip_panic() {
+    let mut s = "a\u{e9}b\u{1F980}c".to_string();
+    let mut meta = vec![0, 1, 2, 3, 4];
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        s.retain_zip(&mut meta, |c, &it| {
+            if c == '\u{1F980}' {
+                panic!("stop here")
+            }
+            it % 2 == 0
+        })
+    }))
+    .unwrap_err();
+    // The string and the metadata are still in step
+    assert_eq!(s, "ab\u{1F980}c");
+    assert_eq!(meta, [0, 2, 3, 4]);
+}
+
+#[test]
+fn retain_map_panic() {
+    // Panicking after the string has grown
+    let mut s = "xa\u{1F980}bc".to_string();
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        s.retain_map(|c, _| match c {
+            'a' => Some('\u{e9}'),
+            'c' => panic!("stop here"),
+            c => Some(c),
+        })
+    }))
+    .unwrap_err();
+    assert_eq!(s, "x\u{e9}\u{1F980}b");
+
+    // Panicking whilst replacing in place
+    let mut s = "\u{1F980}a\u{e9}b".to_string();
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        s.retain_map(|c, _| match c {
+            '\u{1F980}' => Some('c'),
+            'b' => panic!("stop here"),
+            c => Some(c.to_ascii_uppercase()),
+        })
+    }))
+    .unwrap_err();
+    assert_eq!(s, "cA\u{e9}");
+}
+
+#[test]
+fn retain_replace_panic() {
+    let mut s = "a<b\u{e9}<c".to_string();
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        s.retain_replace(|c, _| match c {
+            '<' => RetainDecision::Replace("&lt;"),
+            'c' => panic!("stop here"),
+            _ => RetainDecision::Keep,
+        })
+    }))
+    .unwrap_err();
+    // The string grew to fit the replacements before the panic
+    assert_eq!(s, "a&lt;b\u{e9}&lt;");
+}
+
+#[test]
+fn retain_splice_panic() {
+    let mut s = "ab12cd34ef".to_string();
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        s.retain_splice(
+            |c, _| {
+                if c == 'e' {
+                    panic!("stop here")
+                }
+                !c.is_ascii_digit()
+            },
+            "#".chars(),
+        )
+    }))
+    .unwrap_err();
+    // The run before `e` was removed, but its replacement not yet inserted
+    assert_eq!(s, "ab#cd");
+}
@@ -0,0 +1,231 @@
+//! The tests of the [`Vec`] and [`VecDeque`] extensions which require access to
+//! [`std::panic::catch_unwind`], which is unavailable in a `no_std` crate
+
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use retain_more::{RetainMoreByteDeque as _, RetainMoreVec as _, RetainMoreVecDeque as _};
+
+/// An element which counts how many times it has been dropped, and which can
+/// be made to panic when dropped.
+struct DropCounter<'a> {
+    value: usize,
+    drops: &'a Cell<usize>,
+    panic_on_drop: bool,
+}
+
+impl<'a> DropCounter<'a> {
+    fn new(value: usize, drops: &'a Cell<usize>) -> Self {
+        DropCounter {
+            value,
+            drops,
+            panic_on_drop: false,
+        }
+    }
+}
+
+impl Drop for DropCounter<'_> {
+    fn drop(&mut self) {
+        self.drops.set(self.drops.get() + 1);
+        if self.panic_on_drop {
+            panic!("drop of {} panicked", self.value);
+        }
+    }
+}
+
+fn counters(drops: &Cell<usize>, len: usize) -> Vec<DropCounter<'_>> {
+    (0..len).map(|i| DropCounter::new(i, drops)).collect()
+}
+
+fn values(v: &[DropCounter<'_>]) -> Vec<usize> {
+    v.iter().map(|it| it.value).collect()
+}
+
+#[test]
+fn retain_all_rev_predicate_panic() {
+    let drops = Cell::new(0);
+    let mut v = counters(&drops, 8);
+    catch_unwind(AssertUnwindSafe(|| {
+        v.retain_all_rev(|_, it, _| match it.value {
+            3 => panic!("predicate panicked"),
+            n => n % 2 == 0,
+        })
+    }))
+    .unwrap_err();
+    // 7 and 5 were removed, 6 and 4 kept, and 0..=3 not yet considered
+    assert_eq!(values(&v), [0, 1, 2, 3, 4, 6]);
+    assert_eq!(drops.get(), 2);
+    drop(v);
+    assert_eq!(drops.get(), 8);
+}
+
+#[test]
+fn retain_all_rev_drop_panic() {
+    let drops = Cell::new(0);
+    let mut v = counters(&drops, 6);
+    v[2].panic_on_drop = true;
+    catch_unwind(AssertUnwindSafe(|| {
+        v.retain_all_rev(|_, it, _| it.value != 2 && it.value != 4)
+    }))
+    .unwrap_err();
+    assert_eq!(values(&v), [0, 1, 3, 5]);
+    assert_eq!(drops.get(), 2);
+    drop(v);
+    assert_eq!(drops.get(), 6);
+}
+
+#[test]
+fn retain_range_predicate_panic() {
+    let drops = Cell::new(0);
+    let mut v = counters(&drops, 8);
+    catch_unwind(AssertUnwindSafe(|| {
+        v.retain_range(2..7, |it| match it.value {
+            5 => panic!("predicate panicked"),
+            n => n == 3,
+        })
+    }))
+    .unwrap_err();
+    assert_eq!(values(&v), [0, 1, 3, 5, 6, 7]);
+    assert_eq!(drops.get(), 2);
+    drop(v);
+    assert_eq!(drops.get(), 8);
+}
+
+#[test]
+fn retain_range_drop_panic() {
+    let drops = Cell::new(0);
+    let mut v = counters(&drops, 8);
+    v[3].panic_on_drop = true;
+    catch_unwind(AssertUnwindSafe(|| {
+        v.retain_range(1..6, |it| it.value % 2 == 0)
+    }))
+    .unwrap_err();
+    // 1 and 3 were dropped, and the panic stopped the pass before 5
+    assert_eq!(values(&v), [0, 2, 4, 5, 6, 7]);
+    assert_eq!(drops.get(), 2);
+    drop(v);
+    assert_eq!(drops.get(), 8);
+}
+
+#[test]
+fn retain_adjust_indices_panic() {
+    let drops = Cell::new(0);
+    let mut v = counters(&drops, 6);
+    let mut indices = [5, 1, 3];
+    catch_unwind(AssertUnwindSafe(|| {
+        v.retain_adjust_indices(&mut indices, |it| match it.value {
+            4 => panic!("predicate panicked"),
+            n => n != 1,
+        })
+    }))
+    .unwrap_err();
+    assert_eq!(values(&v), [0, 2, 3, 4, 5]);
+    assert_eq!(indices, [5, 1, 3]);
+    drop(v);
+    assert_eq!(drops.get(), 6);
+}
+
+#[test]
+fn extract_if_ctx_predicate_panic() {
+    let drops = Cell::new(0);
+    let mut v = counters(&drops, 6);
+    let mut extracted = Vec::new();
+    catch_unwind(AssertUnwindSafe(|| {
+        for it in v.extract_if_ctx(|_, it, _| match it.value {
+            4 => panic!("predicate panicked"),
+            n => n % 2 == 1,
+        }) {
+            extracted.push(it);
+        }
+    }))
+    .unwrap_err();
+    assert_eq!(values(&extracted), [1, 3]);
+    assert_eq!(values(&v), [0, 2, 4, 5]);
+    assert_eq!(drops.get(), 0);
+    drop(v);
+    drop(extracted);
+    assert_eq!(drops.get(), 6);
+}
+
+#[test]
+fn extract_if_ctx_consumer_panic() {
+    let drops = Cell::new(0);
+    let mut v = counters(&drops, 6);
+    catch_unwind(AssertUnwindSafe(|| {
+        for it in v.extract_if_ctx(|_, it, _| it.value >= 2) {
+            if it.value == 3 {
+                panic!("consumer panicked holding {}", it.value);
+            }
+        }
+    }))
+    .unwrap_err();
+    // 2 was dropped by the loop body, and 3 whilst unwinding
+    assert_eq!(drops.get(), 2);
+    assert_eq!(values(&v), [0, 1, 4, 5]);
+    drop(v);
+    assert_eq!(drops.get(), 6);
+}
+
+#[test]
+fn retain_window_panic() {
+    let drops = Cell::new(0);
+    let mut v = counters(&drops, 5);
+    catch_unwind(AssertUnwindSafe(|| {
+        v.retain_window(|it, [next]| match it.value {
+            3 => panic!("predicate panicked"),
+            _ => next.is_some_and(|next| next.value != 2),
+        })
+    }))
+    .unwrap_err();
+    assert_eq!(values(&v), [0, 2, 3, 4]);
+    assert_eq!(drops.get(), 1);
+    drop(v);
+    assert_eq!(drops.get(), 5);
+}
+
+#[test]
+fn retain_map_panic() {
+    let drops = Cell::new(0);
+    // Wrap the contents around the end of the buffer
+    let mut front = counters(&drops, 8);
+    let back = front.split_off(3);
+    let mut deque = VecDeque::with_capacity(8);
+    for it in front.into_iter().rev() {
+        deque.push_front(it);
+    }
+    deque.extend(back);
+    assert!(!deque.as_slices().1.is_empty());
+    catch_unwind(AssertUnwindSafe(|| {
+        deque.retain_map(|mut it| match it.value {
+            4 => panic!("closure panicked"),
+            n if n % 2 == 0 => {
+                it.value *= 10;
+                Some(it)
+            }
+            _ => None,
+        })
+    }))
+    .unwrap_err();
+    // 1 and 3 were removed, and 4 was dropped whilst unwinding
+    assert_eq!(drops.get(), 3);
+    assert_eq!(values(deque.make_contiguous()), [0, 20, 5, 6, 7]);
+    drop(deque);
+    assert_eq!(drops.get(), 8);
+}
+
+#[test]
+fn retain_utf8_panic() {
+    let mut deque: VecDeque<u8> = "a-b-\u{1F980}-c".bytes().collect();
+    catch_unwind(AssertUnwindSafe(|| {
+        deque.retain_utf8(|c| match c {
+            '\u{1F980}' => panic!("predicate panicked"),
+            c => c != '-',
+        })
+    }))
+    .unwrap_err();
+    let contents: Vec<u8> = deque.into_iter().collect();
+    assert_eq!(String::from_utf8(contents).unwrap(), "ab\u{1F980}-c");
+}